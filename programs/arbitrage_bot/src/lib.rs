@@ -1,6 +1,17 @@
 use anchor_lang::prelude::*;
 use anchor_spl::token::{Token, TokenAccount};
+use shared::vlog;
 
+#[cfg(all(feature = "devnet", feature = "mainnet"))]
+compile_error!("features `devnet` and `mainnet` are mutually exclusive");
+
+// 各集群的程序 ID，通过 cargo feature 选择；未指定时默认为 localnet
+// devnet / mainnet 目前与 localnet 共用同一部署密钥，更换密钥后只需修改对应分支
+#[cfg(feature = "mainnet")]
+declare_id!("138D5SkLsTLz8GmEMEYAntRPyvZXmiyR8Mb2rooDjx2A");
+#[cfg(feature = "devnet")]
+declare_id!("138D5SkLsTLz8GmEMEYAntRPyvZXmiyR8Mb2rooDjx2A");
+#[cfg(not(any(feature = "devnet", feature = "mainnet")))]
 declare_id!("138D5SkLsTLz8GmEMEYAntRPyvZXmiyR8Mb2rooDjx2A");
 
 #[program]
@@ -14,7 +25,7 @@ pub mod arbitrage_bot {
         ctx: Context<ExecuteArbitrageAtomic>,
         loan_amount: u64,
         min_expected_profit: u64,
-    ) -> Result<u64> {
+    ) -> Result<ArbitrageResult> {
         // === CHECK 阶段：所有验证和检查 ===
         ArbitrageHandler::validate_inputs(&ctx.accounts.arbitrage_bot, loan_amount, min_expected_profit)?;
 
@@ -45,8 +56,23 @@ pub mod arbitrage_bot {
             arbitrage_bot.is_executing = false;
         }
 
-        msg!("✅ ArbitrageBot: 套利完成，利润: {} lamports", actual_profit);
-        Ok(actual_profit)
+        vlog!("✅ ArbitrageBot: 套利完成，利润: {} lamports", actual_profit);
+        Ok(ArbitrageResult {
+            profit: actual_profit,
+            first_leg_amount_out: first_result,
+            second_leg_amount_out: second_result,
+        })
+    }
+
+    /// 校验机器人核心不变量（仅非主网构建）
+    /// 任何交易结束时都不应停留在执行中状态
+    #[cfg(not(feature = "mainnet"))]
+    pub fn assert_invariants(ctx: Context<AssertInvariants>) -> Result<()> {
+        require!(
+            !ctx.accounts.arbitrage_bot.is_executing,
+            ErrorCode::InvariantBotStillExecuting
+        );
+        Ok(())
     }
 }
 
@@ -65,9 +91,9 @@ impl ArbitrageHandler {
         require!(loan_amount > 0, ErrorCode::InvalidLoanAmount);
         require!(min_expected_profit > 0, ErrorCode::InvalidProfitRequirement);
 
-        msg!("🤖 ArbitrageBot: 开始原子性套利执行");
-        msg!("  借款金额: {} lamports", loan_amount);
-        msg!("  最小期望利润: {} lamports", min_expected_profit);
+        vlog!("🤖 ArbitrageBot: 开始原子性套利执行");
+        vlog!("  借款金额: {} lamports", loan_amount);
+        vlog!("  最小期望利润: {} lamports", min_expected_profit);
         
         Ok(())
     }
@@ -79,14 +105,17 @@ impl ArbitrageHandler {
     ) -> Result<u64> {
         let min_amount_out = Self::calculate_min_amount_out(loan_amount)?;
         
-        Self::perform_swap(
+        // 使用 mock_dex 返回的成交数量，而不是读取未 reload 且含原有余额的账户
+        let result = Self::perform_swap(
             &ctx.accounts.mock_dex_program,
             &ctx.accounts.dex_pool_a,
             &ctx.accounts.token_in_account,
             &ctx.accounts.dex_a_token_x_vault,
             &ctx.accounts.dex_a_token_y_vault,
-            &ctx.accounts.user_token_x,
+            &ctx.accounts.dex_a_fee_vault,
             &ctx.accounts.user_token_y,
+            &ctx.accounts.token_x_mint,
+            &ctx.accounts.token_y_mint,
             &ctx.accounts.arbitrage_bot,
             &ctx.accounts.token_program,
             &ctx.bumps.arbitrage_bot,
@@ -94,9 +123,13 @@ impl ArbitrageHandler {
             min_amount_out,
         )?;
 
-        let result = ctx.accounts.user_token_y.amount;
-        msg!("  DEX A 交换完成，获得Token Y: {}", result);
-        Ok(result)
+        vlog!(
+            "  DEX A 交换完成，获得Token Y: {} (手续费: {}, 价格影响: {}bps)",
+            result.amount_out,
+            result.fee_paid,
+            result.price_impact_bps
+        );
+        Ok(result.amount_out)
     }
 
     /// 执行第二次交换 - DEX B
@@ -106,14 +139,16 @@ impl ArbitrageHandler {
     ) -> Result<u64> {
         let min_amount_out = Self::calculate_min_amount_out(token_y_amount)?;
         
-        Self::perform_swap(
+        let result = Self::perform_swap(
             &ctx.accounts.mock_dex_program,
             &ctx.accounts.dex_pool_b,
             &ctx.accounts.user_token_y,
             &ctx.accounts.dex_b_token_x_vault,
             &ctx.accounts.dex_b_token_y_vault,
+            &ctx.accounts.dex_b_fee_vault,
             &ctx.accounts.user_token_x,
-            &ctx.accounts.user_token_y,
+            &ctx.accounts.token_x_mint,
+            &ctx.accounts.token_y_mint,
             &ctx.accounts.arbitrage_bot,
             &ctx.accounts.token_program,
             &ctx.bumps.arbitrage_bot,
@@ -121,9 +156,13 @@ impl ArbitrageHandler {
             min_amount_out,
         )?;
 
-        let result = ctx.accounts.user_token_x.amount;
-        msg!("  DEX B 交换完成，最终Token X: {}", result);
-        Ok(result)
+        vlog!(
+            "  DEX B 交换完成，最终Token X: {} (手续费: {}, 价格影响: {}bps)",
+            result.amount_out,
+            result.fee_paid,
+            result.price_impact_bps
+        );
+        Ok(result.amount_out)
     }
 
     /// 计算最小输出金额（考虑手续费和滑点）
@@ -150,23 +189,31 @@ impl ArbitrageHandler {
         token_in_account: &Account<'info, TokenAccount>,
         token_x_vault: &Account<'info, TokenAccount>,
         token_y_vault: &Account<'info, TokenAccount>,
-        user_token_x: &Account<'info, TokenAccount>,
-        user_token_y: &Account<'info, TokenAccount>,
+        protocol_fee_vault: &Account<'info, TokenAccount>,
+        token_out_account: &Account<'info, TokenAccount>,
+        token_x_mint: &AccountInfo<'info>,
+        token_y_mint: &AccountInfo<'info>,
         user_authority: &Account<'info, ArbitrageBotState>,
         token_program: &Program<'info, Token>,
         bump: &u8,
         amount_in: u64,
         min_amount_out: u64,
-    ) -> Result<()> {
+    ) -> Result<mock_dex::SwapResult> {
         let cpi_accounts = mock_dex::cpi::accounts::Swap {
             pool: pool.to_account_info(),
             token_in_account: token_in_account.to_account_info(),
             token_x_vault: token_x_vault.to_account_info(),
             token_y_vault: token_y_vault.to_account_info(),
-            user_token_x: user_token_x.to_account_info(),
-            user_token_y: user_token_y.to_account_info(),
+            protocol_fee_vault: protocol_fee_vault.to_account_info(),
+            token_out_account: token_out_account.to_account_info(),
+            token_x_mint: token_x_mint.to_account_info(),
+            token_y_mint: token_y_mint.to_account_info(),
             user_authority: user_authority.to_account_info(),
             token_program: token_program.to_account_info(),
+            referrer_token_account: None, // 机器人自己的兑换不带推荐人
+            oracle: None,
+            fee_allowlist: None, // 按池子的标准费率交易
+            trader_allowlist: None, // 不在许可模式的池子上交易
         };
 
         let seeds = &[b"arbitrage_bot".as_ref(), &[*bump]];
@@ -178,7 +225,10 @@ impl ArbitrageHandler {
             signer_seeds,
         );
 
-        mock_dex::cpi::swap(cpi_ctx, amount_in, min_amount_out, "test-pool".to_string())
+        // 池子由 mock_dex 按 mint 对和手续费档位校验，这里只确认账户归属
+        require_keys_eq!(*pool.owner, mock_dex::ID, ErrorCode::InvalidDexPool);
+
+        Ok(mock_dex::cpi::swap(cpi_ctx, amount_in, min_amount_out)?.get())
     }
 }
 
@@ -209,6 +259,10 @@ pub struct ExecuteArbitrageAtomic<'info> {
     #[account(mut)]
     pub dex_a_token_y_vault: Account<'info, TokenAccount>,
 
+    /// CHECK: DEX A的Token X 协议费 vault (第一次交换输入 X)
+    #[account(mut)]
+    pub dex_a_fee_vault: Account<'info, TokenAccount>,
+
     // DEX B相关账户
     /// CHECK: DEX B的池子
     #[account(mut)]
@@ -222,6 +276,10 @@ pub struct ExecuteArbitrageAtomic<'info> {
     #[account(mut)]
     pub dex_b_token_y_vault: Account<'info, TokenAccount>,
 
+    /// CHECK: DEX B的Token Y 协议费 vault (第二次交换输入 Y)
+    #[account(mut)]
+    pub dex_b_fee_vault: Account<'info, TokenAccount>,
+
     // 用户代币账户
     /// CHECK: 输入代币账户
     #[account(mut)]
@@ -235,6 +293,12 @@ pub struct ExecuteArbitrageAtomic<'info> {
     #[account(mut)]
     pub user_token_y: Account<'info, TokenAccount>,
 
+    /// CHECK: Token X 的 mint，由 mock_dex 与池子记录比对
+    pub token_x_mint: AccountInfo<'info>,
+
+    /// CHECK: Token Y 的 mint，由 mock_dex 与池子记录比对
+    pub token_y_mint: AccountInfo<'info>,
+
     pub token_program: Program<'info, Token>,
     
     #[account(mut)]
@@ -243,6 +307,16 @@ pub struct ExecuteArbitrageAtomic<'info> {
     pub system_program: Program<'info, System>,
 }
 
+#[cfg(not(feature = "mainnet"))]
+#[derive(Accounts)]
+pub struct AssertInvariants<'info> {
+    #[account(
+        seeds = [b"arbitrage_bot"],
+        bump,
+    )]
+    pub arbitrage_bot: Account<'info, ArbitrageBotState>,
+}
+
 #[account]
 pub struct ArbitrageBotState {
     pub owner: Pubkey,
@@ -259,6 +333,14 @@ impl ArbitrageBotState {
         8; // total_profit
 }
 
+/// execute_arbitrage_atomic 通过 return data 返回的结果
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct ArbitrageResult {
+    pub profit: u64,
+    pub first_leg_amount_out: u64, // DEX A 兑换得到的中间代币数量
+    pub second_leg_amount_out: u64, // DEX B 换回的代币数量
+}
+
 #[error_code]
 pub enum ErrorCode {
     #[msg("利润不足以偿还闪电贷")]
@@ -271,4 +353,8 @@ pub enum ErrorCode {
     InvalidProfitRequirement,
     #[msg("计算溢出")]
     CalculationOverflow,
+    #[msg("无效的 DEX 池子账户")]
+    InvalidDexPool,
+    #[msg("不变量被破坏：机器人仍处于执行中状态")]
+    InvariantBotStillExecuting,
 } 
\ No newline at end of file
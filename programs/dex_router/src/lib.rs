@@ -0,0 +1,322 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface};
+use mock_dex::program::MockDex;
+use mock_dex::{CurveType, MockDexPool, POOL_VERSION};
+use shared::vlog;
+
+#[cfg(all(feature = "devnet", feature = "mainnet"))]
+compile_error!("features `devnet` and `mainnet` are mutually exclusive");
+
+// 各集群的程序 ID，通过 cargo feature 选择；未指定时默认为 localnet
+// devnet / mainnet 目前与 localnet 共用同一部署密钥，更换密钥后只需修改对应分支
+#[cfg(feature = "mainnet")]
+declare_id!("G9GPNsgdnG6a7qCtAzAK9tVZhgQ8uMk98aojirA5J35y");
+#[cfg(feature = "devnet")]
+declare_id!("G9GPNsgdnG6a7qCtAzAK9tVZhgQ8uMk98aojirA5J35y");
+#[cfg(not(any(feature = "devnet", feature = "mainnet")))]
+declare_id!("G9GPNsgdnG6a7qCtAzAK9tVZhgQ8uMk98aojirA5J35y");
+
+/// 单次路由最多比较的池子数量
+pub const MAX_ROUTER_POOLS: usize = 4;
+
+/// 每个候选池子在 remaining_accounts 里占用的账户数：
+/// [pool, token_x_vault, token_y_vault, 输入侧协议费 vault]
+pub const ROUTER_ACCOUNTS_PER_POOL: usize = 4;
+
+/// 拆单时把输入数量切成的份数，每一份分配给当时边际输出最高的池子
+pub const ROUTER_SPLIT_PARTS: u64 = 10;
+
+#[program]
+pub mod dex_router {
+    use super::*;
+
+    /// 在同一交易对的多个 mock_dex 池子中选出输出最高的一个并整笔兑换
+    /// 候选池子通过 remaining_accounts 传入，返回实际成交的输出数量
+    pub fn swap_best<'info>(
+        ctx: Context<'_, '_, 'info, 'info, RouterSwap<'info>>,
+        amount_in: u64,
+        min_amount_out: u64,
+    ) -> Result<u64> {
+        // === CHECK 阶段：加载候选池子并逐个报价 ===
+        require!(amount_in > 0, ErrorCode::InvalidAmount);
+        let input_is_x = RouterHandler::input_is_x(ctx.accounts)?;
+        let candidates = RouterHandler::load_candidates(ctx.accounts, ctx.remaining_accounts, input_is_x)?;
+
+        let mut best: Option<(usize, u64)> = None;
+        for (index, candidate) in candidates.iter().enumerate() {
+            let quote = candidate.quote(amount_in, candidate.reserve_in, candidate.reserve_out, input_is_x);
+            vlog!("  池子 {} 报价: {}", candidate.pool.key, quote);
+            if quote > best.map_or(0, |(_, out)| out) {
+                best = Some((index, quote));
+            }
+        }
+        let (best_index, quote) = best.ok_or(ErrorCode::NoRoutablePool)?;
+
+        // === INTERACTIONS 阶段：滑点由 mock_dex 按 min_amount_out 校验 ===
+        let candidate = &candidates[best_index];
+        let amount_out = RouterHandler::execute_swap(ctx.accounts, candidate, amount_in, min_amount_out)?;
+
+        emit!(RouterSwapExecuted {
+            user: ctx.accounts.user_authority.key(),
+            input_mint: ctx.accounts.token_in_account.mint,
+            amount_in,
+            amount_out,
+            pools: vec![candidate.pool.key()],
+            amounts_in: vec![amount_in],
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        vlog!("🧭 Router: 选择池子 {} (报价 {}，成交 {})", candidate.pool.key, quote, amount_out);
+        Ok(amount_out)
+    }
+
+    /// 把输入数量拆分到多个池子上兑换，使总输出尽量高
+    /// 按 ROUTER_SPLIT_PARTS 份逐份分配给边际输出最高的池子，整笔输出不足 min_amount_out 时整体回滚
+    pub fn swap_split<'info>(
+        ctx: Context<'_, '_, 'info, 'info, RouterSwap<'info>>,
+        amount_in: u64,
+        min_amount_out: u64,
+    ) -> Result<u64> {
+        // === CHECK 阶段 ===
+        require!(amount_in > 0, ErrorCode::InvalidAmount);
+        let input_is_x = RouterHandler::input_is_x(ctx.accounts)?;
+        let candidates = RouterHandler::load_candidates(ctx.accounts, ctx.remaining_accounts, input_is_x)?;
+        let allocations = RouterHandler::split_allocations(&candidates, amount_in, input_is_x)?;
+
+        // === INTERACTIONS 阶段：逐个池子成交，单笔不设下限，最后统一做滑点检查 ===
+        let mut amount_out: u64 = 0;
+        let mut pools = Vec::new();
+        let mut amounts_in = Vec::new();
+        for (candidate, allocation) in candidates.iter().zip(allocations.iter()) {
+            if *allocation == 0 {
+                continue;
+            }
+            let leg_out = RouterHandler::execute_swap(ctx.accounts, candidate, *allocation, 0)?;
+            amount_out = amount_out.checked_add(leg_out).ok_or(ErrorCode::CalculationOverflow)?;
+            pools.push(candidate.pool.key());
+            amounts_in.push(*allocation);
+            vlog!("  池子 {} 成交: {} -> {}", candidate.pool.key, allocation, leg_out);
+        }
+
+        require!(amount_out >= min_amount_out, ErrorCode::SlippageTooHigh);
+
+        emit!(RouterSwapExecuted {
+            user: ctx.accounts.user_authority.key(),
+            input_mint: ctx.accounts.token_in_account.mint,
+            amount_in,
+            amount_out,
+            pools,
+            amounts_in,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        vlog!("🧭 Router: 拆单成交 {} -> {}", amount_in, amount_out);
+        Ok(amount_out)
+    }
+}
+
+/// 候选池子：报价所需的池子状态快照以及兑换 CPI 需要的账户
+pub struct PoolCandidate<'info> {
+    pub pool: &'info AccountInfo<'info>,
+    pub token_x_vault: &'info AccountInfo<'info>,
+    pub token_y_vault: &'info AccountInfo<'info>,
+    pub protocol_fee_vault: &'info AccountInfo<'info>,
+    pub curve: CurveType,
+    pub fee_bps: u64,
+    pub reserve_in: u64,
+    pub reserve_out: u64,
+}
+
+impl<'info> PoolCandidate<'info> {
+    /// 按池子曲线在给定储备下报价，无法成交时返回 0
+    pub fn quote(&self, amount_in: u64, reserve_in: u64, reserve_out: u64, input_is_x: bool) -> u64 {
+        match self.curve.amount_out(amount_in, self.fee_bps, reserve_in, reserve_out, input_is_x) {
+            Ok(amount_out) if amount_out < reserve_out => amount_out,
+            _ => 0,
+        }
+    }
+}
+
+/// 路由处理器
+pub struct RouterHandler;
+
+impl RouterHandler {
+    /// 根据输入账户的 mint 判断兑换方向
+    pub fn input_is_x(accounts: &RouterSwap) -> Result<bool> {
+        let input_mint = accounts.token_in_account.mint;
+        let input_is_x = input_mint == accounts.token_x_mint.key();
+        require!(
+            input_is_x || input_mint == accounts.token_y_mint.key(),
+            ErrorCode::InvalidTokenInAccount
+        );
+        Ok(input_is_x)
+    }
+
+    /// 从 remaining_accounts 加载候选池子
+    /// 池子必须属于 mock_dex 且交易对与本次兑换一致；暂停、方向关闭、许可模式、版本不符、
+    /// 储备为空或集中流动性（报价需要区间账户）的池子不参与路由；报价按当前生效的折扣费率计算
+    pub fn load_candidates<'info>(
+        accounts: &RouterSwap<'info>,
+        remaining_accounts: &'info [AccountInfo<'info>],
+        input_is_x: bool,
+    ) -> Result<Vec<PoolCandidate<'info>>> {
+        require!(
+            !remaining_accounts.is_empty()
+                && remaining_accounts.len() % ROUTER_ACCOUNTS_PER_POOL == 0
+                && remaining_accounts.len() / ROUTER_ACCOUNTS_PER_POOL <= MAX_ROUTER_POOLS,
+            ErrorCode::InvalidPoolAccounts
+        );
+
+        let now = Clock::get()?.unix_timestamp;
+        let mut candidates = Vec::new();
+        for group in remaining_accounts.chunks(ROUTER_ACCOUNTS_PER_POOL) {
+            // AccountLoader::try_from 校验账户归属 mock_dex 与 discriminator；
+            // 只读借用在本轮循环结束时释放，不影响之后以池子为签名者的 CPI
+            let pool_loader: AccountLoader<'info, MockDexPool> = AccountLoader::try_from(&group[0])?;
+            let pool = pool_loader.load()?;
+            require_keys_eq!(pool.token_x_mint, accounts.token_x_mint.key(), ErrorCode::PoolPairMismatch);
+            require_keys_eq!(pool.token_y_mint, accounts.token_y_mint.key(), ErrorCode::PoolPairMismatch);
+
+            let direction_enabled = if input_is_x { pool.x_to_y_enabled } else { pool.y_to_x_enabled };
+            if pool.is_paused()
+                || direction_enabled == 0
+                || pool.permissioned != 0
+                || pool.version != POOL_VERSION
+                || pool.curve() == CurveType::Concentrated
+                || pool.x_balance == 0
+                || pool.y_balance == 0
+            {
+                vlog!("  跳过池子 {}", group[0].key);
+                continue;
+            }
+
+            let (reserve_in, reserve_out) = pool.reserves(input_is_x);
+            candidates.push(PoolCandidate {
+                pool: &group[0],
+                token_x_vault: &group[1],
+                token_y_vault: &group[2],
+                protocol_fee_vault: &group[3],
+                curve: pool.curve(),
+                fee_bps: pool.current_fee_bps(now) as u64,
+                reserve_in,
+                reserve_out,
+            });
+        }
+
+        require!(!candidates.is_empty(), ErrorCode::NoRoutablePool);
+        Ok(candidates)
+    }
+
+    /// 贪心拆单：把 amount_in 切成 ROUTER_SPLIT_PARTS 份，每份交给在模拟储备下边际输出最高的池子
+    /// 模拟储备只按曲线输出更新，不考虑协议费和转账手续费，实际成交以 mock_dex 为准
+    pub fn split_allocations(candidates: &[PoolCandidate], amount_in: u64, input_is_x: bool) -> Result<Vec<u64>> {
+        let parts = ROUTER_SPLIT_PARTS.min(amount_in);
+        let part_size = amount_in / parts;
+
+        let mut reserves: Vec<(u64, u64)> = candidates.iter().map(|c| (c.reserve_in, c.reserve_out)).collect();
+        let mut allocations = vec![0u64; candidates.len()];
+
+        for part in 0..parts {
+            // 余数并入最后一份
+            let chunk = if part == parts - 1 { amount_in - part_size * (parts - 1) } else { part_size };
+
+            let mut best: Option<(usize, u64)> = None;
+            for (index, candidate) in candidates.iter().enumerate() {
+                let (reserve_in, reserve_out) = reserves[index];
+                let quote = candidate.quote(chunk, reserve_in, reserve_out, input_is_x);
+                if quote > best.map_or(0, |(_, out)| out) {
+                    best = Some((index, quote));
+                }
+            }
+            let (index, quote) = best.ok_or(ErrorCode::NoRoutablePool)?;
+
+            let (reserve_in, reserve_out) = reserves[index];
+            reserves[index] = (
+                reserve_in.checked_add(chunk).ok_or(ErrorCode::CalculationOverflow)?,
+                reserve_out - quote,
+            );
+            allocations[index] += chunk;
+        }
+
+        Ok(allocations)
+    }
+
+    /// 通过 CPI 在候选池子上执行一笔 mock_dex 兑换，返回 mock_dex 的成交数量
+    /// user_authority 是外层交易的签名者，签名权限随 CPI 传递，router 不持有任何代币
+    pub fn execute_swap<'info>(
+        accounts: &RouterSwap<'info>,
+        candidate: &PoolCandidate<'info>,
+        amount_in: u64,
+        min_amount_out: u64,
+    ) -> Result<u64> {
+        let cpi_accounts = mock_dex::cpi::accounts::Swap {
+            pool: candidate.pool.clone(),
+            token_in_account: accounts.token_in_account.to_account_info(),
+            token_x_vault: candidate.token_x_vault.clone(),
+            token_y_vault: candidate.token_y_vault.clone(),
+            protocol_fee_vault: candidate.protocol_fee_vault.clone(),
+            token_out_account: accounts.token_out_account.to_account_info(),
+            token_x_mint: accounts.token_x_mint.to_account_info(),
+            token_y_mint: accounts.token_y_mint.to_account_info(),
+            user_authority: accounts.user_authority.to_account_info(),
+            token_program: accounts.token_program.to_account_info(),
+            referrer_token_account: None,
+            oracle: None,
+            fee_allowlist: None, // 路由按池子的标准费率报价，成交也按标准费率
+            trader_allowlist: None, // 许可模式的池子已在 load_candidates 中跳过
+        };
+
+        let cpi_ctx = CpiContext::new(accounts.mock_dex_program.to_account_info(), cpi_accounts);
+        Ok(mock_dex::cpi::swap(cpi_ctx, amount_in, min_amount_out)?.get().amount_out)
+    }
+}
+
+#[derive(Accounts)]
+pub struct RouterSwap<'info> {
+    /// 用户的输入代币账户，必须是 token_x_mint 或 token_y_mint
+    #[account(mut)]
+    pub token_in_account: InterfaceAccount<'info, TokenAccount>,
+
+    /// 用户的输出代币账户，由 mock_dex 校验 mint
+    #[account(mut)]
+    pub token_out_account: InterfaceAccount<'info, TokenAccount>,
+
+    /// 交易对的两个 mint，所有候选池子必须是这一对
+    pub token_x_mint: Box<InterfaceAccount<'info, Mint>>,
+    pub token_y_mint: Box<InterfaceAccount<'info, Mint>>,
+
+    /// 用户的签名 authority，签名权限经 CPI 传给 mock_dex 的转账
+    pub user_authority: Signer<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub mock_dex_program: Program<'info, MockDex>,
+}
+
+#[event]
+pub struct RouterSwapExecuted {
+    pub user: Pubkey,
+    pub input_mint: Pubkey,
+    pub amount_in: u64,
+    pub amount_out: u64,
+    pub pools: Vec<Pubkey>, // 实际成交的池子
+    pub amounts_in: Vec<u64>, // 与 pools 一一对应的输入数量
+    pub timestamp: i64,
+}
+
+#[error_code]
+pub enum ErrorCode {
+    #[msg("无效的兑换数量")]
+    InvalidAmount,
+    #[msg("输入代币账户与交易对不匹配")]
+    InvalidTokenInAccount,
+    #[msg("候选池子账户数量无效")]
+    InvalidPoolAccounts,
+    #[msg("池子的交易对与本次兑换不一致")]
+    PoolPairMismatch,
+    #[msg("没有可路由的池子")]
+    NoRoutablePool,
+    #[msg("滑点过大")]
+    SlippageTooHigh,
+    #[msg("计算溢出")]
+    CalculationOverflow,
+}
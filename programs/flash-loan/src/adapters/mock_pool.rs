@@ -0,0 +1,110 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token::{self, TokenAccount, TransferChecked};
+use shared::MockPoolState;
+
+use super::{AdapterAccounts, LenderAdapter};
+use crate::{BorrowTracker, FlashLoanError, FlashLoanHandler};
+
+/// 从本程序为 mock_pool 池子管理的代币 vault 借款，费率取池子的 fee_bps
+/// lender_accounts: [mock_pool_state, token_vault, borrower_allowlist, borrow_tracker]，lender_program 须为本程序；
+/// borrow_tracker 须事先通过 initialize_borrow_tracker 创建，并以可写账户传入
+pub struct MockPoolAdapter<'info> {
+    accounts: AdapterAccounts<'info>,
+    pool_state: Account<'info, MockPoolState>,
+    token_vault: Account<'info, TokenAccount>,
+    borrow_tracker: Account<'info, BorrowTracker>,
+    vault_bump: u8,
+}
+
+impl<'info> MockPoolAdapter<'info> {
+    pub const ACCOUNTS: usize = 4;
+
+    pub fn load(accounts: AdapterAccounts<'info>, lender_accounts: &'info [AccountInfo<'info>]) -> Result<Self> {
+        require_keys_eq!(accounts.lender_program.key(), crate::ID, FlashLoanError::InvalidAdapterAccounts);
+
+        let pool_state: Account<'info, MockPoolState> = Account::try_from(&lender_accounts[0])?;
+        FlashLoanHandler::ensure_pool_lendable(&pool_state)?;
+        let pool_key = pool_state.key();
+
+        let (expected_vault, vault_bump) = Pubkey::find_program_address(
+            &[b"token_vault", pool_key.as_ref(), accounts.mint.key.as_ref()],
+            &crate::ID,
+        );
+        require_keys_eq!(lender_accounts[1].key(), expected_vault, FlashLoanError::InvalidAdapterAccounts);
+        let token_vault: Account<'info, TokenAccount> = Account::try_from(&lender_accounts[1])?;
+
+        let (expected_allowlist, _) =
+            Pubkey::find_program_address(&[b"borrower_allowlist", pool_key.as_ref()], &crate::ID);
+        require_keys_eq!(lender_accounts[2].key(), expected_allowlist, FlashLoanError::InvalidAdapterAccounts);
+        FlashLoanHandler::ensure_borrower_allowed(&lender_accounts[2], accounts.borrower.key)?;
+
+        let (expected_tracker, _) = Pubkey::find_program_address(&[b"borrow_tracker", pool_key.as_ref()], &crate::ID);
+        require_keys_eq!(lender_accounts[3].key(), expected_tracker, FlashLoanError::InvalidAdapterAccounts);
+        let borrow_tracker: Account<'info, BorrowTracker> = Account::try_from(&lender_accounts[3])?;
+
+        Ok(Self {
+            accounts,
+            pool_state,
+            token_vault,
+            borrow_tracker,
+            vault_bump,
+        })
+    }
+}
+
+impl<'info> LenderAdapter<'info> for MockPoolAdapter<'info> {
+    fn borrow(&mut self, amount: u64) -> Result<()> {
+        require!(self.token_vault.amount >= amount, FlashLoanError::InsufficientPoolBalance);
+        FlashLoanHandler::ensure_within_borrow_caps(
+            &self.pool_state,
+            &self.borrow_tracker,
+            amount,
+            self.token_vault.amount,
+        )?;
+
+        // 借款计入池子的 slot 累计；borrow_tracker 来自 remaining_accounts，Anchor 不会自动写回，由本程序 exit
+        self.borrow_tracker.record(amount, Clock::get()?.slot);
+        self.borrow_tracker.exit(&crate::ID)?;
+
+        let pool_key = self.pool_state.key();
+        let vault_bump = [self.vault_bump];
+        let vault_seeds = &[
+            b"token_vault".as_ref(),
+            pool_key.as_ref(),
+            self.accounts.mint.key.as_ref(),
+            &vault_bump,
+        ];
+        token::transfer_checked(
+            CpiContext::new_with_signer(
+                self.accounts.token_program.clone(),
+                TransferChecked {
+                    from: self.token_vault.to_account_info(),
+                    mint: self.accounts.mint.clone(),
+                    to: self.accounts.borrower_token_account.clone(),
+                    authority: self.token_vault.to_account_info(),
+                },
+                &[&vault_seeds[..]],
+            ),
+            amount,
+            self.accounts.mint_decimals,
+        )
+    }
+
+    fn repay(&self, amount: u64) -> Result<()> {
+        let fee = self.pool_state.calculate_fee(amount)?;
+        let total_repayment = amount.checked_add(fee).ok_or(FlashLoanError::Overflow)?;
+        token::transfer_checked(
+            CpiContext::new(
+                self.accounts.token_program.clone(),
+                TransferChecked {
+                    from: self.accounts.borrower_token_account.clone(),
+                    mint: self.accounts.mint.clone(),
+                    to: self.token_vault.to_account_info(),
+                    authority: self.accounts.borrower.clone(),
+                },
+            ),
+            total_repayment,
+            self.accounts.mint_decimals,
+        )
+    }
+}
@@ -0,0 +1,57 @@
+use anchor_lang::prelude::*;
+
+pub mod mock_pool;
+pub mod solend;
+
+pub use self::mock_pool::MockPoolAdapter;
+pub use self::solend::SolendAdapter;
+
+/// 闪电贷的流动性来源，由 external_flash_loan_with_callback 的 adapter 参数选择
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum LenderAdapterId {
+    MockPool, // 本程序为 mock_pool 池子管理的代币 vault
+    Solend,   // 兼容 Solend 指令格式的外部借贷程序
+}
+
+impl LenderAdapterId {
+    /// 适配器在 remaining_accounts 开头占用的账户数，其余账户原样传给执行程序
+    pub fn account_count(&self) -> usize {
+        match self {
+            LenderAdapterId::MockPool => MockPoolAdapter::ACCOUNTS,
+            LenderAdapterId::Solend => SolendAdapter::ACCOUNTS,
+        }
+    }
+}
+
+/// 各适配器共用的账户，由指令的固定账户构造
+#[derive(Clone)]
+pub struct AdapterAccounts<'info> {
+    pub lender_program: AccountInfo<'info>,
+    pub borrower: AccountInfo<'info>,
+    pub borrower_token_account: AccountInfo<'info>,
+    pub mint: AccountInfo<'info>,
+    pub mint_decimals: u8,
+    pub token_program: AccountInfo<'info>,
+    pub instructions: AccountInfo<'info>,
+}
+
+/// 借贷来源适配器
+/// borrow 把 amount 转入 borrower_token_account；repay 从同一账户归还本金和来源收取的费用，
+/// 费用由适配器按来源自己的规则计算，调用方按还款前后的余额差得出实际费用；
+/// borrow 可能需要更新本程序持有的借款记录（如 mock_pool 的 slot 借款累计），因此取 &mut self
+pub trait LenderAdapter<'info> {
+    fn borrow(&mut self, amount: u64) -> Result<()>;
+    fn repay(&self, amount: u64) -> Result<()>;
+}
+
+/// 按 adapter 构造适配器，lender_accounts 为 remaining_accounts 中属于适配器的部分
+pub fn load_adapter<'info>(
+    adapter: LenderAdapterId,
+    accounts: AdapterAccounts<'info>,
+    lender_accounts: &'info [AccountInfo<'info>],
+) -> Result<Box<dyn LenderAdapter<'info> + 'info>> {
+    Ok(match adapter {
+        LenderAdapterId::MockPool => Box::new(MockPoolAdapter::load(accounts, lender_accounts)?),
+        LenderAdapterId::Solend => Box::new(SolendAdapter::load(accounts, lender_accounts)?),
+    })
+}
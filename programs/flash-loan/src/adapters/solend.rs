@@ -0,0 +1,126 @@
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::instruction::Instruction;
+use anchor_lang::solana_program::program::invoke;
+use anchor_lang::solana_program::sysvar::instructions::load_current_index_checked;
+
+use super::{AdapterAccounts, LenderAdapter};
+use crate::FlashLoanError;
+
+/// Solend 指令枚举中 FlashBorrowReserveLiquidity / FlashRepayReserveLiquidity 的编号
+const FLASH_BORROW_RESERVE_LIQUIDITY_TAG: u8 = 19;
+const FLASH_REPAY_RESERVE_LIQUIDITY_TAG: u8 = 20;
+
+/// 通过 CPI 从兼容 Solend 指令格式的借贷程序借款，费用由借贷程序按储备配置在还款时收取
+/// lender_accounts: [reserve_liquidity_supply, reserve_liquidity_fee_receiver, host_fee_receiver,
+/// reserve, lending_market, lending_market_authority]
+/// 注意 Solend 主网部署要求闪电贷指令位于交易顶层，不接受 CPI；此适配器面向允许 CPI 的兼容部署
+pub struct SolendAdapter<'info> {
+    accounts: AdapterAccounts<'info>,
+    reserve_liquidity_supply: AccountInfo<'info>,
+    reserve_liquidity_fee_receiver: AccountInfo<'info>,
+    host_fee_receiver: AccountInfo<'info>,
+    reserve: AccountInfo<'info>,
+    lending_market: AccountInfo<'info>,
+    lending_market_authority: AccountInfo<'info>,
+}
+
+impl<'info> SolendAdapter<'info> {
+    pub const ACCOUNTS: usize = 6;
+
+    pub fn load(accounts: AdapterAccounts<'info>, lender_accounts: &'info [AccountInfo<'info>]) -> Result<Self> {
+        // 储备账户归借贷程序所有，防止传入伪造的储备
+        require_keys_eq!(
+            *lender_accounts[3].owner,
+            accounts.lender_program.key(),
+            FlashLoanError::InvalidAdapterAccounts
+        );
+
+        Ok(Self {
+            accounts,
+            reserve_liquidity_supply: lender_accounts[0].clone(),
+            reserve_liquidity_fee_receiver: lender_accounts[1].clone(),
+            host_fee_receiver: lender_accounts[2].clone(),
+            reserve: lender_accounts[3].clone(),
+            lending_market: lender_accounts[4].clone(),
+            lending_market_authority: lender_accounts[5].clone(),
+        })
+    }
+
+    fn instruction_data(tag: u8, amount: u64) -> Vec<u8> {
+        let mut data = Vec::with_capacity(1 + 8 + 1);
+        data.push(tag);
+        data.extend_from_slice(&amount.to_le_bytes());
+        data
+    }
+}
+
+impl<'info> LenderAdapter<'info> for SolendAdapter<'info> {
+    fn borrow(&mut self, amount: u64) -> Result<()> {
+        let ix = Instruction {
+            program_id: self.accounts.lender_program.key(),
+            accounts: vec![
+                AccountMeta::new(self.reserve_liquidity_supply.key(), false),
+                AccountMeta::new(self.accounts.borrower_token_account.key(), false),
+                AccountMeta::new(self.reserve.key(), false),
+                AccountMeta::new_readonly(self.lending_market.key(), false),
+                AccountMeta::new_readonly(self.lending_market_authority.key(), false),
+                AccountMeta::new_readonly(self.accounts.instructions.key(), false),
+                AccountMeta::new_readonly(self.accounts.token_program.key(), false),
+            ],
+            data: Self::instruction_data(FLASH_BORROW_RESERVE_LIQUIDITY_TAG, amount),
+        };
+        invoke(
+            &ix,
+            &[
+                self.reserve_liquidity_supply.clone(),
+                self.accounts.borrower_token_account.clone(),
+                self.reserve.clone(),
+                self.lending_market.clone(),
+                self.lending_market_authority.clone(),
+                self.accounts.instructions.clone(),
+                self.accounts.token_program.clone(),
+                self.accounts.lender_program.clone(),
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn repay(&self, amount: u64) -> Result<()> {
+        // 借款与还款都在当前顶层指令内发起，borrow_instruction_index 即当前指令的位置
+        let borrow_instruction_index = load_current_index_checked(&self.accounts.instructions)?;
+        let mut data = Self::instruction_data(FLASH_REPAY_RESERVE_LIQUIDITY_TAG, amount);
+        data.push(u8::try_from(borrow_instruction_index).map_err(|_| FlashLoanError::Overflow)?);
+
+        let ix = Instruction {
+            program_id: self.accounts.lender_program.key(),
+            accounts: vec![
+                AccountMeta::new(self.accounts.borrower_token_account.key(), false),
+                AccountMeta::new(self.reserve_liquidity_supply.key(), false),
+                AccountMeta::new(self.reserve_liquidity_fee_receiver.key(), false),
+                AccountMeta::new(self.host_fee_receiver.key(), false),
+                AccountMeta::new(self.reserve.key(), false),
+                AccountMeta::new_readonly(self.lending_market.key(), false),
+                AccountMeta::new_readonly(self.accounts.borrower.key(), true),
+                AccountMeta::new_readonly(self.accounts.instructions.key(), false),
+                AccountMeta::new_readonly(self.accounts.token_program.key(), false),
+            ],
+            data,
+        };
+        invoke(
+            &ix,
+            &[
+                self.accounts.borrower_token_account.clone(),
+                self.reserve_liquidity_supply.clone(),
+                self.reserve_liquidity_fee_receiver.clone(),
+                self.host_fee_receiver.clone(),
+                self.reserve.clone(),
+                self.lending_market.clone(),
+                self.accounts.borrower.clone(),
+                self.accounts.instructions.clone(),
+                self.accounts.token_program.clone(),
+                self.accounts.lender_program.clone(),
+            ],
+        )?;
+        Ok(())
+    }
+}
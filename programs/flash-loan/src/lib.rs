@@ -1,300 +1,2416 @@
 use anchor_lang::prelude::*;
-use anchor_spl::token::{Token, TokenAccount};
-use shared::{MockPoolState, TransactionRecord};
+use anchor_lang::solana_program::instruction::Instruction;
+use anchor_lang::system_program;
+use anchor_lang::solana_program::program::invoke;
+use anchor_lang::solana_program::sysvar::instructions::{
+    get_instruction_relative, load_current_index_checked, load_instruction_at_checked,
+};
+use anchor_lang::Discriminator;
+use anchor_spl::token::{self, Mint, Token, TokenAccount, TransferChecked};
+use arbitrage_bot::ArbitrageResult;
+use shared::{vlog, MockPoolState, PoolStatus, TransactionRecord};
 
+pub mod adapters;
+
+use adapters::{load_adapter, AdapterAccounts, LenderAdapterId};
+
+#[cfg(all(feature = "devnet", feature = "mainnet"))]
+compile_error!("features `devnet` and `mainnet` are mutually exclusive");
+
+// 各集群的程序 ID，通过 cargo feature 选择；未指定时默认为 localnet
+// devnet / mainnet 目前与 localnet 共用同一部署密钥，更换密钥后只需修改对应分支
+#[cfg(feature = "mainnet")]
+declare_id!("HfsaDERzuB1m79Z1JHcbNz2JtwVcRowBso7xb5vWVQK");
+#[cfg(feature = "devnet")]
 declare_id!("HfsaDERzuB1m79Z1JHcbNz2JtwVcRowBso7xb5vWVQK");
+#[cfg(not(any(feature = "devnet", feature = "mainnet")))]
+declare_id!("HfsaDERzuB1m79Z1JHcbNz2JtwVcRowBso7xb5vWVQK");
+
+/// 交易记录保留期（秒），超过后任何人都可以清理
+pub const RECORD_RETENTION_SECS: i64 = 30 * 24 * 60 * 60;
+
+/// 清理过期记录时从协议 treasury 支付给调用者的赏金（lamports）
+pub const CRANK_BOUNTY_LAMPORTS: u64 = 5_000;
+
+/// 单次 flash_borrow 最多借出的 SPL 代币种类数
+pub const MAX_FLASH_TOKEN_ASSETS: usize = 4;
+
+/// flash_borrow / flash_repay 的固定账户数，remaining_accounts 紧随其后；指令内省时据此定位各代币的 vault
+pub const FLASH_BORROW_FIXED_ACCOUNTS: usize = 8;
+pub const FLASH_REPAY_FIXED_ACCOUNTS: usize = 6;
+
+/// 借款人名单的容量
+pub const MAX_ALLOWED_BORROWERS: usize = 16;
+
+/// flash_borrow / flash_repay 中每种代币在 remaining_accounts 里占用的账户数：
+/// [mint, token_vault, borrower_token_account]
+pub const FLASH_ACCOUNTS_PER_TOKEN: usize = 3;
 
 #[program]
 pub mod flash_loan_program {
     use super::*;
 
-    /// 原子性闪电贷与套利 - 单个交易完成借款、套利、还款
+    /// 原子性闪电贷与套利 - 单个交易完成借款、套利、还款，结果通过 return data 返回
+    /// 最低利润可以用 min_expected_profit（lamports）或 min_profit_bps（借款金额的基点）表示，两者都给出时取较高者；
+    /// 给出 deadline（unix 时间戳）时，交易在其之后才上链直接拒绝，避免在套利机会消失后执行；None 表示不限制
     /// 优化栈使用，避免栈溢出
     pub fn atomic_flash_loan_with_arbitrage(
         mut ctx: Context<AtomicFlashLoanWithArbitrage>,
         amount: u64,
         min_expected_profit: u64,
+        min_profit_bps: Option<u16>,
+        deadline: Option<i64>,
         _description: String,
-    ) -> Result<()> {
+    ) -> Result<FlashLoanResult> {
         // === CHECK阶段 ===
-        let fee = FlashLoanHandler::validate_and_prepare(&ctx, amount)?;
+        let (fee, min_profit) =
+            FlashLoanHandler::validate_and_prepare(&ctx, amount, min_expected_profit, min_profit_bps, deadline)?;
         
         // === EFFECTS阶段 ===
         FlashLoanHandler::execute_loan(&mut ctx, amount)?;
         
         // === INTERACTIONS阶段 ===
-        let actual_profit = FlashLoanHandler::execute_arbitrage(&ctx, amount, min_expected_profit)?;
+        let arbitrage_result = FlashLoanHandler::execute_arbitrage(&ctx, amount, min_profit)?;
         
         // === 还款阶段 ===
         FlashLoanHandler::process_repayment(&mut ctx, amount, fee)?;
+        let protocol_share = FlashLoanHandler::collect_profit_share(&ctx, arbitrage_result.profit)?;
         
         // === 记录阶段 ===
-        FlashLoanHandler::record_transaction(&mut ctx, amount, fee, actual_profit)?;
-        
-        Ok(())
+        FlashLoanHandler::record_transaction(&mut ctx, amount, fee, &arbitrage_result, protocol_share)
     }
 
-    /// 查询交易记录 - 只读函数
-    pub fn get_transaction_record(ctx: Context<GetTransactionRecord>, user: Pubkey) -> Result<()> {
-        let transaction_record = &ctx.accounts.transaction_record;
-        
-        // 验证用户匹配
-        require!(
-            transaction_record.user == user,
-            FlashLoanError::UnauthorizedAccess
-        );
-        
-        msg!("Transaction Record:");
-        msg!("  Transaction ID: {}", transaction_record.transaction_id);
-        msg!("  User: {}", transaction_record.user);
-        msg!("  Loan Amount: {}", transaction_record.loan_amount);
-        msg!("  Fee: {}", transaction_record.fee);
-        msg!("  Profit: {}", transaction_record.profit);
-        msg!("  Net Profit: {}", transaction_record.net_profit);
-        msg!("  ROI (bps): {}", transaction_record.calculate_roi_bps());
-        msg!("  Is Profitable: {}", transaction_record.is_profitable());
-        
+    /// 为池子创建 SPL 代币 vault - 仅池子 authority 可调用
+    /// vault 是以自身为 authority 的 PDA 代币账户，放贷时由本程序按 PDA 签名转出；任何人都可以直接向 vault 转入代币补充流动性
+    pub fn initialize_token_vault(ctx: Context<InitializeTokenVault>) -> Result<()> {
+        emit!(TokenVaultInitialized {
+            pool: ctx.accounts.mock_pool_state.key(),
+            mint: ctx.accounts.mint.key(),
+            vault: ctx.accounts.token_vault.key(),
+            authority: ctx.accounts.authority.key(),
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        vlog!("🏦 已创建代币 vault: {}", ctx.accounts.mint.key());
         Ok(())
     }
-}
-
-/// 闪电贷处理器 - 将所有辅助函数移到这里
-pub struct FlashLoanHandler;
 
-impl FlashLoanHandler {
-    /// 验证和准备阶段
-    pub fn validate_and_prepare(
-        ctx: &Context<AtomicFlashLoanWithArbitrage>,
+    /// SPL 代币闪电贷与套利 - 单个指令内完成借出、套利、还款
+    /// 借出的代币转入套利使用的输入账户 token_in_account，还款从 user_token_x 转回 vault，两者都必须属于 borrower，
+    /// borrower 需事先 approve 套利机器人 PDA 作为 delegate，套利机器人才能用借来的代币兑换。
+    /// collateral_amount 大于 0 时先从 collateral_account 锁定同种代币作为抵押：套利完成后 user_token_x 不足以还款时，
+    /// 差额从抵押中扣除，其余抵押退回。套利 CPI 本身失败时整笔交易仍会回滚，抵押只覆盖收益不足以还款的情况
+    pub fn token_flash_loan_with_arbitrage(
+        ctx: Context<TokenFlashLoanWithArbitrage>,
         amount: u64,
-    ) -> Result<u64> {
-        let fee = ctx.accounts.mock_pool_state.calculate_fee(amount)?;
-        
+        min_expected_profit: u64,
+        collateral_amount: u64,
+    ) -> Result<()> {
+        // === CHECK阶段 ===
+        require!(amount > 0, FlashLoanError::InvalidAmount);
         require!(
-            ctx.accounts.mock_pool_state.can_lend(),
-            FlashLoanError::PoolNotActive
+            collateral_amount == 0 || ctx.accounts.collateral_account.is_some(),
+            FlashLoanError::CollateralAccountRequired
         );
-        
+        let pool_state = &ctx.accounts.mock_pool_state;
+        FlashLoanHandler::ensure_pool_lendable(pool_state)?;
+        FlashLoanHandler::ensure_borrower_allowed(&ctx.accounts.borrower_allowlist, &ctx.accounts.borrower.key())?;
         require!(
-            ctx.accounts.mock_pool_state.has_sufficient_funds(amount),
+            ctx.accounts.token_vault.amount >= amount,
             FlashLoanError::InsufficientPoolBalance
         );
-        
-        msg!("💰 开始原子性闪电贷与套利: {} lamports", amount);
-        Ok(fee)
-    }
+        FlashLoanHandler::ensure_within_borrow_caps(
+            pool_state,
+            &ctx.accounts.borrow_tracker,
+            amount,
+            ctx.accounts.token_vault.amount,
+        )?;
+        let fee = pool_state.calculate_fee(amount)?;
 
-    /// 执行借款
-    pub fn execute_loan(
-        ctx: &mut Context<AtomicFlashLoanWithArbitrage>,
-        amount: u64,
-    ) -> Result<()> {
-        // 先更新池子状态 - 防止重入攻击 (CEI模式)
-        let mock_pool_state = &mut ctx.accounts.mock_pool_state;
-        mock_pool_state.balance -= amount;
-        mock_pool_state.total_borrowed += amount;
-        
-        msg!("🔒 已更新池子状态，防止重入攻击");
-        
-        // 然后进行实际SOL转账
-        **ctx.accounts.mock_pool_state.to_account_info().try_borrow_mut_lamports()? -= amount;
-        **ctx.accounts.borrower.to_account_info().try_borrow_mut_lamports()? += amount;
-        
-        msg!("✅ 已转账 {} lamports 给用户", amount);
-        Ok(())
-    }
+        let mint = &ctx.accounts.mint;
+        let arbitrage = &ctx.accounts.arbitrage;
+        require_keys_eq!(arbitrage.token_in_account.mint, mint.key(), FlashLoanError::InvalidTokenMint);
+        require_keys_eq!(arbitrage.user_token_x.mint, mint.key(), FlashLoanError::InvalidTokenMint);
+        require_keys_eq!(
+            arbitrage.user_token_x.owner,
+            ctx.accounts.borrower.key(),
+            FlashLoanError::UnauthorizedAccess
+        );
 
-    /// 执行套利
-    pub fn execute_arbitrage(
-        ctx: &Context<AtomicFlashLoanWithArbitrage>,
-        amount: u64,
-        min_expected_profit: u64,
-    ) -> Result<u64> {
-        Self::call_arbitrage_bot(ctx, amount, min_expected_profit)
-    }
+        let vault_balance_before = ctx.accounts.token_vault.amount;
+        vlog!("💰 开始代币闪电贷与套利: {} (mint {})", amount, mint.key());
 
-    /// 调用套利机器人（提取CPI逻辑）
-    pub fn call_arbitrage_bot(
-        ctx: &Context<AtomicFlashLoanWithArbitrage>,
-        amount: u64,
-        min_expected_profit: u64,
-    ) -> Result<u64> {
-        let cpi_accounts = arbitrage_bot::cpi::accounts::ExecuteArbitrageAtomic {
-            arbitrage_bot: ctx.accounts.arbitrage_bot.to_account_info(),
-            mock_dex_program: ctx.accounts.mock_dex_program.to_account_info(),
-            dex_pool_a: ctx.accounts.dex_pool_a.to_account_info(),
-            dex_a_token_x_vault: ctx.accounts.dex_a_token_x_vault.to_account_info(),
-            dex_a_token_y_vault: ctx.accounts.dex_a_token_y_vault.to_account_info(),
-            dex_pool_b: ctx.accounts.dex_pool_b.to_account_info(),
-            dex_b_token_x_vault: ctx.accounts.dex_b_token_x_vault.to_account_info(),
-            dex_b_token_y_vault: ctx.accounts.dex_b_token_y_vault.to_account_info(),
-            token_in_account: ctx.accounts.token_in_account.to_account_info(),
-            user_token_x: ctx.accounts.user_token_x.to_account_info(),
-            user_token_y: ctx.accounts.user_token_y.to_account_info(),
-            token_program: ctx.accounts.token_program.to_account_info(),
-            payer: ctx.accounts.borrower.to_account_info(),
-            system_program: ctx.accounts.system_program.to_account_info(),
-        };
+        // === EFFECTS阶段：借款计入当前 slot 的累计 ===
+        FlashLoanHandler::record_slot_borrow(
+            &mut ctx.accounts.borrow_tracker,
+            ctx.accounts.mock_pool_state.key(),
+            ctx.bumps.borrow_tracker,
+            amount,
+        )?;
 
-        let cpi_ctx = CpiContext::new(
-            ctx.accounts.arbitrage_bot_program.to_account_info(),
-            cpi_accounts,
-        );
+        // === 借款阶段：vault 按 PDA 签名转出 ===
+        let pool_key = ctx.accounts.mock_pool_state.key();
+        let mint_key = mint.key();
+        let vault_bump = [ctx.bumps.token_vault];
+        let vault_seeds = &[
+            b"token_vault".as_ref(),
+            pool_key.as_ref(),
+            mint_key.as_ref(),
+            &vault_bump,
+        ];
+        let signer_seeds = &[&vault_seeds[..]];
 
-        let result = arbitrage_bot::cpi::execute_arbitrage_atomic(cpi_ctx, amount, min_expected_profit)?.get();
-        msg!("✅ 套利完成，获得利润: {} lamports", result);
-        Ok(result)
-    }
+        // === 抵押阶段：借款前把抵押转入 vault ===
+        if let Some(collateral_account) = ctx.accounts.collateral_account.as_ref().filter(|_| collateral_amount > 0) {
+            token::transfer_checked(
+                CpiContext::new(
+                    ctx.accounts.token_program.to_account_info(),
+                    TransferChecked {
+                        from: collateral_account.to_account_info(),
+                        mint: mint.to_account_info(),
+                        to: ctx.accounts.token_vault.to_account_info(),
+                        authority: ctx.accounts.borrower.to_account_info(),
+                    },
+                ),
+                collateral_amount,
+                mint.decimals,
+            )?;
+            vlog!("🔒 已锁定抵押 {} 代币", collateral_amount);
+        }
 
-    /// 处理还款
-    pub fn process_repayment(
-        ctx: &mut Context<AtomicFlashLoanWithArbitrage>,
-        amount: u64,
-        fee: u64,
-    ) -> Result<()> {
-        let total_repayment = amount + fee;
-        
-        // 检查用户是否有足够资金还款
+        token::transfer_checked(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                TransferChecked {
+                    from: ctx.accounts.token_vault.to_account_info(),
+                    mint: mint.to_account_info(),
+                    to: arbitrage.token_in_account.to_account_info(),
+                    authority: ctx.accounts.token_vault.to_account_info(),
+                },
+                signer_seeds,
+            ),
+            amount,
+            mint.decimals,
+        )?;
+        vlog!("✅ 已借出 {} 代币到套利输入账户", amount);
+
+        // === INTERACTIONS阶段 ===
+        let actual_profit = FlashLoanHandler::call_arbitrage_bot(
+            arbitrage,
+            &ctx.accounts.borrower,
+            &ctx.accounts.token_program,
+            &ctx.accounts.system_program,
+            amount,
+            min_expected_profit,
+        )?
+        .profit;
+
+        // === 还款阶段：borrower 签名从 user_token_x 归还本金和费用，不足部分由抵押补足 ===
+        let total_repayment = amount.checked_add(fee).ok_or(FlashLoanError::Overflow)?;
+        ctx.accounts.arbitrage.user_token_x.reload()?;
+        let repaid_by_user = ctx.accounts.arbitrage.user_token_x.amount.min(total_repayment);
+        let collateral_seized = total_repayment - repaid_by_user;
         require!(
-            ctx.accounts.borrower.lamports() >= total_repayment,
+            collateral_seized <= collateral_amount,
             FlashLoanError::InsufficientFundsForRepayment
         );
-        
-        // 先更新池子状态 - 防止重入攻击
-        let mock_pool_state = &mut ctx.accounts.mock_pool_state;
-        mock_pool_state.balance += total_repayment;
-        mock_pool_state.total_repaid += total_repayment;
-        
-        msg!("🔒 已更新还款状态，防止重入攻击");
-        
-        // 然后进行实际SOL转账
-        **ctx.accounts.borrower.to_account_info().try_borrow_mut_lamports()? -= total_repayment;
-        **ctx.accounts.mock_pool_state.to_account_info().try_borrow_mut_lamports()? += total_repayment;
-        
-        msg!("✅ 已归还 {} lamports (本金 {} + 费用 {})", total_repayment, amount, fee);
+        if repaid_by_user > 0 {
+            token::transfer_checked(
+                CpiContext::new(
+                    ctx.accounts.token_program.to_account_info(),
+                    TransferChecked {
+                        from: ctx.accounts.arbitrage.user_token_x.to_account_info(),
+                        mint: mint.to_account_info(),
+                        to: ctx.accounts.token_vault.to_account_info(),
+                        authority: ctx.accounts.borrower.to_account_info(),
+                    },
+                ),
+                repaid_by_user,
+                mint.decimals,
+            )?;
+        }
+
+        // 未扣除的抵押由 vault 按 PDA 签名退回
+        let collateral_refund = collateral_amount - collateral_seized;
+        if let Some(collateral_account) = ctx.accounts.collateral_account.as_ref().filter(|_| collateral_refund > 0) {
+            token::transfer_checked(
+                CpiContext::new_with_signer(
+                    ctx.accounts.token_program.to_account_info(),
+                    TransferChecked {
+                        from: ctx.accounts.token_vault.to_account_info(),
+                        mint: mint.to_account_info(),
+                        to: collateral_account.to_account_info(),
+                        authority: ctx.accounts.token_vault.to_account_info(),
+                    },
+                    signer_seeds,
+                ),
+                collateral_refund,
+                mint.decimals,
+            )?;
+        }
+        if collateral_seized > 0 {
+            vlog!("⚠️ 套利收益不足以还款，已扣除抵押 {} 代币", collateral_seized);
+        }
+
+        // 按 vault 实际余额核对：同一指令内必须收回本金并多出费用
+        ctx.accounts.token_vault.reload()?;
+        let expected_balance = vault_balance_before.checked_add(fee).ok_or(FlashLoanError::Overflow)?;
+        require!(
+            ctx.accounts.token_vault.amount >= expected_balance,
+            FlashLoanError::RepaymentNotReceived
+        );
+
+        emit!(TokenFlashLoanCompleted {
+            user: ctx.accounts.borrower.key(),
+            mint: mint.key(),
+            loan_amount: amount,
+            fee,
+            profit: actual_profit,
+            collateral_seized,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        vlog!("✅ 已归还 {} 代币 (本金 {} + 费用 {})", total_repayment, amount, fee);
         Ok(())
     }
 
-    /// 记录交易
-    pub fn record_transaction(
-        ctx: &mut Context<AtomicFlashLoanWithArbitrage>,
+    /// 回调式代币闪电贷 - 借出后调用调用方指定的执行程序，执行程序可以是任意策略
+    /// 执行程序以 executor_data 为指令数据、remaining_accounts 为账户列表被调用（签名与可写标记原样传递）；
+    /// 回调结束后从 borrower_token_account 归还本金和费用，并按 vault 实际余额核对
+    pub fn flash_loan_with_callback<'info>(
+        ctx: Context<'_, '_, 'info, 'info, FlashLoanWithCallback<'info>>,
         amount: u64,
-        fee: u64,
-        actual_profit: u64,
+        executor_data: Vec<u8>,
     ) -> Result<()> {
-        let transaction_record = &mut ctx.accounts.transaction_record;
-        transaction_record.transaction_id = Clock::get()?.unix_timestamp as u64;
-        transaction_record.user = ctx.accounts.borrower.key();
-        transaction_record.loan_amount = amount;
-        transaction_record.fee = fee;
-        transaction_record.profit = actual_profit;
-        transaction_record.net_profit = actual_profit.saturating_sub(fee);
-        transaction_record.timestamp = Clock::get()?.unix_timestamp;
-        transaction_record.bump = ctx.bumps.transaction_record;
-        
-        emit!(AtomicFlashLoanCompleted {
+        // === CHECK阶段 ===
+        require!(amount > 0, FlashLoanError::InvalidAmount);
+        let pool_state = &ctx.accounts.mock_pool_state;
+        FlashLoanHandler::ensure_pool_lendable(pool_state)?;
+        FlashLoanHandler::ensure_borrower_allowed(&ctx.accounts.borrower_allowlist, &ctx.accounts.borrower.key())?;
+        require!(
+            ctx.accounts.token_vault.amount >= amount,
+            FlashLoanError::InsufficientPoolBalance
+        );
+        // 执行程序不能回调本程序，避免在借款未归还时重入
+        require_keys_neq!(
+            ctx.accounts.executor_program.key(),
+            crate::ID,
+            FlashLoanError::InvalidExecutorProgram
+        );
+        FlashLoanHandler::ensure_within_borrow_caps(
+            pool_state,
+            &ctx.accounts.borrow_tracker,
+            amount,
+            ctx.accounts.token_vault.amount,
+        )?;
+        let fee = pool_state.calculate_fee(amount)?;
+        let vault_balance_before = ctx.accounts.token_vault.amount;
+
+        // === EFFECTS阶段：借款计入当前 slot 的累计 ===
+        FlashLoanHandler::record_slot_borrow(
+            &mut ctx.accounts.borrow_tracker,
+            ctx.accounts.mock_pool_state.key(),
+            ctx.bumps.borrow_tracker,
+            amount,
+        )?;
+
+        // === 借款阶段：vault 按 PDA 签名转出 ===
+        let pool_key = ctx.accounts.mock_pool_state.key();
+        let mint_key = ctx.accounts.mint.key();
+        let vault_bump = [ctx.bumps.token_vault];
+        let vault_seeds = &[
+            b"token_vault".as_ref(),
+            pool_key.as_ref(),
+            mint_key.as_ref(),
+            &vault_bump,
+        ];
+        let signer_seeds = &[&vault_seeds[..]];
+        token::transfer_checked(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                TransferChecked {
+                    from: ctx.accounts.token_vault.to_account_info(),
+                    mint: ctx.accounts.mint.to_account_info(),
+                    to: ctx.accounts.borrower_token_account.to_account_info(),
+                    authority: ctx.accounts.token_vault.to_account_info(),
+                },
+                signer_seeds,
+            ),
+            amount,
+            ctx.accounts.mint.decimals,
+        )?;
+        vlog!("💸 已借出 {}，调用执行程序 {}", amount, ctx.accounts.executor_program.key());
+
+        // === INTERACTIONS阶段：调用执行程序 ===
+        FlashLoanHandler::invoke_executor(
+            &ctx.accounts.executor_program.to_account_info(),
+            ctx.remaining_accounts,
+            executor_data,
+        )?;
+
+        // === 还款阶段：borrower 签名归还本金和费用 ===
+        let total_repayment = amount.checked_add(fee).ok_or(FlashLoanError::Overflow)?;
+        ctx.accounts.borrower_token_account.reload()?;
+        require!(
+            ctx.accounts.borrower_token_account.amount >= total_repayment,
+            FlashLoanError::InsufficientFundsForRepayment
+        );
+        token::transfer_checked(
+            CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                TransferChecked {
+                    from: ctx.accounts.borrower_token_account.to_account_info(),
+                    mint: ctx.accounts.mint.to_account_info(),
+                    to: ctx.accounts.token_vault.to_account_info(),
+                    authority: ctx.accounts.borrower.to_account_info(),
+                },
+            ),
+            total_repayment,
+            ctx.accounts.mint.decimals,
+        )?;
+
+        // 按 vault 实际余额核对：执行程序无论做了什么，vault 必须收回本金并多出费用
+        ctx.accounts.token_vault.reload()?;
+        let expected_balance = vault_balance_before.checked_add(fee).ok_or(FlashLoanError::Overflow)?;
+        require!(
+            ctx.accounts.token_vault.amount >= expected_balance,
+            FlashLoanError::RepaymentNotReceived
+        );
+
+        emit!(CallbackFlashLoanCompleted {
             user: ctx.accounts.borrower.key(),
-            transaction_id: transaction_record.transaction_id,
+            mint: mint_key,
+            executor_program: ctx.accounts.executor_program.key(),
             loan_amount: amount,
             fee,
-            net_profit: transaction_record.net_profit,
             timestamp: Clock::get()?.unix_timestamp,
         });
-        
-        msg!("✅ 套利闪电贷完成! 净利润: {} lamports", transaction_record.net_profit);
+
+        vlog!("✅ 回调闪电贷完成，已归还 {} (本金 {} + 费用 {})", total_repayment, amount, fee);
         Ok(())
     }
-}
 
-#[derive(Accounts)]
-#[instruction(amount: u64, min_expected_profit: u64, description: String)]
-pub struct AtomicFlashLoanWithArbitrage<'info> {
-    #[account(
-        mut,
-        seeds = [b"mock_pool_state"],
-        bump = mock_pool_state.bump,
-    )]
-    pub mock_pool_state: Account<'info, MockPoolState>,
+    /// 借出 SOL 和多种 SPL 代币，必须在同一交易中由后续的 flash_repay 逐项归还 - 不绑定任何套利策略
+    /// sol_amount 为 0 时不借 SOL；token_amounts 与 remaining_accounts 中的代币逐一对应。
+    /// 通过 instructions sysvar 检查：本指令不能经 CPI 调用，之后必须有一条 flash_repay 指向本指令，
+    /// 每种资产的归还数量等于本金加费用且使用同一组 vault；在那之前不能再出现 flash_borrow。
+    /// 两条指令之间可以插入任意用户程序的指令
+    pub fn flash_borrow<'info>(
+        ctx: Context<'_, '_, 'info, 'info, FlashBorrow<'info>>,
+        sol_amount: u64,
+        token_amounts: Vec<u64>,
+    ) -> Result<()> {
+        // === CHECK阶段 ===
+        require!(
+            (sol_amount > 0 || !token_amounts.is_empty()) && token_amounts.len() <= MAX_FLASH_TOKEN_ASSETS,
+            FlashLoanError::InvalidAmount
+        );
+        require!(
+            ctx.remaining_accounts.len() == token_amounts.len() * FLASH_ACCOUNTS_PER_TOKEN,
+            FlashLoanError::InvalidFlashAssetAccounts
+        );
+        let pool_state = &ctx.accounts.mock_pool_state;
+        FlashLoanHandler::ensure_pool_lendable(pool_state)?;
+        FlashLoanHandler::ensure_borrower_allowed(&ctx.accounts.borrower_allowlist, &ctx.accounts.borrower.key())?;
+        require!(
+            pool_state.has_sufficient_funds(sol_amount),
+            FlashLoanError::InsufficientPoolBalance
+        );
+        if sol_amount > 0 {
+            FlashLoanHandler::ensure_within_borrow_caps(
+                pool_state,
+                &ctx.accounts.borrow_tracker,
+                sol_amount,
+                pool_state.balance,
+            )?;
+        }
 
-    #[account(
-        init,
-        payer = borrower,
-        seeds = [b"transaction_record", borrower.key().as_ref(), &Clock::get().unwrap_or_default().unix_timestamp.to_le_bytes()],
-        bump,
-        space = TransactionRecord::SPACE,
-    )]
-    pub transaction_record: Account<'info, TransactionRecord>,
+        let sol_repay = FlashLoanHandler::repay_amount(pool_state, sol_amount)?;
+        let token_repays = token_amounts
+            .iter()
+            .map(|&amount| FlashLoanHandler::repay_amount(pool_state, amount))
+            .collect::<Result<Vec<u64>>>()?;
+        let vault_keys: Vec<Pubkey> = ctx
+            .remaining_accounts
+            .chunks(FLASH_ACCOUNTS_PER_TOKEN)
+            .map(|asset_accounts| asset_accounts[1].key())
+            .collect();
 
-    #[account(mut)]
-    pub borrower: Signer<'info>,
+        let instructions = ctx.accounts.instructions.to_account_info();
+        FlashLoanHandler::ensure_top_level(&instructions)?;
+        FlashLoanHandler::find_flash_repay(
+            &instructions,
+            ctx.accounts.mock_pool_state.key(),
+            &vault_keys,
+            sol_repay,
+            &token_repays,
+        )?;
 
-    // 套利机器人相关账户
-    /// CHECK: arbitrage_bot程序
-    pub arbitrage_bot_program: AccountInfo<'info>,
+        let now = Clock::get()?.unix_timestamp;
 
-    #[account(mut)]
-    pub arbitrage_bot: Account<'info, arbitrage_bot::ArbitrageBotState>,
+        // === SOL：与 atomic_flash_loan_with_arbitrage 相同，先更新池子状态再从 SOL vault 转出 ===
+        if sol_amount > 0 {
+            let mock_pool_state = &mut ctx.accounts.mock_pool_state;
+            mock_pool_state.balance -= sol_amount;
+            mock_pool_state.total_borrowed += sol_amount;
+            FlashLoanHandler::record_slot_borrow(
+                &mut ctx.accounts.borrow_tracker,
+                ctx.accounts.mock_pool_state.key(),
+                ctx.bumps.borrow_tracker,
+                sol_amount,
+            )?;
 
-    // DEX和代币相关账户
-    /// CHECK: mock_dex程序
-    pub mock_dex_program: AccountInfo<'info>,
-    
-    /// CHECK: DEX A的池子
-    #[account(mut)]
-    pub dex_pool_a: AccountInfo<'info>,
+            FlashLoanHandler::transfer_from_sol_vault(
+                ctx.accounts.mock_pool_state.key(),
+                &ctx.accounts.sol_vault,
+                ctx.bumps.sol_vault,
+                ctx.accounts.borrower.to_account_info(),
+                &ctx.accounts.system_program,
+                sol_amount,
+            )?;
 
-    /// CHECK: DEX A的Token X vault
-    #[account(mut)]
-    pub dex_a_token_x_vault: Account<'info, TokenAccount>,
+            emit!(FlashBorrowed {
+                user: ctx.accounts.borrower.key(),
+                mint: Pubkey::default(),
+                amount: sol_amount,
+                fee: sol_repay - sol_amount,
+                timestamp: now,
+            });
+            vlog!("💸 flash_borrow: 借出 {} lamports，须归还 {}", sol_amount, sol_repay);
+        }
 
-    /// CHECK: DEX A的Token Y vault  
-    #[account(mut)]
-    pub dex_a_token_y_vault: Account<'info, TokenAccount>,
+        // === SPL 代币：各 vault 按 PDA 签名转出 ===
+        let pool_key = ctx.accounts.mock_pool_state.key();
+        for ((&amount, &repay), asset_accounts) in token_amounts
+            .iter()
+            .zip(token_repays.iter())
+            .zip(ctx.remaining_accounts.chunks(FLASH_ACCOUNTS_PER_TOKEN))
+        {
+            require!(amount > 0, FlashLoanError::InvalidAmount);
+            let (mint, token_vault, borrower_token_account, vault_bump) =
+                FlashLoanHandler::load_token_asset(pool_key, asset_accounts)?;
+            require!(token_vault.amount >= amount, FlashLoanError::InsufficientPoolBalance);
+            FlashLoanHandler::ensure_within_borrow_caps(
+                &ctx.accounts.mock_pool_state,
+                &ctx.accounts.borrow_tracker,
+                amount,
+                token_vault.amount,
+            )?;
+            FlashLoanHandler::record_slot_borrow(&mut ctx.accounts.borrow_tracker, pool_key, ctx.bumps.borrow_tracker, amount)?;
 
-    /// CHECK: DEX B的池子
-    #[account(mut)]
-    pub dex_pool_b: AccountInfo<'info>,
+            let mint_key = mint.key();
+            let vault_bump = [vault_bump];
+            let vault_seeds = &[
+                b"token_vault".as_ref(),
+                pool_key.as_ref(),
+                mint_key.as_ref(),
+                &vault_bump,
+            ];
+            let signer_seeds = &[&vault_seeds[..]];
+            token::transfer_checked(
+                CpiContext::new_with_signer(
+                    ctx.accounts.token_program.to_account_info(),
+                    TransferChecked {
+                        from: token_vault.to_account_info(),
+                        mint: mint.to_account_info(),
+                        to: borrower_token_account.to_account_info(),
+                        authority: token_vault.to_account_info(),
+                    },
+                    signer_seeds,
+                ),
+                amount,
+                mint.decimals,
+            )?;
 
-    /// CHECK: DEX B的Token X vault
-    #[account(mut)]
-    pub dex_b_token_x_vault: Account<'info, TokenAccount>,
+            emit!(FlashBorrowed {
+                user: ctx.accounts.borrower.key(),
+                mint: mint_key,
+                amount,
+                fee: repay - amount,
+                timestamp: now,
+            });
+            vlog!("💸 flash_borrow: 借出 {} (mint {})，须归还 {}", amount, mint_key, repay);
+        }
 
-    /// CHECK: DEX B的Token Y vault
-    #[account(mut)]
-    pub dex_b_token_y_vault: Account<'info, TokenAccount>,
+        Ok(())
+    }
 
-    /// CHECK: 输入代币账户
-    #[account(mut)]
-    pub token_in_account: Account<'info, TokenAccount>,
+    /// 归还 flash_borrow 借出的全部资产 - borrow_instruction_index 为对应 flash_borrow 在交易中的位置
+    /// sol_repay_amount / token_repay_amounts 必须逐项等于该笔借款各资产的本金加费用，代币账户顺序与借款时一致
+    pub fn flash_repay<'info>(
+        ctx: Context<'_, '_, 'info, 'info, FlashRepay<'info>>,
+        sol_repay_amount: u64,
+        token_repay_amounts: Vec<u64>,
+        borrow_instruction_index: u8,
+    ) -> Result<()> {
+        // === CHECK阶段 ===
+        require!(
+            ctx.remaining_accounts.len() == token_repay_amounts.len() * FLASH_ACCOUNTS_PER_TOKEN,
+            FlashLoanError::InvalidFlashAssetAccounts
+        );
+        let instructions = ctx.accounts.instructions.to_account_info();
+        FlashLoanHandler::ensure_top_level(&instructions)?;
 
-    /// CHECK: 用户Token X账户
-    #[account(mut)]
-    pub user_token_x: Account<'info, TokenAccount>,
+        let current_index = load_current_index_checked(&instructions)?;
+        require!(
+            (borrow_instruction_index as u16) < current_index,
+            FlashLoanError::InvalidFlashRepay
+        );
+        let borrow_ix = load_instruction_at_checked(borrow_instruction_index as usize, &instructions)?;
+        require!(
+            borrow_ix.program_id == crate::ID && borrow_ix.data.starts_with(instruction::FlashBorrow::DISCRIMINATOR),
+            FlashLoanError::InvalidFlashRepay
+        );
+        let borrow_args = instruction::FlashBorrow::try_from_slice(
+            &borrow_ix.data[instruction::FlashBorrow::DISCRIMINATOR.len()..],
+        )?;
+        // 借款与还款必须针对同一个池子，池子是两条指令的第一个账户
+        require!(
+            borrow_ix.accounts.first().map(|meta| meta.pubkey) == Some(ctx.accounts.mock_pool_state.key()),
+            FlashLoanError::InvalidFlashRepay
+        );
 
-    /// CHECK: 用户Token Y账户
-    #[account(mut)]
-    pub user_token_y: Account<'info, TokenAccount>,
+        // 池子在借款后被切换到紧急状态时拒绝还款，整笔交易连同借款一起回滚
+        let pool_state = &ctx.accounts.mock_pool_state;
+        require!(!pool_state.is_emergency(), FlashLoanError::PoolEmergency);
 
-    pub token_program: Program<'info, Token>,
-    pub system_program: Program<'info, System>,
-}
+        // 每项资产的归还数量和 vault 都必须与借款逐一对应
+        require!(
+            FlashLoanHandler::repay_amount(pool_state, borrow_args.sol_amount)? == sol_repay_amount,
+            FlashLoanError::InvalidFlashRepay
+        );
+        require!(
+            borrow_args.token_amounts.len() == token_repay_amounts.len(),
+            FlashLoanError::InvalidFlashRepay
+        );
+        for (&amount, &repay) in borrow_args.token_amounts.iter().zip(token_repay_amounts.iter()) {
+            require!(
+                FlashLoanHandler::repay_amount(pool_state, amount)? == repay,
+                FlashLoanError::InvalidFlashRepay
+            );
+        }
+        let vault_keys: Vec<Pubkey> = ctx
+            .remaining_accounts
+            .chunks(FLASH_ACCOUNTS_PER_TOKEN)
+            .map(|asset_accounts| asset_accounts[1].key())
+            .collect();
+        require!(
+            FlashLoanHandler::flash_vault_keys(&borrow_ix, FLASH_BORROW_FIXED_ACCOUNTS, vault_keys.len()) == Some(vault_keys),
+            FlashLoanError::InvalidFlashRepay
+        );
 
-#[derive(Accounts)]
-#[instruction(user: Pubkey)]
-pub struct GetTransactionRecord<'info> {
-    #[account(
-        seeds = [b"transaction_record", user.as_ref()],
-        bump,
-    )]
-    pub transaction_record: Account<'info, TransactionRecord>,
-}
+        let now = Clock::get()?.unix_timestamp;
 
-#[event]
-pub struct AtomicFlashLoanCompleted {
-    pub user: Pubkey,
-    pub transaction_id: u64,
-    pub loan_amount: u64,
-    pub fee: u64,
-    pub net_profit: u64,
+        // === SOL：borrower 签名经系统程序转回池子 ===
+        if sol_repay_amount > 0 {
+            let mock_pool_state = &mut ctx.accounts.mock_pool_state;
+            mock_pool_state.balance += sol_repay_amount;
+            mock_pool_state.total_repaid += sol_repay_amount;
+
+            system_program::transfer(
+                CpiContext::new(
+                    ctx.accounts.system_program.to_account_info(),
+                    system_program::Transfer {
+                        from: ctx.accounts.borrower.to_account_info(),
+                        to: ctx.accounts.sol_vault.to_account_info(),
+                    },
+                ),
+                sol_repay_amount,
+            )?;
+
+            emit!(FlashRepaid {
+                user: ctx.accounts.borrower.key(),
+                mint: Pubkey::default(),
+                amount: borrow_args.sol_amount,
+                fee: sol_repay_amount - borrow_args.sol_amount,
+                timestamp: now,
+            });
+        }
+
+        // === SPL 代币：borrower 签名逐项归还 ===
+        let pool_key = ctx.accounts.mock_pool_state.key();
+        for ((&amount, &repay), asset_accounts) in borrow_args
+            .token_amounts
+            .iter()
+            .zip(token_repay_amounts.iter())
+            .zip(ctx.remaining_accounts.chunks(FLASH_ACCOUNTS_PER_TOKEN))
+        {
+            let (mint, token_vault, borrower_token_account, _) =
+                FlashLoanHandler::load_token_asset(pool_key, asset_accounts)?;
+            token::transfer_checked(
+                CpiContext::new(
+                    ctx.accounts.token_program.to_account_info(),
+                    TransferChecked {
+                        from: borrower_token_account.to_account_info(),
+                        mint: mint.to_account_info(),
+                        to: token_vault.to_account_info(),
+                        authority: ctx.accounts.borrower.to_account_info(),
+                    },
+                ),
+                repay,
+                mint.decimals,
+            )?;
+
+            emit!(FlashRepaid {
+                user: ctx.accounts.borrower.key(),
+                mint: mint.key(),
+                amount,
+                fee: repay - amount,
+                timestamp: now,
+            });
+        }
+
+        vlog!(
+            "✅ flash_repay: 已归还 {} lamports 和 {} 种代币",
+            sol_repay_amount,
+            token_repay_amounts.len()
+        );
+        Ok(())
+    }
+
+    /// 查询交易记录 - 只读函数，按 (user, index) 定位，index 即记录编号
+    pub fn get_transaction_record(ctx: Context<GetTransactionRecord>, user: Pubkey, index: u64) -> Result<()> {
+        let transaction_record = &ctx.accounts.transaction_record;
+        
+        // 验证用户和编号匹配
+        require!(
+            transaction_record.user == user && transaction_record.transaction_id == index,
+            FlashLoanError::UnauthorizedAccess
+        );
+        
+        FlashLoanHandler::log_transaction_record(transaction_record);
+        Ok(())
+    }
+
+    /// 查询用户最近一笔交易记录 - 只读函数，记录地址取自 user_stats.latest_record
+    pub fn get_latest_transaction_record(ctx: Context<GetLatestTransactionRecord>, user: Pubkey) -> Result<()> {
+        let transaction_record = &ctx.accounts.transaction_record;
+        require!(transaction_record.user == user, FlashLoanError::UnauthorizedAccess);
+
+        FlashLoanHandler::log_transaction_record(transaction_record);
+        Ok(())
+    }
+
+    /// 清理过期交易记录 - 无需权限，任何人都可以调用
+    /// 租金全部退还给记录所属用户，赏金从记录所属池子的协议 treasury 支付给调用者
+    pub fn close_expired_record(ctx: Context<CloseExpiredRecord>) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        let record = &ctx.accounts.transaction_record;
+
+        require!(
+            now.saturating_sub(record.timestamp) >= RECORD_RETENTION_SECS,
+            FlashLoanError::RecordNotExpired
+        );
+
+        let transaction_id = record.transaction_id;
+
+        // 租金由 close 约束退还给用户，赏金不占用用户的租金
+        let bounty = FlashLoanHandler::pay_crank_bounty(
+            record.pool,
+            &ctx.accounts.protocol_treasury,
+            ctx.bumps.protocol_treasury,
+            ctx.accounts.cranker.to_account_info(),
+            &ctx.accounts.system_program,
+        )?;
+
+        emit!(TransactionRecordClosed {
+            user: ctx.accounts.user.key(),
+            transaction_id,
+            cranker: ctx.accounts.cranker.key(),
+            bounty,
+            timestamp: now,
+        });
+
+        vlog!("🧹 已清理过期交易记录 {}，赏金: {} lamports", transaction_id, bounty);
+        Ok(())
+    }
+
+    /// 批量清理过期交易记录 - 无需权限
+    /// remaining_accounts 按 [record, user] 成对传入，记录须属于同一个池子，未过期的记录会被跳过
+    pub fn sweep_expired_records<'info>(
+        ctx: Context<'_, '_, 'info, 'info, SweepExpiredRecords<'info>>,
+    ) -> Result<()> {
+        let remaining = ctx.remaining_accounts;
+        require!(
+            !remaining.is_empty() && remaining.len() % 2 == 0,
+            FlashLoanError::InvalidSweepAccounts
+        );
+
+        let now = Clock::get()?.unix_timestamp;
+        let cranker_info = ctx.accounts.cranker.to_account_info();
+        let mut closed_count: u64 = 0;
+        let mut total_bounty: u64 = 0;
+
+        for pair in remaining.chunks(2) {
+            let record_info = &pair[0];
+            let user_info = &pair[1];
+
+            let record: Account<'info, TransactionRecord> = Account::try_from(record_info)?;
+            require_keys_eq!(record.user, user_info.key(), FlashLoanError::UnauthorizedAccess);
+            require_keys_eq!(record.pool, ctx.accounts.pool.key(), FlashLoanError::InvalidSweepAccounts);
+
+            if now.saturating_sub(record.timestamp) < RECORD_RETENTION_SECS {
+                continue;
+            }
+
+            let bounty = FlashLoanHandler::pay_crank_bounty(
+                record.pool,
+                &ctx.accounts.protocol_treasury,
+                ctx.bumps.protocol_treasury,
+                cranker_info.clone(),
+                &ctx.accounts.system_program,
+            )?;
+            record.close(user_info.clone())?;
+
+            emit!(TransactionRecordClosed {
+                user: user_info.key(),
+                transaction_id: record.transaction_id,
+                cranker: ctx.accounts.cranker.key(),
+                bounty,
+                timestamp: now,
+            });
+
+            closed_count += 1;
+            total_bounty = total_bounty.checked_add(bounty).ok_or(FlashLoanError::Overflow)?;
+        }
+
+        vlog!("🧹 批量清理完成: 关闭 {} 条记录，赏金合计: {} lamports", closed_count, total_bounty);
+        Ok(())
+    }
+
+    /// 为池子创建 slot 借款累计账户 - 任何人都可以付费创建
+    /// 各借款指令在首次借款时自动创建；external_flash_loan_with_callback 从 remaining_accounts 读取该账户，须事先创建
+    pub fn initialize_borrow_tracker(ctx: Context<InitializeBorrowTracker>) -> Result<()> {
+        let borrow_tracker = &mut ctx.accounts.borrow_tracker;
+        borrow_tracker.pool = ctx.accounts.mock_pool_state.key();
+        borrow_tracker.borrow_slot = 0;
+        borrow_tracker.borrowed_in_slot = 0;
+        borrow_tracker.bump = ctx.bumps.borrow_tracker;
+
+        vlog!("📋 已创建 slot 借款累计账户");
+        Ok(())
+    }
+
+    /// 为池子创建借款人名单 - 仅池子 authority 可调用，创建后默认不启用
+    pub fn initialize_borrower_allowlist(ctx: Context<InitializeBorrowerAllowlist>) -> Result<()> {
+        let allowlist = &mut ctx.accounts.borrower_allowlist;
+        allowlist.pool = ctx.accounts.mock_pool_state.key();
+        allowlist.enabled = false;
+        allowlist.bump = ctx.bumps.borrower_allowlist;
+        allowlist.borrowers = Vec::new();
+
+        vlog!("📋 已创建借款人名单");
+        Ok(())
+    }
+
+    /// 把借款人加入名单 - 仅池子 authority 可调用，已在名单中时不重复添加
+    pub fn add_allowed_borrower(ctx: Context<UpdateBorrowerAllowlist>, borrower: Pubkey) -> Result<()> {
+        let allowlist = &mut ctx.accounts.borrower_allowlist;
+        if !allowlist.is_allowed(&borrower) {
+            require!(
+                allowlist.borrowers.len() < MAX_ALLOWED_BORROWERS,
+                FlashLoanError::BorrowerAllowlistFull
+            );
+            allowlist.borrowers.push(borrower);
+        }
+
+        emit!(AllowedBorrowerUpdated {
+            borrower,
+            allowed: true,
+            authority: ctx.accounts.authority.key(),
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        vlog!("🪪 允许借款人: {}", borrower);
+        Ok(())
+    }
+
+    /// 把借款人移出名单 - 仅池子 authority 可调用
+    pub fn remove_allowed_borrower(ctx: Context<UpdateBorrowerAllowlist>, borrower: Pubkey) -> Result<()> {
+        let allowlist = &mut ctx.accounts.borrower_allowlist;
+        let index = allowlist
+            .borrowers
+            .iter()
+            .position(|allowed| *allowed == borrower)
+            .ok_or(FlashLoanError::AllowedBorrowerNotFound)?;
+        allowlist.borrowers.swap_remove(index);
+
+        emit!(AllowedBorrowerUpdated {
+            borrower,
+            allowed: false,
+            authority: ctx.accounts.authority.key(),
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        vlog!("🪪 移除借款人: {}", borrower);
+        Ok(())
+    }
+
+    /// 开关借款人名单 - 仅池子 authority 可调用
+    /// 启用后所有借款指令（套利闪电贷、代币闪电贷、回调闪电贷和 flash_borrow）只接受名单中的 borrower
+    pub fn set_borrower_allowlist_enabled(ctx: Context<UpdateBorrowerAllowlist>, enabled: bool) -> Result<()> {
+        ctx.accounts.borrower_allowlist.enabled = enabled;
+
+        emit!(BorrowerAllowlistToggled {
+            enabled,
+            authority: ctx.accounts.authority.key(),
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        vlog!("🔐 借款人名单启用: {}", enabled);
+        Ok(())
+    }
+
+    /// 从 adapter 选择的借贷来源借入代币，调用执行程序后按来源的规则归还 - 流程与 flash_loan_with_callback 相同
+    /// remaining_accounts 开头的 adapter.account_count() 个账户属于借贷来源，其余原样传给执行程序
+    pub fn external_flash_loan_with_callback<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ExternalFlashLoanWithCallback<'info>>,
+        adapter: LenderAdapterId,
+        amount: u64,
+        executor_data: Vec<u8>,
+    ) -> Result<()> {
+        // === CHECK阶段 ===
+        require!(amount > 0, FlashLoanError::InvalidAmount);
+        let lender_account_count = adapter.account_count();
+        require!(
+            ctx.remaining_accounts.len() >= lender_account_count,
+            FlashLoanError::InvalidAdapterAccounts
+        );
+        // 执行程序不能回调本程序或借贷来源，避免在借款未归还时重入
+        require!(
+            ctx.accounts.executor_program.key() != crate::ID
+                && ctx.accounts.executor_program.key() != ctx.accounts.lender_program.key(),
+            FlashLoanError::InvalidExecutorProgram
+        );
+        let (lender_accounts, executor_accounts) = ctx.remaining_accounts.split_at(lender_account_count);
+        let mut lender = load_adapter(
+            adapter,
+            AdapterAccounts {
+                lender_program: ctx.accounts.lender_program.to_account_info(),
+                borrower: ctx.accounts.borrower.to_account_info(),
+                borrower_token_account: ctx.accounts.borrower_token_account.to_account_info(),
+                mint: ctx.accounts.mint.to_account_info(),
+                mint_decimals: ctx.accounts.mint.decimals,
+                token_program: ctx.accounts.token_program.to_account_info(),
+                instructions: ctx.accounts.instructions.to_account_info(),
+            },
+            lender_accounts,
+        )?;
+
+        // === 借款阶段 ===
+        lender.borrow(amount)?;
+        vlog!("💸 已从 {:?} 借出 {}，调用执行程序 {}", adapter, amount, ctx.accounts.executor_program.key());
+
+        // === INTERACTIONS阶段：调用执行程序 ===
+        FlashLoanHandler::invoke_executor(
+            &ctx.accounts.executor_program.to_account_info(),
+            executor_accounts,
+            executor_data,
+        )?;
+
+        // === 还款阶段：按还款前后的余额差得出来源实际收取的费用 ===
+        ctx.accounts.borrower_token_account.reload()?;
+        let balance_before_repay = ctx.accounts.borrower_token_account.amount;
+        lender.repay(amount)?;
+        ctx.accounts.borrower_token_account.reload()?;
+        let fee = balance_before_repay
+            .checked_sub(ctx.accounts.borrower_token_account.amount)
+            .and_then(|repaid| repaid.checked_sub(amount))
+            .ok_or(FlashLoanError::RepaymentNotReceived)?;
+
+        emit!(ExternalFlashLoanCompleted {
+            user: ctx.accounts.borrower.key(),
+            adapter,
+            lender_program: ctx.accounts.lender_program.key(),
+            mint: ctx.accounts.mint.key(),
+            executor_program: ctx.accounts.executor_program.key(),
+            loan_amount: amount,
+            fee,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        vlog!("✅ 外部闪电贷完成，已归还本金 {} + 费用 {}", amount, fee);
+        Ok(())
+    }
+
+    /// 取空已弃用池子的 SOL vault - 仅池子 authority 可调用
+    /// vault 的全部 lamports 转给 treasury，之后才能在 mock_pool 中关闭池子；池子状态随关闭一并回收
+    pub fn drain_sol_vault(ctx: Context<DrainSolVault>) -> Result<()> {
+        // === CHECK阶段 ===
+        require!(
+            ctx.accounts.mock_pool_state.status == PoolStatus::Deprecated,
+            FlashLoanError::PoolNotDeprecated
+        );
+        let amount = ctx.accounts.sol_vault.lamports();
+
+        // === INTERACTIONS阶段 ===
+        let pool_key = ctx.accounts.mock_pool_state.key();
+        let vault_bump = [ctx.bumps.sol_vault];
+        let vault_seeds = &[b"sol_vault".as_ref(), pool_key.as_ref(), &vault_bump];
+        system_program::transfer(
+            CpiContext::new_with_signer(
+                ctx.accounts.system_program.to_account_info(),
+                system_program::Transfer {
+                    from: ctx.accounts.sol_vault.to_account_info(),
+                    to: ctx.accounts.treasury.to_account_info(),
+                },
+                &[&vault_seeds[..]],
+            ),
+            amount,
+        )?;
+
+        emit!(SolVaultDrained {
+            pool: pool_key,
+            vault: ctx.accounts.sol_vault.key(),
+            amount,
+            treasury: ctx.accounts.treasury.key(),
+            authority: ctx.accounts.authority.key(),
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        vlog!("🏦 已取空 SOL vault，{} lamports 转给 {}", amount, ctx.accounts.treasury.key());
+        Ok(())
+    }
+
+    /// 为已完成的闪电贷签发回执 - 可选，借款人可以在借款指令之后的同一交易中调用
+    /// 回执是以交易记录为种子的 PDA，不能转让，汇总借款金额、费用和利润，便于其他程序据此构建信誉等功能
+    pub fn issue_loan_receipt(ctx: Context<IssueLoanReceipt>, _index: u64) -> Result<()> {
+        let transaction_record = &ctx.accounts.transaction_record;
+        let receipt = &mut ctx.accounts.loan_receipt;
+        receipt.borrower = ctx.accounts.borrower.key();
+        receipt.transaction_record = transaction_record.key();
+        receipt.transaction_id = transaction_record.transaction_id;
+        receipt.loan_amount = transaction_record.loan_amount;
+        receipt.fee = transaction_record.fee;
+        receipt.profit = transaction_record.profit;
+        receipt.issued_at = Clock::get()?.unix_timestamp;
+        receipt.bump = ctx.bumps.loan_receipt;
+
+        emit!(LoanReceiptIssued {
+            borrower: receipt.borrower,
+            receipt: receipt.key(),
+            transaction_id: receipt.transaction_id,
+            loan_amount: receipt.loan_amount,
+            fee: receipt.fee,
+            profit: receipt.profit,
+            timestamp: receipt.issued_at,
+        });
+
+        vlog!("🧾 已签发闪电贷回执: 第 {} 笔", receipt.transaction_id);
+        Ok(())
+    }
+
+    /// 创建池子的协议 treasury - 仅池子 authority 可调用
+    /// treasury 是系统程序所有的 PDA，由 authority 预存租金豁免下限，之后任意金额的利润分成都能转入
+    pub fn initialize_protocol_treasury(ctx: Context<InitializeProtocolTreasury>) -> Result<()> {
+        let rent_floor = Rent::get()?.minimum_balance(0);
+        let amount = rent_floor.saturating_sub(ctx.accounts.protocol_treasury.lamports());
+        if amount > 0 {
+            system_program::transfer(
+                CpiContext::new(
+                    ctx.accounts.system_program.to_account_info(),
+                    system_program::Transfer {
+                        from: ctx.accounts.authority.to_account_info(),
+                        to: ctx.accounts.protocol_treasury.to_account_info(),
+                    },
+                ),
+                amount,
+            )?;
+        }
+
+        emit!(ProtocolTreasuryInitialized {
+            pool: ctx.accounts.mock_pool_state.key(),
+            treasury: ctx.accounts.protocol_treasury.key(),
+            amount,
+            authority: ctx.accounts.authority.key(),
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        vlog!("🏛️ 已创建协议 treasury，预存租金 {} lamports", amount);
+        Ok(())
+    }
+
+    /// 从协议 treasury 提取 lamports - 仅池子 authority 可调用，提取后 treasury 不能低于租金豁免下限
+    pub fn withdraw_treasury(ctx: Context<WithdrawTreasury>, amount: u64) -> Result<()> {
+        require!(amount > 0, FlashLoanError::InvalidAmount);
+
+        let pool_key = ctx.accounts.mock_pool_state.key();
+        FlashLoanHandler::transfer_from_treasury(
+            pool_key,
+            &ctx.accounts.protocol_treasury,
+            ctx.bumps.protocol_treasury,
+            ctx.accounts.recipient.to_account_info(),
+            &ctx.accounts.system_program,
+            amount,
+        )?;
+
+        emit!(TreasuryWithdrawn {
+            pool: pool_key,
+            treasury: ctx.accounts.protocol_treasury.key(),
+            amount,
+            recipient: ctx.accounts.recipient.key(),
+            authority: ctx.accounts.authority.key(),
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        vlog!("🏛️ 已从协议 treasury 提取 {} lamports 给 {}", amount, ctx.accounts.recipient.key());
+        Ok(())
+    }
+}
+
+/// load_token_asset 的返回值：mint、池子 vault、借款人代币账户和 vault 的 bump
+pub type TokenAssetAccounts<'info> = (
+    Account<'info, Mint>,
+    Account<'info, TokenAccount>,
+    Account<'info, TokenAccount>,
+    u8,
+);
+
+/// 闪电贷处理器 - 将所有辅助函数移到这里
+pub struct FlashLoanHandler;
+
+impl FlashLoanHandler {
+    /// 从池子的协议 treasury 向调用者支付清理赏金，treasury 在租金下限以上的余额不足时按余额支付
+    pub fn pay_crank_bounty<'info>(
+        pool_key: Pubkey,
+        protocol_treasury: &SystemAccount<'info>,
+        treasury_bump: u8,
+        cranker: AccountInfo<'info>,
+        system_program: &Program<'info, System>,
+    ) -> Result<u64> {
+        let available = protocol_treasury
+            .lamports()
+            .saturating_sub(Rent::get()?.minimum_balance(0));
+        let bounty = available.min(CRANK_BOUNTY_LAMPORTS);
+        if bounty > 0 {
+            Self::transfer_from_treasury(pool_key, protocol_treasury, treasury_bump, cranker, system_program, bounty)?;
+        }
+        Ok(bounty)
+    }
+
+    /// 以 remaining_accounts 为账户列表调用执行程序，签名与可写标记按传入时原样传递
+    pub fn invoke_executor<'info>(
+        executor_program: &AccountInfo<'info>,
+        remaining_accounts: &[AccountInfo<'info>],
+        data: Vec<u8>,
+    ) -> Result<()> {
+        let accounts = remaining_accounts
+            .iter()
+            .map(|account| AccountMeta {
+                pubkey: account.key(),
+                is_signer: account.is_signer,
+                is_writable: account.is_writable,
+            })
+            .collect();
+        let ix = Instruction {
+            program_id: executor_program.key(),
+            accounts,
+            data,
+        };
+
+        let mut account_infos = remaining_accounts.to_vec();
+        account_infos.push(executor_program.clone());
+        invoke(&ix, &account_infos)?;
+        Ok(())
+    }
+
+    /// 池子必须处于 Active 状态，暂停和紧急状态分别返回不同的错误
+    pub fn ensure_pool_lendable(pool_state: &MockPoolState) -> Result<()> {
+        match pool_state.status {
+            PoolStatus::Active => Ok(()),
+            PoolStatus::Paused => err!(FlashLoanError::PoolPaused),
+            PoolStatus::Emergency => err!(FlashLoanError::PoolEmergency),
+            _ => err!(FlashLoanError::PoolNotActive),
+        }
+    }
+
+    /// 从池子的 SOL vault 按 PDA 签名转出 lamports，转出后 vault 不能低于租金豁免下限
+    pub fn transfer_from_sol_vault<'info>(
+        pool_key: Pubkey,
+        sol_vault: &SystemAccount<'info>,
+        sol_vault_bump: u8,
+        to: AccountInfo<'info>,
+        system_program: &Program<'info, System>,
+        amount: u64,
+    ) -> Result<()> {
+        let rent_floor = Rent::get()?.minimum_balance(0);
+        let remaining = sol_vault
+            .lamports()
+            .checked_sub(amount)
+            .ok_or(FlashLoanError::InsufficientPoolBalance)?;
+        require!(remaining >= rent_floor, FlashLoanError::SolVaultBelowRentFloor);
+
+        let vault_bump = [sol_vault_bump];
+        let vault_seeds = &[b"sol_vault".as_ref(), pool_key.as_ref(), &vault_bump];
+        system_program::transfer(
+            CpiContext::new_with_signer(
+                system_program.to_account_info(),
+                system_program::Transfer {
+                    from: sol_vault.to_account_info(),
+                    to,
+                },
+                &[&vault_seeds[..]],
+            ),
+            amount,
+        )
+    }
+
+    /// 从池子的协议 treasury 按 PDA 签名转出 lamports，转出后 treasury 不能低于租金豁免下限
+    pub fn transfer_from_treasury<'info>(
+        pool_key: Pubkey,
+        protocol_treasury: &SystemAccount<'info>,
+        treasury_bump: u8,
+        to: AccountInfo<'info>,
+        system_program: &Program<'info, System>,
+        amount: u64,
+    ) -> Result<()> {
+        let rent_floor = Rent::get()?.minimum_balance(0);
+        let remaining = protocol_treasury
+            .lamports()
+            .checked_sub(amount)
+            .ok_or(FlashLoanError::InsufficientTreasuryBalance)?;
+        require!(remaining >= rent_floor, FlashLoanError::InsufficientTreasuryBalance);
+
+        let treasury_bump = [treasury_bump];
+        let treasury_seeds = &[b"protocol_treasury".as_ref(), pool_key.as_ref(), &treasury_bump];
+        system_program::transfer(
+            CpiContext::new_with_signer(
+                system_program.to_account_info(),
+                system_program::Transfer {
+                    from: protocol_treasury.to_account_info(),
+                    to,
+                },
+                &[&treasury_seeds[..]],
+            ),
+            amount,
+        )
+    }
+
+    /// 借款不能超过池子的单笔上限和当前 slot 的剩余额度，占借款前可用数量 liquidity 的比例也不能超过池子的上限
+    /// SOL 与代币借款共用同一组上限，按借出资产的原始数量计；SOL 的 liquidity 为池子 balance，代币为 vault 余额
+    /// 每 slot 的累计记录在本程序持有的 borrow_tracker 中
+    pub fn ensure_within_borrow_caps(
+        pool_state: &MockPoolState,
+        borrow_tracker: &BorrowTracker,
+        amount: u64,
+        liquidity: u64,
+    ) -> Result<()> {
+        require!(
+            pool_state.within_loan_limit(amount),
+            FlashLoanError::LoanAmountExceedsLimit
+        );
+        require!(
+            borrow_tracker.within_slot_limit(pool_state.max_borrowed_per_slot, amount, Clock::get()?.slot),
+            FlashLoanError::SlotBorrowLimitExceeded
+        );
+        require!(
+            pool_state.within_utilization_cap_of(amount, liquidity),
+            FlashLoanError::UtilizationCapExceeded
+        );
+        Ok(())
+    }
+
+    /// 把借款计入池子当前 slot 的累计；borrow_tracker 由 init_if_needed 在首次借款时创建，每次写入所属池子与 bump 是幂等的
+    pub fn record_slot_borrow(borrow_tracker: &mut BorrowTracker, pool_key: Pubkey, bump: u8, amount: u64) -> Result<()> {
+        borrow_tracker.pool = pool_key;
+        borrow_tracker.bump = bump;
+        borrow_tracker.record(amount, Clock::get()?.slot);
+        Ok(())
+    }
+
+    /// 输出交易记录的内容，供查询指令使用
+    pub fn log_transaction_record(transaction_record: &TransactionRecord) {
+        msg!("Transaction Record:");
+        msg!("  Transaction ID: {}", transaction_record.transaction_id);
+        msg!("  User: {}", transaction_record.user);
+        msg!("  Loan Amount: {}", transaction_record.loan_amount);
+        msg!("  Fee: {}", transaction_record.fee);
+        msg!("  Profit: {}", transaction_record.profit);
+        msg!("  Net Profit: {}", transaction_record.net_profit);
+        msg!("  Protocol Share: {}", transaction_record.protocol_share);
+        msg!("  Route: {} -> {}", transaction_record.dex_pool_a, transaction_record.dex_pool_b);
+        msg!(
+            "  Leg Outputs: {} / {}",
+            transaction_record.first_leg_amount_out,
+            transaction_record.second_leg_amount_out
+        );
+        msg!(
+            "  Final Balances: X {} / Y {}",
+            transaction_record.final_token_x_balance,
+            transaction_record.final_token_y_balance
+        );
+        msg!("  ROI (bps): {}", transaction_record.calculate_roi_bps());
+        msg!("  Is Profitable: {}", transaction_record.is_profitable());
+    }
+
+    /// 池子创建了借款人名单且已启用时，borrower 必须在名单中；名单账户不存在视为不限制
+    pub fn ensure_borrower_allowed(allowlist_info: &AccountInfo, borrower: &Pubkey) -> Result<()> {
+        if allowlist_info.data_is_empty() {
+            return Ok(());
+        }
+        require_keys_eq!(*allowlist_info.owner, crate::ID, FlashLoanError::BorrowerNotAllowed);
+        let allowlist = BorrowerAllowlist::try_deserialize(&mut &allowlist_info.try_borrow_data()?[..])?;
+        require!(
+            !allowlist.enabled || allowlist.is_allowed(borrower),
+            FlashLoanError::BorrowerNotAllowed
+        );
+        Ok(())
+    }
+
+    /// 当前指令必须是交易中的顶层指令，不能经由其他程序 CPI 调用
+    pub fn ensure_top_level(instructions: &AccountInfo) -> Result<()> {
+        let current_ix = get_instruction_relative(0, instructions)?;
+        require!(current_ix.program_id == crate::ID, FlashLoanError::FlashLoanCpiNotAllowed);
+        Ok(())
+    }
+
+    /// 本金加闪电贷费用；本金为 0 时为 0
+    pub fn repay_amount(pool_state: &MockPoolState, amount: u64) -> Result<u64> {
+        let fee = pool_state.calculate_fee(amount)?;
+        Ok(amount.checked_add(fee).ok_or(FlashLoanError::Overflow)?)
+    }
+
+    /// 解析 remaining_accounts 中的一组代币账户 [mint, token_vault, borrower_token_account]，
+    /// 校验 vault 是该 mint 的池子 vault PDA，返回 vault 的 bump 供签名
+    pub fn load_token_asset<'info>(
+        pool_key: Pubkey,
+        asset_accounts: &'info [AccountInfo<'info>],
+    ) -> Result<TokenAssetAccounts<'info>> {
+        let mint: Account<'info, Mint> = Account::try_from(&asset_accounts[0])?;
+        let (expected_vault, vault_bump) = Pubkey::find_program_address(
+            &[b"token_vault", pool_key.as_ref(), mint.key().as_ref()],
+            &crate::ID,
+        );
+        require_keys_eq!(asset_accounts[1].key(), expected_vault, FlashLoanError::InvalidFlashAssetAccounts);
+        let token_vault: Account<'info, TokenAccount> = Account::try_from(&asset_accounts[1])?;
+        let borrower_token_account: Account<'info, TokenAccount> = Account::try_from(&asset_accounts[2])?;
+        require_keys_eq!(borrower_token_account.mint, mint.key(), FlashLoanError::InvalidTokenMint);
+        Ok((mint, token_vault, borrower_token_account, vault_bump))
+    }
+
+    /// 从 flash_borrow / flash_repay 指令的账户列表中取出前 count 种代币的 vault 地址，账户数不足时返回 None
+    pub fn flash_vault_keys(ix: &Instruction, fixed_accounts: usize, count: usize) -> Option<Vec<Pubkey>> {
+        (0..count)
+            .map(|i| {
+                ix.accounts
+                    .get(fixed_accounts + i * FLASH_ACCOUNTS_PER_TOKEN + 1)
+                    .map(|meta| meta.pubkey)
+            })
+            .collect()
+    }
+
+    /// 在当前指令之后查找归还本次借款的 flash_repay：必须指向当前指令、使用同一个池子和同一组 vault 且各资产归还数量一致；
+    /// 找到之前如果先遇到另一条 flash_borrow 则拒绝，避免两笔借款共用一条还款
+    pub fn find_flash_repay(
+        instructions: &AccountInfo,
+        pool_key: Pubkey,
+        vault_keys: &[Pubkey],
+        sol_repay: u64,
+        token_repays: &[u64],
+    ) -> Result<()> {
+        let current_index = load_current_index_checked(instructions)?;
+        let mut index = current_index as usize + 1;
+        while let Ok(ix) = load_instruction_at_checked(index, instructions) {
+            index += 1;
+            if ix.program_id != crate::ID {
+                continue;
+            }
+            require!(
+                !ix.data.starts_with(instruction::FlashBorrow::DISCRIMINATOR),
+                FlashLoanError::MultipleFlashBorrows
+            );
+            if !ix.data.starts_with(instruction::FlashRepay::DISCRIMINATOR) {
+                continue;
+            }
+
+            let args = instruction::FlashRepay::try_from_slice(
+                &ix.data[instruction::FlashRepay::DISCRIMINATOR.len()..],
+            )?;
+            let same_pool = ix.accounts.first().map(|meta| meta.pubkey) == Some(pool_key);
+            let same_vaults = Self::flash_vault_keys(&ix, FLASH_REPAY_FIXED_ACCOUNTS, vault_keys.len()).as_deref() == Some(vault_keys);
+            require!(
+                args.borrow_instruction_index as u16 == current_index
+                    && same_pool
+                    && same_vaults
+                    && args.sol_repay_amount == sol_repay
+                    && args.token_repay_amounts == token_repays,
+                FlashLoanError::InvalidFlashRepay
+            );
+            return Ok(());
+        }
+        err!(FlashLoanError::FlashRepayNotFound)
+    }
+
+    /// 验证和准备阶段，返回费用和换算后的最低利润（lamports）
+    pub fn validate_and_prepare(
+        ctx: &Context<AtomicFlashLoanWithArbitrage>,
+        amount: u64,
+        min_expected_profit: u64,
+        min_profit_bps: Option<u16>,
+        deadline: Option<i64>,
+    ) -> Result<(u64, u64)> {
+        if let Some(deadline) = deadline {
+            require!(
+                Clock::get()?.unix_timestamp <= deadline,
+                FlashLoanError::DeadlineExceeded
+            );
+        }
+        let fee = ctx.accounts.mock_pool_state.calculate_fee(amount)?;
+        let min_profit = Self::min_profit(amount, min_expected_profit, min_profit_bps)?;
+        
+        Self::ensure_pool_lendable(&ctx.accounts.mock_pool_state)?;
+        Self::ensure_borrower_allowed(&ctx.accounts.borrower_allowlist, &ctx.accounts.borrower.key())?;
+        
+        require!(
+            ctx.accounts.mock_pool_state.has_sufficient_funds(amount),
+            FlashLoanError::InsufficientPoolBalance
+        );
+        Self::ensure_within_borrow_caps(
+            &ctx.accounts.mock_pool_state,
+            &ctx.accounts.borrow_tracker,
+            amount,
+            ctx.accounts.mock_pool_state.balance,
+        )?;
+        
+        vlog!("💰 开始原子性闪电贷与套利: {} lamports，最低利润: {} lamports", amount, min_profit);
+        Ok((fee, min_profit))
+    }
+
+    /// 把基点表示的最低利润换算为 lamports，与绝对值 min_expected_profit 取较高者
+    pub fn min_profit(amount: u64, min_expected_profit: u64, min_profit_bps: Option<u16>) -> Result<u64> {
+        let Some(bps) = min_profit_bps else {
+            return Ok(min_expected_profit);
+        };
+        require!(bps <= 10_000, FlashLoanError::InvalidMinProfitBps);
+        let relative = (amount as u128)
+            .checked_mul(bps as u128)
+            .and_then(|v| v.checked_div(10_000))
+            .ok_or(FlashLoanError::Overflow)? as u64;
+        Ok(min_expected_profit.max(relative))
+    }
+
+    /// 执行借款
+    pub fn execute_loan(
+        ctx: &mut Context<AtomicFlashLoanWithArbitrage>,
+        amount: u64,
+    ) -> Result<()> {
+        // 先更新池子状态 - 防止重入攻击 (CEI模式)
+        let mock_pool_state = &mut ctx.accounts.mock_pool_state;
+        mock_pool_state.balance -= amount;
+        mock_pool_state.total_borrowed += amount;
+        Self::record_slot_borrow(
+            &mut ctx.accounts.borrow_tracker,
+            ctx.accounts.mock_pool_state.key(),
+            ctx.bumps.borrow_tracker,
+            amount,
+        )?;
+        
+        vlog!("🔒 已更新池子状态，防止重入攻击");
+        
+        // 然后从 SOL vault 按 PDA 签名转出
+        FlashLoanHandler::transfer_from_sol_vault(
+            ctx.accounts.mock_pool_state.key(),
+            &ctx.accounts.sol_vault,
+            ctx.bumps.sol_vault,
+            ctx.accounts.borrower.to_account_info(),
+            &ctx.accounts.system_program,
+            amount,
+        )?;
+        
+        vlog!("✅ 已转账 {} lamports 给用户", amount);
+        Ok(())
+    }
+
+    /// 执行套利
+    pub fn execute_arbitrage(
+        ctx: &Context<AtomicFlashLoanWithArbitrage>,
+        amount: u64,
+        min_expected_profit: u64,
+    ) -> Result<ArbitrageResult> {
+        Self::call_arbitrage_bot(
+            &ctx.accounts.arbitrage,
+            &ctx.accounts.borrower,
+            &ctx.accounts.token_program,
+            &ctx.accounts.system_program,
+            amount,
+            min_expected_profit,
+        )
+    }
+
+    /// 调用套利机器人（提取CPI逻辑），lamport 与 SPL 代币两种闪电贷共用
+    pub fn call_arbitrage_bot<'info>(
+        arbitrage: &ArbitrageAccounts<'info>,
+        payer: &Signer<'info>,
+        token_program: &Program<'info, Token>,
+        system_program: &Program<'info, System>,
+        amount: u64,
+        min_expected_profit: u64,
+    ) -> Result<ArbitrageResult> {
+        let cpi_accounts = arbitrage_bot::cpi::accounts::ExecuteArbitrageAtomic {
+            arbitrage_bot: arbitrage.arbitrage_bot.to_account_info(),
+            mock_dex_program: arbitrage.mock_dex_program.to_account_info(),
+            dex_pool_a: arbitrage.dex_pool_a.to_account_info(),
+            dex_a_token_x_vault: arbitrage.dex_a_token_x_vault.to_account_info(),
+            dex_a_token_y_vault: arbitrage.dex_a_token_y_vault.to_account_info(),
+            dex_a_fee_vault: arbitrage.dex_a_fee_vault.to_account_info(),
+            dex_pool_b: arbitrage.dex_pool_b.to_account_info(),
+            dex_b_token_x_vault: arbitrage.dex_b_token_x_vault.to_account_info(),
+            dex_b_token_y_vault: arbitrage.dex_b_token_y_vault.to_account_info(),
+            dex_b_fee_vault: arbitrage.dex_b_fee_vault.to_account_info(),
+            token_in_account: arbitrage.token_in_account.to_account_info(),
+            user_token_x: arbitrage.user_token_x.to_account_info(),
+            user_token_y: arbitrage.user_token_y.to_account_info(),
+            token_x_mint: arbitrage.token_x_mint.to_account_info(),
+            token_y_mint: arbitrage.token_y_mint.to_account_info(),
+            token_program: token_program.to_account_info(),
+            payer: payer.to_account_info(),
+            system_program: system_program.to_account_info(),
+        };
+
+        let cpi_ctx = CpiContext::new(
+            arbitrage.arbitrage_bot_program.to_account_info(),
+            cpi_accounts,
+        );
+
+        let result = arbitrage_bot::cpi::execute_arbitrage_atomic(cpi_ctx, amount, min_expected_profit)?.get();
+        vlog!(
+            "✅ 套利完成，获得利润: {} lamports (A 腿输出 {}，B 腿输出 {})",
+            result.profit,
+            result.first_leg_amount_out,
+            result.second_leg_amount_out
+        );
+        Ok(result)
+    }
+
+    /// 处理还款
+    pub fn process_repayment(
+        ctx: &mut Context<AtomicFlashLoanWithArbitrage>,
+        amount: u64,
+        fee: u64,
+    ) -> Result<()> {
+        let total_repayment = amount + fee;
+
+        // 池子处于紧急状态时不再更新池子状态
+        require!(
+            !ctx.accounts.mock_pool_state.is_emergency(),
+            FlashLoanError::PoolEmergency
+        );
+        
+        // 检查用户是否有足够资金还款
+        require!(
+            ctx.accounts.borrower.lamports() >= total_repayment,
+            FlashLoanError::InsufficientFundsForRepayment
+        );
+        
+        // 先更新池子状态 - 防止重入攻击
+        let mock_pool_state = &mut ctx.accounts.mock_pool_state;
+        mock_pool_state.balance += total_repayment;
+        mock_pool_state.total_repaid += total_repayment;
+        
+        vlog!("🔒 已更新还款状态，防止重入攻击");
+        
+        // 然后由 borrower 签名转回 SOL vault
+        system_program::transfer(
+            CpiContext::new(
+                ctx.accounts.system_program.to_account_info(),
+                system_program::Transfer {
+                    from: ctx.accounts.borrower.to_account_info(),
+                    to: ctx.accounts.sol_vault.to_account_info(),
+                },
+            ),
+            total_repayment,
+        )?;
+        
+        vlog!("✅ 已归还 {} lamports (本金 {} + 费用 {})", total_repayment, amount, fee);
+        Ok(())
+    }
+
+    /// 按池子的 profit_share_bps 从套利利润中抽取协议份额，由 borrower 转入 treasury PDA
+    pub fn collect_profit_share(
+        ctx: &Context<AtomicFlashLoanWithArbitrage>,
+        actual_profit: u64,
+    ) -> Result<u64> {
+        let protocol_share = ctx.accounts.mock_pool_state.calculate_profit_share(actual_profit)?;
+        if protocol_share == 0 {
+            return Ok(0);
+        }
+
+        // treasury 须先由 initialize_protocol_treasury 预存租金，否则小额分成会因低于租金下限而转账失败
+        require!(
+            ctx.accounts.protocol_treasury.lamports() >= Rent::get()?.minimum_balance(0),
+            FlashLoanError::ProtocolTreasuryNotInitialized
+        );
+
+        require!(
+            ctx.accounts.borrower.lamports() >= protocol_share,
+            FlashLoanError::InsufficientFundsForRepayment
+        );
+        system_program::transfer(
+            CpiContext::new(
+                ctx.accounts.system_program.to_account_info(),
+                system_program::Transfer {
+                    from: ctx.accounts.borrower.to_account_info(),
+                    to: ctx.accounts.protocol_treasury.to_account_info(),
+                },
+            ),
+            protocol_share,
+        )?;
+
+        vlog!("🏛️ 已划入协议 treasury: {} lamports", protocol_share);
+        Ok(protocol_share)
+    }
+
+    /// 记录交易和套利路径，返回本次闪电贷的结果
+    pub fn record_transaction(
+        ctx: &mut Context<AtomicFlashLoanWithArbitrage>,
+        amount: u64,
+        fee: u64,
+        arbitrage_result: &ArbitrageResult,
+        protocol_share: u64,
+    ) -> Result<FlashLoanResult> {
+        let now = Clock::get()?.unix_timestamp;
+        let actual_profit = arbitrage_result.profit;
+
+        // 套利 CPI 之后账户余额已变化，读取最终余额前先 reload
+        ctx.accounts.arbitrage.user_token_x.reload()?;
+        ctx.accounts.arbitrage.user_token_y.reload()?;
+        let final_token_x_balance = ctx.accounts.arbitrage.user_token_x.amount;
+        let final_token_y_balance = ctx.accounts.arbitrage.user_token_y.amount;
+        let dex_pool_a = ctx.accounts.arbitrage.dex_pool_a.key();
+        let dex_pool_b = ctx.accounts.arbitrage.dex_pool_b.key();
+        let net_profit = actual_profit.saturating_sub(fee).saturating_sub(protocol_share);
+
+        // 记录编号取自借款人统计中的借款次数，与 transaction_record 的 PDA 种子一致
+        let user_stats = &mut ctx.accounts.user_stats;
+        let transaction_id = user_stats.loan_count;
+        user_stats.borrower = ctx.accounts.borrower.key();
+        user_stats.loan_count = transaction_id.checked_add(1).ok_or(FlashLoanError::Overflow)?;
+        user_stats.total_volume = user_stats.total_volume.checked_add(amount).ok_or(FlashLoanError::Overflow)?;
+        user_stats.total_net_profit = user_stats.total_net_profit.saturating_add(net_profit);
+        user_stats.last_activity = now;
+        user_stats.latest_record = ctx.accounts.transaction_record.key();
+        user_stats.bump = ctx.bumps.user_stats;
+
+        let transaction_record = &mut ctx.accounts.transaction_record;
+        transaction_record.transaction_id = transaction_id;
+        transaction_record.user = ctx.accounts.borrower.key();
+        transaction_record.pool = ctx.accounts.mock_pool_state.key();
+        transaction_record.loan_amount = amount;
+        transaction_record.fee = fee;
+        transaction_record.profit = actual_profit;
+        transaction_record.net_profit = net_profit;
+        transaction_record.protocol_share = protocol_share;
+        transaction_record.dex_pool_a = dex_pool_a;
+        transaction_record.dex_pool_b = dex_pool_b;
+        transaction_record.first_leg_amount_out = arbitrage_result.first_leg_amount_out;
+        transaction_record.second_leg_amount_out = arbitrage_result.second_leg_amount_out;
+        transaction_record.final_token_x_balance = final_token_x_balance;
+        transaction_record.final_token_y_balance = final_token_y_balance;
+        transaction_record.timestamp = now;
+        transaction_record.bump = ctx.bumps.transaction_record;
+        
+        emit!(AtomicFlashLoanCompleted {
+            user: ctx.accounts.borrower.key(),
+            transaction_id: transaction_record.transaction_id,
+            loan_amount: amount,
+            fee,
+            net_profit: transaction_record.net_profit,
+            protocol_share,
+            dex_pool_a,
+            dex_pool_b,
+            first_leg_amount_out: arbitrage_result.first_leg_amount_out,
+            second_leg_amount_out: arbitrage_result.second_leg_amount_out,
+            final_token_x_balance,
+            final_token_y_balance,
+            timestamp: now,
+        });
+        
+        vlog!("✅ 套利闪电贷完成! 净利润: {} lamports", transaction_record.net_profit);
+        Ok(FlashLoanResult {
+            transaction_id,
+            loan_amount: amount,
+            fee,
+            profit: actual_profit,
+            protocol_share,
+            net_profit,
+        })
+    }
+}
+
+#[derive(Accounts)]
+#[instruction(amount: u64, min_expected_profit: u64, min_profit_bps: Option<u16>, deadline: Option<i64>, description: String)]
+pub struct AtomicFlashLoanWithArbitrage<'info> {
+    /// 借出资金的池子，可以是任意 MockPoolState 账户，由 Account 校验 owner 与 discriminator
+    #[account(mut)]
+    pub mock_pool_state: Account<'info, MockPoolState>,
+
+    /// 池子的 slot 借款累计，首次借款时创建
+    #[account(
+        init_if_needed,
+        payer = borrower,
+        seeds = [b"borrow_tracker", mock_pool_state.key().as_ref()],
+        bump,
+        space = BorrowTracker::SPACE,
+    )]
+    pub borrow_tracker: Account<'info, BorrowTracker>,
+
+    /// 借款人的统计账户，首次借款时创建
+    #[account(
+        init_if_needed,
+        payer = borrower,
+        seeds = [b"user_stats", borrower.key().as_ref()],
+        bump,
+        space = UserStats::SPACE,
+    )]
+    pub user_stats: Account<'info, UserStats>,
+
+    /// 以借款人的当前计数为种子，同一借款人的每笔借款各占一个记录，客户端可按计数推导地址
+    #[account(
+        init,
+        payer = borrower,
+        seeds = [b"transaction_record", borrower.key().as_ref(), &user_stats.loan_count.to_le_bytes()],
+        bump,
+        space = TransactionRecord::SPACE,
+    )]
+    pub transaction_record: Account<'info, TransactionRecord>,
+
+    #[account(mut)]
+    pub borrower: Signer<'info>,
+
+    /// CHECK: 池子的借款人名单 PDA，未创建时不限制借款人；已创建时在 ensure_borrower_allowed 中反序列化
+    #[account(seeds = [b"borrower_allowlist", mock_pool_state.key().as_ref()], bump)]
+    pub borrower_allowlist: UncheckedAccount<'info>,
+
+    /// 池子的 SOL vault，系统程序所有的 PDA，由本程序按种子签名转出
+    #[account(mut, seeds = [b"sol_vault", mock_pool_state.key().as_ref()], bump)]
+    pub sol_vault: SystemAccount<'info>,
+
+    /// 协议 treasury PDA，系统程序所有，按 profit_share_bps 收取套利利润分成；profit_share_bps 非 0 时须已创建
+    #[account(mut, seeds = [b"protocol_treasury", mock_pool_state.key().as_ref()], bump)]
+    pub protocol_treasury: SystemAccount<'info>,
+
+    pub arbitrage: ArbitrageAccounts<'info>,
+
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+/// 套利机器人 CPI 所需的账户，lamport 与 SPL 代币闪电贷共用
+#[derive(Accounts)]
+pub struct ArbitrageAccounts<'info> {
+    // 套利机器人相关账户
+    /// CHECK: arbitrage_bot程序
+    pub arbitrage_bot_program: AccountInfo<'info>,
+
+    #[account(mut)]
+    pub arbitrage_bot: Account<'info, arbitrage_bot::ArbitrageBotState>,
+
+    // DEX和代币相关账户
+    /// CHECK: mock_dex程序
+    pub mock_dex_program: AccountInfo<'info>,
+    
+    /// CHECK: DEX A的池子
+    #[account(mut)]
+    pub dex_pool_a: AccountInfo<'info>,
+
+    /// CHECK: DEX A的Token X vault
+    #[account(mut)]
+    pub dex_a_token_x_vault: Account<'info, TokenAccount>,
+
+    /// CHECK: DEX A的Token Y vault  
+    #[account(mut)]
+    pub dex_a_token_y_vault: Account<'info, TokenAccount>,
+
+    /// CHECK: DEX A的Token X 协议费 vault
+    #[account(mut)]
+    pub dex_a_fee_vault: Account<'info, TokenAccount>,
+
+    /// CHECK: DEX B的池子
+    #[account(mut)]
+    pub dex_pool_b: AccountInfo<'info>,
+
+    /// CHECK: DEX B的Token X vault
+    #[account(mut)]
+    pub dex_b_token_x_vault: Account<'info, TokenAccount>,
+
+    /// CHECK: DEX B的Token Y vault
+    #[account(mut)]
+    pub dex_b_token_y_vault: Account<'info, TokenAccount>,
+
+    /// CHECK: DEX B的Token Y 协议费 vault
+    #[account(mut)]
+    pub dex_b_fee_vault: Account<'info, TokenAccount>,
+
+    /// CHECK: 输入代币账户
+    #[account(mut)]
+    pub token_in_account: Account<'info, TokenAccount>,
+
+    /// CHECK: 用户Token X账户
+    #[account(mut)]
+    pub user_token_x: Account<'info, TokenAccount>,
+
+    /// CHECK: 用户Token Y账户
+    #[account(mut)]
+    pub user_token_y: Account<'info, TokenAccount>,
+
+    /// CHECK: Token X 的 mint，由 mock_dex 与池子记录比对
+    pub token_x_mint: AccountInfo<'info>,
+
+    /// CHECK: Token Y 的 mint，由 mock_dex 与池子记录比对
+    pub token_y_mint: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeTokenVault<'info> {
+    #[account(
+        constraint = mock_pool_state.authority == authority.key() @ FlashLoanError::UnauthorizedAccess,
+    )]
+    pub mock_pool_state: Account<'info, MockPoolState>,
+
+    pub mint: Account<'info, Mint>,
+
+    /// 池子的代币 vault，以自身为 authority
+    #[account(
+        init,
+        payer = authority,
+        seeds = [b"token_vault", mock_pool_state.key().as_ref(), mint.key().as_ref()],
+        bump,
+        token::mint = mint,
+        token::authority = token_vault,
+    )]
+    pub token_vault: Account<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct TokenFlashLoanWithArbitrage<'info> {
+    #[account(mut)]
+    pub mock_pool_state: Account<'info, MockPoolState>,
+
+    /// 池子的 slot 借款累计，首次借款时创建
+    #[account(
+        init_if_needed,
+        payer = borrower,
+        seeds = [b"borrow_tracker", mock_pool_state.key().as_ref()],
+        bump,
+        space = BorrowTracker::SPACE,
+    )]
+    pub borrow_tracker: Account<'info, BorrowTracker>,
+
+    pub mint: Account<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [b"token_vault", mock_pool_state.key().as_ref(), mint.key().as_ref()],
+        bump,
+    )]
+    pub token_vault: Account<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub borrower: Signer<'info>,
+
+    /// CHECK: 池子的借款人名单 PDA，未创建时不限制借款人；已创建时在 ensure_borrower_allowed 中反序列化
+    #[account(seeds = [b"borrower_allowlist", mock_pool_state.key().as_ref()], bump)]
+    pub borrower_allowlist: UncheckedAccount<'info>,
+
+    /// 可选的抵押来源，与借款同一种代币，collateral_amount 大于 0 时必须提供
+    #[account(mut, token::mint = mint, token::authority = borrower)]
+    pub collateral_account: Option<Account<'info, TokenAccount>>,
+
+    pub arbitrage: ArbitrageAccounts<'info>,
+
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct FlashLoanWithCallback<'info> {
+    #[account(mut)]
+    pub mock_pool_state: Account<'info, MockPoolState>,
+
+    /// 池子的 slot 借款累计，首次借款时创建
+    #[account(
+        init_if_needed,
+        payer = borrower,
+        seeds = [b"borrow_tracker", mock_pool_state.key().as_ref()],
+        bump,
+        space = BorrowTracker::SPACE,
+    )]
+    pub borrow_tracker: Account<'info, BorrowTracker>,
+
+    pub mint: Account<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [b"token_vault", mock_pool_state.key().as_ref(), mint.key().as_ref()],
+        bump,
+    )]
+    pub token_vault: Account<'info, TokenAccount>,
+
+    /// 接收借款并在回调结束后归还的代币账户，borrower 必须是 owner 或 delegate
+    #[account(mut, token::mint = mint)]
+    pub borrower_token_account: Account<'info, TokenAccount>,
+
+    /// 支付首次借款时创建 borrow_tracker 的租金
+    #[account(mut)]
+    pub borrower: Signer<'info>,
+
+    /// CHECK: 池子的借款人名单 PDA，未创建时不限制借款人；已创建时在 ensure_borrower_allowed 中反序列化
+    #[account(seeds = [b"borrower_allowlist", mock_pool_state.key().as_ref()], bump)]
+    pub borrower_allowlist: UncheckedAccount<'info>,
+
+    /// CHECK: 调用方指定的执行程序，只要求可执行；不能是本程序
+    #[account(executable)]
+    pub executor_program: UncheckedAccount<'info>,
+
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+    // remaining_accounts: 原样传给执行程序的账户列表
+}
+
+/// 账户数须与 FLASH_BORROW_FIXED_ACCOUNTS 一致
+#[derive(Accounts)]
+pub struct FlashBorrow<'info> {
+    #[account(mut)]
+    pub mock_pool_state: Account<'info, MockPoolState>,
+
+    /// 池子的 slot 借款累计，首次借款时创建
+    #[account(
+        init_if_needed,
+        payer = borrower,
+        seeds = [b"borrow_tracker", mock_pool_state.key().as_ref()],
+        bump,
+        space = BorrowTracker::SPACE,
+    )]
+    pub borrow_tracker: Account<'info, BorrowTracker>,
+
+    /// 接收借出的 SOL，同时签名本次借款
+    #[account(mut)]
+    pub borrower: Signer<'info>,
+
+    /// CHECK: 池子的借款人名单 PDA，未创建时不限制借款人；已创建时在 ensure_borrower_allowed 中反序列化
+    #[account(seeds = [b"borrower_allowlist", mock_pool_state.key().as_ref()], bump)]
+    pub borrower_allowlist: UncheckedAccount<'info>,
+
+    /// 池子的 SOL vault，系统程序所有的 PDA，由本程序按种子签名转出
+    #[account(mut, seeds = [b"sol_vault", mock_pool_state.key().as_ref()], bump)]
+    pub sol_vault: SystemAccount<'info>,
+
+    /// CHECK: instructions sysvar，由 address 约束校验
+    #[account(address = anchor_lang::solana_program::sysvar::instructions::ID)]
+    pub instructions: UncheckedAccount<'info>,
+
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+    // remaining_accounts: 每种代币 [mint, token_vault, borrower_token_account]
+}
+
+/// 账户数须与 FLASH_REPAY_FIXED_ACCOUNTS 一致
+#[derive(Accounts)]
+pub struct FlashRepay<'info> {
+    #[account(mut)]
+    pub mock_pool_state: Account<'info, MockPoolState>,
+
+    /// 归还 SOL 的来源，同时是代币还款账户的 owner 或 delegate
+    #[account(mut)]
+    pub borrower: Signer<'info>,
+
+    #[account(mut, seeds = [b"sol_vault", mock_pool_state.key().as_ref()], bump)]
+    pub sol_vault: SystemAccount<'info>,
+
+    /// CHECK: instructions sysvar，由 address 约束校验
+    #[account(address = anchor_lang::solana_program::sysvar::instructions::ID)]
+    pub instructions: UncheckedAccount<'info>,
+
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+    // remaining_accounts: 每种代币 [mint, token_vault, borrower_token_account]，顺序与借款时一致
+}
+
+#[derive(Accounts)]
+#[instruction(user: Pubkey, index: u64)]
+pub struct GetTransactionRecord<'info> {
+    #[account(
+        seeds = [b"transaction_record", user.as_ref(), &index.to_le_bytes()],
+        bump = transaction_record.bump,
+    )]
+    pub transaction_record: Account<'info, TransactionRecord>,
+}
+
+#[derive(Accounts)]
+#[instruction(user: Pubkey)]
+pub struct GetLatestTransactionRecord<'info> {
+    #[account(
+        seeds = [b"user_stats", user.as_ref()],
+        bump = user_stats.bump,
+    )]
+    pub user_stats: Account<'info, UserStats>,
+
+    #[account(address = user_stats.latest_record @ FlashLoanError::TransactionRecordNotFound)]
+    pub transaction_record: Account<'info, TransactionRecord>,
+}
+
+#[derive(Accounts)]
+pub struct CloseExpiredRecord<'info> {
+    #[account(
+        mut,
+        has_one = user,
+        close = user,
+    )]
+    pub transaction_record: Account<'info, TransactionRecord>,
+
+    /// CHECK: 记录所属用户，仅用于接收退还的租金，由 has_one 约束校验
+    #[account(mut)]
+    pub user: AccountInfo<'info>,
+
+    /// 记录所属池子的协议 treasury，支付清理赏金
+    #[account(mut, seeds = [b"protocol_treasury", transaction_record.pool.as_ref()], bump)]
+    pub protocol_treasury: SystemAccount<'info>,
+
+    #[account(mut)]
+    pub cranker: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SweepExpiredRecords<'info> {
+    /// CHECK: 被清理记录所属的池子，只用于推导 treasury 并与每条记录的 pool 比对
+    pub pool: UncheckedAccount<'info>,
+
+    /// 池子的协议 treasury，支付清理赏金
+    #[account(mut, seeds = [b"protocol_treasury", pool.key().as_ref()], bump)]
+    pub protocol_treasury: SystemAccount<'info>,
+
+    #[account(mut)]
+    pub cranker: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeBorrowTracker<'info> {
+    pub mock_pool_state: Account<'info, MockPoolState>,
+
+    #[account(
+        init,
+        payer = payer,
+        seeds = [b"borrow_tracker", mock_pool_state.key().as_ref()],
+        bump,
+        space = BorrowTracker::SPACE,
+    )]
+    pub borrow_tracker: Account<'info, BorrowTracker>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeBorrowerAllowlist<'info> {
+    #[account(
+        constraint = mock_pool_state.authority == authority.key() @ FlashLoanError::UnauthorizedAccess,
+    )]
+    pub mock_pool_state: Account<'info, MockPoolState>,
+
+    #[account(
+        init,
+        payer = authority,
+        seeds = [b"borrower_allowlist", mock_pool_state.key().as_ref()],
+        bump,
+        space = BorrowerAllowlist::SPACE,
+    )]
+    pub borrower_allowlist: Account<'info, BorrowerAllowlist>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateBorrowerAllowlist<'info> {
+    #[account(
+        constraint = mock_pool_state.authority == authority.key() @ FlashLoanError::UnauthorizedAccess,
+    )]
+    pub mock_pool_state: Account<'info, MockPoolState>,
+
+    #[account(
+        mut,
+        seeds = [b"borrower_allowlist", mock_pool_state.key().as_ref()],
+        bump = borrower_allowlist.bump,
+    )]
+    pub borrower_allowlist: Account<'info, BorrowerAllowlist>,
+
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct DrainSolVault<'info> {
+    #[account(
+        constraint = mock_pool_state.authority == authority.key() @ FlashLoanError::UnauthorizedAccess,
+    )]
+    pub mock_pool_state: Account<'info, MockPoolState>,
+
+    #[account(mut, seeds = [b"sol_vault", mock_pool_state.key().as_ref()], bump)]
+    pub sol_vault: SystemAccount<'info>,
+
+    /// CHECK: 接收 vault lamports 的账户，由 authority 指定
+    #[account(mut)]
+    pub treasury: UncheckedAccount<'info>,
+
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(index: u64)]
+pub struct IssueLoanReceipt<'info> {
+    #[account(
+        seeds = [b"transaction_record", borrower.key().as_ref(), &index.to_le_bytes()],
+        bump = transaction_record.bump,
+    )]
+    pub transaction_record: Account<'info, TransactionRecord>,
+
+    /// 每条交易记录只能签发一张回执
+    #[account(
+        init,
+        payer = borrower,
+        seeds = [b"loan_receipt", transaction_record.key().as_ref()],
+        bump,
+        space = LoanReceipt::SPACE,
+    )]
+    pub loan_receipt: Account<'info, LoanReceipt>,
+
+    #[account(mut)]
+    pub borrower: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeProtocolTreasury<'info> {
+    #[account(
+        constraint = mock_pool_state.authority == authority.key() @ FlashLoanError::UnauthorizedAccess,
+    )]
+    pub mock_pool_state: Account<'info, MockPoolState>,
+
+    #[account(mut, seeds = [b"protocol_treasury", mock_pool_state.key().as_ref()], bump)]
+    pub protocol_treasury: SystemAccount<'info>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct WithdrawTreasury<'info> {
+    #[account(
+        constraint = mock_pool_state.authority == authority.key() @ FlashLoanError::UnauthorizedAccess,
+    )]
+    pub mock_pool_state: Account<'info, MockPoolState>,
+
+    #[account(mut, seeds = [b"protocol_treasury", mock_pool_state.key().as_ref()], bump)]
+    pub protocol_treasury: SystemAccount<'info>,
+
+    /// CHECK: 接收提取 lamports 的账户，由 authority 指定
+    #[account(mut)]
+    pub recipient: UncheckedAccount<'info>,
+
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ExternalFlashLoanWithCallback<'info> {
+    pub mint: Account<'info, Mint>,
+
+    /// 接收借款并在回调结束后归还的代币账户，borrower 必须是 owner 或 delegate
+    #[account(mut, token::mint = mint)]
+    pub borrower_token_account: Account<'info, TokenAccount>,
+
+    pub borrower: Signer<'info>,
+
+    /// CHECK: 借贷来源程序，由各适配器按自己的规则校验
+    #[account(executable)]
+    pub lender_program: UncheckedAccount<'info>,
+
+    /// CHECK: 调用方指定的执行程序，只要求可执行；不能是本程序或借贷来源
+    #[account(executable)]
+    pub executor_program: UncheckedAccount<'info>,
+
+    /// CHECK: instructions sysvar，由 address 约束校验
+    #[account(address = anchor_lang::solana_program::sysvar::instructions::ID)]
+    pub instructions: UncheckedAccount<'info>,
+
+    pub token_program: Program<'info, Token>,
+    // remaining_accounts: [借贷来源账户..., 传给执行程序的账户...]
+}
+
+/// 池子的借款人名单，enabled 为 true 时只有名单中的 borrower 可以借款
+#[account]
+pub struct BorrowerAllowlist {
+    pub pool: Pubkey, // 所属池子
+    pub enabled: bool,
+    pub bump: u8,
+    pub borrowers: Vec<Pubkey>,
+}
+
+impl BorrowerAllowlist {
+    pub const SPACE: usize = 8 + // discriminator
+        32 + // pool
+        1 + // enabled
+        1 + // bump
+        4 + MAX_ALLOWED_BORROWERS * 32; // borrowers
+
+    pub fn is_allowed(&self, borrower: &Pubkey) -> bool {
+        self.borrowers.contains(borrower)
+    }
+}
+
+/// 池子在当前 slot 内的累计借款，由本程序持有：MockPoolState 属于 mock_pool，本程序对它的修改不会被写回
+#[account]
+pub struct BorrowTracker {
+    pub pool: Pubkey,           // 所属池子
+    pub borrow_slot: u64,       // borrowed_in_slot 所属的 slot
+    pub borrowed_in_slot: u64,  // borrow_slot 内已累计借出的金额
+    pub bump: u8,
+}
+
+impl BorrowTracker {
+    pub const SPACE: usize = 8 + // discriminator
+        32 + // pool
+        8 + // borrow_slot
+        8 + // borrowed_in_slot
+        1; // bump
+
+    /// 检查借款计入 slot 累计后是否仍在每 slot 上限之内，进入新 slot 时累计从零开始；上限为 0 表示不限制
+    pub fn within_slot_limit(&self, max_borrowed_per_slot: u64, amount: u64, slot: u64) -> bool {
+        if max_borrowed_per_slot == 0 {
+            return true;
+        }
+        let borrowed = if slot == self.borrow_slot { self.borrowed_in_slot } else { 0 };
+        borrowed
+            .checked_add(amount)
+            .is_some_and(|total| total <= max_borrowed_per_slot)
+    }
+
+    /// 把借款计入当前 slot 的累计
+    pub fn record(&mut self, amount: u64, slot: u64) {
+        if slot != self.borrow_slot {
+            self.borrow_slot = slot;
+            self.borrowed_in_slot = 0;
+        }
+        self.borrowed_in_slot = self.borrowed_in_slot.saturating_add(amount);
+    }
+}
+
+/// 借款人的累计统计，loan_count 同时是下一笔借款的记录编号
+#[account]
+pub struct UserStats {
+    pub borrower: Pubkey,
+    pub loan_count: u64,
+    pub total_volume: u64,     // 累计借款金额
+    pub total_net_profit: u64, // 累计净利润
+    pub last_activity: i64,
+    pub latest_record: Pubkey, // 最近一笔借款的 transaction_record 地址
+    pub bump: u8,
+}
+
+impl UserStats {
+    pub const SPACE: usize = 8 + // discriminator
+        32 + // borrower
+        8 + // loan_count
+        8 + // total_volume
+        8 + // total_net_profit
+        8 + // last_activity
+        32 + // latest_record
+        1; // bump
+}
+
+/// 闪电贷回执，地址由交易记录推导，没有转让指令
+#[account]
+pub struct LoanReceipt {
+    pub borrower: Pubkey,
+    pub transaction_record: Pubkey,
+    pub transaction_id: u64,
+    pub loan_amount: u64,
+    pub fee: u64,
+    pub profit: u64,
+    pub issued_at: i64,
+    pub bump: u8,
+}
+
+impl LoanReceipt {
+    pub const SPACE: usize = 8 + // discriminator
+        32 + // borrower
+        32 + // transaction_record
+        8 + // transaction_id
+        8 + // loan_amount
+        8 + // fee
+        8 + // profit
+        8 + // issued_at
+        1; // bump
+}
+
+/// atomic_flash_loan_with_arbitrage 通过 return data 返回的结果
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct FlashLoanResult {
+    pub transaction_id: u64, // 对应 transaction_record 的编号
+    pub loan_amount: u64,
+    pub fee: u64,
+    pub profit: u64, // 套利的毛利润
+    pub protocol_share: u64,
+    pub net_profit: u64, // 扣除费用和协议分成后的净利润
+}
+
+#[event]
+pub struct AtomicFlashLoanCompleted {
+    pub user: Pubkey,
+    pub transaction_id: u64,
+    pub loan_amount: u64,
+    pub fee: u64,
+    pub net_profit: u64,
+    pub protocol_share: u64,
+    pub dex_pool_a: Pubkey,
+    pub dex_pool_b: Pubkey,
+    pub first_leg_amount_out: u64,
+    pub second_leg_amount_out: u64,
+    pub final_token_x_balance: u64,
+    pub final_token_y_balance: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct TokenVaultInitialized {
+    pub pool: Pubkey,
+    pub mint: Pubkey,
+    pub vault: Pubkey,
+    pub authority: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct TokenFlashLoanCompleted {
+    pub user: Pubkey,
+    pub mint: Pubkey,
+    pub loan_amount: u64,
+    pub fee: u64,
+    pub profit: u64,
+    pub collateral_seized: u64, // 因收益不足从抵押中扣除的数量
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct CallbackFlashLoanCompleted {
+    pub user: Pubkey,
+    pub mint: Pubkey,
+    pub executor_program: Pubkey,
+    pub loan_amount: u64,
+    pub fee: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct LoanReceiptIssued {
+    pub borrower: Pubkey,
+    pub receipt: Pubkey,
+    pub transaction_id: u64,
+    pub loan_amount: u64,
+    pub fee: u64,
+    pub profit: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct SolVaultDrained {
+    pub pool: Pubkey,
+    pub vault: Pubkey,
+    pub amount: u64,
+    pub treasury: Pubkey,
+    pub authority: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct ProtocolTreasuryInitialized {
+    pub pool: Pubkey,
+    pub treasury: Pubkey,
+    pub amount: u64,
+    pub authority: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct TreasuryWithdrawn {
+    pub pool: Pubkey,
+    pub treasury: Pubkey,
+    pub amount: u64,
+    pub recipient: Pubkey,
+    pub authority: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct ExternalFlashLoanCompleted {
+    pub user: Pubkey,
+    pub adapter: LenderAdapterId,
+    pub lender_program: Pubkey,
+    pub mint: Pubkey,
+    pub executor_program: Pubkey,
+    pub loan_amount: u64,
+    pub fee: u64, // 按还款前后余额差计算的实际费用
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct FlashBorrowed {
+    pub user: Pubkey,
+    pub mint: Pubkey, // 借出 SOL 时为 Pubkey::default()
+    pub amount: u64,
+    pub fee: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct FlashRepaid {
+    pub user: Pubkey,
+    pub mint: Pubkey, // 归还 SOL 时为 Pubkey::default()
+    pub amount: u64,
+    pub fee: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct AllowedBorrowerUpdated {
+    pub borrower: Pubkey,
+    pub allowed: bool, // false 表示已移出名单
+    pub authority: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct BorrowerAllowlistToggled {
+    pub enabled: bool,
+    pub authority: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct TransactionRecordClosed {
+    pub user: Pubkey,
+    pub transaction_id: u64,
+    pub cranker: Pubkey,
+    pub bounty: u64,
     pub timestamp: i64,
 }
 
@@ -316,4 +2432,58 @@ pub enum FlashLoanError {
     InsufficientProfit,
     #[msg("Unauthorized access")]
     UnauthorizedAccess,
+    #[msg("Transaction record has not reached its retention period")]
+    RecordNotExpired,
+    #[msg("Sweep accounts must be passed as (record, user) pairs of records from the given pool")]
+    InvalidSweepAccounts,
+    #[msg("Token account mint does not match the loan mint")]
+    InvalidTokenMint,
+    #[msg("Token vault did not receive the principal plus fee")]
+    RepaymentNotReceived,
+    #[msg("Flash loan instructions cannot be invoked via CPI")]
+    FlashLoanCpiNotAllowed,
+    #[msg("No matching flash_repay found later in the transaction")]
+    FlashRepayNotFound,
+    #[msg("flash_repay does not match its flash_borrow")]
+    InvalidFlashRepay,
+    #[msg("Another flash_borrow appears before the matching flash_repay")]
+    MultipleFlashBorrows,
+    #[msg("Executor program cannot be the flash loan program")]
+    InvalidExecutorProgram,
+    #[msg("Flash loan asset accounts must be (mint, vault, token account) triples matching the amounts")]
+    InvalidFlashAssetAccounts,
+    #[msg("Borrower allowlist is full")]
+    BorrowerAllowlistFull,
+    #[msg("Borrower is not on the allowlist")]
+    AllowedBorrowerNotFound,
+    #[msg("Borrower is not allowed to take flash loans from this pool")]
+    BorrowerNotAllowed,
+    #[msg("Loan amount exceeds the pool's per-loan limit")]
+    LoanAmountExceedsLimit,
+    #[msg("Pool has reached its borrow limit for the current slot")]
+    SlotBorrowLimitExceeded,
+    #[msg("Minimum profit in basis points must not exceed 10000")]
+    InvalidMinProfitBps,
+    #[msg("Transaction record does not match the user's latest record")]
+    TransactionRecordNotFound,
+    #[msg("Lender adapter accounts are missing or invalid")]
+    InvalidAdapterAccounts,
+    #[msg("Pool is paused")]
+    PoolPaused,
+    #[msg("Pool is in emergency status")]
+    PoolEmergency,
+    #[msg("Collateral account is required when locking collateral")]
+    CollateralAccountRequired,
+    #[msg("Loan would push pool utilization above its cap")]
+    UtilizationCapExceeded,
+    #[msg("SOL vault would fall below the rent-exempt minimum")]
+    SolVaultBelowRentFloor,
+    #[msg("Pool must be deprecated before its SOL vault can be drained")]
+    PoolNotDeprecated,
+    #[msg("Transaction executed after its deadline")]
+    DeadlineExceeded,
+    #[msg("Protocol treasury must be initialized before collecting a profit share")]
+    ProtocolTreasuryNotInitialized,
+    #[msg("Protocol treasury balance is insufficient or would fall below the rent-exempt minimum")]
+    InsufficientTreasuryBalance,
 }
\ No newline at end of file
@@ -1,32 +1,209 @@
+
 use anchor_lang::prelude::*;
-use anchor_spl::token::{self, Mint, Token, TokenAccount, Transfer};
+use anchor_spl::associated_token::AssociatedToken;
+use anchor_spl::token_interface::{
+    self, Burn, CloseAccount, Mint, MintTo, TokenAccount, TokenInterface, TransferChecked,
+};
+use anchor_lang::solana_program::hash::hashv;
+use anchor_lang::solana_program::instruction::Instruction;
+use anchor_lang::solana_program::program::invoke;
+use shared::vlog;
 
 // 确保这里的 Program ID 与你 build 后生成的实际 ID 匹配
+#[cfg(all(feature = "devnet", feature = "mainnet"))]
+compile_error!("features `devnet` and `mainnet` are mutually exclusive");
+
+// 各集群的程序 ID，通过 cargo feature 选择；未指定时默认为 localnet
+// devnet / mainnet 目前与 localnet 共用同一部署密钥，更换密钥后只需修改对应分支
+#[cfg(feature = "mainnet")]
+declare_id!("CP8F2b4Dh43ovvwJ6MBYXx9gKuFZ4zFvw9y74Ahk2wy6");
+#[cfg(feature = "devnet")]
+declare_id!("CP8F2b4Dh43ovvwJ6MBYXx9gKuFZ4zFvw9y74Ahk2wy6");
+#[cfg(not(any(feature = "devnet", feature = "mainnet")))]
 declare_id!("CP8F2b4Dh43ovvwJ6MBYXx9gKuFZ4zFvw9y74Ahk2wy6");
 
+/// LP 代币精度
+pub const LP_DECIMALS: u8 = 9;
+
+/// 支持的手续费档位（基点），同一交易对在不同档位可以各有一个池子
+pub const FEE_TIERS: [u16; 3] = [5, 30, 100];
+
+/// StableSwap 放大系数的上限
+pub const MAX_AMP: u64 = 10_000;
+
+/// 加权池两侧权重之和（基点，即 100%）
+pub const TOTAL_WEIGHT_BPS: u16 = 10_000;
+
+/// 协议费占交易手续费的最大比例（基点）
+pub const MAX_PROTOCOL_FEE_SHARE_BPS: u16 = 5_000;
+
+/// 推荐人分成占交易手续费的最大比例（基点），与协议费之和不超过 100%
+pub const MAX_REFERRAL_FEE_SHARE_BPS: u16 = 5_000;
+
+/// 集中流动性池最多支持的价格区间数
+pub const MAX_LIQUIDITY_RANGES: usize = 8;
+
+/// 每个池子的价格观测环形缓冲区容量
+pub const MAX_PRICE_OBSERVATIONS: usize = 16;
+
+/// 统计快照按 UTC 自然日划分，每个池子每天一个快照账户
+pub const SECONDS_PER_DAY: i64 = 86_400;
+
+/// 手续费豁免名单最多容纳的交易者数量
+pub const MAX_FEE_EXEMPTIONS: usize = 8;
+
+/// 许可模式下交易者名单最多容纳的交易者数量
+pub const MAX_ALLOWED_TRADERS: usize = 16;
+
+/// 流动性挖矿每单位质押累计奖励的精度
+pub const REWARD_PRECISION: u128 = 1_000_000_000_000;
+
+/// 当前程序支持的池子账户版本，布局变化时递增；版本不符的池子拒绝所有指令
+/// 版本 2 起池子改为 zero-copy 布局
+pub const POOL_VERSION: u8 = 2;
+
+/// 池子账户末尾预留的字节数，新增字段时从中划出，避免改变账户大小
+pub const POOL_RESERVED_BYTES: usize = 16;
+
+/// 池子最多同时配置的手续费折扣时间窗口数
+pub const MAX_FEE_DISCOUNT_WINDOWS: usize = 2;
+
+/// 池子展示名称的最大长度（字节）
+pub const MAX_POOL_NAME_LEN: usize = 32;
+
+/// 池子缓存的代币符号最大长度（字节）
+pub const MAX_SYMBOL_LEN: usize = 10;
+
+/// 价格影响上限的最大值（基点），等于不限制
+pub const MAX_PRICE_IMPACT_BPS: u16 = 10_000;
+
+/// 熔断上限的最大值（基点）
+pub const MAX_RESERVE_MOVE_BPS: u16 = 10_000;
+
+/// swap_route 最多支持的跳数
+pub const MAX_ROUTE_HOPS: usize = 4;
+
+/// swap_route 中每一跳在 remaining_accounts 里占用的账户数：
+/// [pool, 输入侧 vault, 输出侧 vault, 输入侧协议费 vault, 输入 mint, 输出 mint]
+pub const ROUTE_ACCOUNTS_PER_HOP: usize = 6;
+
+/// batch_swap 最多支持的兑换笔数
+pub const MAX_BATCH_SWAPS: usize = 4;
+
+/// batch_swap 中每笔兑换在 remaining_accounts 里占用的账户数：
+/// [pool, 输入侧 vault, 输出侧 vault, 输入侧协议费 vault, 输入 mint, 输出 mint, 用户输入账户, 用户输出账户,
+///  手续费豁免名单, 推荐人账户]，后两个可选，不使用时传入本程序 ID
+pub const BATCH_ACCOUNTS_PER_SWAP: usize = 10;
+
+/// multi_pool_swap 最多同时使用的池子数
+pub const MAX_MULTI_POOL_SWAPS: usize = 4;
+
+/// multi_pool_swap 中每个池子在 remaining_accounts 里占用的账户数：
+/// [pool, 输入侧 vault, 输出侧 vault, 输入侧协议费 vault, 手续费豁免名单]，名单可选，不使用时传入本程序 ID
+pub const MULTI_POOL_ACCOUNTS_PER_POOL: usize = 5;
+
+/// 区间价格精度：price 表示 1 个 X 可兑换的 Y 数量 * PRICE_SCALE
+pub const PRICE_SCALE: u64 = 1_000_000_000;
+
+/// 预言机偏离上限的最大值（基点）
+pub const MAX_ORACLE_DEVIATION_BPS: u16 = 10_000;
+
+/// 预言机价格允许的最大滞后 slot 数
+pub const MAX_ORACLE_STALENESS_SLOTS: u64 = 25;
+
+/// Pyth 价格账户（legacy push oracle）布局中用到的字段
+const PYTH_MAGIC: u32 = 0xa1b2_c3d4;
+const PYTH_PRICE_ACCOUNT_TYPE: u32 = 3;
+const PYTH_STATUS_TRADING: u32 = 1;
+const PYTH_EXPO_OFFSET: usize = 20;
+const PYTH_AGG_PRICE_OFFSET: usize = 208;
+const PYTH_AGG_STATUS_OFFSET: usize = 224;
+const PYTH_AGG_PUB_SLOT_OFFSET: usize = 232;
+
 #[program]
 pub mod mock_dex {
     use super::*;
 
+    /// 创建全局池子注册表，部署后调用一次即可
+    pub fn initialize_registry(ctx: Context<InitializeRegistry>) -> Result<()> {
+        let registry = &mut ctx.accounts.registry;
+        registry.pool_count = 0;
+        registry.bump = ctx.bumps.registry;
+
+        vlog!("📒 池子注册表已创建");
+        Ok(())
+    }
+
+    /// 按 mint 对和手续费档位查找池子的注册表条目，通过 return data 返回登记信息
+    pub fn get_registered_pool(
+        ctx: Context<GetRegisteredPool>,
+        _token_x_mint: Pubkey,
+        _token_y_mint: Pubkey,
+        _fee_bps: u16,
+    ) -> Result<PoolRegistryEntry> {
+        let entry_info = ctx.accounts.registry_entry.to_account_info();
+        require!(!entry_info.data_is_empty(), ErrorCode::PoolNotRegistered);
+        let entry = PoolRegistryEntry::try_deserialize(&mut &entry_info.try_borrow_data()?[..])?;
+
+        vlog!("📒 找到池子 '{}': {}", entry.name, entry.pool);
+        Ok(entry)
+    }
+
     /// 初始化一个模拟的流动性池 (DEX Instance)
-    /// 每个池子由一个唯一的 `pool_name` 字符串区分
+    /// 两侧代币可以是 SPL Token 或 Token-2022，但必须属于同一个代币程序
+    /// 每个池子由排序后的 mint 对加手续费档位确定，同一交易对可以在不同档位各建一个
+    /// token_x_mint 必须小于 token_y_mint，pool_name 仅作为展示用的标签
+    /// 两侧 mint 的精度在初始化时读取并缓存，代币符号由调用者传入，客户端无需再查询 mint
     /// 遵循CEI模式：Check-Effects-Interactions
     pub fn initialize_pool(
         ctx: Context<InitializePool>,
-        pool_name: String, // 用于区分不同池子的唯一名称
+        pool_name: String, // 池子的展示名称
         initial_x_amount: u64,
         initial_y_amount: u64,
+        fee_bps: u16, // 交易手续费 (bps)
+        protocol_fee_share_bps: u16, // 手续费中归协议的比例
+        config: InitializePoolConfig, // 定价曲线、代币符号与可选的初始价格校验
     ) -> Result<()> {
-        let pool = &mut ctx.accounts.pool;
-        
         // === CHECK 阶段：所有验证和检查 ===
+        let InitializePoolConfig { curve, x_symbol, y_symbol, initial_price_check } = config;
         
         // 验证 pool_name 长度
-        require!(!pool_name.is_empty() && pool_name.len() <= 32, ErrorCode::InvalidPoolName);
+        require!(
+            !pool_name.is_empty() && pool_name.len() <= MAX_POOL_NAME_LEN,
+            ErrorCode::InvalidPoolName
+        );
+
+        // 验证代币符号长度
+        require!(
+            !x_symbol.is_empty() && x_symbol.len() <= MAX_SYMBOL_LEN,
+            ErrorCode::InvalidSymbol
+        );
+        require!(
+            !y_symbol.is_empty() && y_symbol.len() <= MAX_SYMBOL_LEN,
+            ErrorCode::InvalidSymbol
+        );
+
+        // mint 对必须按顺序传入，保证同一交易对只对应一个 PDA
+        require!(
+            ctx.accounts.token_x_mint.key() < ctx.accounts.token_y_mint.key(),
+            ErrorCode::UnsortedMints
+        );
 
         // 验证初始金额
         require!(initial_x_amount > 0 && initial_y_amount > 0, ErrorCode::InvalidAmount);
 
+        // 验证手续费档位
+        require!(FEE_TIERS.contains(&fee_bps), ErrorCode::InvalidFeeRate);
+
+        // 验证曲线参数
+        curve.validate()?;
+
+        // 验证协议费比例
+        require!(
+            protocol_fee_share_bps <= MAX_PROTOCOL_FEE_SHARE_BPS,
+            ErrorCode::InvalidProtocolFeeShare
+        );
+
         // 验证初始化者的代币余额
         require!(
             ctx.accounts.initializer_token_x_account.amount >= initial_x_amount,
@@ -50,340 +227,7359 @@ pub mod mock_dex {
         // === EFFECTS 阶段：更新所有状态 ===
         
         // 设置池子状态（在转账之前）
+        let mut pool = ctx.accounts.pool.load_init()?;
         pool.x_balance = initial_x_amount;
         pool.y_balance = initial_y_amount;
-        pool.name = pool_name.clone();
+        pool.set_name(&pool_name);
+        pool.lp_mint = ctx.accounts.lp_mint.key();
+        pool.bump = ctx.bumps.pool;
+        pool.admin = ctx.accounts.initializer.key();
+        pool.token_x_mint = ctx.accounts.token_x_mint.key();
+        pool.token_y_mint = ctx.accounts.token_y_mint.key();
+        pool.token_x_vault = ctx.accounts.token_x_vault.key();
+        pool.token_y_vault = ctx.accounts.token_y_vault.key();
+        pool.fee_x_vault = ctx.accounts.fee_x_vault.key();
+        pool.fee_y_vault = ctx.accounts.fee_y_vault.key();
+        pool.protocol_fee_share_bps = protocol_fee_share_bps;
+        pool.fee_bps = fee_bps;
+        pool.set_curve(curve);
+        pool.price_cumulative = 0;
+        pool.last_update_slot = Clock::get()?.slot;
+        pool.max_price_impact_bps = MAX_PRICE_IMPACT_BPS; // 默认不限制，由 admin 按需收紧
+        pool.paused = 0;
+        pool.referral_fee_share_bps = 0; // 默认不给推荐人分成
+        pool.oracle = Pubkey::default();
+        pool.max_oracle_deviation_bps = 0; // 默认不启用预言机保护
+        pool.fee_recipient = ctx.accounts.initializer.key(); // 默认由创建者接收协议费
+        pool.x_decimals = ctx.accounts.token_x_mint.decimals;
+        pool.y_decimals = ctx.accounts.token_y_mint.decimals;
+        pool.set_symbols(&x_symbol, &y_symbol);
+        pool.swap_sequence = 0;
+        pool.cumulative_volume_x = 0;
+        pool.cumulative_volume_y = 0;
+        pool.cumulative_fees_x = 0;
+        pool.cumulative_fees_y = 0;
+        pool.swap_count = 0;
+        pool.max_reserve_move_bps = 0; // 默认不启用熔断
+        pool.breaker_slot = 0;
+        pool.slot_outflow_x = 0;
+        pool.slot_outflow_y = 0;
+        pool.x_to_y_enabled = 1;
+        pool.y_to_x_enabled = 1;
+        pool.pending_admin = Pubkey::default();
+        pool.version = POOL_VERSION;
+        pool.event_nonce = 0;
+        pool.permissioned = 0;
+        pool.padding = [0; 3];
+        pool.fee_discounts = [FeeDiscountWindow::EMPTY; MAX_FEE_DISCOUNT_WINDOWS];
+        pool.reserved = [0; POOL_RESERVED_BYTES];
+
+        // 可选的初始价格校验：按初始储备和曲线推出的现货价格不能偏离参考价格太多，
+        // 避免误填数量创建出价格离谱的池子并登记到注册表
+        if let Some(check) = initial_price_check {
+            let reference_price = if check.reference_price > 0 {
+                check.reference_price as u128
+            } else {
+                let oracle = ctx.accounts.oracle.as_ref().ok_or(ErrorCode::InvalidOracleAccount)?;
+                read_pyth_price(oracle, Clock::get()?.slot)?
+            };
+            let initial_price = pool.spot_price()?;
+            require!(
+                price_deviation_bps(initial_price, reference_price)? <= check.max_deviation_bps as u64,
+                ErrorCode::InitialPriceOutOfBounds
+            );
+            vlog!("🎯 初始价格 {} 在参考价格 {} 的允许范围内", initial_price, reference_price);
+        }
+
+        // 判别符在指令结束时才写入，本指令内无法再 load_mut，初始化事件的序号在这里分配
+        let event_nonce = pool.next_event_nonce()?;
 
-        msg!("🏊‍♀️ Pool状态已设置: '{}' with X: {}, Y: {}", pool_name, initial_x_amount, initial_y_amount);
+        // 为池子创建注册表条目，供链下扫描和套利机器人发现池子；注册表本身只记录池子总数
+        let registry = &mut ctx.accounts.registry;
+        registry.pool_count = registry.pool_count.checked_add(1).ok_or(ErrorCode::Overflow)?;
+        let registry_entry = &mut ctx.accounts.registry_entry;
+        registry_entry.pool = ctx.accounts.pool.key();
+        registry_entry.name = pool_name.clone();
+        registry_entry.token_x_mint = pool.token_x_mint;
+        registry_entry.token_y_mint = pool.token_y_mint;
+        registry_entry.token_x_vault = pool.token_x_vault;
+        registry_entry.token_y_vault = pool.token_y_vault;
+        registry_entry.fee_bps = fee_bps;
+        registry_entry.bump = ctx.bumps.registry_entry;
+        // 铸造 LP 时池子作为签名者参与 CPI，必须先释放对账户数据的可变借用
+        drop(pool);
+
+        // 初始 LP 数量 = sqrt(x * y)
+        let initial_lp = integer_sqrt((initial_x_amount as u128) * (initial_y_amount as u128)) as u64;
+        require!(initial_lp > 0, ErrorCode::InvalidAmount);
+
+        vlog!("🏊‍♀️ Pool状态已设置: '{}' with X: {}, Y: {}, fee: {}bps", pool_name, initial_x_amount, initial_y_amount, fee_bps);
 
         // === INTERACTIONS 阶段：所有外部调用 ===
         
         // 将初始流动性从 initializer 转移到 DEX 的 Vaults
         // 转移 Token X
-        token::transfer(
+        token_interface::transfer_checked(
             CpiContext::new(
                 ctx.accounts.token_program.to_account_info(),
-                Transfer {
+                TransferChecked {
                     from: ctx.accounts.initializer_token_x_account.to_account_info(),
+                    mint: ctx.accounts.token_x_mint.to_account_info(),
                     to: ctx.accounts.token_x_vault.to_account_info(),
                     authority: ctx.accounts.initializer.to_account_info(),
                 },
             ),
             initial_x_amount,
+            ctx.accounts.token_x_mint.decimals,
         )?;
 
-        msg!("📥 Token X 转移完成: {}", initial_x_amount);
+        vlog!("📥 Token X 转移完成: {}", initial_x_amount);
 
         // 转移 Token Y
-        token::transfer(
+        token_interface::transfer_checked(
             CpiContext::new(
                 ctx.accounts.token_program.to_account_info(),
-                Transfer {
+                TransferChecked {
                     from: ctx.accounts.initializer_token_y_account.to_account_info(),
+                    mint: ctx.accounts.token_y_mint.to_account_info(),
                     to: ctx.accounts.token_y_vault.to_account_info(),
                     authority: ctx.accounts.initializer.to_account_info(),
                 },
             ),
             initial_y_amount,
+            ctx.accounts.token_y_mint.decimals,
+        )?;
+
+        vlog!("📥 Token Y 转移完成: {}", initial_y_amount);
+
+        // 向初始化者铸造初始 LP 代币
+        let token_x_mint = ctx.accounts.token_x_mint.key();
+        let token_y_mint = ctx.accounts.token_y_mint.key();
+        let fee_tier = fee_bps.to_le_bytes();
+        let pool_bump = [ctx.bumps.pool];
+        let pool_seeds = &[
+            b"mock_dex_pool".as_ref(),
+            token_x_mint.as_ref(),
+            token_y_mint.as_ref(),
+            &fee_tier,
+            &pool_bump,
+        ];
+        token_interface::mint_to(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                MintTo {
+                    mint: ctx.accounts.lp_mint.to_account_info(),
+                    to: ctx.accounts.initializer_lp_account.to_account_info(),
+                    authority: ctx.accounts.pool.to_account_info(),
+                },
+                &[&pool_seeds[..]],
+            ),
+            initial_lp,
         )?;
 
-        msg!("📥 Token Y 转移完成: {}", initial_y_amount);
+        vlog!("🪙 初始 LP 铸造完成: {}", initial_lp);
 
         // 发送事件
         emit!(PoolInitialized {
             pool_name: pool_name.clone(),
             initial_x_amount,
             initial_y_amount,
+            fee_bps,
+            curve,
             initializer: ctx.accounts.initializer.key(),
+            event_nonce,
             timestamp: Clock::get()?.unix_timestamp,
         });
 
-        msg!("✅ Initialized DEX pool '{}' with X: {} and Y: {}", pool_name, initial_x_amount, initial_y_amount);
+        vlog!("✅ Initialized DEX pool '{}' with X: {} and Y: {}", pool_name, initial_x_amount, initial_y_amount);
         Ok(())
     }
 
-    /// 真正的AMM兑换功能 - 使用恒定乘积模型 (x * y = k)
+    /// 真正的AMM兑换功能 - 按池子的曲线类型定价
+    /// (恒定乘积 x * y = k、StableSwap 不变量、加权恒定均值，或集中流动性区间)
+    /// 集中流动性池需要在 remaining_accounts[0] 传入池子的 LiquidityRanges 账户
+    /// 返回转给用户的 amount_out
     /// 遵循CEI模式：Check-Effects-Interactions
-    pub fn swap(
-        ctx: Context<Swap>,
+    pub fn swap<'info>(
+        ctx: Context<'_, '_, 'info, 'info, Swap<'info>>,
         amount_in: u64, // 卖出多少
         min_amount_out: u64, // 至少得到多少 (滑点保护)
-        pool_name: String, // 池子名称
-    ) -> Result<()> {
-        let pool = &mut ctx.accounts.pool;
-        let token_program = &ctx.accounts.token_program;
-
-        // 复制 pool 的名称和 bump，避免借用冲突
-        let pool_bump = ctx.bumps.pool;
-
+    ) -> Result<SwapResult> {
         // === CHECK 阶段：所有验证和检查 ===
         
-        require!(!pool_name.is_empty(), ErrorCode::InvalidPoolName);
         require!(amount_in > 0, ErrorCode::InvalidAmount);
 
-        // 检查 token_in_account 是 X 还是 Y
-        let from_token_account = &ctx.accounts.token_in_account;
-        let input_is_x = from_token_account.mint == ctx.accounts.user_token_x.mint;
-        let input_is_y = from_token_account.mint == ctx.accounts.user_token_y.mint;
+        let input_is_x = SwapHandler::validate_accounts(ctx.accounts)?;
 
-        require!(input_is_x || input_is_y, ErrorCode::InvalidTokenInAccount);
+        // 先转入并测量 vault 实际到账数量：带转账手续费的 Token-2022 mint 到账会少于 amount_in
+        let deposit = SwapHandler::deposit(ctx.accounts, input_is_x, amount_in)?;
 
-        let to_token_account = if input_is_x {
-            &ctx.accounts.user_token_y // 卖出 X 得到 Y
-        } else {
-            &ctx.accounts.user_token_x // 卖出 Y 得到 X
-        };
+        // 按实际到账数量和池子的曲线计算输出；集中流动性池按价格区间逐段成交
+        let pool = ctx.accounts.pool.load()?;
+        let amount_out = SwapHandler::compute_amount_out(
+            &pool,
+            ctx.accounts.pool.key(),
+            ctx.remaining_accounts,
+            deposit.effective_amount_in()?,
+            input_is_x,
+            SwapHandler::fee_bps(ctx.accounts)?,
+            true,
+        )?;
+        drop(pool);
 
-        // AMM 恒定乘积计算 (x * y = k)
-        let (reserve_in, reserve_out) = if input_is_x {
-            (pool.x_balance, pool.y_balance)
-        } else {
-            (pool.y_balance, pool.x_balance)
-        };
+        // 滑点保护：确保输出不少于最小预期
+        require!(amount_out >= min_amount_out, ErrorCode::SlippageTooHigh);
 
-        // 检查流动性
-        require!(reserve_in > 0 && reserve_out > 0, ErrorCode::InsufficientLiquidity);
+        // === EFFECTS / INTERACTIONS 阶段 ===
+        let pool_bump = ctx.accounts.pool.load()?.bump;
+        let result = SwapHandler::settle(ctx.accounts, pool_bump, input_is_x, amount_in, &deposit, amount_out)?;
 
-        // 计算手续费 (0.3% = 30 bps)
-        let fee_bps = 30u64;
-        let amount_in_with_fee = amount_in
-            .checked_mul(10000 - fee_bps)
-            .ok_or(ErrorCode::Overflow)?;
+        // 通过 return data 返回成交数量、手续费、价格影响和成交后储备，CPI 调用方无需再读取账户推算
+        Ok(result)
+    }
 
-        // AMM 恒定乘积公式计算输出
-        let numerator = amount_in_with_fee
-            .checked_mul(reserve_out)
-            .ok_or(ErrorCode::Overflow)?;
-        
-        let denominator = reserve_in
-            .checked_mul(10000)
-            .ok_or(ErrorCode::Overflow)?
-            .checked_add(amount_in_with_fee)
-            .ok_or(ErrorCode::Overflow)?;
+    /// 提交兑换承诺：commitment = swap_commitment_hash(池子, 用户, 输入 mint, amount_in, min_amount_out, salt)
+    /// 每个用户在每个池子上同时只能有一个未揭示的承诺，兑换参数在 reveal_swap 之前不会公开
+    pub fn commit_swap(ctx: Context<CommitSwap>, commitment: [u8; 32]) -> Result<()> {
+        require!(!ctx.accounts.pool.load()?.is_paused(), ErrorCode::PoolPaused);
 
-        let amount_out = numerator
-            .checked_div(denominator)
-            .ok_or(ErrorCode::Underflow)?;
+        let clock = Clock::get()?;
+        let swap_commitment = &mut ctx.accounts.commitment;
+        swap_commitment.pool = ctx.accounts.pool.key();
+        swap_commitment.owner = ctx.accounts.owner.key();
+        swap_commitment.commitment = commitment;
+        swap_commitment.committed_slot = clock.slot;
+        swap_commitment.bump = ctx.bumps.commitment;
 
-        // 滑点保护：确保输出不少于最小预期
+        let mut pool = ctx.accounts.pool.load_mut()?;
+        emit!(SwapCommitted {
+            pool_name: pool.name(),
+            owner: ctx.accounts.owner.key(),
+            commitment,
+            slot: clock.slot,
+            event_nonce: pool.next_event_nonce()?,
+            timestamp: clock.unix_timestamp,
+        });
+
+        vlog!("🔒 兑换承诺已提交 (slot {})", clock.slot);
+        Ok(())
+    }
+
+    /// 揭示并执行承诺的兑换：只能在提交之后的 slot 调用，参数与 salt 必须与承诺一致
+    /// 执行逻辑与 swap 相同，完成后关闭承诺账户并把租金退还用户
+    pub fn reveal_swap<'info>(
+        ctx: Context<'_, '_, 'info, 'info, RevealSwap<'info>>,
+        amount_in: u64,
+        min_amount_out: u64,
+        salt: [u8; 32],
+    ) -> Result<u64> {
+        // === CHECK 阶段 ===
+        require!(amount_in > 0, ErrorCode::InvalidAmount);
+        require!(
+            Clock::get()?.slot > ctx.accounts.commitment.committed_slot,
+            ErrorCode::RevealTooEarly
+        );
+
+        let accounts = &mut ctx.accounts.swap;
+        let input_is_x = SwapHandler::validate_accounts(accounts)?;
+        let expected = swap_commitment_hash(
+            &accounts.pool.key(),
+            &accounts.user_authority.key(),
+            &accounts.token_in_account.mint,
+            amount_in,
+            min_amount_out,
+            &salt,
+        );
+        require!(expected == ctx.accounts.commitment.commitment, ErrorCode::CommitmentMismatch);
+
+        let deposit = SwapHandler::deposit(accounts, input_is_x, amount_in)?;
+        let pool = accounts.pool.load()?;
+        let amount_out = SwapHandler::compute_amount_out(
+            &pool,
+            accounts.pool.key(),
+            ctx.remaining_accounts,
+            deposit.effective_amount_in()?,
+            input_is_x,
+            SwapHandler::fee_bps(accounts)?,
+            true,
+        )?;
+        drop(pool);
         require!(amount_out >= min_amount_out, ErrorCode::SlippageTooHigh);
 
-        // 确保池子有足够的储备
-        require!(amount_out < reserve_out, ErrorCode::InsufficientLiquidity);
+        // === EFFECTS / INTERACTIONS 阶段 ===
+        let pool_bump = accounts.pool.load()?.bump;
+        SwapHandler::settle(accounts, pool_bump, input_is_x, amount_in, &deposit, amount_out)?;
 
-        // 计算价格影响 (用于事件记录)
-        let price_before = if reserve_in > 0 { 
-            (reserve_out * 10000) / reserve_in 
-        } else { 
-            0 
-        };
-        
-        let new_reserve_in = reserve_in + amount_in;
-        let new_reserve_out = reserve_out - amount_out;
-        let price_after = if new_reserve_in > 0 { 
-            (new_reserve_out * 10000) / new_reserve_in 
-        } else { 
-            0 
-        };
+        vlog!("🔓 承诺兑换已揭示并执行: {} -> {}", amount_in, amount_out);
+        Ok(amount_out)
+    }
 
-        let price_impact_bps = if price_before > 0 {
-            ((price_before.max(price_after) - price_before.min(price_after)) * 10000) / price_before
-        } else {
-            0
-        };
+    /// 精确输出兑换：指定要得到的 amount_out，反解恒定乘积公式求所需输入
+    /// 实际输入超过 max_amount_in 时失败，适合凑出闪电贷还款所需的精确数量
+    pub fn swap_exact_out<'info>(
+        ctx: Context<'_, '_, 'info, 'info, Swap<'info>>,
+        amount_out: u64, // 要得到多少
+        max_amount_in: u64, // 最多卖出多少 (滑点保护)
+    ) -> Result<()> {
+        // === CHECK 阶段 ===
+        require!(amount_out > 0, ErrorCode::InvalidAmount);
 
-        // === EFFECTS 阶段：更新所有状态 ===
-        
-        // 更新池子储备状态（在所有外部转账之前）
-        if input_is_x {
-            pool.x_balance = pool.x_balance.checked_add(amount_in).ok_or(ErrorCode::Overflow)?;
-            pool.y_balance = pool.y_balance.checked_sub(amount_out).ok_or(ErrorCode::Underflow)?;
-        } else {
-            pool.y_balance = pool.y_balance.checked_add(amount_in).ok_or(ErrorCode::Overflow)?;
-            pool.x_balance = pool.x_balance.checked_sub(amount_out).ok_or(ErrorCode::Underflow)?;
-        }
+        let input_is_x = SwapHandler::validate_accounts(ctx.accounts)?;
 
-        msg!("💰 Pool状态已更新: X={}, Y={}", pool.x_balance, pool.y_balance);
+        let pool = ctx.accounts.pool.load()?;
+        let (reserve_in, reserve_out) = pool.reserves(input_is_x);
+        require!(reserve_in > 0 && amount_out < reserve_out, ErrorCode::InsufficientLiquidity);
 
-        // === INTERACTIONS 阶段：所有外部调用 ===
-        
-        // 1. 从用户账户转入到 DEX Vault
-        token::transfer(
-            CpiContext::new(
-                token_program.to_account_info(),
-                Transfer {
-                    from: from_token_account.to_account_info(),
-                    to: if input_is_x { 
-                        ctx.accounts.token_x_vault.to_account_info() 
-                    } else { 
-                        ctx.accounts.token_y_vault.to_account_info() 
-                    },
-                    authority: ctx.accounts.user_authority.to_account_info(),
-                },
-            ),
-            amount_in,
+        let amount_in = pool.curve().amount_in(
+            amount_out,
+            SwapHandler::fee_bps(ctx.accounts)?,
+            reserve_in,
+            reserve_out,
         )?;
+        require!(amount_in <= max_amount_in, ErrorCode::MaxAmountInExceeded);
+        drop(pool);
 
-        msg!("📥 转入完成: {} tokens", amount_in);
+        // 转账手续费会让实际到账少于 amount_in，此时无法保证精确输出
+        let deposit = SwapHandler::deposit(ctx.accounts, input_is_x, amount_in)?;
+        require!(
+            deposit.effective_amount_in()? >= amount_in,
+            ErrorCode::InsufficientInputReceived
+        );
 
-        // 2. 从 DEX Vault 转出到用户账户
-        let pool_seeds = &[
-            b"mock_dex_pool".as_ref(),
-            pool_name.as_bytes(),
-            &[pool_bump]
-        ];
-        let signer_seeds = &[&pool_seeds[..]];
+        // === EFFECTS / INTERACTIONS 阶段 ===
+        let pool_bump = ctx.accounts.pool.load()?.bump;
+        SwapHandler::settle(ctx.accounts, pool_bump, input_is_x, amount_in, &deposit, amount_out)?;
+        Ok(())
+    }
 
-        token::transfer(
-            CpiContext::new_with_signer(
-                token_program.to_account_info(),
-                Transfer {
-                    from: if input_is_x { 
-                        ctx.accounts.token_y_vault.to_account_info() 
-                    } else { 
-                        ctx.accounts.token_x_vault.to_account_info() 
-                    },
-                    to: to_token_account.to_account_info(),
-                    authority: ctx.accounts.pool.to_account_info(),
-                },
-                signer_seeds,
-            ),
-            amount_out,
+    /// 限价兑换：只成交不会让兑换后现货价格越过 limit_price 的那部分输入，其余输入不转出
+    /// limit_price 为按精度换算后 1 个 X 可兑换的 Y 数量 * PRICE_SCALE；卖出 X 时是价格下限，卖出 Y 时是价格上限
+    /// 通过 return data 返回未成交的输入数量，仅支持恒定乘积池
+    pub fn swap_with_price_limit<'info>(
+        ctx: Context<'_, '_, 'info, 'info, Swap<'info>>,
+        amount_in: u64, // 最多卖出多少
+        min_amount_out: u64, // 至少得到多少 (滑点保护，针对实际成交部分)
+        limit_price: u64,
+    ) -> Result<u64> {
+        // === CHECK 阶段 ===
+        require!(amount_in > 0, ErrorCode::InvalidAmount);
+
+        let input_is_x = SwapHandler::validate_accounts(ctx.accounts)?;
+
+        let max_amount_in = {
+            let pool = ctx.accounts.pool.load()?;
+            require!(pool.curve() == CurveType::ConstantProduct, ErrorCode::UnsupportedCurveOperation);
+            pool.max_amount_in_for_price_limit(input_is_x, limit_price)?
+        };
+        let executed_amount_in = amount_in.min(max_amount_in);
+        require!(executed_amount_in > 0, ErrorCode::PriceLimitReached);
+
+        let deposit = SwapHandler::deposit(ctx.accounts, input_is_x, executed_amount_in)?;
+        let pool = ctx.accounts.pool.load()?;
+        let amount_out = SwapHandler::compute_amount_out(
+            &pool,
+            ctx.accounts.pool.key(),
+            ctx.remaining_accounts,
+            deposit.effective_amount_in()?,
+            input_is_x,
+            SwapHandler::fee_bps(ctx.accounts)?,
+            true,
         )?;
+        drop(pool);
+        require!(amount_out >= min_amount_out, ErrorCode::SlippageTooHigh);
 
-        msg!("📤 转出完成: {} tokens", amount_out);
+        // === EFFECTS / INTERACTIONS 阶段 ===
+        let pool_bump = ctx.accounts.pool.load()?.bump;
+        SwapHandler::settle(ctx.accounts, pool_bump, input_is_x, executed_amount_in, &deposit, amount_out)?;
 
-        // 发送增强事件
-        emit!(SwapExecuted {
-            pool_name: pool_name.clone(),
+        let unused_amount_in = amount_in - executed_amount_in;
+        vlog!(
+            "🎯 限价兑换: 成交 {} / {}，未成交 {}",
+            executed_amount_in,
             amount_in,
-            amount_out,
-            price_impact_bps,
-            user: ctx.accounts.user_authority.key(),
-            timestamp: Clock::get()?.unix_timestamp,
-        });
-
-        msg!(
-            "✅ AMM Swap: {} -> {} (滑点: {}bps) on DEX '{}'", 
-            amount_in, 
-            amount_out, 
-            price_impact_bps,
-            pool_name
+            unused_amount_in
         );
-        Ok(())
+        Ok(unused_amount_in)
     }
-}
 
-// ---------------------------------------------------------------- //
-//                          账户定义                               //
-// ---------------------------------------------------------------- //
+    /// 部分成交兑换：流动性或价格不足以成交全部 amount_in 时，只成交满足 min_price 的最大部分
+    /// min_price 为每 1 个输入代币（按精度换算后的整币）至少得到的输出整币数量 * PRICE_SCALE（按平均成交价计）
+    /// 未成交的输入留在用户账户中，数量通过 return data 返回
+    pub fn swap_partial_fill<'info>(
+        ctx: Context<'_, '_, 'info, 'info, Swap<'info>>,
+        amount_in: u64, // 最多卖出多少
+        min_price: u64, // 最低平均成交价 (滑点保护)
+    ) -> Result<u64> {
+        // === CHECK 阶段 ===
+        require!(amount_in > 0, ErrorCode::InvalidAmount);
 
-#[derive(Accounts)]
-#[instruction(pool_name: String, initial_x_amount: u64, initial_y_amount: u64)]
-pub struct InitializePool<'info> {
-    #[account(
-        init,
-        payer = initializer,
-        seeds = [b"mock_dex_pool", pool_name.as_bytes()],
-        bump,
-        space = 8 + 8 + 8 + 32,
-    )]
-    pub pool: Account<'info, MockDexPool>,
+        let input_is_x = SwapHandler::validate_accounts(ctx.accounts)?;
 
-    #[account(mut)]
-    pub initializer: Signer<'info>,
+        let pool = ctx.accounts.pool.load()?;
+        let executed_amount_in = SwapHandler::max_fillable_amount_in(
+            &pool,
+            ctx.accounts.pool.key(),
+            ctx.remaining_accounts,
+            amount_in,
+            input_is_x,
+            SwapHandler::fee_bps(ctx.accounts)?,
+            min_price,
+        )?;
+        drop(pool);
+        require!(executed_amount_in > 0, ErrorCode::InsufficientLiquidity);
 
-    #[account(mut)]
-    pub initializer_token_x_account: Account<'info, TokenAccount>,
-    #[account(mut)]
-    pub initializer_token_y_account: Account<'info, TokenAccount>,
+        let deposit = SwapHandler::deposit(ctx.accounts, input_is_x, executed_amount_in)?;
+        let pool = ctx.accounts.pool.load()?;
+        let amount_out = SwapHandler::compute_amount_out(
+            &pool,
+            ctx.accounts.pool.key(),
+            ctx.remaining_accounts,
+            deposit.effective_amount_in()?,
+            input_is_x,
+            SwapHandler::fee_bps(ctx.accounts)?,
+            true,
+        )?;
+        drop(pool);
+        // 转账手续费可能让实际成交价低于查找时的估算，按实际输出再检查一次
+        require!(
+            amount_out
+                >= ctx.accounts.pool.load()?.amount_at_price(
+                    executed_amount_in,
+                    min_price as u128,
+                    PRICE_SCALE as u128,
+                    input_is_x,
+                )?,
+            ErrorCode::SlippageTooHigh
+        );
 
-    #[account(
-        init,
-        payer = initializer,
-        seeds = [b"token_x_vault", pool.key().as_ref()],
-        bump,
-        token::mint = token_x_mint,
-        token::authority = pool,
-    )]
-    pub token_x_vault: Account<'info, TokenAccount>,
-    #[account(
-        init,
-        payer = initializer,
-        seeds = [b"token_y_vault", pool.key().as_ref()],
-        bump,
-        token::mint = token_y_mint,
-        token::authority = pool,
-    )]
-    pub token_y_vault: Account<'info, TokenAccount>,
+        // === EFFECTS / INTERACTIONS 阶段 ===
+        let pool_bump = ctx.accounts.pool.load()?.bump;
+        SwapHandler::settle(ctx.accounts, pool_bump, input_is_x, executed_amount_in, &deposit, amount_out)?;
 
-    pub token_x_mint: Account<'info, Mint>,
-    pub token_y_mint: Account<'info, Mint>,
+        let unfilled_amount_in = amount_in - executed_amount_in;
+        vlog!(
+            "🧩 部分成交兑换: 成交 {} / {}，未成交 {}",
+            executed_amount_in,
+            amount_in,
+            unfilled_amount_in
+        );
+        Ok(unfilled_amount_in)
+    }
 
-    pub token_program: Program<'info, Token>,
-    pub system_program: Program<'info, System>,
-    pub rent: Sysvar<'info, Rent>,
-}
+    /// 多跳兑换：按 remaining_accounts 中的池子顺序依次兑换，上一跳的输出直接作为下一跳的输入
+    /// 中间代币在池子 vault 之间直接划转，不经过用户账户；只检查最终输出的滑点
+    /// 每一跳都按 vault 实际到账数量计价，兼容带转账手续费的 Token-2022 mint
+    /// 集中流动性池不支持出现在路由中
+    pub fn swap_route<'info>(
+        ctx: Context<'_, '_, 'info, 'info, SwapRoute<'info>>,
+        amount_in: u64,
+        min_amount_out: u64,
+    ) -> Result<()> {
+        // === CHECK 阶段 ===
+        require!(amount_in > 0, ErrorCode::InvalidAmount);
 
-#[derive(Accounts)]
-#[instruction(amount_in: u64, min_amount_out: u64, pool_name: String)]
-pub struct Swap<'info> {
-    #[account(
-        mut,
-        seeds = [b"mock_dex_pool", pool_name.as_bytes()], // 使用传入的 pool_name 作为种子
-        bump,
-    )]
-    pub pool: Account<'info, MockDexPool>,
+        let remaining = ctx.remaining_accounts;
+        require!(
+            !remaining.is_empty() && remaining.len() % ROUTE_ACCOUNTS_PER_HOP == 0,
+            ErrorCode::InvalidRoute
+        );
+        let hops = remaining.len() / ROUTE_ACCOUNTS_PER_HOP;
+        require!(hops <= MAX_ROUTE_HOPS, ErrorCode::InvalidRoute);
 
-    /// CHECK: 用户的输入Token账户 (可以是 Token X 或 Token Y)
-    /// 必须是 mut 因为会从中转出Token
-    #[account(mut)]
-    pub token_in_account: Account<'info, TokenAccount>,
+        let token_program = ctx.accounts.token_program.to_account_info();
+        let user_authority = ctx.accounts.user_authority.to_account_info();
+        let user_token_in = ctx.accounts.user_token_in.to_account_info();
+        let user_token_out = ctx.accounts.user_token_out.to_account_info();
+        let mut hop_amount_in = amount_in;
+        let mut expected_mint = ctx.accounts.user_token_in.mint;
 
-    /// CHECK: DEX 的 Token X Vault
-    #[account(mut)]
-    pub token_x_vault: Account<'info, TokenAccount>,
-    /// CHECK: DEX 的 Token Y Vault
-    #[account(mut)]
-    pub token_y_vault: Account<'info, TokenAccount>,
+        for (hop, hop_accounts) in remaining.chunks(ROUTE_ACCOUNTS_PER_HOP).enumerate() {
+            let is_first = hop == 0;
+            let is_last = hop + 1 == hops;
 
-    /// CHECK: 用户的 Token X 账户 (可能用于接收或发送)
-    #[account(mut)]
-    pub user_token_x: Account<'info, TokenAccount>,
-    /// CHECK: 用户的 Token Y 账户 (可能用于接收或发送)
-    #[account(mut)]
-    pub user_token_y: Account<'info, TokenAccount>,
+            let pool_loader: AccountLoader<'info, MockDexPool> = AccountLoader::try_from(&hop_accounts[0])?;
+            // transfer_checked 需要 mint 账户及其精度
+            let mint_in: InterfaceAccount<'info, Mint> = InterfaceAccount::try_from(&hop_accounts[4])?;
+            let mint_out: InterfaceAccount<'info, Mint> = InterfaceAccount::try_from(&hop_accounts[5])?;
+            require_keys_eq!(mint_in.key(), expected_mint, ErrorCode::InvalidRoute);
+            if is_last {
+                require_keys_eq!(mint_out.key(), ctx.accounts.user_token_out.mint, ErrorCode::InvalidRoute);
+            }
 
-    /// 用户的签名 authority (例如：套利机器人 PDA)
-    /// 这个账户必须签名从 `token_in_account` 到 `DEX Vault` 的转账
-    pub user_authority: Signer<'info>,
+            // 协议费 vault 决定本跳方向，vault 与 mint 由 swap_in_pool 按方向校验
+            let input_is_x = hop_accounts[3].key() == pool_loader.load()?.fee_x_vault;
+            // 输出转给用户（最后一跳）或直接转入下一跳的输入 vault
+            let destination = if is_last {
+                &user_token_out
+            } else {
+                &remaining[(hop + 1) * ROUTE_ACCOUNTS_PER_HOP + 1]
+            };
+            let outcome = SwapHandler::swap_in_pool(
+                &PoolSwapLeg {
+                    pool: &pool_loader,
+                    input_is_x,
+                    vault_in: &hop_accounts[1],
+                    vault_out: &hop_accounts[2],
+                    fee_vault: &hop_accounts[3],
+                    mint_in: &mint_in,
+                    mint_out: &mint_out,
+                    // 后续跳的输入已由上一跳转入 vault_in
+                    source: is_first.then_some(&user_token_in),
+                    destination,
+                    fee_allowlist: None,
+                    referrer: None,
+                },
+                &token_program,
+                &user_authority,
+                hop_amount_in,
+            )?;
+            // 下一跳只能使用实际转入其 vault 的数量；最后一跳按用户实际到账检查滑点
+            if is_last {
+                require!(outcome.amount_received >= min_amount_out, ErrorCode::SlippageTooHigh);
+            }
 
-    pub token_program: Program<'info, Token>,
-}
+            vlog!("🔀 第 {} 跳 '{}': {} -> {}", hop + 1, outcome.pool_name, hop_amount_in, outcome.amount_out);
 
-#[account]
-pub struct MockDexPool {
-    pub x_balance: u64,
-    pub y_balance: u64,
-    pub name: String, // 存储池子名称，用于PDA种子和区分
-}
+            expected_mint = mint_out.key();
+            hop_amount_in = outcome.amount_received;
+        }
 
-#[event]
-pub struct SwapExecuted {
-    pub pool_name: String,
-    pub amount_in: u64,
-    pub amount_out: u64,
-    pub price_impact_bps: u64,
-    pub user: Pubkey,
-    pub timestamp: i64,
-}
+        emit!(RouteSwapExecuted {
+            hops: hops as u8,
+            amount_in,
+            amount_out: hop_amount_in,
+            user: ctx.accounts.user_authority.key(),
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        vlog!("✅ 多跳兑换完成: {} -> {} ({} 跳)", amount_in, hop_amount_in, hops);
+        Ok(())
+    }
+
+    /// 批量兑换：在一笔交易中原子地执行多笔互不依赖的兑换，任意一笔失败则全部回滚
+    /// 每笔兑换在 remaining_accounts 中按 BATCH_ACCOUNTS_PER_SWAP 个账户依次传入，
+    /// 与 swap 一样按池子的手续费豁免名单收费，传入推荐人账户时给推荐人分成
+    /// 集中流动性池不支持出现在批量兑换中
+    pub fn batch_swap<'info>(
+        ctx: Context<'_, '_, 'info, 'info, BatchSwap<'info>>,
+        swaps: Vec<BatchSwapLeg>,
+    ) -> Result<()> {
+        // === CHECK 阶段 ===
+        require!(
+            !swaps.is_empty() && swaps.len() <= MAX_BATCH_SWAPS,
+            ErrorCode::InvalidBatchSwap
+        );
+        let remaining = ctx.remaining_accounts;
+        require!(
+            remaining.len() == swaps.len() * BATCH_ACCOUNTS_PER_SWAP,
+            ErrorCode::InvalidBatchSwap
+        );
+
+        let token_program = ctx.accounts.token_program.to_account_info();
+        let user_authority = ctx.accounts.user_authority.to_account_info();
+
+        for (leg, leg_accounts) in swaps.iter().zip(remaining.chunks(BATCH_ACCOUNTS_PER_SWAP)) {
+            let pool_loader: AccountLoader<'info, MockDexPool> = AccountLoader::try_from(&leg_accounts[0])?;
+            require_keys_eq!(pool_loader.key(), leg.pool, ErrorCode::InvalidBatchSwap);
+            let mint_in: InterfaceAccount<'info, Mint> = InterfaceAccount::try_from(&leg_accounts[4])?;
+            let mint_out: InterfaceAccount<'info, Mint> = InterfaceAccount::try_from(&leg_accounts[5])?;
+            let fee_allowlist = optional_account(&leg_accounts[8])
+                .map(Account::<'info, FeeAllowlist>::try_from)
+                .transpose()?;
+
+            let outcome = SwapHandler::swap_in_pool(
+                &PoolSwapLeg {
+                    pool: &pool_loader,
+                    input_is_x: leg.direction == SwapDirection::XToY,
+                    vault_in: &leg_accounts[1],
+                    vault_out: &leg_accounts[2],
+                    fee_vault: &leg_accounts[3],
+                    mint_in: &mint_in,
+                    mint_out: &mint_out,
+                    source: Some(&leg_accounts[6]),
+                    destination: &leg_accounts[7],
+                    fee_allowlist: fee_allowlist.as_deref(),
+                    referrer: optional_account(&leg_accounts[9]),
+                },
+                &token_program,
+                &user_authority,
+                leg.amount_in,
+            )?;
+            // 滑点保护按用户实际到账数量检查
+            require!(outcome.amount_received >= leg.min_amount_out, ErrorCode::SlippageTooHigh);
+
+            vlog!("📦 批量兑换 '{}': {} -> {}", outcome.pool_name, leg.amount_in, outcome.amount_received);
+        }
+
+        emit!(BatchSwapExecuted {
+            swap_count: swaps.len() as u8,
+            user: ctx.accounts.user_authority.key(),
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        vlog!("✅ 批量兑换完成: {} 笔", swaps.len());
+        Ok(())
+    }
+
+    /// 多池同向兑换：把同一交易对、同一方向的兑换按 amounts_in 分摊到 remaining_accounts 中的多个池子，
+    /// 单个池子不设滑点下限，只要求所有池子的输出合计不少于 min_total_out，否则整条指令回滚
+    /// 与 swap 一样按各池子的手续费豁免名单收费，传入推荐人账户时给推荐人分成
+    /// 通过 return data 返回用户实际收到的输出合计；集中流动性池不支持出现在其中
+    pub fn multi_pool_swap<'info>(
+        ctx: Context<'_, '_, 'info, 'info, MultiPoolSwap<'info>>,
+        amounts_in: Vec<u64>,
+        min_total_out: u64,
+    ) -> Result<u64> {
+        // === CHECK 阶段 ===
+        require!(
+            !amounts_in.is_empty() && amounts_in.len() <= MAX_MULTI_POOL_SWAPS,
+            ErrorCode::InvalidMultiPoolSwap
+        );
+        let remaining = ctx.remaining_accounts;
+        require!(
+            remaining.len() == amounts_in.len() * MULTI_POOL_ACCOUNTS_PER_POOL,
+            ErrorCode::InvalidMultiPoolSwap
+        );
+
+        let token_program = ctx.accounts.token_program.to_account_info();
+        let user_authority = ctx.accounts.user_authority.to_account_info();
+        let user_token_in = ctx.accounts.user_token_in.to_account_info();
+        let user_token_out = ctx.accounts.user_token_out.to_account_info();
+        let referrer = ctx
+            .accounts
+            .referrer_token_account
+            .as_ref()
+            .map(|referrer| referrer.to_account_info());
+        let mint_in = &ctx.accounts.mint_in;
+        let total_before = token_balance(&user_token_out)?;
+        let mut total_amount_in = 0u64;
+
+        for (&amount_in, pool_accounts) in amounts_in.iter().zip(remaining.chunks(MULTI_POOL_ACCOUNTS_PER_POOL)) {
+            total_amount_in = total_amount_in.checked_add(amount_in).ok_or(ErrorCode::Overflow)?;
+
+            let pool_loader: AccountLoader<'info, MockDexPool> = AccountLoader::try_from(&pool_accounts[0])?;
+            let fee_allowlist = optional_account(&pool_accounts[4])
+                .map(Account::<'info, FeeAllowlist>::try_from)
+                .transpose()?;
+            // 所有池子必须是同一交易对，方向由输入 mint 决定，vault 与 mint 由 swap_in_pool 按方向校验
+            let input_is_x = mint_in.key() == pool_loader.load()?.token_x_mint;
+
+            let outcome = SwapHandler::swap_in_pool(
+                &PoolSwapLeg {
+                    pool: &pool_loader,
+                    input_is_x,
+                    vault_in: &pool_accounts[1],
+                    vault_out: &pool_accounts[2],
+                    fee_vault: &pool_accounts[3],
+                    mint_in,
+                    mint_out: &ctx.accounts.mint_out,
+                    source: Some(&user_token_in),
+                    destination: &user_token_out,
+                    fee_allowlist: fee_allowlist.as_deref(),
+                    referrer: referrer.as_ref(),
+                },
+                &token_program,
+                &user_authority,
+                amount_in,
+            )?;
+
+            vlog!("🧺 多池兑换 '{}': {} -> {}", outcome.pool_name, amount_in, outcome.amount_out);
+        }
+
+        // 滑点保护只看合计：按用户实际到账数量检查，任何一个池子成交不足都可以由其他池子弥补
+        let total_amount_out = token_balance(&user_token_out)?
+            .checked_sub(total_before)
+            .ok_or(ErrorCode::Underflow)?;
+        require!(total_amount_out >= min_total_out, ErrorCode::SlippageTooHigh);
+
+        emit!(MultiPoolSwapExecuted {
+            pool_count: amounts_in.len() as u8,
+            amount_in: total_amount_in,
+            amount_out: total_amount_out,
+            user: ctx.accounts.user_authority.key(),
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        vlog!("✅ 多池兑换完成: {} -> {} ({} 个池子)", total_amount_in, total_amount_out, amounts_in.len());
+        Ok(total_amount_out)
+    }
+
+    /// 闪电兑换：先把 amount_out 乐观地转给 receiver，再 CPI 调用方指定的回调程序，
+    /// 回调结束后要求输入 vault 实际收到的还款按池子曲线（含手续费）足以换出 amount_out
+    /// 无需借贷池即可完成 Uniswap v2 式套利；回调所需账户通过 remaining_accounts 原样传入
+    pub fn flash_swap<'info>(
+        ctx: Context<'_, '_, 'info, 'info, FlashSwap<'info>>,
+        amount_out: u64,
+        direction: SwapDirection, // 还款方向：XToY 表示借出 Y、还入 X
+        callback_data: Vec<u8>,
+    ) -> Result<()> {
+        // === CHECK 阶段 ===
+        require!(amount_out > 0, ErrorCode::InvalidAmount);
+
+        // 运行时只允许程序直接自递归，排除本程序后回调期间无法重入修改池子
+        require_keys_neq!(
+            ctx.accounts.callback_program.key(),
+            crate::ID,
+            ErrorCode::InvalidCallbackProgram
+        );
+
+        let input_is_x = direction == SwapDirection::XToY;
+        let pool = ctx.accounts.pool.load()?;
+        require!(!pool.is_paused(), ErrorCode::PoolPaused);
+        pool.ensure_trader_allowed(&ctx.accounts.user_authority.key(), None)?;
+        pool.ensure_direction_enabled(input_is_x)?;
+        require!(pool.curve() != CurveType::Concentrated, ErrorCode::UnsupportedCurveOperation);
+
+        let (reserve_in, reserve_out) = pool.reserves(input_is_x);
+        require!(reserve_in > 0 && amount_out < reserve_out, ErrorCode::InsufficientLiquidity);
+        pool.assert_vaults_cover_reserves(
+            ctx.accounts.token_x_vault.amount,
+            ctx.accounts.token_y_vault.amount,
+        )?;
+
+        let (expected_fee_vault, expected_mint_out) = if input_is_x {
+            (pool.fee_x_vault, pool.token_y_mint)
+        } else {
+            (pool.fee_y_vault, pool.token_x_mint)
+        };
+        require_keys_eq!(
+            ctx.accounts.protocol_fee_vault.key(),
+            expected_fee_vault,
+            ErrorCode::InvalidFeeVault
+        );
+        require_keys_eq!(ctx.accounts.receiver.mint, expected_mint_out, ErrorCode::InvalidTokenMint);
+
+        let pool_name = pool.name();
+        let curve = pool.curve();
+        let fee_bps = pool.current_fee_bps(Clock::get()?.unix_timestamp) as u64;
+        let protocol_fee_share_bps = pool.protocol_fee_share_bps as u64;
+        let max_price_impact_bps = pool.max_price_impact_bps as u64;
+        let token_x_mint = pool.token_x_mint;
+        let token_y_mint = pool.token_y_mint;
+        let fee_tier = pool.fee_bps.to_le_bytes();
+        let pool_bump = [pool.bump];
+        drop(pool);
+        let pool_seeds = &[
+            b"mock_dex_pool".as_ref(),
+            token_x_mint.as_ref(),
+            token_y_mint.as_ref(),
+            &fee_tier,
+            &pool_bump,
+        ];
+        let signer_seeds = &[&pool_seeds[..]];
+
+        let balance_in_before = if input_is_x {
+            ctx.accounts.token_x_vault.amount
+        } else {
+            ctx.accounts.token_y_vault.amount
+        };
+
+        // === INTERACTIONS 阶段：乐观转出并执行回调 ===
+        let (vault_out, mint_out) = if input_is_x {
+            (&ctx.accounts.token_y_vault, &ctx.accounts.token_y_mint)
+        } else {
+            (&ctx.accounts.token_x_vault, &ctx.accounts.token_x_mint)
+        };
+        token_interface::transfer_checked(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                TransferChecked {
+                    from: vault_out.to_account_info(),
+                    mint: mint_out.to_account_info(),
+                    to: ctx.accounts.receiver.to_account_info(),
+                    authority: ctx.accounts.pool.to_account_info(),
+                },
+                signer_seeds,
+            ),
+            amount_out,
+            mint_out.decimals,
+        )?;
+
+        vlog!("⚡ 闪电兑换 '{}': 已转出 {}，调用回调程序 {}", pool_name, amount_out, ctx.accounts.callback_program.key());
+
+        // 回调程序在其中完成套利，并把输入代币还入 vault_in
+        let callback_ix = Instruction {
+            program_id: ctx.accounts.callback_program.key(),
+            accounts: ctx
+                .remaining_accounts
+                .iter()
+                .map(|account| {
+                    if account.is_writable {
+                        AccountMeta::new(account.key(), account.is_signer)
+                    } else {
+                        AccountMeta::new_readonly(account.key(), account.is_signer)
+                    }
+                })
+                .collect(),
+            data: callback_data,
+        };
+        let mut callback_infos = ctx.remaining_accounts.to_vec();
+        callback_infos.push(ctx.accounts.callback_program.to_account_info());
+        invoke(&callback_ix, &callback_infos)?;
+
+        // === CHECK 阶段：按 vault 实际到账数量校验还款 ===
+        let vault_in = if input_is_x {
+            &mut ctx.accounts.token_x_vault
+        } else {
+            &mut ctx.accounts.token_y_vault
+        };
+        vault_in.reload()?;
+        let amount_paid = vault_in
+            .amount
+            .checked_sub(balance_in_before)
+            .ok_or(ErrorCode::FlashSwapNotRepaid)?;
+        require!(amount_paid > 0, ErrorCode::FlashSwapNotRepaid);
+
+        // 还款按交易前的储备定价，必须至少能换出借走的数量
+        let amount_covered = curve.amount_out(amount_paid, fee_bps, reserve_in, reserve_out, input_is_x)?;
+        require!(amount_covered >= amount_out, ErrorCode::FlashSwapNotRepaid);
+
+        let fee_amount = mul_bps(amount_paid, fee_bps)?;
+        let protocol_fee = mul_bps(fee_amount, protocol_fee_share_bps)?;
+        let amount_to_reserve = amount_paid - protocol_fee;
+
+        let price_impact_bps = ctx
+            .accounts
+            .pool
+            .load()?
+            .price_impact_bps(input_is_x, amount_to_reserve, amount_out)?;
+        require!(price_impact_bps <= max_price_impact_bps, ErrorCode::PriceImpactTooHigh);
+
+        // === EFFECTS 阶段 ===
+        let mut pool = ctx.accounts.pool.load_mut()?;
+        pool.accumulate_price(Clock::get()?.slot)?;
+        if input_is_x {
+            pool.x_balance = pool.x_balance.checked_add(amount_to_reserve).ok_or(ErrorCode::Overflow)?;
+            pool.y_balance = pool.y_balance.checked_sub(amount_out).ok_or(ErrorCode::Underflow)?;
+        } else {
+            pool.y_balance = pool.y_balance.checked_add(amount_to_reserve).ok_or(ErrorCode::Overflow)?;
+            pool.x_balance = pool.x_balance.checked_sub(amount_out).ok_or(ErrorCode::Underflow)?;
+        }
+        pool.check_circuit_breaker(Clock::get()?.slot, input_is_x, amount_out)?;
+        pool.record_swap(input_is_x, amount_paid, amount_out, fee_amount)?;
+        let event_nonce = pool.next_event_nonce()?;
+        drop(pool);
+
+        // === INTERACTIONS 阶段：协议费从 vault_in 划入 fee vault ===
+        if protocol_fee > 0 {
+            let (vault_in, mint_in) = if input_is_x {
+                (&ctx.accounts.token_x_vault, &ctx.accounts.token_x_mint)
+            } else {
+                (&ctx.accounts.token_y_vault, &ctx.accounts.token_y_mint)
+            };
+            token_interface::transfer_checked(
+                CpiContext::new_with_signer(
+                    ctx.accounts.token_program.to_account_info(),
+                    TransferChecked {
+                        from: vault_in.to_account_info(),
+                        mint: mint_in.to_account_info(),
+                        to: ctx.accounts.protocol_fee_vault.to_account_info(),
+                        authority: ctx.accounts.pool.to_account_info(),
+                    },
+                    signer_seeds,
+                ),
+                protocol_fee,
+                mint_in.decimals,
+            )?;
+        }
+
+        ctx.accounts.token_x_vault.reload()?;
+        ctx.accounts.token_y_vault.reload()?;
+        ctx.accounts.pool.load()?.assert_vaults_cover_reserves(
+            ctx.accounts.token_x_vault.amount,
+            ctx.accounts.token_y_vault.amount,
+        )?;
+
+        emit!(FlashSwapExecuted {
+            pool_name: pool_name.clone(),
+            amount_out,
+            amount_paid,
+            protocol_fee,
+            callback_program: ctx.accounts.callback_program.key(),
+            user: ctx.accounts.user_authority.key(),
+            event_nonce,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        vlog!("✅ 闪电兑换完成 '{}': 借出 {}，还入 {}", pool_name, amount_out, amount_paid);
+        Ok(())
+    }
+
+    /// 挂限价单：把 amount_in 转入订单托管账户，等待 keeper 在池子价格穿过限价时成交
+    /// mint_in 决定方向；limit_price 为按精度换算后 1 个 X 可兑换的 Y 数量 * PRICE_SCALE，
+    /// 卖出 X 时是最低成交价，卖出 Y 时是最高成交价
+    pub fn place_limit_order(
+        ctx: Context<PlaceLimitOrder>,
+        order_id: u64,
+        amount_in: u64,
+        limit_price: u64,
+    ) -> Result<()> {
+        // === CHECK 阶段 ===
+        require!(amount_in > 0, ErrorCode::InvalidAmount);
+        require!(limit_price > 0, ErrorCode::InvalidPriceLimit);
+        require!(!ctx.accounts.pool.load()?.is_paused(), ErrorCode::PoolPaused);
+
+        // === INTERACTIONS 阶段 ===
+        token_interface::transfer_checked(
+            CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                TransferChecked {
+                    from: ctx.accounts.owner_token_in.to_account_info(),
+                    mint: ctx.accounts.mint_in.to_account_info(),
+                    to: ctx.accounts.escrow.to_account_info(),
+                    authority: ctx.accounts.owner.to_account_info(),
+                },
+            ),
+            amount_in,
+            ctx.accounts.mint_in.decimals,
+        )?;
+
+        // 按托管账户实际到账数量记录订单，兼容带转账手续费的 mint
+        ctx.accounts.escrow.reload()?;
+        let escrowed_amount = ctx.accounts.escrow.amount;
+
+        // === EFFECTS 阶段 ===
+        let mut pool = ctx.accounts.pool.load_mut()?;
+        let order = &mut ctx.accounts.order;
+        order.pool = ctx.accounts.pool.key();
+        order.owner = ctx.accounts.owner.key();
+        order.mint_in = ctx.accounts.mint_in.key();
+        order.input_is_x = ctx.accounts.mint_in.key() == pool.token_x_mint;
+        order.amount_in = escrowed_amount;
+        order.limit_price = limit_price;
+        order.order_id = order_id;
+        order.created_at = Clock::get()?.unix_timestamp;
+        order.bump = ctx.bumps.order;
+        order.escrow_bump = ctx.bumps.escrow;
+
+        emit!(LimitOrderPlaced {
+            pool_name: pool.name(),
+            order: order.key(),
+            owner: order.owner,
+            input_is_x: order.input_is_x,
+            amount_in: escrowed_amount,
+            limit_price,
+            event_nonce: pool.next_event_nonce()?,
+            timestamp: order.created_at,
+        });
+
+        vlog!("📝 限价单 #{} 已挂出: {} @ {}", order_id, escrowed_amount, limit_price);
+        Ok(())
+    }
+
+    /// 撤销限价单 - 仅订单 owner 可调用，退回托管代币并关闭订单账户
+    pub fn cancel_order(ctx: Context<CancelOrder>) -> Result<()> {
+        let order = &ctx.accounts.order;
+        let amount = ctx.accounts.escrow.amount;
+
+        let pool_key = order.pool;
+        let owner_key = order.owner;
+        let order_id = order.order_id.to_le_bytes();
+        let order_bump = [order.bump];
+        let order_seeds = &[
+            b"limit_order".as_ref(),
+            pool_key.as_ref(),
+            owner_key.as_ref(),
+            &order_id,
+            &order_bump,
+        ];
+        let signer_seeds = &[&order_seeds[..]];
+
+        if amount > 0 {
+            token_interface::transfer_checked(
+                CpiContext::new_with_signer(
+                    ctx.accounts.token_program.to_account_info(),
+                    TransferChecked {
+                        from: ctx.accounts.escrow.to_account_info(),
+                        mint: ctx.accounts.mint_in.to_account_info(),
+                        to: ctx.accounts.owner_token_in.to_account_info(),
+                        authority: ctx.accounts.order.to_account_info(),
+                    },
+                    signer_seeds,
+                ),
+                amount,
+                ctx.accounts.mint_in.decimals,
+            )?;
+        }
+        token_interface::close_account(CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            CloseAccount {
+                account: ctx.accounts.escrow.to_account_info(),
+                destination: ctx.accounts.owner.to_account_info(),
+                authority: ctx.accounts.order.to_account_info(),
+            },
+            signer_seeds,
+        ))?;
+
+        let event_nonce = ctx.accounts.pool.load_mut()?.next_event_nonce()?;
+        emit!(LimitOrderCancelled {
+            order: ctx.accounts.order.key(),
+            owner: owner_key,
+            amount_returned: amount,
+            event_nonce,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        vlog!("🗑️ 限价单 #{} 已撤销，退回 {}", ctx.accounts.order.order_id, amount);
+        Ok(())
+    }
+
+    /// keeper 成交限价单：按池子曲线把托管代币兑换给订单 owner
+    /// 成交结果必须不差于限价（价格已穿过限价），否则拒绝；成交后关闭订单，租金退回 owner
+    pub fn fill_order(ctx: Context<FillOrder>) -> Result<()> {
+        // === CHECK 阶段 ===
+        let order = &ctx.accounts.order;
+        let input_is_x = order.input_is_x;
+        let amount_in = order.amount_in;
+        let limit_price = order.limit_price;
+
+        let pool = ctx.accounts.pool.load()?;
+        require!(!pool.is_paused(), ErrorCode::PoolPaused);
+        pool.ensure_trader_allowed(&order.owner, None)?;
+        pool.ensure_direction_enabled(input_is_x)?;
+        require!(pool.curve() != CurveType::Concentrated, ErrorCode::UnsupportedCurveOperation);
+        pool.assert_vaults_cover_reserves(
+            ctx.accounts.token_x_vault.amount,
+            ctx.accounts.token_y_vault.amount,
+        )?;
+
+        let (expected_fee_vault, expected_mint_out) = if input_is_x {
+            (pool.fee_x_vault, pool.token_y_mint)
+        } else {
+            (pool.fee_y_vault, pool.token_x_mint)
+        };
+        require_keys_eq!(
+            ctx.accounts.protocol_fee_vault.key(),
+            expected_fee_vault,
+            ErrorCode::InvalidFeeVault
+        );
+        require_keys_eq!(ctx.accounts.owner_token_out.mint, expected_mint_out, ErrorCode::InvalidTokenMint);
+
+        let (reserve_in, reserve_out) = pool.reserves(input_is_x);
+        require!(reserve_in > 0 && reserve_out > 0, ErrorCode::InsufficientLiquidity);
+
+        let fee_bps = pool.current_fee_bps(Clock::get()?.unix_timestamp) as u64;
+        let fee_amount = mul_bps(amount_in, fee_bps)?;
+        let protocol_fee = mul_bps(fee_amount, pool.protocol_fee_share_bps as u64)?;
+
+        let pool_name = pool.name();
+        let curve = pool.curve();
+        let max_price_impact_bps = pool.max_price_impact_bps as u64;
+        let token_x_mint = pool.token_x_mint;
+        let token_y_mint = pool.token_y_mint;
+        let fee_tier = pool.fee_bps.to_le_bytes();
+        let pool_bump = [pool.bump];
+        drop(pool);
+        let pool_seeds = &[
+            b"mock_dex_pool".as_ref(),
+            token_x_mint.as_ref(),
+            token_y_mint.as_ref(),
+            &fee_tier,
+            &pool_bump,
+        ];
+        let pool_signer = &[&pool_seeds[..]];
+
+        let pool_key = order.pool;
+        let owner_key = order.owner;
+        let order_id = order.order_id.to_le_bytes();
+        let order_bump = [order.bump];
+        let order_seeds = &[
+            b"limit_order".as_ref(),
+            pool_key.as_ref(),
+            owner_key.as_ref(),
+            &order_id,
+            &order_bump,
+        ];
+        let order_signer = &[&order_seeds[..]];
+
+        let (vault_in, vault_out, mint_in, mint_out) = if input_is_x {
+            (
+                &ctx.accounts.token_x_vault,
+                &ctx.accounts.token_y_vault,
+                &ctx.accounts.token_x_mint,
+                &ctx.accounts.token_y_mint,
+            )
+        } else {
+            (
+                &ctx.accounts.token_y_vault,
+                &ctx.accounts.token_x_vault,
+                &ctx.accounts.token_y_mint,
+                &ctx.accounts.token_x_mint,
+            )
+        };
+
+        // === INTERACTIONS 阶段：托管代币转入 vault 并测量实际到账 ===
+        let balance_before = token_balance(&vault_in.to_account_info())?;
+        token_interface::transfer_checked(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                TransferChecked {
+                    from: ctx.accounts.escrow.to_account_info(),
+                    mint: mint_in.to_account_info(),
+                    to: vault_in.to_account_info(),
+                    authority: ctx.accounts.order.to_account_info(),
+                },
+                order_signer,
+            ),
+            amount_in - protocol_fee,
+            mint_in.decimals,
+        )?;
+        let amount_to_reserve = token_balance(&vault_in.to_account_info())?
+            .checked_sub(balance_before)
+            .ok_or(ErrorCode::Underflow)?;
+        if protocol_fee > 0 {
+            token_interface::transfer_checked(
+                CpiContext::new_with_signer(
+                    ctx.accounts.token_program.to_account_info(),
+                    TransferChecked {
+                        from: ctx.accounts.escrow.to_account_info(),
+                        mint: mint_in.to_account_info(),
+                        to: ctx.accounts.protocol_fee_vault.to_account_info(),
+                        authority: ctx.accounts.order.to_account_info(),
+                    },
+                    order_signer,
+                ),
+                protocol_fee,
+                mint_in.decimals,
+            )?;
+        }
+
+        // 按实际到账数量定价，成交结果必须不差于限价
+        let effective_amount_in = amount_to_reserve
+            .checked_add(protocol_fee)
+            .ok_or(ErrorCode::Overflow)?;
+        let amount_out = curve.amount_out(effective_amount_in, fee_bps, reserve_in, reserve_out, input_is_x)?;
+        require!(amount_out < reserve_out, ErrorCode::InsufficientLiquidity);
+        let (price_num, price_den) = if input_is_x {
+            (limit_price as u128, PRICE_SCALE as u128)
+        } else {
+            (PRICE_SCALE as u128, limit_price as u128)
+        };
+        let min_amount_out = ctx
+            .accounts
+            .pool
+            .load()?
+            .amount_at_price(amount_in, price_num, price_den, input_is_x)?;
+        require!(amount_out >= min_amount_out, ErrorCode::OrderNotCrossed);
+
+        let price_impact_bps = ctx
+            .accounts
+            .pool
+            .load()?
+            .price_impact_bps(input_is_x, amount_to_reserve, amount_out)?;
+        require!(price_impact_bps <= max_price_impact_bps, ErrorCode::PriceImpactTooHigh);
+
+        // === EFFECTS 阶段 ===
+        let mut pool = ctx.accounts.pool.load_mut()?;
+        pool.accumulate_price(Clock::get()?.slot)?;
+        if input_is_x {
+            pool.x_balance = pool.x_balance.checked_add(amount_to_reserve).ok_or(ErrorCode::Overflow)?;
+            pool.y_balance = pool.y_balance.checked_sub(amount_out).ok_or(ErrorCode::Underflow)?;
+        } else {
+            pool.y_balance = pool.y_balance.checked_add(amount_to_reserve).ok_or(ErrorCode::Overflow)?;
+            pool.x_balance = pool.x_balance.checked_sub(amount_out).ok_or(ErrorCode::Underflow)?;
+        }
+        pool.check_circuit_breaker(Clock::get()?.slot, input_is_x, amount_out)?;
+        pool.record_swap(input_is_x, amount_in, amount_out, fee_amount)?;
+        let event_nonce = pool.next_event_nonce()?;
+        // 池子作为转出签名者参与 CPI，必须先释放对账户数据的可变借用
+        drop(pool);
+
+        // === INTERACTIONS 阶段：转出给订单 owner ===
+        token_interface::transfer_checked(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                TransferChecked {
+                    from: vault_out.to_account_info(),
+                    mint: mint_out.to_account_info(),
+                    to: ctx.accounts.owner_token_out.to_account_info(),
+                    authority: ctx.accounts.pool.to_account_info(),
+                },
+                pool_signer,
+            ),
+            amount_out,
+            mint_out.decimals,
+        )?;
+
+        // 托管账户已清空，关闭并把租金退回 owner
+        token_interface::close_account(CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            CloseAccount {
+                account: ctx.accounts.escrow.to_account_info(),
+                destination: ctx.accounts.owner.to_account_info(),
+                authority: ctx.accounts.order.to_account_info(),
+            },
+            order_signer,
+        ))?;
+
+        emit!(LimitOrderFilled {
+            pool_name: pool_name.clone(),
+            order: ctx.accounts.order.key(),
+            owner: owner_key,
+            amount_in,
+            amount_out,
+            keeper: ctx.accounts.keeper.key(),
+            event_nonce,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        vlog!("✅ 限价单已成交 '{}': {} -> {}", pool_name, amount_in, amount_out);
+        Ok(())
+    }
+
+    /// 初始化三资产池：一个池子账户持有三种代币，任意两种之间可以直接兑换
+    /// 三个 mint 必须严格升序传入；每对代币按两侧储备做恒定乘积定价，
+    /// 因此三种储备的乘积在兑换中只增不减，可以在单个池子内构造三角套利
+    pub fn initialize_tri_pool(
+        ctx: Context<InitializeTriPool>,
+        fee_bps: u16, // 交易手续费 (bps)
+        initial_amounts: [u64; 3],
+    ) -> Result<()> {
+        // === CHECK 阶段 ===
+        let mints = [
+            ctx.accounts.mint_0.key(),
+            ctx.accounts.mint_1.key(),
+            ctx.accounts.mint_2.key(),
+        ];
+        require!(mints[0] < mints[1] && mints[1] < mints[2], ErrorCode::UnsortedMints);
+        require!(FEE_TIERS.contains(&fee_bps), ErrorCode::InvalidFeeRate);
+        require!(initial_amounts.iter().all(|amount| *amount > 0), ErrorCode::InvalidAmount);
+
+        // === INTERACTIONS 阶段：转入初始储备，按实际到账记账 ===
+        let sources = [
+            &ctx.accounts.initializer_token_0,
+            &ctx.accounts.initializer_token_1,
+            &ctx.accounts.initializer_token_2,
+        ];
+        let vaults = [&ctx.accounts.vault_0, &ctx.accounts.vault_1, &ctx.accounts.vault_2];
+        let mint_accounts = [&ctx.accounts.mint_0, &ctx.accounts.mint_1, &ctx.accounts.mint_2];
+
+        let mut balances = [0u64; 3];
+        for i in 0..3 {
+            token_interface::transfer_checked(
+                CpiContext::new(
+                    ctx.accounts.token_program.to_account_info(),
+                    TransferChecked {
+                        from: sources[i].to_account_info(),
+                        mint: mint_accounts[i].to_account_info(),
+                        to: vaults[i].to_account_info(),
+                        authority: ctx.accounts.initializer.to_account_info(),
+                    },
+                ),
+                initial_amounts[i],
+                mint_accounts[i].decimals,
+            )?;
+            balances[i] = token_balance(&vaults[i].to_account_info())?;
+        }
+
+        // === EFFECTS 阶段 ===
+        let pool = &mut ctx.accounts.pool;
+        pool.mints = mints;
+        pool.vaults = [vaults[0].key(), vaults[1].key(), vaults[2].key()];
+        pool.balances = balances;
+        pool.fee_bps = fee_bps;
+        pool.admin = ctx.accounts.initializer.key();
+        pool.bump = ctx.bumps.pool;
+        pool.event_nonce = 0;
+
+        emit!(TriPoolInitialized {
+            pool: pool.key(),
+            mints,
+            balances,
+            fee_bps,
+            initializer: pool.admin,
+            event_nonce: pool.next_event_nonce()?,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        vlog!("✅ 三资产池已初始化: {:?} (fee {}bps)", balances, fee_bps);
+        Ok(())
+    }
+
+    /// 三资产池内两两兑换：卖出 index_in 对应的代币，得到 index_out 对应的代币
+    pub fn tri_pool_swap(
+        ctx: Context<TriPoolSwap>,
+        index_in: u8,
+        index_out: u8,
+        amount_in: u64,
+        min_amount_out: u64,
+    ) -> Result<()> {
+        // === CHECK 阶段 ===
+        require!(amount_in > 0, ErrorCode::InvalidAmount);
+        let (index_in, index_out) = (index_in as usize, index_out as usize);
+        require!(
+            index_in < 3 && index_out < 3 && index_in != index_out,
+            ErrorCode::InvalidTriPoolIndex
+        );
+
+        let pool = &ctx.accounts.pool;
+        require_keys_eq!(ctx.accounts.vault_in.key(), pool.vaults[index_in], ErrorCode::InvalidVault);
+        require_keys_eq!(ctx.accounts.vault_out.key(), pool.vaults[index_out], ErrorCode::InvalidVault);
+        require_keys_eq!(ctx.accounts.mint_in.key(), pool.mints[index_in], ErrorCode::InvalidTokenMint);
+        require_keys_eq!(ctx.accounts.mint_out.key(), pool.mints[index_out], ErrorCode::InvalidTokenMint);
+
+        let reserve_in = pool.balances[index_in];
+        let reserve_out = pool.balances[index_out];
+        require!(reserve_in > 0 && reserve_out > 0, ErrorCode::InsufficientLiquidity);
+        require!(
+            ctx.accounts.vault_in.amount >= reserve_in && ctx.accounts.vault_out.amount >= reserve_out,
+            ErrorCode::VaultBelowReserves
+        );
+
+        // === INTERACTIONS 阶段：先转入并测量实际到账数量 ===
+        let balance_before = ctx.accounts.vault_in.amount;
+        token_interface::transfer_checked(
+            CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                TransferChecked {
+                    from: ctx.accounts.user_token_in.to_account_info(),
+                    mint: ctx.accounts.mint_in.to_account_info(),
+                    to: ctx.accounts.vault_in.to_account_info(),
+                    authority: ctx.accounts.user_authority.to_account_info(),
+                },
+            ),
+            amount_in,
+            ctx.accounts.mint_in.decimals,
+        )?;
+        ctx.accounts.vault_in.reload()?;
+        let amount_received = ctx
+            .accounts
+            .vault_in
+            .amount
+            .checked_sub(balance_before)
+            .ok_or(ErrorCode::Underflow)?;
+
+        // 每对代币按恒定乘积定价
+        let amount_out = CurveType::ConstantProduct.amount_out(
+            amount_received,
+            ctx.accounts.pool.fee_bps as u64,
+            reserve_in,
+            reserve_out,
+            true,
+        )?;
+        require!(amount_out < reserve_out, ErrorCode::InsufficientLiquidity);
+        require!(amount_out >= min_amount_out, ErrorCode::SlippageTooHigh);
+
+        // === EFFECTS 阶段 ===
+        let pool = &mut ctx.accounts.pool;
+        pool.balances[index_in] = reserve_in.checked_add(amount_received).ok_or(ErrorCode::Overflow)?;
+        pool.balances[index_out] = reserve_out.checked_sub(amount_out).ok_or(ErrorCode::Underflow)?;
+
+        // === INTERACTIONS 阶段：转出 ===
+        let mints = pool.mints;
+        let fee_tier = pool.fee_bps.to_le_bytes();
+        let pool_bump = [pool.bump];
+        let pool_seeds = &[
+            b"tri_pool".as_ref(),
+            mints[0].as_ref(),
+            mints[1].as_ref(),
+            mints[2].as_ref(),
+            &fee_tier,
+            &pool_bump,
+        ];
+        let signer_seeds = &[&pool_seeds[..]];
+
+        token_interface::transfer_checked(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                TransferChecked {
+                    from: ctx.accounts.vault_out.to_account_info(),
+                    mint: ctx.accounts.mint_out.to_account_info(),
+                    to: ctx.accounts.user_token_out.to_account_info(),
+                    authority: ctx.accounts.pool.to_account_info(),
+                },
+                signer_seeds,
+            ),
+            amount_out,
+            ctx.accounts.mint_out.decimals,
+        )?;
+
+        emit!(TriPoolSwapExecuted {
+            pool: ctx.accounts.pool.key(),
+            index_in: index_in as u8,
+            index_out: index_out as u8,
+            amount_in,
+            amount_out,
+            user: ctx.accounts.user_authority.key(),
+            event_nonce: ctx.accounts.pool.next_event_nonce()?,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        vlog!("🔺 三资产池兑换 {} -> {}: {} -> {}", index_in, index_out, amount_in, amount_out);
+        Ok(())
+    }
+
+    /// 只读报价：按池子当前状态计算含手续费的输出数量，通过 return data 返回
+    /// 客户端和套利机器人可以模拟调用获取报价，不必在链下重新实现曲线计算
+    /// 集中流动性池同样需要在 remaining_accounts[0] 传入 LiquidityRanges 账户
+    pub fn quote_swap<'info>(
+        ctx: Context<'_, '_, 'info, 'info, QuoteSwap<'info>>,
+        amount_in: u64,
+        direction: SwapDirection,
+    ) -> Result<u64> {
+        let pool = ctx.accounts.pool.load()?;
+        require!(amount_in > 0, ErrorCode::InvalidAmount);
+        require!(!pool.is_paused(), ErrorCode::PoolPaused);
+        require!(pool.x_balance > 0 && pool.y_balance > 0, ErrorCode::InsufficientLiquidity);
+
+        // 集中流动性池只在内存中成交，不写回区间账户
+        let input_is_x = direction == SwapDirection::XToY;
+        pool.ensure_direction_enabled(input_is_x)?;
+        let amount_out = SwapHandler::compute_amount_out(
+            &pool,
+            ctx.accounts.pool.key(),
+            ctx.remaining_accounts,
+            amount_in,
+            input_is_x,
+            pool.current_fee_bps(Clock::get()?.unix_timestamp) as u64,
+            false,
+        )?;
+
+        vlog!("🔎 报价 '{}': {} -> {} ({:?})", pool.name(), amount_in, amount_out, direction);
+        Ok(amount_out)
+    }
+
+    /// 提取累计的协议手续费到 fee_recipient 名下的账户 - 仅池子 admin 可调用
+    pub fn collect_protocol_fees(ctx: Context<CollectProtocolFees>) -> Result<()> {
+        let amount_x = ctx.accounts.fee_x_vault.amount;
+        let amount_y = ctx.accounts.fee_y_vault.amount;
+        require!(amount_x > 0 || amount_y > 0, ErrorCode::NoProtocolFees);
+
+        let pool = ctx.accounts.pool.load()?;
+        let pool_name = pool.name();
+        let token_x_mint = pool.token_x_mint;
+        let token_y_mint = pool.token_y_mint;
+        let fee_tier = pool.fee_bps.to_le_bytes();
+        let pool_bump = [pool.bump];
+        drop(pool);
+        let pool_seeds = &[
+            b"mock_dex_pool".as_ref(),
+            token_x_mint.as_ref(),
+            token_y_mint.as_ref(),
+            &fee_tier,
+            &pool_bump,
+        ];
+        let signer_seeds = &[&pool_seeds[..]];
+
+        if amount_x > 0 {
+            token_interface::transfer_checked(
+                CpiContext::new_with_signer(
+                    ctx.accounts.token_program.to_account_info(),
+                    TransferChecked {
+                        from: ctx.accounts.fee_x_vault.to_account_info(),
+                        mint: ctx.accounts.token_x_mint.to_account_info(),
+                        to: ctx.accounts.destination_x.to_account_info(),
+                        authority: ctx.accounts.pool.to_account_info(),
+                    },
+                    signer_seeds,
+                ),
+                amount_x,
+                ctx.accounts.token_x_mint.decimals,
+            )?;
+        }
+        if amount_y > 0 {
+            token_interface::transfer_checked(
+                CpiContext::new_with_signer(
+                    ctx.accounts.token_program.to_account_info(),
+                    TransferChecked {
+                        from: ctx.accounts.fee_y_vault.to_account_info(),
+                        mint: ctx.accounts.token_y_mint.to_account_info(),
+                        to: ctx.accounts.destination_y.to_account_info(),
+                        authority: ctx.accounts.pool.to_account_info(),
+                    },
+                    signer_seeds,
+                ),
+                amount_y,
+                ctx.accounts.token_y_mint.decimals,
+            )?;
+        }
+
+        let event_nonce = ctx.accounts.pool.load_mut()?.next_event_nonce()?;
+        emit!(ProtocolFeesCollected {
+            pool_name: pool_name.clone(),
+            amount_x,
+            amount_y,
+            admin: ctx.accounts.admin.key(),
+            event_nonce,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        vlog!("💸 已提取协议费 '{}': X {}, Y {}", pool_name, amount_x, amount_y);
+        Ok(())
+    }
+
+    /// 按 vault 实际余额校准池子记录的储备 - 任何人都可以调用
+    /// 直接转入 vault 的代币等会让 x_balance / y_balance 与真实余额不一致
+    /// 集中流动性池的储备已分配到价格区间，不支持校准
+    pub fn sync_reserves(ctx: Context<SyncReserves>) -> Result<()> {
+        let new_x_balance = ctx.accounts.token_x_vault.amount;
+        let new_y_balance = ctx.accounts.token_y_vault.amount;
+
+        let mut pool = ctx.accounts.pool.load_mut()?;
+        require!(pool.curve() != CurveType::Concentrated, ErrorCode::UnsupportedCurveOperation);
+
+        let old_x_balance = pool.x_balance;
+        let old_y_balance = pool.y_balance;
+
+        // 先用校准前的价格累计 TWAP，再更新储备
+        pool.accumulate_price(Clock::get()?.slot)?;
+        pool.x_balance = new_x_balance;
+        pool.y_balance = new_y_balance;
+
+        emit!(ReservesSynced {
+            pool_name: pool.name(),
+            old_x_balance,
+            old_y_balance,
+            new_x_balance,
+            new_y_balance,
+            caller: ctx.accounts.caller.key(),
+            event_nonce: pool.next_event_nonce()?,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        vlog!(
+            "🔄 池子 '{}' 储备已校准: X {} -> {}, Y {} -> {}",
+            pool.name(),
+            old_x_balance,
+            new_x_balance,
+            old_y_balance,
+            new_y_balance
+        );
+        Ok(())
+    }
+
+    /// 把 vault 中超出记录储备的余额转给指定账户 - 仅池子 admin 可调用
+    /// 与 sync_reserves 相反：保留记录的储备，移走直接捐赠进 vault 的代币，避免其被计入定价
+    pub fn skim(ctx: Context<Skim>) -> Result<()> {
+        let pool = ctx.accounts.pool.load()?;
+        let excess_x = ctx.accounts.token_x_vault.amount.saturating_sub(pool.x_balance);
+        let excess_y = ctx.accounts.token_y_vault.amount.saturating_sub(pool.y_balance);
+        require!(excess_x > 0 || excess_y > 0, ErrorCode::NoExcessReserves);
+
+        let pool_name = pool.name();
+        let token_x_mint = pool.token_x_mint;
+        let token_y_mint = pool.token_y_mint;
+        let fee_tier = pool.fee_bps.to_le_bytes();
+        let pool_bump = [pool.bump];
+        drop(pool);
+        let pool_seeds = &[
+            b"mock_dex_pool".as_ref(),
+            token_x_mint.as_ref(),
+            token_y_mint.as_ref(),
+            &fee_tier,
+            &pool_bump,
+        ];
+        let signer_seeds = &[&pool_seeds[..]];
+
+        if excess_x > 0 {
+            token_interface::transfer_checked(
+                CpiContext::new_with_signer(
+                    ctx.accounts.token_program.to_account_info(),
+                    TransferChecked {
+                        from: ctx.accounts.token_x_vault.to_account_info(),
+                        mint: ctx.accounts.token_x_mint.to_account_info(),
+                        to: ctx.accounts.destination_x.to_account_info(),
+                        authority: ctx.accounts.pool.to_account_info(),
+                    },
+                    signer_seeds,
+                ),
+                excess_x,
+                ctx.accounts.token_x_mint.decimals,
+            )?;
+        }
+        if excess_y > 0 {
+            token_interface::transfer_checked(
+                CpiContext::new_with_signer(
+                    ctx.accounts.token_program.to_account_info(),
+                    TransferChecked {
+                        from: ctx.accounts.token_y_vault.to_account_info(),
+                        mint: ctx.accounts.token_y_mint.to_account_info(),
+                        to: ctx.accounts.destination_y.to_account_info(),
+                        authority: ctx.accounts.pool.to_account_info(),
+                    },
+                    signer_seeds,
+                ),
+                excess_y,
+                ctx.accounts.token_y_mint.decimals,
+            )?;
+        }
+
+        let event_nonce = ctx.accounts.pool.load_mut()?.next_event_nonce()?;
+        emit!(ReservesSkimmed {
+            pool_name: pool_name.clone(),
+            amount_x: excess_x,
+            amount_y: excess_y,
+            admin: ctx.accounts.admin.key(),
+            event_nonce,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        vlog!("🧹 已清出 '{}' 的多余余额: X {}, Y {}", pool_name, excess_x, excess_y);
+        Ok(())
+    }
+
+    /// 调整池子价格 - 仅池子 admin 可调用，用于在测试中构造价差
+    /// 保持 k = x * y 不变，把储备移动到按精度换算后的 y / x = target_price_bps / 10000：
+    /// 一侧由 admin 补入，另一侧转给 admin，相当于一次不收手续费的 admin 兑换
+    pub fn rebalance(ctx: Context<Rebalance>, target_price_bps: u64) -> Result<()> {
+        // === CHECK 阶段 ===
+        require!(target_price_bps > 0, ErrorCode::InvalidPriceLimit);
+
+        let pool = ctx.accounts.pool.load()?;
+        require!(pool.curve() == CurveType::ConstantProduct, ErrorCode::UnsupportedCurveOperation);
+        require!(pool.x_balance > 0 && pool.y_balance > 0, ErrorCode::InsufficientLiquidity);
+
+        let old_x_balance = pool.x_balance;
+        let old_y_balance = pool.y_balance;
+        let k = (old_x_balance as u128) * (old_y_balance as u128);
+        // 原始储备比例 y / x = target_price_bps * factor_x / (10000 * factor_y)
+        let (factor_x, factor_y) = pool.decimal_factors()?;
+        let price_num = (target_price_bps as u128)
+            .checked_mul(factor_x)
+            .ok_or(ErrorCode::Overflow)?;
+        let price_den = 10_000u128.checked_mul(factor_y).ok_or(ErrorCode::Overflow)?;
+        let target_x = integer_sqrt(k.checked_mul(price_den).ok_or(ErrorCode::Overflow)? / price_num);
+        let target_y = integer_sqrt(k.checked_mul(price_num).ok_or(ErrorCode::Overflow)? / price_den);
+        let target_x = u64::try_from(target_x).map_err(|_| error!(ErrorCode::Overflow))?;
+        let target_y = u64::try_from(target_y).map_err(|_| error!(ErrorCode::Overflow))?;
+        require!(target_x > 0 && target_y > 0, ErrorCode::InsufficientLiquidity);
+
+        let pool_name = pool.name();
+        let token_x_mint = pool.token_x_mint;
+        let token_y_mint = pool.token_y_mint;
+        let fee_tier = pool.fee_bps.to_le_bytes();
+        let pool_bump = [pool.bump];
+        drop(pool);
+        let pool_seeds = &[
+            b"mock_dex_pool".as_ref(),
+            token_x_mint.as_ref(),
+            token_y_mint.as_ref(),
+            &fee_tier,
+            &pool_bump,
+        ];
+        let signer_seeds = &[&pool_seeds[..]];
+
+        // === INTERACTIONS 阶段：补入一侧、转出另一侧 ===
+        let new_x_balance = ctx.accounts.move_reserve(true, old_x_balance, target_x, signer_seeds)?;
+        let new_y_balance = ctx.accounts.move_reserve(false, old_y_balance, target_y, signer_seeds)?;
+
+        // === EFFECTS 阶段 ===
+        let mut pool = ctx.accounts.pool.load_mut()?;
+        pool.accumulate_price(Clock::get()?.slot)?;
+        pool.x_balance = new_x_balance;
+        pool.y_balance = new_y_balance;
+        let event_nonce = pool.next_event_nonce()?;
+        emit!(PoolRebalanced {
+            pool_name: pool_name.clone(),
+            target_price_bps,
+            old_x_balance,
+            old_y_balance,
+            new_x_balance,
+            new_y_balance,
+            admin: ctx.accounts.admin.key(),
+            event_nonce,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        vlog!(
+            "⚖️ 池子 '{}' 已调整价格: X {} -> {}, Y {} -> {}",
+            pool_name,
+            old_x_balance,
+            new_x_balance,
+            old_y_balance,
+            new_y_balance
+        );
+        Ok(())
+    }
+
+    /// 测试专用：直接把池子储备设置为 (x_balance, y_balance) - 仅池子 admin 可调用
+    /// 账户与 rebalance 相同，差额在 admin 与 vault 之间转移，不要求 k 不变，便于一步构造任意价差
+    #[cfg(feature = "test-utils")]
+    pub fn set_reserves(ctx: Context<SetReserves>, x_balance: u64, y_balance: u64) -> Result<()> {
+        // === CHECK 阶段 ===
+        require!(x_balance > 0 && y_balance > 0, ErrorCode::InvalidAmount);
+
+        let pool = ctx.accounts.pool.load()?;
+        require!(pool.curve() != CurveType::Concentrated, ErrorCode::UnsupportedCurveOperation);
+
+        let old_x_balance = pool.x_balance;
+        let old_y_balance = pool.y_balance;
+        let pool_name = pool.name();
+        let token_x_mint = pool.token_x_mint;
+        let token_y_mint = pool.token_y_mint;
+        let fee_tier = pool.fee_bps.to_le_bytes();
+        let pool_bump = [pool.bump];
+        drop(pool);
+        let pool_seeds = &[
+            b"mock_dex_pool".as_ref(),
+            token_x_mint.as_ref(),
+            token_y_mint.as_ref(),
+            &fee_tier,
+            &pool_bump,
+        ];
+        let signer_seeds = &[&pool_seeds[..]];
+
+        // === INTERACTIONS 阶段 ===
+        let new_x_balance = ctx.accounts.move_reserve(true, old_x_balance, x_balance, signer_seeds)?;
+        let new_y_balance = ctx.accounts.move_reserve(false, old_y_balance, y_balance, signer_seeds)?;
+
+        // === EFFECTS 阶段 ===
+        let mut pool = ctx.accounts.pool.load_mut()?;
+        pool.accumulate_price(Clock::get()?.slot)?;
+        pool.x_balance = new_x_balance;
+        pool.y_balance = new_y_balance;
+        let event_nonce = pool.next_event_nonce()?;
+        emit!(ReservesSynced {
+            pool_name: pool_name.clone(),
+            old_x_balance,
+            old_y_balance,
+            new_x_balance,
+            new_y_balance,
+            caller: ctx.accounts.admin.key(),
+            event_nonce,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        vlog!(
+            "🧪 池子 '{}' 储备已设置: X {} -> {}, Y {} -> {}",
+            pool_name,
+            old_x_balance,
+            new_x_balance,
+            old_y_balance,
+            new_y_balance
+        );
+        Ok(())
+    }
+
+    /// 暂停池子 - 仅池子 admin 可调用，暂停期间兑换和流动性操作都会被拒绝
+    pub fn pause_pool(ctx: Context<SetPoolPaused>) -> Result<()> {
+        let mut pool = ctx.accounts.pool.load_mut()?;
+        require!(!pool.is_paused(), ErrorCode::PoolPaused);
+        pool.paused = 1;
+
+        emit!(PoolPauseChanged {
+            pool_name: pool.name(),
+            paused: true,
+            admin: ctx.accounts.admin.key(),
+            event_nonce: pool.next_event_nonce()?,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        vlog!("⏸️ 池子 '{}' 已暂停", pool.name());
+        Ok(())
+    }
+
+    /// 恢复池子 - 仅池子 admin 可调用
+    pub fn resume_pool(ctx: Context<SetPoolPaused>) -> Result<()> {
+        let mut pool = ctx.accounts.pool.load_mut()?;
+        require!(pool.is_paused(), ErrorCode::PoolNotPaused);
+        pool.paused = 0;
+
+        emit!(PoolPauseChanged {
+            pool_name: pool.name(),
+            paused: false,
+            admin: ctx.accounts.admin.key(),
+            event_nonce: pool.next_event_nonce()?,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        vlog!("▶️ 池子 '{}' 已恢复", pool.name());
+        Ok(())
+    }
+
+    /// 提名新的池子 admin - 仅当前 admin 可调用，需新 admin 调用 accept_admin 后才生效
+    /// 传入 Pubkey::default() 可撤销尚未接受的提名
+    pub fn propose_admin(ctx: Context<ProposeAdmin>, new_admin: Pubkey) -> Result<()> {
+        let mut pool = ctx.accounts.pool.load_mut()?;
+        pool.pending_admin = new_admin;
+
+        emit!(AdminProposed {
+            pool_name: pool.name(),
+            admin: pool.admin,
+            pending_admin: new_admin,
+            event_nonce: pool.next_event_nonce()?,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        vlog!("👤 池子 '{}' 提名新 admin: {}", pool.name(), new_admin);
+        Ok(())
+    }
+
+    /// 接受 admin 提名 - 仅被提名的账户可调用
+    pub fn accept_admin(ctx: Context<AcceptAdmin>) -> Result<()> {
+        let mut pool = ctx.accounts.pool.load_mut()?;
+        let old_admin = pool.admin;
+        pool.admin = pool.pending_admin;
+        pool.pending_admin = Pubkey::default();
+
+        emit!(AdminTransferred {
+            pool_name: pool.name(),
+            old_admin,
+            new_admin: pool.admin,
+            event_nonce: pool.next_event_nonce()?,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        vlog!("👤 池子 '{}' admin 已移交: {} -> {}", pool.name(), old_admin, pool.admin);
+        Ok(())
+    }
+
+    /// 设置协议费接收人 - 仅池子 admin 可调用
+    pub fn set_fee_recipient(ctx: Context<SetFeeRecipient>, fee_recipient: Pubkey) -> Result<()> {
+        let mut pool = ctx.accounts.pool.load_mut()?;
+        let old_fee_recipient = pool.fee_recipient;
+        pool.fee_recipient = fee_recipient;
+
+        emit!(FeeRecipientUpdated {
+            pool_name: pool.name(),
+            old_fee_recipient,
+            new_fee_recipient: fee_recipient,
+            admin: ctx.accounts.admin.key(),
+            event_nonce: pool.next_event_nonce()?,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        vlog!("📮 池子 '{}' 协议费接收人: {} -> {}", pool.name(), old_fee_recipient, fee_recipient);
+        Ok(())
+    }
+
+    /// 修改池子展示名称 - 仅池子 admin 可调用，同步更新注册表中的条目
+    /// 名称存放在定长字段中，账户大小不变，无需重新分配
+    pub fn rename_pool(ctx: Context<RenamePool>, new_name: String) -> Result<()> {
+        require!(
+            !new_name.is_empty() && new_name.len() <= MAX_POOL_NAME_LEN,
+            ErrorCode::InvalidPoolName
+        );
+
+        let pool_key = ctx.accounts.pool.key();
+        let mut pool = ctx.accounts.pool.load_mut()?;
+        let old_name = pool.name();
+        pool.set_name(&new_name);
+        let event_nonce = pool.next_event_nonce()?;
+        drop(pool);
+
+        ctx.accounts.registry_entry.name = new_name.clone();
+
+        emit!(PoolRenamed {
+            pool: pool_key,
+            old_name: old_name.clone(),
+            new_name: new_name.clone(),
+            admin: ctx.accounts.admin.key(),
+            event_nonce,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        vlog!("🏷️ 池子改名: '{}' -> '{}'", old_name, new_name);
+        Ok(())
+    }
+
+    /// 配置预言机保护 - 仅池子 admin 可调用
+    /// max_oracle_deviation_bps 为 0 时关闭保护；兑换时传入预言机账户才会校验
+    pub fn set_oracle_guard(
+        ctx: Context<SetOracleGuard>,
+        oracle: Pubkey,
+        max_oracle_deviation_bps: u16,
+    ) -> Result<()> {
+        require!(
+            max_oracle_deviation_bps <= MAX_ORACLE_DEVIATION_BPS,
+            ErrorCode::InvalidOracleDeviation
+        );
+
+        let mut pool = ctx.accounts.pool.load_mut()?;
+        pool.oracle = oracle;
+        pool.max_oracle_deviation_bps = max_oracle_deviation_bps;
+
+        emit!(OracleGuardUpdated {
+            pool_name: pool.name(),
+            oracle,
+            max_oracle_deviation_bps,
+            admin: ctx.accounts.admin.key(),
+            event_nonce: pool.next_event_nonce()?,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        vlog!(
+            "🔮 池子 '{}' 预言机保护: {} (最大偏离 {}bps)",
+            pool.name(),
+            oracle,
+            max_oracle_deviation_bps
+        );
+        Ok(())
+    }
+
+    /// 设置手续费中归协议的比例 - 仅池子 admin 可调用
+    /// 每笔兑换的手续费按该比例拆分：协议部分转入 fee vault，其余留在储备中归 LP
+    pub fn set_protocol_fee_share(ctx: Context<SetProtocolFeeShare>, protocol_fee_share_bps: u16) -> Result<()> {
+        require!(
+            protocol_fee_share_bps <= MAX_PROTOCOL_FEE_SHARE_BPS,
+            ErrorCode::InvalidProtocolFeeShare
+        );
+
+        let mut pool = ctx.accounts.pool.load_mut()?;
+        let old_protocol_fee_share_bps = pool.protocol_fee_share_bps;
+        pool.protocol_fee_share_bps = protocol_fee_share_bps;
+
+        emit!(ProtocolFeeShareUpdated {
+            pool_name: pool.name(),
+            old_protocol_fee_share_bps,
+            new_protocol_fee_share_bps: protocol_fee_share_bps,
+            admin: ctx.accounts.admin.key(),
+            event_nonce: pool.next_event_nonce()?,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        vlog!(
+            "🏦 池子 '{}' 协议费比例: {}bps -> {}bps",
+            pool.name(),
+            old_protocol_fee_share_bps,
+            protocol_fee_share_bps
+        );
+        Ok(())
+    }
+
+    /// 设置推荐人分成比例 - 仅池子 admin 可调用
+    /// 分成从 LP 所得的手续费中扣出，只在兑换时传入推荐人账户才生效
+    pub fn set_referral_fee_share(ctx: Context<SetReferralFeeShare>, referral_fee_share_bps: u16) -> Result<()> {
+        require!(
+            referral_fee_share_bps <= MAX_REFERRAL_FEE_SHARE_BPS,
+            ErrorCode::InvalidReferralFeeShare
+        );
+
+        let mut pool = ctx.accounts.pool.load_mut()?;
+        let old_referral_fee_share_bps = pool.referral_fee_share_bps;
+        pool.referral_fee_share_bps = referral_fee_share_bps;
+
+        emit!(ReferralFeeShareUpdated {
+            pool_name: pool.name(),
+            old_referral_fee_share_bps,
+            new_referral_fee_share_bps: referral_fee_share_bps,
+            admin: ctx.accounts.admin.key(),
+            event_nonce: pool.next_event_nonce()?,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        vlog!(
+            "🤝 池子 '{}' 推荐人分成: {}bps -> {}bps",
+            pool.name(),
+            old_referral_fee_share_bps,
+            referral_fee_share_bps
+        );
+        Ok(())
+    }
+
+    /// 设置单笔交易的最大价格影响 - 仅池子 admin 可调用
+    pub fn set_max_price_impact(ctx: Context<SetMaxPriceImpact>, max_price_impact_bps: u16) -> Result<()> {
+        require!(
+            max_price_impact_bps > 0 && max_price_impact_bps <= MAX_PRICE_IMPACT_BPS,
+            ErrorCode::InvalidPriceImpactLimit
+        );
+
+        let mut pool = ctx.accounts.pool.load_mut()?;
+        let old_max_price_impact_bps = pool.max_price_impact_bps;
+        pool.max_price_impact_bps = max_price_impact_bps;
+
+        emit!(MaxPriceImpactUpdated {
+            pool_name: pool.name(),
+            old_max_price_impact_bps,
+            new_max_price_impact_bps: max_price_impact_bps,
+            admin: ctx.accounts.admin.key(),
+            event_nonce: pool.next_event_nonce()?,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        vlog!(
+            "🛡️ 池子 '{}' 最大价格影响: {}bps -> {}bps",
+            pool.name(),
+            old_max_price_impact_bps,
+            max_price_impact_bps
+        );
+        Ok(())
+    }
+
+    /// 为池子创建手续费豁免名单 - 仅池子 admin 可调用
+    pub fn initialize_fee_allowlist(ctx: Context<InitializeFeeAllowlist>) -> Result<()> {
+        let allowlist = &mut ctx.accounts.fee_allowlist;
+        allowlist.pool = ctx.accounts.pool.key();
+        allowlist.bump = ctx.bumps.fee_allowlist;
+        allowlist.entries = Vec::new();
+
+        vlog!("📋 池子 '{}' 创建手续费豁免名单", ctx.accounts.pool.load()?.name());
+        Ok(())
+    }
+
+    /// 添加或更新手续费豁免 - 仅池子 admin 可调用
+    /// fee_bps 不能高于池子的手续费，0 表示完全免手续费
+    pub fn set_fee_exemption(ctx: Context<SetFeeExemption>, trader: Pubkey, fee_bps: u16) -> Result<()> {
+        require!(fee_bps <= ctx.accounts.pool.load()?.fee_bps, ErrorCode::InvalidFeeRate);
+
+        let allowlist = &mut ctx.accounts.fee_allowlist;
+        match allowlist.entries.iter_mut().find(|entry| entry.trader == trader) {
+            Some(entry) => entry.fee_bps = fee_bps,
+            None => {
+                require!(allowlist.entries.len() < MAX_FEE_EXEMPTIONS, ErrorCode::FeeAllowlistFull);
+                allowlist.entries.push(FeeExemption { trader, fee_bps });
+            }
+        }
+
+        let event_nonce = ctx.accounts.pool.load_mut()?.next_event_nonce()?;
+        emit!(FeeExemptionUpdated {
+            pool_name: ctx.accounts.pool.load()?.name(),
+            trader,
+            fee_bps: Some(fee_bps),
+            admin: ctx.accounts.admin.key(),
+            event_nonce,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        vlog!("🎟️ 池子 '{}' 手续费豁免: {} -> {}bps", ctx.accounts.pool.load()?.name(), trader, fee_bps);
+        Ok(())
+    }
+
+    /// 移除手续费豁免 - 仅池子 admin 可调用
+    pub fn remove_fee_exemption(ctx: Context<SetFeeExemption>, trader: Pubkey) -> Result<()> {
+        let allowlist = &mut ctx.accounts.fee_allowlist;
+        let index = allowlist
+            .entries
+            .iter()
+            .position(|entry| entry.trader == trader)
+            .ok_or(ErrorCode::FeeExemptionNotFound)?;
+        allowlist.entries.swap_remove(index);
+
+        let event_nonce = ctx.accounts.pool.load_mut()?.next_event_nonce()?;
+        emit!(FeeExemptionUpdated {
+            pool_name: ctx.accounts.pool.load()?.name(),
+            trader,
+            fee_bps: None,
+            admin: ctx.accounts.admin.key(),
+            event_nonce,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        vlog!("🎟️ 池子 '{}' 移除手续费豁免: {}", ctx.accounts.pool.load()?.name(), trader);
+        Ok(())
+    }
+
+    /// 为池子创建交易者名单 - 仅池子 admin 可调用
+    /// 名单只在池子开启许可模式后生效，见 set_permissioned
+    pub fn initialize_trader_allowlist(ctx: Context<InitializeTraderAllowlist>) -> Result<()> {
+        let allowlist = &mut ctx.accounts.trader_allowlist;
+        allowlist.pool = ctx.accounts.pool.key();
+        allowlist.bump = ctx.bumps.trader_allowlist;
+        allowlist.traders = Vec::new();
+
+        vlog!("📋 池子 '{}' 创建交易者名单", ctx.accounts.pool.load()?.name());
+        Ok(())
+    }
+
+    /// 把交易者加入名单 - 仅池子 admin 可调用，已在名单中时不重复添加
+    pub fn add_allowed_trader(ctx: Context<SetAllowedTrader>, trader: Pubkey) -> Result<()> {
+        let allowlist = &mut ctx.accounts.trader_allowlist;
+        if !allowlist.is_allowed(&trader) {
+            require!(allowlist.traders.len() < MAX_ALLOWED_TRADERS, ErrorCode::TraderAllowlistFull);
+            allowlist.traders.push(trader);
+        }
+
+        let event_nonce = ctx.accounts.pool.load_mut()?.next_event_nonce()?;
+        emit!(AllowedTraderUpdated {
+            pool_name: ctx.accounts.pool.load()?.name(),
+            trader,
+            allowed: true,
+            admin: ctx.accounts.admin.key(),
+            event_nonce,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        vlog!("🪪 池子 '{}' 允许交易者: {}", ctx.accounts.pool.load()?.name(), trader);
+        Ok(())
+    }
+
+    /// 把交易者移出名单 - 仅池子 admin 可调用
+    pub fn remove_allowed_trader(ctx: Context<SetAllowedTrader>, trader: Pubkey) -> Result<()> {
+        let allowlist = &mut ctx.accounts.trader_allowlist;
+        let index = allowlist
+            .traders
+            .iter()
+            .position(|allowed| *allowed == trader)
+            .ok_or(ErrorCode::AllowedTraderNotFound)?;
+        allowlist.traders.swap_remove(index);
+
+        let event_nonce = ctx.accounts.pool.load_mut()?.next_event_nonce()?;
+        emit!(AllowedTraderUpdated {
+            pool_name: ctx.accounts.pool.load()?.name(),
+            trader,
+            allowed: false,
+            admin: ctx.accounts.admin.key(),
+            event_nonce,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        vlog!("🪪 池子 '{}' 移除交易者: {}", ctx.accounts.pool.load()?.name(), trader);
+        Ok(())
+    }
+
+    /// 开关许可模式 - 仅池子 admin 可调用
+    /// 开启后只有交易者名单中的 user_authority 可以通过 swap 系列指令兑换；
+    /// 多跳、批量、闪电兑换和限价单成交不携带名单，在许可池上一律拒绝
+    pub fn set_permissioned(ctx: Context<SetPermissioned>, permissioned: bool) -> Result<()> {
+        let mut pool = ctx.accounts.pool.load_mut()?;
+        pool.permissioned = permissioned as u8;
+
+        emit!(PermissionedModeUpdated {
+            pool_name: pool.name(),
+            permissioned,
+            admin: ctx.accounts.admin.key(),
+            event_nonce: pool.next_event_nonce()?,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        vlog!("🔐 池子 '{}' 许可模式: {}", pool.name(), permissioned);
+        Ok(())
+    }
+
+    /// 设置手续费折扣时间窗口 - 仅池子 admin 可调用
+    /// [start_ts, end_ts) 内的兑换按 fee_bps 收费，多个窗口重叠时取最低费率；end_ts 传 0 清空该槽位
+    pub fn set_fee_discount_window(
+        ctx: Context<SetFeeDiscountWindow>,
+        index: u8,
+        start_ts: i64,
+        end_ts: i64,
+        fee_bps: u16,
+    ) -> Result<()> {
+        let mut pool = ctx.accounts.pool.load_mut()?;
+
+        // === CHECK 阶段 ===
+        require!(
+            (index as usize) < MAX_FEE_DISCOUNT_WINDOWS,
+            ErrorCode::InvalidFeeDiscountWindow
+        );
+        let window = if end_ts == 0 {
+            FeeDiscountWindow::EMPTY
+        } else {
+            require!(
+                start_ts < end_ts && fee_bps < pool.fee_bps,
+                ErrorCode::InvalidFeeDiscountWindow
+            );
+            FeeDiscountWindow {
+                start_ts,
+                end_ts,
+                fee_bps,
+                padding: [0; 6],
+            }
+        };
+
+        // === EFFECTS 阶段 ===
+        pool.fee_discounts[index as usize] = window;
+
+        emit!(FeeDiscountWindowUpdated {
+            pool_name: pool.name(),
+            index,
+            start_ts: window.start_ts,
+            end_ts: window.end_ts,
+            fee_bps: window.fee_bps,
+            admin: ctx.accounts.admin.key(),
+            event_nonce: pool.next_event_nonce()?,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        vlog!(
+            "🏷️ 池子 '{}' 折扣窗口 #{}: [{}, {}) {}bps",
+            pool.name(),
+            index,
+            window.start_ts,
+            window.end_ts,
+            window.fee_bps
+        );
+        Ok(())
+    }
+
+    /// 单独开关兑换方向 - 仅池子 admin 可调用
+    /// 与 pause_pool 不同，只影响兑换，流动性操作不受限制
+    pub fn set_swap_directions(
+        ctx: Context<SetSwapDirections>,
+        x_to_y_enabled: bool,
+        y_to_x_enabled: bool,
+    ) -> Result<()> {
+        let mut pool = ctx.accounts.pool.load_mut()?;
+        pool.x_to_y_enabled = x_to_y_enabled as u8;
+        pool.y_to_x_enabled = y_to_x_enabled as u8;
+
+        emit!(SwapDirectionsUpdated {
+            pool_name: pool.name(),
+            x_to_y_enabled,
+            y_to_x_enabled,
+            admin: ctx.accounts.admin.key(),
+            event_nonce: pool.next_event_nonce()?,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        vlog!(
+            "↔️ 池子 '{}' 兑换方向: X->Y {}, Y->X {}",
+            pool.name(),
+            x_to_y_enabled,
+            y_to_x_enabled
+        );
+        Ok(())
+    }
+
+    /// 设置熔断上限 - 仅池子 admin 可调用
+    /// 同一 slot 内从任一侧储备流出的累计数量不得超过该侧储备的 max_reserve_move_bps，0 表示不启用
+    pub fn set_circuit_breaker(ctx: Context<SetCircuitBreaker>, max_reserve_move_bps: u16) -> Result<()> {
+        require!(
+            max_reserve_move_bps <= MAX_RESERVE_MOVE_BPS,
+            ErrorCode::InvalidCircuitBreakerLimit
+        );
+
+        let mut pool = ctx.accounts.pool.load_mut()?;
+        let old_max_reserve_move_bps = pool.max_reserve_move_bps;
+        pool.max_reserve_move_bps = max_reserve_move_bps;
+
+        emit!(CircuitBreakerUpdated {
+            pool_name: pool.name(),
+            old_max_reserve_move_bps,
+            new_max_reserve_move_bps: max_reserve_move_bps,
+            admin: ctx.accounts.admin.key(),
+            event_nonce: pool.next_event_nonce()?,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        vlog!(
+            "🚦 池子 '{}' 熔断上限: {}bps -> {}bps",
+            pool.name(),
+            old_max_reserve_move_bps,
+            max_reserve_move_bps
+        );
+        Ok(())
+    }
+
+    /// 按当前储备比例添加流动性，并铸造对应数量的 LP 代币
+    /// 实际存入数量不会超过 max_amount_x / max_amount_y，LP 与储备均按 vault 实际到账数量计算
+    /// 其中一侧传 0 时为单边存入：到账数量的一半先按池子曲线兑换（收取当前适用的交易手续费，全部留在储备中），
+    /// 再按兑换后的储备比例计算 LP，到账的代币全部进入储备；这次兑换与 swap 一样受方向开关、TWAP 和熔断约束
+    pub fn add_liquidity(
+        ctx: Context<AddLiquidity>,
+        max_amount_x: u64,
+        max_amount_y: u64,
+        min_lp_out: u64,
+    ) -> Result<()> {
+        let single_sided = max_amount_x == 0 || max_amount_y == 0;
+
+        // === CHECK 阶段 ===
+        let lp_supply = ctx.accounts.lp_mint.supply;
+        let (amount_x, amount_y) = {
+            let pool = ctx.accounts.pool.load()?;
+            require!(max_amount_x > 0 || max_amount_y > 0, ErrorCode::InvalidAmount);
+            require!(!pool.is_paused(), ErrorCode::PoolPaused);
+            // 集中流动性池的储备按区间分配，LP 份额无法对应到区间
+            require!(pool.curve() != CurveType::Concentrated, ErrorCode::UnsupportedCurveOperation);
+            require!(
+                lp_supply > 0 && pool.x_balance > 0 && pool.y_balance > 0,
+                ErrorCode::InsufficientLiquidity
+            );
+
+            if single_sided {
+                // 单边存入包含一次兑换，对应方向关闭时拒绝
+                pool.ensure_direction_enabled(max_amount_x > 0)?;
+                (max_amount_x, max_amount_y)
+            } else {
+                // 按两侧中较小的比例确定存入数量，向上取整，保证不会稀释已有 LP
+                let lp_from_x = mul_div_floor(max_amount_x, lp_supply, pool.x_balance)?;
+                let lp_from_y = mul_div_floor(max_amount_y, lp_supply, pool.y_balance)?;
+                let lp_target = lp_from_x.min(lp_from_y);
+                require!(lp_target > 0, ErrorCode::InvalidAmount);
+                (
+                    mul_div_ceil(lp_target, pool.x_balance, lp_supply)?,
+                    mul_div_ceil(lp_target, pool.y_balance, lp_supply)?,
+                )
+            }
+        };
+
+        // 先转入再按实际到账计算 LP：带转账手续费的 mint 到账会少于转出数量
+        // 偏离了 CEI 顺序，但 token 程序不会回调本程序，不存在重入
+        let received_x = ctx.accounts.deposit(true, amount_x)?;
+        let received_y = ctx.accounts.deposit(false, amount_y)?;
+
+        let mut pool = ctx.accounts.pool.load_mut()?;
+        let clock = Clock::get()?;
+        // 单边存入时记录兑换方向与输出数量，储备更新后做熔断检查
+        let mut half_swap = None;
+        let lp_amount = if single_sided {
+            // 单边存入：在内存中把到账数量的一半兑换成另一侧，先用兑换前的价格累计 TWAP
+            pool.accumulate_price(clock.slot)?;
+            let input_is_x = max_amount_x > 0;
+            let amount_in = received_x.max(received_y);
+            let swap_amount_in = amount_in / 2;
+            let (reserve_in, reserve_out) = pool.reserves(input_is_x);
+            let swap_amount_out = pool.curve().amount_out(
+                swap_amount_in,
+                pool.current_fee_bps(clock.unix_timestamp) as u64,
+                reserve_in,
+                reserve_out,
+                input_is_x,
+            )?;
+            require!(swap_amount_out < reserve_out, ErrorCode::InsufficientLiquidity);
+            require!(
+                pool.price_impact_bps(input_is_x, swap_amount_in, swap_amount_out)?
+                    <= pool.max_price_impact_bps as u64,
+                ErrorCode::PriceImpactTooHigh
+            );
+
+            // 兑换后的储备下，剩余一半与兑换所得按两侧中较小的比例计算 LP，零头留给已有 LP
+            let reserve_in_after = reserve_in.checked_add(swap_amount_in).ok_or(ErrorCode::Overflow)?;
+            let reserve_out_after = reserve_out - swap_amount_out;
+            let lp_from_in = mul_div_floor(amount_in - swap_amount_in, lp_supply, reserve_in_after)?;
+            let lp_from_out = mul_div_floor(swap_amount_out, lp_supply, reserve_out_after)?;
+            half_swap = Some((input_is_x, swap_amount_out));
+            lp_from_in.min(lp_from_out)
+        } else {
+            // 按实际到账数量重新计算 LP，到账不足的部分不再铸造 LP
+            let lp_from_x = mul_div_floor(received_x, lp_supply, pool.x_balance)?;
+            let lp_from_y = mul_div_floor(received_y, lp_supply, pool.y_balance)?;
+            lp_from_x.min(lp_from_y)
+        };
+
+        require!(lp_amount > 0, ErrorCode::InvalidAmount);
+        require!(lp_amount >= min_lp_out, ErrorCode::SlippageTooHigh);
+
+        // === EFFECTS 阶段 ===
+        pool.x_balance = pool.x_balance.checked_add(received_x).ok_or(ErrorCode::Overflow)?;
+        pool.y_balance = pool.y_balance.checked_add(received_y).ok_or(ErrorCode::Overflow)?;
+        if let Some((input_is_x, swap_amount_out)) = half_swap {
+            pool.check_circuit_breaker(clock.slot, input_is_x, swap_amount_out)?;
+        }
+        let event_nonce = pool.next_event_nonce()?;
+        let pool_name = pool.name();
+        let token_x_mint = pool.token_x_mint;
+        let token_y_mint = pool.token_y_mint;
+        let fee_tier = pool.fee_bps.to_le_bytes();
+        let pool_bump = [pool.bump];
+        // 池子作为签名者参与后续 CPI，必须先释放对账户数据的可变借用
+        drop(pool);
+
+        // === INTERACTIONS 阶段 ===
+        let token_program = ctx.accounts.token_program.to_account_info();
+        let pool_seeds = &[
+            b"mock_dex_pool".as_ref(),
+            token_x_mint.as_ref(),
+            token_y_mint.as_ref(),
+            &fee_tier,
+            &pool_bump,
+        ];
+        token_interface::mint_to(
+            CpiContext::new_with_signer(
+                token_program,
+                MintTo {
+                    mint: ctx.accounts.lp_mint.to_account_info(),
+                    to: ctx.accounts.provider_lp_account.to_account_info(),
+                    authority: ctx.accounts.pool.to_account_info(),
+                },
+                &[&pool_seeds[..]],
+            ),
+            lp_amount,
+        )?;
+
+        emit!(LiquidityAdded {
+            pool_name: pool_name.clone(),
+            provider: ctx.accounts.provider.key(),
+            amount_x: received_x,
+            amount_y: received_y,
+            lp_amount,
+            event_nonce,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        vlog!("➕ 添加流动性 '{}': X {}, Y {}, LP {}", pool_name, received_x, received_y, lp_amount);
+        Ok(())
+    }
+
+    /// 销毁 LP 代币并按比例取回两侧储备
+    /// 取回数量低于 min_amount_x / min_amount_y 时失败，防止提取前被夹击改变储备比例
+    pub fn remove_liquidity(
+        ctx: Context<RemoveLiquidity>,
+        lp_amount: u64,
+        min_amount_x: u64,
+        min_amount_y: u64,
+    ) -> Result<()> {
+        let mut pool = ctx.accounts.pool.load_mut()?;
+
+        // === CHECK 阶段 ===
+        require!(lp_amount > 0, ErrorCode::InvalidAmount);
+        require!(!pool.is_paused(), ErrorCode::PoolPaused);
+        require!(pool.curve() != CurveType::Concentrated, ErrorCode::UnsupportedCurveOperation);
+
+        let lp_supply = ctx.accounts.lp_mint.supply;
+        require!(lp_amount <= lp_supply, ErrorCode::InsufficientLiquidity);
+
+        // 取回数量向下取整，余数留在池子里
+        let amount_x = mul_div_floor(lp_amount, pool.x_balance, lp_supply)?;
+        let amount_y = mul_div_floor(lp_amount, pool.y_balance, lp_supply)?;
+        require!(amount_x > 0 && amount_y > 0, ErrorCode::InvalidAmount);
+        require!(
+            amount_x >= min_amount_x && amount_y >= min_amount_y,
+            ErrorCode::WithdrawalBelowMinimum
+        );
+
+        // === EFFECTS 阶段 ===
+        pool.x_balance = pool.x_balance.checked_sub(amount_x).ok_or(ErrorCode::Underflow)?;
+        pool.y_balance = pool.y_balance.checked_sub(amount_y).ok_or(ErrorCode::Underflow)?;
+        let event_nonce = pool.next_event_nonce()?;
+        let pool_name = pool.name();
+        let token_x_mint = pool.token_x_mint;
+        let token_y_mint = pool.token_y_mint;
+        let fee_tier = pool.fee_bps.to_le_bytes();
+        let pool_bump = [pool.bump];
+        // 池子作为签名者参与后续 CPI，必须先释放对账户数据的可变借用
+        drop(pool);
+
+        // === INTERACTIONS 阶段 ===
+        let token_program = ctx.accounts.token_program.to_account_info();
+        token_interface::burn(
+            CpiContext::new(
+                token_program.clone(),
+                Burn {
+                    mint: ctx.accounts.lp_mint.to_account_info(),
+                    from: ctx.accounts.provider_lp_account.to_account_info(),
+                    authority: ctx.accounts.provider.to_account_info(),
+                },
+            ),
+            lp_amount,
+        )?;
+
+        let pool_seeds = &[
+            b"mock_dex_pool".as_ref(),
+            token_x_mint.as_ref(),
+            token_y_mint.as_ref(),
+            &fee_tier,
+            &pool_bump,
+        ];
+        let signer_seeds = &[&pool_seeds[..]];
+
+        token_interface::transfer_checked(
+            CpiContext::new_with_signer(
+                token_program.clone(),
+                TransferChecked {
+                    from: ctx.accounts.token_x_vault.to_account_info(),
+                    mint: ctx.accounts.token_x_mint.to_account_info(),
+                    to: ctx.accounts.provider_token_x.to_account_info(),
+                    authority: ctx.accounts.pool.to_account_info(),
+                },
+                signer_seeds,
+            ),
+            amount_x,
+            ctx.accounts.token_x_mint.decimals,
+        )?;
+        token_interface::transfer_checked(
+            CpiContext::new_with_signer(
+                token_program,
+                TransferChecked {
+                    from: ctx.accounts.token_y_vault.to_account_info(),
+                    mint: ctx.accounts.token_y_mint.to_account_info(),
+                    to: ctx.accounts.provider_token_y.to_account_info(),
+                    authority: ctx.accounts.pool.to_account_info(),
+                },
+                signer_seeds,
+            ),
+            amount_y,
+            ctx.accounts.token_y_mint.decimals,
+        )?;
+
+        emit!(LiquidityRemoved {
+            pool_name: pool_name.clone(),
+            provider: ctx.accounts.provider.key(),
+            amount_x,
+            amount_y,
+            lp_amount,
+            event_nonce,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        vlog!("➖ 移除流动性 '{}': X {}, Y {}, LP {}", pool_name, amount_x, amount_y, lp_amount);
+        Ok(())
+    }
+
+    /// 创建池子的流动性挖矿奖励 - 仅池子 admin 可调用
+    /// LP 把 LP 代币质押到 rewarder 后按质押份额分享 emission_per_second 的奖励
+    pub fn initialize_rewarder(ctx: Context<InitializeRewarder>, emission_per_second: u64) -> Result<()> {
+        let rewarder = &mut ctx.accounts.rewarder;
+        rewarder.pool = ctx.accounts.pool.key();
+        rewarder.lp_mint = ctx.accounts.lp_mint.key();
+        rewarder.reward_mint = ctx.accounts.reward_mint.key();
+        rewarder.emission_per_second = emission_per_second;
+        rewarder.acc_reward_per_share = 0;
+        rewarder.rewards_remaining = 0;
+        rewarder.total_staked = 0;
+        rewarder.last_update_ts = Clock::get()?.unix_timestamp;
+        rewarder.bump = ctx.bumps.rewarder;
+
+        let event_nonce = ctx.accounts.pool.load_mut()?.next_event_nonce()?;
+        emit!(RewarderInitialized {
+            pool: rewarder.pool,
+            rewarder: rewarder.key(),
+            reward_mint: rewarder.reward_mint,
+            emission_per_second,
+            event_nonce,
+            timestamp: rewarder.last_update_ts,
+        });
+
+        vlog!(
+            "🌾 池子 '{}' 开启流动性挖矿，每秒发放 {} 奖励",
+            ctx.accounts.pool.load()?.name(),
+            emission_per_second
+        );
+        Ok(())
+    }
+
+    /// 向奖励 vault 注资 - 仅池子 admin 可调用
+    /// 奖励只从已注资的额度中发放，额度用完后停止累计
+    pub fn fund_rewards(ctx: Context<FundRewards>, amount: u64) -> Result<()> {
+        // === CHECK 阶段 ===
+        require!(amount > 0, ErrorCode::InvalidAmount);
+
+        // === INTERACTIONS 阶段 ===
+        let balance_before = ctx.accounts.reward_vault.amount;
+        token_interface::transfer_checked(
+            CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                TransferChecked {
+                    from: ctx.accounts.admin_reward_account.to_account_info(),
+                    mint: ctx.accounts.reward_mint.to_account_info(),
+                    to: ctx.accounts.reward_vault.to_account_info(),
+                    authority: ctx.accounts.admin.to_account_info(),
+                },
+            ),
+            amount,
+            ctx.accounts.reward_mint.decimals,
+        )?;
+
+        // 按实际到账数量增加可发放额度，兼容带转账手续费的 mint
+        ctx.accounts.reward_vault.reload()?;
+        let received = ctx
+            .accounts
+            .reward_vault
+            .amount
+            .checked_sub(balance_before)
+            .ok_or(ErrorCode::Underflow)?;
+
+        // === EFFECTS 阶段 ===
+        let rewarder = &mut ctx.accounts.rewarder;
+        rewarder.accrue(Clock::get()?.unix_timestamp)?;
+        rewarder.rewards_remaining = rewarder
+            .rewards_remaining
+            .checked_add(received)
+            .ok_or(ErrorCode::Overflow)?;
+
+        let event_nonce = ctx.accounts.pool.load_mut()?.next_event_nonce()?;
+        emit!(RewardsFunded {
+            rewarder: rewarder.key(),
+            amount: received,
+            rewards_remaining: rewarder.rewards_remaining,
+            event_nonce,
+            timestamp: rewarder.last_update_ts,
+        });
+
+        vlog!("💧 奖励 vault 注资 {}，剩余可发放 {}", received, rewarder.rewards_remaining);
+        Ok(())
+    }
+
+    /// 修改每秒奖励发放量 - 仅池子 admin 可调用
+    /// 修改前先按旧速率结算到当前时间，已累计的奖励不受影响
+    pub fn set_emission_rate(ctx: Context<SetEmissionRate>, emission_per_second: u64) -> Result<()> {
+        let rewarder = &mut ctx.accounts.rewarder;
+        rewarder.accrue(Clock::get()?.unix_timestamp)?;
+        let old_emission_per_second = rewarder.emission_per_second;
+        rewarder.emission_per_second = emission_per_second;
+
+        let event_nonce = ctx.accounts.pool.load_mut()?.next_event_nonce()?;
+        emit!(EmissionRateUpdated {
+            rewarder: rewarder.key(),
+            old_emission_per_second,
+            new_emission_per_second: emission_per_second,
+            admin: ctx.accounts.admin.key(),
+            event_nonce,
+            timestamp: rewarder.last_update_ts,
+        });
+
+        vlog!("🌾 奖励速率: {} -> {} / 秒", old_emission_per_second, emission_per_second);
+        Ok(())
+    }
+
+    /// 创建 LP 的质押记录，质押前调用一次
+    pub fn open_lp_stake(ctx: Context<OpenLpStake>) -> Result<()> {
+        let stake = &mut ctx.accounts.lp_stake;
+        stake.rewarder = ctx.accounts.rewarder.key();
+        stake.owner = ctx.accounts.owner.key();
+        stake.amount = 0;
+        stake.reward_debt = 0;
+        stake.pending_rewards = 0;
+        stake.bump = ctx.bumps.lp_stake;
+        Ok(())
+    }
+
+    /// 质押 LP 代币开始累计奖励
+    pub fn stake_lp(ctx: Context<StakeLp>, amount: u64) -> Result<()> {
+        // === CHECK 阶段 ===
+        require!(amount > 0, ErrorCode::InvalidAmount);
+
+        // === EFFECTS 阶段：先按质押前的份额结算奖励 ===
+        let rewarder = &mut ctx.accounts.rewarder;
+        rewarder.accrue(Clock::get()?.unix_timestamp)?;
+        let stake = &mut ctx.accounts.lp_stake;
+        stake.settle(rewarder.acc_reward_per_share)?;
+        stake.amount = stake.amount.checked_add(amount).ok_or(ErrorCode::Overflow)?;
+        stake.reset_debt(rewarder.acc_reward_per_share)?;
+        rewarder.total_staked = rewarder.total_staked.checked_add(amount).ok_or(ErrorCode::Overflow)?;
+
+        // === INTERACTIONS 阶段 ===
+        token_interface::transfer_checked(
+            CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                TransferChecked {
+                    from: ctx.accounts.owner_lp_account.to_account_info(),
+                    mint: ctx.accounts.lp_mint.to_account_info(),
+                    to: ctx.accounts.staked_lp_vault.to_account_info(),
+                    authority: ctx.accounts.owner.to_account_info(),
+                },
+            ),
+            amount,
+            ctx.accounts.lp_mint.decimals,
+        )?;
+
+        let event_nonce = ctx.accounts.pool.load_mut()?.next_event_nonce()?;
+        emit!(LpStaked {
+            rewarder: ctx.accounts.rewarder.key(),
+            owner: ctx.accounts.owner.key(),
+            amount,
+            total_staked: ctx.accounts.rewarder.total_staked,
+            event_nonce,
+            timestamp: ctx.accounts.rewarder.last_update_ts,
+        });
+
+        vlog!("🔒 质押 LP {}，当前总质押 {}", amount, ctx.accounts.rewarder.total_staked);
+        Ok(())
+    }
+
+    /// 取回质押的 LP 代币，已累计的奖励保留在质押记录中，可随时领取
+    pub fn unstake_lp(ctx: Context<StakeLp>, amount: u64) -> Result<()> {
+        // === CHECK 阶段 ===
+        require!(amount > 0, ErrorCode::InvalidAmount);
+        require!(amount <= ctx.accounts.lp_stake.amount, ErrorCode::InsufficientStake);
+
+        // === EFFECTS 阶段 ===
+        let rewarder = &mut ctx.accounts.rewarder;
+        rewarder.accrue(Clock::get()?.unix_timestamp)?;
+        let stake = &mut ctx.accounts.lp_stake;
+        stake.settle(rewarder.acc_reward_per_share)?;
+        stake.amount -= amount;
+        stake.reset_debt(rewarder.acc_reward_per_share)?;
+        rewarder.total_staked = rewarder.total_staked.checked_sub(amount).ok_or(ErrorCode::Underflow)?;
+
+        // === INTERACTIONS 阶段 ===
+        let pool_key = rewarder.pool;
+        let rewarder_bump = [rewarder.bump];
+        let rewarder_seeds = &[b"rewarder".as_ref(), pool_key.as_ref(), &rewarder_bump];
+        let signer_seeds = &[&rewarder_seeds[..]];
+
+        token_interface::transfer_checked(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                TransferChecked {
+                    from: ctx.accounts.staked_lp_vault.to_account_info(),
+                    mint: ctx.accounts.lp_mint.to_account_info(),
+                    to: ctx.accounts.owner_lp_account.to_account_info(),
+                    authority: ctx.accounts.rewarder.to_account_info(),
+                },
+                signer_seeds,
+            ),
+            amount,
+            ctx.accounts.lp_mint.decimals,
+        )?;
+
+        let event_nonce = ctx.accounts.pool.load_mut()?.next_event_nonce()?;
+        emit!(LpUnstaked {
+            rewarder: ctx.accounts.rewarder.key(),
+            owner: ctx.accounts.owner.key(),
+            amount,
+            total_staked: ctx.accounts.rewarder.total_staked,
+            event_nonce,
+            timestamp: ctx.accounts.rewarder.last_update_ts,
+        });
+
+        vlog!("🔓 取回 LP {}，当前总质押 {}", amount, ctx.accounts.rewarder.total_staked);
+        Ok(())
+    }
+
+    /// 领取累计的流动性挖矿奖励，返回领取数量
+    pub fn claim_rewards(ctx: Context<ClaimRewards>) -> Result<u64> {
+        // === EFFECTS 阶段 ===
+        let rewarder = &mut ctx.accounts.rewarder;
+        rewarder.accrue(Clock::get()?.unix_timestamp)?;
+        let stake = &mut ctx.accounts.lp_stake;
+        stake.settle(rewarder.acc_reward_per_share)?;
+        let amount = stake.pending_rewards;
+        stake.pending_rewards = 0;
+
+        // === INTERACTIONS 阶段 ===
+        if amount > 0 {
+            let pool_key = rewarder.pool;
+            let rewarder_bump = [rewarder.bump];
+            let rewarder_seeds = &[b"rewarder".as_ref(), pool_key.as_ref(), &rewarder_bump];
+            let signer_seeds = &[&rewarder_seeds[..]];
+
+            token_interface::transfer_checked(
+                CpiContext::new_with_signer(
+                    ctx.accounts.token_program.to_account_info(),
+                    TransferChecked {
+                        from: ctx.accounts.reward_vault.to_account_info(),
+                        mint: ctx.accounts.reward_mint.to_account_info(),
+                        to: ctx.accounts.owner_reward_account.to_account_info(),
+                        authority: ctx.accounts.rewarder.to_account_info(),
+                    },
+                    signer_seeds,
+                ),
+                amount,
+                ctx.accounts.reward_mint.decimals,
+            )?;
+        }
+
+        let event_nonce = ctx.accounts.pool.load_mut()?.next_event_nonce()?;
+        emit!(RewardsClaimed {
+            rewarder: ctx.accounts.rewarder.key(),
+            owner: ctx.accounts.owner.key(),
+            amount,
+            event_nonce,
+            timestamp: ctx.accounts.rewarder.last_update_ts,
+        });
+
+        vlog!("🎁 领取挖矿奖励 {}", amount);
+        Ok(amount)
+    }
+
+    /// 查询时间加权平均价格 (按精度换算后 1 个 X 可兑换的 Y 数量 * PRICE_SCALE)
+    /// 调用方先记录某一时刻池子的 price_cumulative 与 last_update_slot 作为观测点，
+    /// 之后传入即可得到观测点到当前 slot 之间的 TWAP
+    pub fn get_twap(
+        ctx: Context<GetTwap>,
+        observed_cumulative: u128,
+        observed_slot: u64,
+    ) -> Result<u64> {
+        let pool = ctx.accounts.pool.load()?;
+        let current_slot = Clock::get()?.slot;
+
+        require!(current_slot > observed_slot, ErrorCode::InvalidTwapWindow);
+
+        let current_cumulative = pool.price_cumulative_at(current_slot)?;
+        let cumulative_delta = current_cumulative.wrapping_sub(observed_cumulative);
+        let twap = cumulative_delta / (current_slot - observed_slot) as u128;
+
+        vlog!(
+            "📈 池子 '{}' TWAP: {} (slot {} -> {})",
+            pool.name(),
+            twap,
+            observed_slot,
+            current_slot
+        );
+        u64::try_from(twap).map_err(|_| error!(ErrorCode::Overflow))
+    }
+
+    /// 只读价格查询：通过 return data 返回当前现货价格（按精度换算）和最近一段的 TWAP
+    /// TWAP 取最近一次价格观测到当前 slot 之间的平均价格；未传入观测缓冲区、尚无观测或观测就在当前 slot 时为 0
+    pub fn get_pool_price(ctx: Context<GetPoolPrice>) -> Result<PoolPrice> {
+        let pool = ctx.accounts.pool.load()?;
+        let current_slot = Clock::get()?.slot;
+        let spot_price = pool.spot_price()?;
+
+        let latest = ctx.accounts.observations.as_ref().and_then(|observations| observations.latest().copied());
+        let (twap, twap_window_slots) = match latest {
+            Some(observation) if current_slot > observation.slot => {
+                let window = current_slot - observation.slot;
+                let cumulative_delta = pool
+                    .price_cumulative_at(current_slot)?
+                    .wrapping_sub(observation.price_cumulative);
+                let twap = u64::try_from(cumulative_delta / window as u128).map_err(|_| error!(ErrorCode::Overflow))?;
+                (twap, window)
+            }
+            _ => (0, 0),
+        };
+
+        vlog!(
+            "💹 池子 '{}' 现货价格: {}, TWAP: {} ({} slots)",
+            pool.name(),
+            spot_price,
+            twap,
+            twap_window_slots
+        );
+        Ok(PoolPrice { spot_price, twap, twap_window_slots })
+    }
+
+    /// 为池子创建价格观测缓冲区 - 任何人都可以调用并支付租金
+    pub fn initialize_observations(ctx: Context<InitializeObservations>) -> Result<()> {
+        let observations = &mut ctx.accounts.observations;
+        observations.pool = ctx.accounts.pool.key();
+        observations.next_index = 0;
+        observations.bump = ctx.bumps.observations;
+        observations.observations = Vec::new();
+
+        vlog!("🗂️ 池子 '{}' 创建价格观测缓冲区", ctx.accounts.pool.load()?.name());
+        Ok(())
+    }
+
+    /// 记录一次价格观测 - 无需权限，供 keeper 定期调用
+    /// 把当前现货价格、价格累计值和储备写入环形缓冲区，写满后覆盖最旧的观测；同一 slot 只记录一次
+    pub fn crank_observation(ctx: Context<CrankObservation>) -> Result<()> {
+        let clock = Clock::get()?;
+
+        // === CHECK 阶段 ===
+        if let Some(latest) = ctx.accounts.observations.latest() {
+            require!(clock.slot > latest.slot, ErrorCode::ObservationTooRecent);
+        }
+
+        // === EFFECTS 阶段 ===
+        let mut pool = ctx.accounts.pool.load_mut()?;
+        pool.accumulate_price(clock.slot)?;
+        let observation = PriceObservation {
+            slot: clock.slot,
+            timestamp: clock.unix_timestamp,
+            price: pool.spot_price()?,
+            price_cumulative: pool.price_cumulative,
+            reserve_x: pool.x_balance,
+            reserve_y: pool.y_balance,
+        };
+        let index = ctx.accounts.observations.record(observation);
+
+        emit!(ObservationRecorded {
+            pool_name: pool.name(),
+            index,
+            slot: observation.slot,
+            price: observation.price,
+            event_nonce: pool.next_event_nonce()?,
+            timestamp: observation.timestamp,
+        });
+
+        vlog!("🕯️ 池子 '{}' 价格观测 #{}: {} (slot {})", pool.name(), index, observation.price, clock.slot);
+        Ok(())
+    }
+
+    /// 记录池子当日的统计快照 - 无需权限，任何人都可以调用并支付租金
+    /// day 为 unix_timestamp / SECONDS_PER_DAY，必须是当天；每个池子每天只能快照一次，
+    /// 相邻两天的累计值之差即为当天的成交量和手续费
+    pub fn snapshot_pool_stats(ctx: Context<SnapshotPoolStats>, day: u64) -> Result<()> {
+        let clock = Clock::get()?;
+
+        // === CHECK 阶段 ===
+        require!(
+            clock.unix_timestamp >= 0 && day == (clock.unix_timestamp / SECONDS_PER_DAY) as u64,
+            ErrorCode::InvalidSnapshotDay
+        );
+
+        // === EFFECTS 阶段 ===
+        let mut pool = ctx.accounts.pool.load_mut()?;
+        let snapshot = &mut ctx.accounts.snapshot;
+        snapshot.pool = ctx.accounts.pool.key();
+        snapshot.day = day;
+        snapshot.slot = clock.slot;
+        snapshot.timestamp = clock.unix_timestamp;
+        snapshot.reserve_x = pool.x_balance;
+        snapshot.reserve_y = pool.y_balance;
+        snapshot.cumulative_volume_x = pool.cumulative_volume_x;
+        snapshot.cumulative_volume_y = pool.cumulative_volume_y;
+        snapshot.cumulative_fees_x = pool.cumulative_fees_x;
+        snapshot.cumulative_fees_y = pool.cumulative_fees_y;
+        snapshot.swap_count = pool.swap_count;
+        snapshot.bump = ctx.bumps.snapshot;
+
+        emit!(PoolStatsSnapshotTaken {
+            pool_name: pool.name(),
+            day,
+            reserve_x: snapshot.reserve_x,
+            reserve_y: snapshot.reserve_y,
+            swap_count: snapshot.swap_count,
+            event_nonce: pool.next_event_nonce()?,
+            timestamp: clock.unix_timestamp,
+        });
+
+        vlog!("📊 池子 '{}' 第 {} 天统计快照: {} 笔成交", pool.name(), day, snapshot.swap_count);
+        Ok(())
+    }
+
+    /// 为集中流动性池创建价格区间 - 仅池子 admin 可调用
+    /// prices 必须严格递增，active_index 为当前价格所在的区间
+    pub fn initialize_ranges(
+        ctx: Context<InitializeRanges>,
+        prices: Vec<u64>,
+        active_index: u8,
+    ) -> Result<()> {
+        // === CHECK 阶段 ===
+        require!(
+            !prices.is_empty() && prices.len() <= MAX_LIQUIDITY_RANGES,
+            ErrorCode::InvalidLiquidityRanges
+        );
+        require!(prices[0] > 0, ErrorCode::InvalidLiquidityRanges);
+        require!(
+            prices.windows(2).all(|pair| pair[0] < pair[1]),
+            ErrorCode::InvalidLiquidityRanges
+        );
+        require!((active_index as usize) < prices.len(), ErrorCode::InvalidLiquidityRanges);
+
+        // === EFFECTS 阶段 ===
+        let ranges = &mut ctx.accounts.ranges;
+        ranges.pool = ctx.accounts.pool.key();
+        ranges.active_index = active_index;
+        ranges.bump = ctx.bumps.ranges;
+        ranges.ranges = prices
+            .iter()
+            .map(|&price| LiquidityRange {
+                price,
+                reserve_x: 0,
+                reserve_y: 0,
+            })
+            .collect();
+
+        let event_nonce = ctx.accounts.pool.load_mut()?.next_event_nonce()?;
+        emit!(LiquidityRangesInitialized {
+            pool_name: ctx.accounts.pool.load()?.name(),
+            range_count: prices.len() as u8,
+            active_index,
+            event_nonce,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        vlog!("📐 池子 '{}' 创建 {} 个价格区间", ctx.accounts.pool.load()?.name(), prices.len());
+        Ok(())
+    }
+
+    /// 把池子中尚未分配的储备放入某个价格区间 - 仅池子 admin 可调用
+    pub fn allocate_range_liquidity(
+        ctx: Context<AllocateRangeLiquidity>,
+        range_index: u8,
+        amount_x: u64,
+        amount_y: u64,
+    ) -> Result<()> {
+        let mut pool = ctx.accounts.pool.load_mut()?;
+        let ranges = &mut ctx.accounts.ranges;
+
+        // === CHECK 阶段 ===
+        require!(amount_x > 0 || amount_y > 0, ErrorCode::InvalidAmount);
+        require!(
+            (range_index as usize) < ranges.ranges.len(),
+            ErrorCode::InvalidLiquidityRanges
+        );
+
+        let (allocated_x, allocated_y) = ranges.total_reserves()?;
+        let new_allocated_x = allocated_x.checked_add(amount_x).ok_or(ErrorCode::Overflow)?;
+        let new_allocated_y = allocated_y.checked_add(amount_y).ok_or(ErrorCode::Overflow)?;
+        require!(
+            new_allocated_x <= pool.x_balance && new_allocated_y <= pool.y_balance,
+            ErrorCode::RangeAllocationExceedsReserves
+        );
+
+        // === EFFECTS 阶段 ===
+        let range = &mut ranges.ranges[range_index as usize];
+        range.reserve_x = range.reserve_x.checked_add(amount_x).ok_or(ErrorCode::Overflow)?;
+        range.reserve_y = range.reserve_y.checked_add(amount_y).ok_or(ErrorCode::Overflow)?;
+
+        emit!(RangeLiquidityAllocated {
+            pool_name: pool.name(),
+            range_index,
+            amount_x,
+            amount_y,
+            event_nonce: pool.next_event_nonce()?,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        vlog!(
+            "📐 区间 {} 分配流动性: X {}, Y {} (池子 '{}')",
+            range_index,
+            amount_x,
+            amount_y,
+            pool.name()
+        );
+        Ok(())
+    }
+
+    /// 校验池子核心不变量（仅非主网构建），供测试在每笔交易末尾追加调用
+    /// vault 实际余额必须不少于池子记录的储备
+    #[cfg(not(feature = "mainnet"))]
+    pub fn assert_invariants(ctx: Context<AssertInvariants>) -> Result<()> {
+        let pool = ctx.accounts.pool.load()?;
+
+        require!(
+            ctx.accounts.token_x_vault.amount >= pool.x_balance,
+            ErrorCode::InvariantVaultXBelowReserve
+        );
+        require!(
+            ctx.accounts.token_y_vault.amount >= pool.y_balance,
+            ErrorCode::InvariantVaultYBelowReserve
+        );
+
+        Ok(())
+    }
+}
+
+/// swap / swap_exact_out 共用的校验与结算逻辑，以及 swap_route / batch_swap / multi_pool_swap 共用的单池兑换
+pub struct SwapHandler;
+
+/// 用户转入后 vault 实际收到的数量
+pub struct SwapDeposit {
+    /// 按 amount_in 收取的全部手续费（含协议费与推荐人分成）
+    pub fee_amount: u64,
+    /// 进入储备的实际到账数量（已扣除 Token-2022 转账手续费）
+    pub reserve_amount: u64,
+    /// 转入 fee vault 的协议费
+    pub protocol_fee: u64,
+    /// 转给推荐人的手续费分成
+    pub referral_fee: u64,
+}
+
+impl SwapDeposit {
+    /// 用于曲线计价的有效输入数量
+    pub fn effective_amount_in(&self) -> Result<u64> {
+        Ok(self
+            .reserve_amount
+            .checked_add(self.protocol_fee)
+            .and_then(|amount| amount.checked_add(self.referral_fee))
+            .ok_or(ErrorCode::Overflow)?)
+    }
+}
+
+/// remaining_accounts 中单个池子的一笔兑换，swap_route / batch_swap / multi_pool_swap 共用
+pub struct PoolSwapLeg<'a, 'info> {
+    pub pool: &'a AccountLoader<'info, MockDexPool>,
+    /// 输入是否为 Token X，由调用方按各自的账户约定确定，vault 与 mint 在 swap_in_pool 中按此校验
+    pub input_is_x: bool,
+    pub vault_in: &'a AccountInfo<'info>,
+    pub vault_out: &'a AccountInfo<'info>,
+    pub fee_vault: &'a AccountInfo<'info>,
+    pub mint_in: &'a InterfaceAccount<'info, Mint>,
+    pub mint_out: &'a InterfaceAccount<'info, Mint>,
+    /// 用户的输入账户；为 None 时输入已由上一跳转入 vault_in
+    pub source: Option<&'a AccountInfo<'info>>,
+    /// 输出的接收账户：用户账户或下一跳的输入 vault
+    pub destination: &'a AccountInfo<'info>,
+    pub fee_allowlist: Option<&'a FeeAllowlist>,
+    pub referrer: Option<&'a AccountInfo<'info>>,
+}
+
+/// swap_in_pool 的成交结果
+pub struct PoolSwapOutcome {
+    pub pool_name: String,
+    pub amount_out: u64,
+    /// destination 实际收到的数量（已扣除 Token-2022 转账手续费）
+    pub amount_received: u64,
+}
+
+impl SwapHandler {
+    /// 本次兑换适用的手续费：user_authority 在池子的手续费豁免名单中时使用名单中的费率，
+    /// 处于折扣时间窗口内时使用折扣费率，两者都适用时取较低者
+    pub fn fee_bps(accounts: &Swap) -> Result<u64> {
+        let pool = accounts.pool.load()?;
+        Self::effective_fee_bps(&pool, accounts.fee_allowlist.as_deref(), &accounts.user_authority.key())
+    }
+
+    fn effective_fee_bps(pool: &MockDexPool, fee_allowlist: Option<&FeeAllowlist>, user: &Pubkey) -> Result<u64> {
+        let exempt_fee_bps = fee_allowlist.and_then(|allowlist| allowlist.fee_bps_for(user));
+        // 豁免费率与当前折扣费率取较低者
+        let current_fee_bps = pool.current_fee_bps(Clock::get()?.unix_timestamp);
+        Ok(match exempt_fee_bps {
+            Some(fee_bps) => fee_bps.min(current_fee_bps),
+            None => current_fee_bps,
+        } as u64)
+    }
+
+    /// 校验输入账户与协议费 vault，返回输入是否为 Token X
+    pub fn validate_accounts(accounts: &Swap) -> Result<bool> {
+        let pool = accounts.pool.load()?;
+
+        // 检查 token_in_account 是 X 还是 Y，输出账户必须是另一侧的代币
+        let input_mint = accounts.token_in_account.mint;
+        let input_is_x = input_mint == pool.token_x_mint;
+        let input_is_y = input_mint == pool.token_y_mint;
+        require!(input_is_x || input_is_y, ErrorCode::InvalidTokenInAccount);
+        let output_mint = if input_is_x { pool.token_y_mint } else { pool.token_x_mint };
+        require_keys_eq!(accounts.token_out_account.mint, output_mint, ErrorCode::InvalidTokenMint);
+
+        // 检查池子状态和流动性
+        require!(!pool.is_paused(), ErrorCode::PoolPaused);
+        pool.ensure_direction_enabled(input_is_x)?;
+        pool.ensure_trader_allowed(&accounts.user_authority.key(), accounts.trader_allowlist.as_deref())?;
+        require!(pool.x_balance > 0 && pool.y_balance > 0, ErrorCode::InsufficientLiquidity);
+        pool.assert_vaults_cover_reserves(accounts.token_x_vault.amount, accounts.token_y_vault.amount)?;
+
+        // 协议费 vault 必须是输入代币一侧的 fee vault
+        let expected_fee_vault = if input_is_x { pool.fee_x_vault } else { pool.fee_y_vault };
+        require_keys_eq!(
+            accounts.protocol_fee_vault.key(),
+            expected_fee_vault,
+            ErrorCode::InvalidFeeVault
+        );
+
+        Ok(input_is_x)
+    }
+
+    /// 检查 user_authority 能否从输入账户转出 amount_in，返回是否为委托转账
+    /// 输入账户的 owner 直接放行；否则必须是通过 approve 授权的 delegate，且剩余授权额度足够。
+    /// 委托转账时输出账户必须属于输入账户的 owner，delegate 不能把成交所得转给自己
+    pub fn check_input_authority(accounts: &Swap, amount_in: u64) -> Result<bool> {
+        let token_in = &accounts.token_in_account;
+        let authority = accounts.user_authority.key();
+        if token_in.owner == authority {
+            return Ok(false);
+        }
+
+        require!(
+            Option::<Pubkey>::from(token_in.delegate) == Some(authority),
+            ErrorCode::InvalidTokenAccountOwner
+        );
+        require!(token_in.delegated_amount >= amount_in, ErrorCode::DelegatedAmountExceeded);
+
+        require_keys_eq!(accounts.token_out_account.owner, token_in.owner, ErrorCode::InvalidTokenAccountOwner);
+        Ok(true)
+    }
+
+    /// 按池子的曲线计算扣除手续费后的输出数量
+    /// 集中流动性池从 remaining_accounts[0] 读取区间账户，persist_ranges 为 true 时写回成交结果
+    pub fn compute_amount_out<'info>(
+        pool: &MockDexPool,
+        pool_key: Pubkey,
+        remaining_accounts: &'info [AccountInfo<'info>],
+        amount_in: u64,
+        input_is_x: bool,
+        fee_bps: u64,
+        persist_ranges: bool,
+    ) -> Result<u64> {
+        let (reserve_in, reserve_out) = pool.reserves(input_is_x);
+
+        let curve = pool.curve();
+        let amount_out = if curve == CurveType::Concentrated {
+            let ranges_info = remaining_accounts
+                .first()
+                .ok_or(ErrorCode::MissingLiquidityRanges)?;
+            let mut ranges: Account<'info, LiquidityRanges> = Account::try_from(ranges_info)?;
+            require_keys_eq!(ranges.pool, pool_key, ErrorCode::InvalidLiquidityRanges);
+
+            let fee_amount = mul_bps(amount_in, fee_bps)?;
+            let amount_out = ranges.fill(amount_in - fee_amount, input_is_x)?;
+            if persist_ranges {
+                ranges.exit(&crate::ID)?;
+            }
+            amount_out
+        } else {
+            curve.amount_out(amount_in, fee_bps, reserve_in, reserve_out, input_is_x)?
+        };
+
+        // 确保池子有足够的储备
+        require!(amount_out < reserve_out, ErrorCode::InsufficientLiquidity);
+        Ok(amount_out)
+    }
+
+    /// 二分查找可成交的最大输入数量：输出不超过储备、平均成交价不低于 min_price、
+    /// 价格影响不超过池子上限；查找过程只做内存计算，不写回区间账户
+    pub fn max_fillable_amount_in<'info>(
+        pool: &MockDexPool,
+        pool_key: Pubkey,
+        remaining_accounts: &'info [AccountInfo<'info>],
+        amount_in: u64,
+        input_is_x: bool,
+        fee_bps: u64,
+        min_price: u64,
+    ) -> Result<u64> {
+        let fillable = |amount: u64| -> bool {
+            let Ok(amount_out) =
+                Self::compute_amount_out(pool, pool_key, remaining_accounts, amount, input_is_x, fee_bps, false)
+            else {
+                return false;
+            };
+            let Ok(min_amount_out) = pool.amount_at_price(amount, min_price as u128, PRICE_SCALE as u128, input_is_x) else {
+                return false;
+            };
+            amount_out > 0
+                && amount_out >= min_amount_out
+                && pool
+                    .price_impact_bps(input_is_x, amount, amount_out)
+                    .is_ok_and(|impact| impact <= pool.max_price_impact_bps as u64)
+        };
+
+        if fillable(amount_in) {
+            return Ok(amount_in);
+        }
+
+        // low 始终可成交（0 视为可成交），high 始终不可成交
+        let (mut low, mut high) = (0u64, amount_in);
+        while high - low > 1 {
+            let mid = low + (high - low) / 2;
+            if fillable(mid) {
+                low = mid;
+            } else {
+                high = mid;
+            }
+        }
+        Ok(low)
+    }
+
+    /// 用户转入 amount_in（其中协议费进入 fee vault，推荐人分成转给推荐人），按 vault 余额变化返回实际到账数量
+    /// 先转入再计价偏离了 CEI 顺序，但 token 程序不会回调本程序，不存在重入
+    pub fn deposit(accounts: &mut Swap, input_is_x: bool, amount_in: u64) -> Result<SwapDeposit> {
+        let delegated = Self::check_input_authority(accounts, amount_in)?;
+
+        // 手续费中协议所得部分进入 fee vault，其余留在储备中归 LP
+        let (protocol_fee_share_bps, referral_fee_share_bps) = {
+            let pool = accounts.pool.load()?;
+            (pool.protocol_fee_share_bps, pool.referral_fee_share_bps)
+        };
+        let fee_amount = mul_bps(amount_in, Self::fee_bps(accounts)?)?;
+        let protocol_fee = mul_bps(fee_amount, protocol_fee_share_bps as u64)?;
+        // 推荐人分成从 LP 所得部分中扣出，未传推荐人账户时为 0
+        let referral_fee = if accounts.referrer_token_account.is_some() {
+            mul_bps(fee_amount, referral_fee_share_bps as u64)?
+        } else {
+            0
+        };
+        let amount_to_reserve = amount_in - protocol_fee - referral_fee;
+
+        let mint_in = if input_is_x { &accounts.token_x_mint } else { &accounts.token_y_mint };
+        let vault_in = if input_is_x {
+            &mut accounts.token_x_vault
+        } else {
+            &mut accounts.token_y_vault
+        };
+        let balance_before = vault_in.amount;
+
+        token_interface::transfer_checked(
+            CpiContext::new(
+                accounts.token_program.to_account_info(),
+                TransferChecked {
+                    from: accounts.token_in_account.to_account_info(),
+                    mint: mint_in.to_account_info(),
+                    to: vault_in.to_account_info(),
+                    authority: accounts.user_authority.to_account_info(),
+                },
+            ),
+            amount_to_reserve,
+            mint_in.decimals,
+        )?;
+
+        // 带转账手续费的 mint 到账会少于转出数量，储备只能按实际到账记账
+        vault_in.reload()?;
+        let reserve_amount = vault_in
+            .amount
+            .checked_sub(balance_before)
+            .ok_or(ErrorCode::Underflow)?;
+
+        // 协议费转入 fee vault
+        if protocol_fee > 0 {
+            token_interface::transfer_checked(
+                CpiContext::new(
+                    accounts.token_program.to_account_info(),
+                    TransferChecked {
+                        from: accounts.token_in_account.to_account_info(),
+                        mint: mint_in.to_account_info(),
+                        to: accounts.protocol_fee_vault.to_account_info(),
+                        authority: accounts.user_authority.to_account_info(),
+                    },
+                ),
+                protocol_fee,
+                mint_in.decimals,
+            )?;
+        }
+
+        // 推荐人分成直接从用户转给推荐人
+        if let Some(referrer) = accounts.referrer_token_account.as_ref() {
+            if referral_fee > 0 {
+                token_interface::transfer_checked(
+                    CpiContext::new(
+                        accounts.token_program.to_account_info(),
+                        TransferChecked {
+                            from: accounts.token_in_account.to_account_info(),
+                            mint: mint_in.to_account_info(),
+                            to: referrer.to_account_info(),
+                            authority: accounts.user_authority.to_account_info(),
+                        },
+                    ),
+                    referral_fee,
+                    mint_in.decimals,
+                )?;
+            }
+        }
+
+        vlog!(
+            "📥 转入完成: {} tokens (实际到账 {}, 协议费 {}, 推荐人分成 {}, 委托转账: {})",
+            amount_in,
+            reserve_amount,
+            protocol_fee,
+            referral_fee,
+            delegated
+        );
+        Ok(SwapDeposit { fee_amount, reserve_amount, protocol_fee, referral_fee })
+    }
+
+    /// 更新储备并完成转出：储备按 deposit 的实际到账数量增加，池子转出 amount_out
+    /// 返回本次成交的结果，供 swap 通过 return data 交给 CPI 调用方
+    pub fn settle(
+        accounts: &mut Swap,
+        pool_bump: u8,
+        input_is_x: bool,
+        amount_in: u64,
+        deposit: &SwapDeposit,
+        amount_out: u64,
+    ) -> Result<SwapResult> {
+        let mut pool = accounts.pool.load_mut()?;
+        let amount_to_reserve = deposit.reserve_amount;
+
+        // 计算价格影响，超过池子上限的交易直接拒绝
+        let price_impact_bps = pool.price_impact_bps(input_is_x, amount_to_reserve, amount_out)?;
+        require!(
+            price_impact_bps <= pool.max_price_impact_bps as u64,
+            ErrorCode::PriceImpactTooHigh
+        );
+
+        // 池子启用了预言机保护且传入预言机账户时，成交价偏离预言机价格过多直接拒绝
+        if let Some(oracle) = accounts.oracle.as_ref() {
+            if pool.max_oracle_deviation_bps > 0 {
+                let oracle_price = read_pyth_price(oracle, Clock::get()?.slot)?;
+                let execution_price = pool.execution_price(amount_in, amount_out, input_is_x)?;
+                require!(
+                    price_deviation_bps(execution_price, oracle_price)? <= pool.max_oracle_deviation_bps as u64,
+                    ErrorCode::OraclePriceDeviation
+                );
+            }
+        }
+
+        // === EFFECTS 阶段：更新所有状态 ===
+
+        // 先用交易前的价格累计 TWAP，再更新储备
+        pool.accumulate_price(Clock::get()?.slot)?;
+
+        // 更新池子储备状态（在转出之前）
+        if input_is_x {
+            pool.x_balance = pool.x_balance.checked_add(amount_to_reserve).ok_or(ErrorCode::Overflow)?;
+            pool.y_balance = pool.y_balance.checked_sub(amount_out).ok_or(ErrorCode::Underflow)?;
+        } else {
+            pool.y_balance = pool.y_balance.checked_add(amount_to_reserve).ok_or(ErrorCode::Overflow)?;
+            pool.x_balance = pool.x_balance.checked_sub(amount_out).ok_or(ErrorCode::Underflow)?;
+        }
+
+        pool.check_circuit_breaker(Clock::get()?.slot, input_is_x, amount_out)?;
+        pool.record_swap(input_is_x, amount_in, amount_out, deposit.fee_amount)?;
+        let sequence = pool.next_swap_sequence()?;
+        let event_nonce = pool.next_event_nonce()?;
+        let (reserve_x_after, reserve_y_after) = (pool.x_balance, pool.y_balance);
+
+        vlog!("💰 Pool状态已更新: X={}, Y={}", pool.x_balance, pool.y_balance);
+
+        let pool_name = pool.name();
+        let token_x_mint = pool.token_x_mint;
+        let token_y_mint = pool.token_y_mint;
+        let fee_tier = pool.fee_bps.to_le_bytes();
+        // 池子作为转出签名者参与 CPI，必须先释放对账户数据的可变借用
+        drop(pool);
+
+        // === INTERACTIONS 阶段：所有外部调用 ===
+
+        let (vault_out, mint_out) = if input_is_x {
+            (&accounts.token_y_vault, &accounts.token_y_mint) // 卖出 X 得到 Y
+        } else {
+            (&accounts.token_x_vault, &accounts.token_x_mint) // 卖出 Y 得到 X
+        };
+        let to_token_account = &accounts.token_out_account;
+
+        // 从 DEX Vault 转出到用户账户
+        let pool_seeds = &[
+            b"mock_dex_pool".as_ref(),
+            token_x_mint.as_ref(),
+            token_y_mint.as_ref(),
+            &fee_tier,
+            &[pool_bump]
+        ];
+        let signer_seeds = &[&pool_seeds[..]];
+
+        token_interface::transfer_checked(
+            CpiContext::new_with_signer(
+                accounts.token_program.to_account_info(),
+                TransferChecked {
+                    from: vault_out.to_account_info(),
+                    mint: mint_out.to_account_info(),
+                    to: to_token_account.to_account_info(),
+                    authority: accounts.pool.to_account_info(),
+                },
+                signer_seeds,
+            ),
+            amount_out,
+            mint_out.decimals,
+        )?;
+
+        vlog!("📤 转出完成: {} tokens", amount_out);
+
+        // 成交后 vault 余额仍须覆盖记录的储备
+        accounts.token_x_vault.reload()?;
+        accounts.token_y_vault.reload()?;
+        accounts
+            .pool
+            .load()?
+            .assert_vaults_cover_reserves(accounts.token_x_vault.amount, accounts.token_y_vault.amount)?;
+
+        // 发送增强事件
+        emit!(SwapExecuted {
+            pool_name: pool_name.clone(),
+            amount_in,
+            amount_out,
+            price_impact_bps,
+            fee_amount: deposit.fee_amount,
+            is_x_to_y: input_is_x,
+            reserve_x_after,
+            reserve_y_after,
+            sequence,
+            user: accounts.user_authority.key(),
+            referrer: accounts.referrer_token_account.as_ref().map(|referrer| referrer.key()),
+            event_nonce,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        vlog!(
+            "✅ AMM Swap: {} -> {} (滑点: {}bps) on DEX '{}'", 
+            amount_in, 
+            amount_out, 
+            price_impact_bps,
+            pool_name
+        );
+        Ok(SwapResult {
+            amount_out,
+            fee_paid: deposit.fee_amount,
+            price_impact_bps,
+            reserve_x_after,
+            reserve_y_after,
+        })
+    }
+
+    /// 在 remaining_accounts 中的单个池子完成一笔兑换：按方向校验账户，按手续费豁免名单收费并给推荐人分成，
+    /// 按 vault 实际到账计价，更新储备后由池子签名转出；滑点由调用方按各自的语义检查
+    /// 这些指令不携带交易者名单，许可模式的池子和集中流动性池都不能出现在其中
+    pub fn swap_in_pool<'info>(
+        leg: &PoolSwapLeg<'_, 'info>,
+        token_program: &AccountInfo<'info>,
+        user_authority: &AccountInfo<'info>,
+        amount_in: u64,
+    ) -> Result<PoolSwapOutcome> {
+        // === CHECK 阶段 ===
+        require!(amount_in > 0, ErrorCode::InvalidAmount);
+        let input_is_x = leg.input_is_x;
+        let mut pool = leg.pool.load_mut()?;
+        require!(pool.version == POOL_VERSION, ErrorCode::UnsupportedPoolVersion);
+
+        let (expected_vault_in, expected_vault_out, expected_fee_vault, mint_in, mint_out) = if input_is_x {
+            (pool.token_x_vault, pool.token_y_vault, pool.fee_x_vault, pool.token_x_mint, pool.token_y_mint)
+        } else {
+            (pool.token_y_vault, pool.token_x_vault, pool.fee_y_vault, pool.token_y_mint, pool.token_x_mint)
+        };
+        require_keys_eq!(leg.vault_in.key(), expected_vault_in, ErrorCode::InvalidVault);
+        require_keys_eq!(leg.vault_out.key(), expected_vault_out, ErrorCode::InvalidVault);
+        require_keys_eq!(leg.fee_vault.key(), expected_fee_vault, ErrorCode::InvalidFeeVault);
+        require_keys_eq!(leg.mint_in.key(), mint_in, ErrorCode::InvalidTokenMint);
+        require_keys_eq!(leg.mint_out.key(), mint_out, ErrorCode::InvalidTokenMint);
+        if let Some(source) = leg.source {
+            require_keys_eq!(token_mint(source)?, mint_in, ErrorCode::InvalidTokenInAccount);
+        }
+        if let Some(referrer) = leg.referrer {
+            require_keys_eq!(token_mint(referrer)?, mint_in, ErrorCode::InvalidReferrerAccount);
+        }
+        if let Some(allowlist) = leg.fee_allowlist {
+            require_keys_eq!(allowlist.pool, leg.pool.key(), ErrorCode::InvalidFeeAllowlist);
+        }
+
+        require!(!pool.is_paused(), ErrorCode::PoolPaused);
+        pool.ensure_trader_allowed(&user_authority.key(), None)?;
+        pool.ensure_direction_enabled(input_is_x)?;
+        require!(pool.curve() != CurveType::Concentrated, ErrorCode::UnsupportedCurveOperation);
+
+        let (reserve_in, reserve_out) = pool.reserves(input_is_x);
+        require!(reserve_in > 0 && reserve_out > 0, ErrorCode::InsufficientLiquidity);
+        assert_route_vaults(&pool, input_is_x, leg.vault_in, leg.vault_out)?;
+
+        // 手续费中协议所得部分进入 fee vault，推荐人分成从 LP 所得部分中扣出，未传推荐人账户时为 0
+        let fee_bps = Self::effective_fee_bps(&pool, leg.fee_allowlist, &user_authority.key())?;
+        let fee_amount = mul_bps(amount_in, fee_bps)?;
+        let protocol_fee = mul_bps(fee_amount, pool.protocol_fee_share_bps as u64)?;
+        let referral_fee = if leg.referrer.is_some() {
+            mul_bps(fee_amount, pool.referral_fee_share_bps as u64)?
+        } else {
+            0
+        };
+        let mut reserve_amount = amount_in - protocol_fee - referral_fee;
+
+        if let Some(source) = leg.source {
+            // 用户先转入储备部分，按 vault 余额变化得到实际到账数量，再转出协议费和推荐人分成
+            // 先转入再计价偏离了 CEI 顺序，但 token 程序不会回调本程序，不存在重入
+            let balance_before = token_balance(leg.vault_in)?;
+            transfer_tokens(token_program, source, leg.mint_in, leg.vault_in, user_authority, reserve_amount, &[])?;
+            reserve_amount = token_balance(leg.vault_in)?
+                .checked_sub(balance_before)
+                .ok_or(ErrorCode::Underflow)?;
+            transfer_tokens(token_program, source, leg.mint_in, leg.fee_vault, user_authority, protocol_fee, &[])?;
+            if let Some(referrer) = leg.referrer {
+                transfer_tokens(token_program, source, leg.mint_in, referrer, user_authority, referral_fee, &[])?;
+            }
+        }
+        let deposit = SwapDeposit { fee_amount, reserve_amount, protocol_fee, referral_fee };
+
+        let amount_out = pool.curve().amount_out(
+            deposit.effective_amount_in()?,
+            fee_bps,
+            reserve_in,
+            reserve_out,
+            input_is_x,
+        )?;
+        require!(amount_out < reserve_out, ErrorCode::InsufficientLiquidity);
+
+        let price_impact_bps = pool.price_impact_bps(input_is_x, reserve_amount, amount_out)?;
+        require!(
+            price_impact_bps <= pool.max_price_impact_bps as u64,
+            ErrorCode::PriceImpactTooHigh
+        );
+
+        // === EFFECTS 阶段 ===
+        pool.accumulate_price(Clock::get()?.slot)?;
+        if input_is_x {
+            pool.x_balance = pool.x_balance.checked_add(reserve_amount).ok_or(ErrorCode::Overflow)?;
+            pool.y_balance = pool.y_balance.checked_sub(amount_out).ok_or(ErrorCode::Underflow)?;
+        } else {
+            pool.y_balance = pool.y_balance.checked_add(reserve_amount).ok_or(ErrorCode::Overflow)?;
+            pool.x_balance = pool.x_balance.checked_sub(amount_out).ok_or(ErrorCode::Underflow)?;
+        }
+        pool.check_circuit_breaker(Clock::get()?.slot, input_is_x, amount_out)?;
+        pool.record_swap(input_is_x, amount_in, amount_out, fee_amount)?;
+        let sequence = pool.next_swap_sequence()?;
+        let event_nonce = pool.next_event_nonce()?;
+
+        // === INTERACTIONS 阶段 ===
+        let pool_name = pool.name();
+        let (token_x_mint, token_y_mint) = (pool.token_x_mint, pool.token_y_mint);
+        let (reserve_x_after, reserve_y_after) = (pool.x_balance, pool.y_balance);
+        let fee_tier = pool.fee_bps.to_le_bytes();
+        let pool_bump = [pool.bump];
+        // zero-copy 账户直接写在账户数据上，同一池子在后续兑换中再次出现时能读到最新状态；
+        // 池子作为转出签名者参与 CPI，必须先释放对账户数据的可变借用
+        drop(pool);
+        let pool_seeds = &[
+            b"mock_dex_pool".as_ref(),
+            token_x_mint.as_ref(),
+            token_y_mint.as_ref(),
+            &fee_tier,
+            &pool_bump,
+        ];
+        let signer_seeds = &[&pool_seeds[..]];
+        let pool_info = leg.pool.to_account_info();
+
+        if leg.source.is_none() {
+            // 输入已在 vault_in 中，由池子签名从中划出协议费和推荐人分成
+            transfer_tokens(token_program, leg.vault_in, leg.mint_in, leg.fee_vault, &pool_info, protocol_fee, signer_seeds)?;
+            if let Some(referrer) = leg.referrer {
+                transfer_tokens(token_program, leg.vault_in, leg.mint_in, referrer, &pool_info, referral_fee, signer_seeds)?;
+            }
+        }
+
+        let destination_before = token_balance(leg.destination)?;
+        transfer_tokens(token_program, leg.vault_out, leg.mint_out, leg.destination, &pool_info, amount_out, signer_seeds)?;
+        let amount_received = token_balance(leg.destination)?
+            .checked_sub(destination_before)
+            .ok_or(ErrorCode::Underflow)?;
+        assert_route_vaults(&*leg.pool.load()?, input_is_x, leg.vault_in, leg.vault_out)?;
+
+        emit!(SwapExecuted {
+            pool_name: pool_name.clone(),
+            amount_in,
+            amount_out,
+            price_impact_bps,
+            fee_amount,
+            is_x_to_y: input_is_x,
+            reserve_x_after,
+            reserve_y_after,
+            sequence,
+            user: user_authority.key(),
+            referrer: leg.referrer.map(|referrer| referrer.key()),
+            event_nonce,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        Ok(PoolSwapOutcome { pool_name, amount_out, amount_received })
+    }
+}
+
+// ---------------------------------------------------------------- //
+//                          账户定义                               //
+// ---------------------------------------------------------------- //
+
+#[derive(Accounts)]
+pub struct InitializeRegistry<'info> {
+    #[account(
+        init,
+        payer = payer,
+        seeds = [b"pool_registry"],
+        bump,
+        space = PoolRegistry::SPACE,
+    )]
+    pub registry: Account<'info, PoolRegistry>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(token_x_mint: Pubkey, token_y_mint: Pubkey, fee_bps: u16)]
+pub struct GetRegisteredPool<'info> {
+    /// CHECK: 按 mint 对和手续费档位推导的注册表条目 PDA，池子未登记时为空账户，在指令中反序列化
+    #[account(
+        seeds = [b"pool_registry_entry", token_x_mint.as_ref(), token_y_mint.as_ref(), &fee_bps.to_le_bytes()],
+        bump,
+    )]
+    pub registry_entry: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(pool_name: String, initial_x_amount: u64, initial_y_amount: u64, fee_bps: u16)]
+pub struct InitializePool<'info> {
+    #[account(
+        init,
+        payer = initializer,
+        seeds = [
+            b"mock_dex_pool",
+            token_x_mint.key().as_ref(),
+            token_y_mint.key().as_ref(),
+            &fee_bps.to_le_bytes(),
+        ],
+        bump,
+        space = MockDexPool::SPACE,
+    )]
+    pub pool: AccountLoader<'info, MockDexPool>,
+
+    #[account(mut)]
+    pub initializer: Signer<'info>,
+
+    #[account(mut)]
+    pub initializer_token_x_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(mut)]
+    pub initializer_token_y_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        init,
+        payer = initializer,
+        seeds = [b"token_x_vault", pool.key().as_ref()],
+        bump,
+        token::mint = token_x_mint,
+        token::authority = pool,
+    )]
+    pub token_x_vault: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        init,
+        payer = initializer,
+        seeds = [b"token_y_vault", pool.key().as_ref()],
+        bump,
+        token::mint = token_y_mint,
+        token::authority = pool,
+    )]
+    pub token_y_vault: InterfaceAccount<'info, TokenAccount>,
+
+    /// 两个 mint 必须属于同一个代币程序（SPL Token 或 Token-2022）
+    #[account(mint::token_program = token_program)]
+    pub token_x_mint: InterfaceAccount<'info, Mint>,
+    #[account(mint::token_program = token_program)]
+    pub token_y_mint: InterfaceAccount<'info, Mint>,
+
+    /// 池子的 LP 代币，铸币权限归池子 PDA
+    #[account(
+        init,
+        payer = initializer,
+        seeds = [b"lp_mint", pool.key().as_ref()],
+        bump,
+        mint::decimals = LP_DECIMALS,
+        mint::authority = pool,
+    )]
+    pub lp_mint: Box<InterfaceAccount<'info, Mint>>,
+
+    /// 初始化者接收初始 LP 的关联代币账户
+    #[account(
+        init,
+        payer = initializer,
+        associated_token::mint = lp_mint,
+        associated_token::authority = initializer,
+        associated_token::token_program = token_program,
+    )]
+    pub initializer_lp_account: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    /// 协议费 vault，与储备 vault 分开记账
+    #[account(
+        init,
+        payer = initializer,
+        seeds = [b"fee_x_vault", pool.key().as_ref()],
+        bump,
+        token::mint = token_x_mint,
+        token::authority = pool,
+    )]
+    pub fee_x_vault: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        init,
+        payer = initializer,
+        seeds = [b"fee_y_vault", pool.key().as_ref()],
+        bump,
+        token::mint = token_y_mint,
+        token::authority = pool,
+    )]
+    pub fee_y_vault: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    /// 全局池子注册表，只记录池子总数，大小固定
+    #[account(
+        mut,
+        seeds = [b"pool_registry"],
+        bump = registry.bump,
+    )]
+    pub registry: Box<Account<'info, PoolRegistry>>,
+
+    /// 池子的注册表条目，与池子使用相同的 mint 对和手续费档位作为种子
+    #[account(
+        init,
+        payer = initializer,
+        seeds = [b"pool_registry_entry", token_x_mint.key().as_ref(), token_y_mint.key().as_ref(), &fee_bps.to_le_bytes()],
+        bump,
+        space = PoolRegistryEntry::SPACE,
+    )]
+    pub registry_entry: Box<Account<'info, PoolRegistryEntry>>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+
+    /// CHECK: 可选的 Pyth 价格账户，initial_price_check 未给出参考价格时作为参考；数据在 read_pyth_price 中解析校验
+    pub oracle: Option<UncheckedAccount<'info>>,
+}
+
+#[derive(Accounts)]
+pub struct Swap<'info> {
+    #[account(
+        mut,
+        seeds = [
+            b"mock_dex_pool",
+            pool.load()?.token_x_mint.as_ref(),
+            pool.load()?.token_y_mint.as_ref(),
+            &pool.load()?.fee_bps.to_le_bytes(),
+        ],
+        bump = pool.load()?.bump,
+        constraint = pool.load()?.version == POOL_VERSION @ ErrorCode::UnsupportedPoolVersion,
+    )]
+    pub pool: AccountLoader<'info, MockDexPool>,
+
+    /// CHECK: 用户的输入Token账户 (可以是 Token X 或 Token Y)
+    /// 必须是 mut 因为会从中转出Token
+    #[account(mut)]
+    pub token_in_account: InterfaceAccount<'info, TokenAccount>,
+
+    /// DEX 的 Token X Vault，由池子 PDA 推导，与 InitializePool 一致
+    #[account(
+        mut,
+        seeds = [b"token_x_vault", pool.key().as_ref()],
+        bump,
+    )]
+    pub token_x_vault: InterfaceAccount<'info, TokenAccount>,
+    /// DEX 的 Token Y Vault，由池子 PDA 推导，与 InitializePool 一致
+    #[account(
+        mut,
+        seeds = [b"token_y_vault", pool.key().as_ref()],
+        bump,
+    )]
+    pub token_y_vault: InterfaceAccount<'info, TokenAccount>,
+
+    /// 输入代币一侧的协议费 vault，在指令中与池子记录的地址比对
+    #[account(mut)]
+    pub protocol_fee_vault: InterfaceAccount<'info, TokenAccount>,
+
+    /// 用户的输出Token账户，接收成交所得；mint 必须是输入代币的另一侧，在 validate_accounts 中校验
+    /// 只传入输入和输出两个用户账户，未使用一侧的账户不再出现在交易中
+    #[account(mut)]
+    pub token_out_account: InterfaceAccount<'info, TokenAccount>,
+
+    /// Token X / Y 的 mint，transfer_checked 需要
+    #[account(address = pool.load()?.token_x_mint @ ErrorCode::InvalidTokenMint)]
+    pub token_x_mint: Box<InterfaceAccount<'info, Mint>>,
+    #[account(address = pool.load()?.token_y_mint @ ErrorCode::InvalidTokenMint)]
+    pub token_y_mint: Box<InterfaceAccount<'info, Mint>>,
+
+    /// 用户的签名 authority (例如：套利机器人 PDA)
+    /// 这个账户必须签名从 `token_in_account` 到 `DEX Vault` 的转账，
+    /// 可以是输入账户的 owner，也可以是通过 approve 获得授权的 delegate
+    pub user_authority: Signer<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+
+    /// 可选的推荐人代币账户（输入代币），按池子的 referral_fee_share_bps 获得手续费分成
+    #[account(
+        mut,
+        constraint = referrer_token_account.mint == token_in_account.mint @ ErrorCode::InvalidReferrerAccount,
+    )]
+    pub referrer_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    /// CHECK: 可选的 Pyth 价格账户，必须是池子配置的预言机；数据在 read_pyth_price 中解析校验
+    #[account(address = pool.load()?.oracle @ ErrorCode::InvalidOracleAccount)]
+    pub oracle: Option<UncheckedAccount<'info>>,
+
+    /// 可选的池子手续费豁免名单，user_authority 在名单中时按名单费率收费
+    #[account(
+        seeds = [b"fee_allowlist", pool.key().as_ref()],
+        bump = fee_allowlist.bump,
+    )]
+    pub fee_allowlist: Option<Account<'info, FeeAllowlist>>,
+
+    /// 可选的池子交易者名单，池子处于许可模式时必须传入且 user_authority 在名单中
+    #[account(
+        seeds = [b"trader_allowlist", pool.key().as_ref()],
+        bump = trader_allowlist.bump,
+    )]
+    pub trader_allowlist: Option<Account<'info, TraderAllowlist>>,
+}
+
+#[derive(Accounts)]
+pub struct CommitSwap<'info> {
+    #[account(
+        mut,
+        seeds = [
+            b"mock_dex_pool",
+            pool.load()?.token_x_mint.as_ref(),
+            pool.load()?.token_y_mint.as_ref(),
+            &pool.load()?.fee_bps.to_le_bytes(),
+        ],
+        bump = pool.load()?.bump,
+        constraint = pool.load()?.version == POOL_VERSION @ ErrorCode::UnsupportedPoolVersion,
+    )]
+    pub pool: AccountLoader<'info, MockDexPool>,
+
+    #[account(
+        init,
+        payer = owner,
+        seeds = [b"swap_commitment", pool.key().as_ref(), owner.key().as_ref()],
+        bump,
+        space = SwapCommitment::SPACE,
+    )]
+    pub commitment: Account<'info, SwapCommitment>,
+
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RevealSwap<'info> {
+    /// 与 swap 相同的账户，user_authority 必须是承诺的提交者
+    pub swap: Swap<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"swap_commitment", swap.pool.key().as_ref(), owner.key().as_ref()],
+        bump = commitment.bump,
+        has_one = owner,
+        close = owner,
+    )]
+    pub commitment: Account<'info, SwapCommitment>,
+
+    /// 承诺的提交者，接收关闭承诺账户退还的租金
+    #[account(
+        mut,
+        address = swap.user_authority.key() @ ErrorCode::CommitmentMismatch,
+    )]
+    pub owner: SystemAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SwapRoute<'info> {
+    /// 用户的输入代币账户（第一跳的输入）
+    #[account(mut, token::authority = user_authority)]
+    pub user_token_in: InterfaceAccount<'info, TokenAccount>,
+
+    /// 用户的输出代币账户（最后一跳的输出）
+    #[account(mut)]
+    pub user_token_out: InterfaceAccount<'info, TokenAccount>,
+
+    pub user_authority: Signer<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    // remaining_accounts: 每跳 [pool, vault_in, vault_out, fee_vault_in, mint_in, mint_out]
+}
+
+#[derive(Accounts)]
+pub struct MultiPoolSwap<'info> {
+    /// 用户的输入代币账户，所有池子的输入都从这里转出
+    #[account(
+        mut,
+        token::authority = user_authority,
+        constraint = user_token_in.mint == mint_in.key() @ ErrorCode::InvalidTokenInAccount,
+    )]
+    pub user_token_in: InterfaceAccount<'info, TokenAccount>,
+
+    /// 用户的输出代币账户，所有池子的输出都转入这里
+    #[account(
+        mut,
+        constraint = user_token_out.mint == mint_out.key() @ ErrorCode::InvalidTokenMint,
+    )]
+    pub user_token_out: InterfaceAccount<'info, TokenAccount>,
+
+    /// 输入 / 输出代币的 mint，transfer_checked 需要
+    pub mint_in: Box<InterfaceAccount<'info, Mint>>,
+    pub mint_out: Box<InterfaceAccount<'info, Mint>>,
+
+    pub user_authority: Signer<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+
+    /// 可选的推荐人代币账户（输入代币），按各池子的 referral_fee_share_bps 获得手续费分成
+    #[account(
+        mut,
+        constraint = referrer_token_account.mint == mint_in.key() @ ErrorCode::InvalidReferrerAccount,
+    )]
+    pub referrer_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+    // remaining_accounts: 每个池子 [pool, vault_in, vault_out, fee_vault_in, fee_allowlist]
+}
+
+#[derive(Accounts)]
+pub struct BatchSwap<'info> {
+    /// 所有输入账户的签名 authority
+    pub user_authority: Signer<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    // remaining_accounts: 每笔 [pool, vault_in, vault_out, fee_vault_in, mint_in, mint_out, user_token_in, user_token_out, fee_allowlist, referrer]
+}
+
+#[derive(Accounts)]
+pub struct FlashSwap<'info> {
+    #[account(
+        mut,
+        seeds = [
+            b"mock_dex_pool",
+            pool.load()?.token_x_mint.as_ref(),
+            pool.load()?.token_y_mint.as_ref(),
+            &pool.load()?.fee_bps.to_le_bytes(),
+        ],
+        bump = pool.load()?.bump,
+        constraint = pool.load()?.version == POOL_VERSION @ ErrorCode::UnsupportedPoolVersion,
+    )]
+    pub pool: AccountLoader<'info, MockDexPool>,
+
+    #[account(
+        mut,
+        seeds = [b"token_x_vault", pool.key().as_ref()],
+        bump,
+    )]
+    pub token_x_vault: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        seeds = [b"token_y_vault", pool.key().as_ref()],
+        bump,
+    )]
+    pub token_y_vault: InterfaceAccount<'info, TokenAccount>,
+
+    /// 还款代币一侧的协议费 vault，在指令中与池子记录的地址比对
+    #[account(mut)]
+    pub protocol_fee_vault: InterfaceAccount<'info, TokenAccount>,
+
+    /// 接收乐观转出代币的账户
+    #[account(mut)]
+    pub receiver: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(address = pool.load()?.token_x_mint @ ErrorCode::InvalidTokenMint)]
+    pub token_x_mint: Box<InterfaceAccount<'info, Mint>>,
+    #[account(address = pool.load()?.token_y_mint @ ErrorCode::InvalidTokenMint)]
+    pub token_y_mint: Box<InterfaceAccount<'info, Mint>>,
+
+    /// CHECK: 调用方指定的回调程序，只要求可执行；还款结果在指令中按 vault 余额校验
+    #[account(executable)]
+    pub callback_program: UncheckedAccount<'info>,
+
+    pub user_authority: Signer<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+#[instruction(order_id: u64)]
+pub struct PlaceLimitOrder<'info> {
+    #[account(
+        mut,
+        seeds = [
+            b"mock_dex_pool",
+            pool.load()?.token_x_mint.as_ref(),
+            pool.load()?.token_y_mint.as_ref(),
+            &pool.load()?.fee_bps.to_le_bytes(),
+        ],
+        bump = pool.load()?.bump,
+        constraint = pool.load()?.version == POOL_VERSION @ ErrorCode::UnsupportedPoolVersion,
+    )]
+    pub pool: AccountLoader<'info, MockDexPool>,
+
+    #[account(
+        init,
+        payer = owner,
+        seeds = [b"limit_order", pool.key().as_ref(), owner.key().as_ref(), &order_id.to_le_bytes()],
+        bump,
+        space = LimitOrder::SPACE,
+    )]
+    pub order: Account<'info, LimitOrder>,
+
+    /// 订单托管账户，由订单 PDA 持有
+    #[account(
+        init,
+        payer = owner,
+        seeds = [b"order_escrow", order.key().as_ref()],
+        bump,
+        token::mint = mint_in,
+        token::authority = order,
+        token::token_program = token_program,
+    )]
+    pub escrow: InterfaceAccount<'info, TokenAccount>,
+
+    /// 卖出代币的 mint，必须是池子的 X 或 Y，决定订单方向
+    #[account(
+        constraint = mint_in.key() == pool.load()?.token_x_mint || mint_in.key() == pool.load()?.token_y_mint
+            @ ErrorCode::InvalidTokenMint,
+    )]
+    pub mint_in: Box<InterfaceAccount<'info, Mint>>,
+
+    #[account(
+        mut,
+        token::mint = mint_in,
+        token::authority = owner,
+    )]
+    pub owner_token_in: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CancelOrder<'info> {
+    #[account(
+        mut,
+        seeds = [
+            b"mock_dex_pool",
+            pool.load()?.token_x_mint.as_ref(),
+            pool.load()?.token_y_mint.as_ref(),
+            &pool.load()?.fee_bps.to_le_bytes(),
+        ],
+        bump = pool.load()?.bump,
+        constraint = pool.load()?.version == POOL_VERSION @ ErrorCode::UnsupportedPoolVersion,
+    )]
+    pub pool: AccountLoader<'info, MockDexPool>,
+
+    #[account(
+        mut,
+        seeds = [b"limit_order", pool.key().as_ref(), owner.key().as_ref(), &order.order_id.to_le_bytes()],
+        bump = order.bump,
+        has_one = pool,
+        has_one = owner @ ErrorCode::InvalidOrderOwner,
+        close = owner,
+    )]
+    pub order: Account<'info, LimitOrder>,
+
+    #[account(
+        mut,
+        seeds = [b"order_escrow", order.key().as_ref()],
+        bump = order.escrow_bump,
+    )]
+    pub escrow: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(address = order.mint_in @ ErrorCode::InvalidTokenMint)]
+    pub mint_in: Box<InterfaceAccount<'info, Mint>>,
+
+    #[account(
+        mut,
+        token::mint = mint_in,
+    )]
+    pub owner_token_in: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct FillOrder<'info> {
+    #[account(
+        mut,
+        seeds = [
+            b"mock_dex_pool",
+            pool.load()?.token_x_mint.as_ref(),
+            pool.load()?.token_y_mint.as_ref(),
+            &pool.load()?.fee_bps.to_le_bytes(),
+        ],
+        bump = pool.load()?.bump,
+        constraint = pool.load()?.version == POOL_VERSION @ ErrorCode::UnsupportedPoolVersion,
+        has_one = token_x_vault @ ErrorCode::InvalidVault,
+        has_one = token_y_vault @ ErrorCode::InvalidVault,
+    )]
+    pub pool: AccountLoader<'info, MockDexPool>,
+
+    #[account(
+        mut,
+        seeds = [b"limit_order", pool.key().as_ref(), owner.key().as_ref(), &order.order_id.to_le_bytes()],
+        bump = order.bump,
+        has_one = pool,
+        has_one = owner @ ErrorCode::InvalidOrderOwner,
+        close = owner,
+    )]
+    pub order: Account<'info, LimitOrder>,
+
+    #[account(
+        mut,
+        seeds = [b"order_escrow", order.key().as_ref()],
+        bump = order.escrow_bump,
+    )]
+    pub escrow: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub token_x_vault: InterfaceAccount<'info, TokenAccount>,
+    #[account(mut)]
+    pub token_y_vault: InterfaceAccount<'info, TokenAccount>,
+
+    /// 卖出代币一侧的协议费 vault，在指令中与池子记录的地址比对
+    #[account(mut)]
+    pub protocol_fee_vault: InterfaceAccount<'info, TokenAccount>,
+
+    /// 订单 owner 接收买入代币的账户
+    #[account(
+        mut,
+        token::authority = owner,
+    )]
+    pub owner_token_out: InterfaceAccount<'info, TokenAccount>,
+
+    /// Token X / Y 的 mint，transfer_checked 需要
+    #[account(address = pool.load()?.token_x_mint @ ErrorCode::InvalidTokenMint)]
+    pub token_x_mint: Box<InterfaceAccount<'info, Mint>>,
+    #[account(address = pool.load()?.token_y_mint @ ErrorCode::InvalidTokenMint)]
+    pub token_y_mint: Box<InterfaceAccount<'info, Mint>>,
+
+    /// CHECK: 订单 owner，只接收关闭订单和托管账户后的租金，由 has_one 校验
+    #[account(mut)]
+    pub owner: UncheckedAccount<'info>,
+
+    pub keeper: Signer<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+#[instruction(fee_bps: u16)]
+pub struct InitializeTriPool<'info> {
+    #[account(
+        init,
+        payer = initializer,
+        seeds = [
+            b"tri_pool",
+            mint_0.key().as_ref(),
+            mint_1.key().as_ref(),
+            mint_2.key().as_ref(),
+            &fee_bps.to_le_bytes(),
+        ],
+        bump,
+        space = TriPool::SPACE,
+    )]
+    pub pool: Box<Account<'info, TriPool>>,
+
+    /// 三个 mint 必须属于同一个代币程序
+    #[account(mint::token_program = token_program)]
+    pub mint_0: Box<InterfaceAccount<'info, Mint>>,
+    #[account(mint::token_program = token_program)]
+    pub mint_1: Box<InterfaceAccount<'info, Mint>>,
+    #[account(mint::token_program = token_program)]
+    pub mint_2: Box<InterfaceAccount<'info, Mint>>,
+
+    #[account(
+        init,
+        payer = initializer,
+        seeds = [b"tri_vault", pool.key().as_ref(), &[0u8]],
+        bump,
+        token::mint = mint_0,
+        token::authority = pool,
+    )]
+    pub vault_0: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        init,
+        payer = initializer,
+        seeds = [b"tri_vault", pool.key().as_ref(), &[1u8]],
+        bump,
+        token::mint = mint_1,
+        token::authority = pool,
+    )]
+    pub vault_1: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        init,
+        payer = initializer,
+        seeds = [b"tri_vault", pool.key().as_ref(), &[2u8]],
+        bump,
+        token::mint = mint_2,
+        token::authority = pool,
+    )]
+    pub vault_2: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(mut, token::mint = mint_0, token::authority = initializer)]
+    pub initializer_token_0: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(mut, token::mint = mint_1, token::authority = initializer)]
+    pub initializer_token_1: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(mut, token::mint = mint_2, token::authority = initializer)]
+    pub initializer_token_2: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(mut)]
+    pub initializer: Signer<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct TriPoolSwap<'info> {
+    #[account(
+        mut,
+        seeds = [
+            b"tri_pool",
+            pool.mints[0].as_ref(),
+            pool.mints[1].as_ref(),
+            pool.mints[2].as_ref(),
+            &pool.fee_bps.to_le_bytes(),
+        ],
+        bump = pool.bump,
+    )]
+    pub pool: Account<'info, TriPool>,
+
+    /// 输入 / 输出代币的 vault，在指令中按 index 与池子记录比对
+    #[account(mut)]
+    pub vault_in: InterfaceAccount<'info, TokenAccount>,
+    #[account(mut)]
+    pub vault_out: InterfaceAccount<'info, TokenAccount>,
+
+    pub mint_in: Box<InterfaceAccount<'info, Mint>>,
+    pub mint_out: Box<InterfaceAccount<'info, Mint>>,
+
+    #[account(mut, token::mint = mint_in)]
+    pub user_token_in: InterfaceAccount<'info, TokenAccount>,
+    #[account(mut, token::mint = mint_out)]
+    pub user_token_out: InterfaceAccount<'info, TokenAccount>,
+
+    pub user_authority: Signer<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct QuoteSwap<'info> {
+    #[account(
+        seeds = [
+            b"mock_dex_pool",
+            pool.load()?.token_x_mint.as_ref(),
+            pool.load()?.token_y_mint.as_ref(),
+            &pool.load()?.fee_bps.to_le_bytes(),
+        ],
+        bump = pool.load()?.bump,
+        constraint = pool.load()?.version == POOL_VERSION @ ErrorCode::UnsupportedPoolVersion,
+    )]
+    pub pool: AccountLoader<'info, MockDexPool>,
+}
+
+#[derive(Accounts)]
+pub struct CollectProtocolFees<'info> {
+    #[account(
+        mut,
+        seeds = [
+            b"mock_dex_pool",
+            pool.load()?.token_x_mint.as_ref(),
+            pool.load()?.token_y_mint.as_ref(),
+            &pool.load()?.fee_bps.to_le_bytes(),
+        ],
+        bump = pool.load()?.bump,
+        constraint = pool.load()?.version == POOL_VERSION @ ErrorCode::UnsupportedPoolVersion,
+        has_one = admin @ ErrorCode::InvalidPoolAuthority,
+        has_one = fee_x_vault,
+        has_one = fee_y_vault,
+    )]
+    pub pool: AccountLoader<'info, MockDexPool>,
+
+    #[account(mut)]
+    pub fee_x_vault: InterfaceAccount<'info, TokenAccount>,
+    #[account(mut)]
+    pub fee_y_vault: InterfaceAccount<'info, TokenAccount>,
+
+    /// Token X / Y 的 mint，transfer_checked 需要
+    #[account(address = pool.load()?.token_x_mint @ ErrorCode::InvalidTokenMint)]
+    pub token_x_mint: Box<InterfaceAccount<'info, Mint>>,
+    #[account(address = pool.load()?.token_y_mint @ ErrorCode::InvalidTokenMint)]
+    pub token_y_mint: Box<InterfaceAccount<'info, Mint>>,
+
+    /// 协议费只能转入 fee_recipient 名下的账户
+    #[account(
+        mut,
+        token::mint = fee_x_vault.mint,
+        token::authority = pool.load()?.fee_recipient,
+    )]
+    pub destination_x: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        token::mint = fee_y_vault.mint,
+        token::authority = pool.load()?.fee_recipient,
+    )]
+    pub destination_y: InterfaceAccount<'info, TokenAccount>,
+
+    pub admin: Signer<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct SyncReserves<'info> {
+    #[account(
+        mut,
+        seeds = [
+            b"mock_dex_pool",
+            pool.load()?.token_x_mint.as_ref(),
+            pool.load()?.token_y_mint.as_ref(),
+            &pool.load()?.fee_bps.to_le_bytes(),
+        ],
+        bump = pool.load()?.bump,
+        constraint = pool.load()?.version == POOL_VERSION @ ErrorCode::UnsupportedPoolVersion,
+        has_one = token_x_vault @ ErrorCode::InvalidVault,
+        has_one = token_y_vault @ ErrorCode::InvalidVault,
+    )]
+    pub pool: AccountLoader<'info, MockDexPool>,
+
+    pub token_x_vault: InterfaceAccount<'info, TokenAccount>,
+    pub token_y_vault: InterfaceAccount<'info, TokenAccount>,
+
+    pub caller: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct Skim<'info> {
+    #[account(
+        mut,
+        seeds = [
+            b"mock_dex_pool",
+            pool.load()?.token_x_mint.as_ref(),
+            pool.load()?.token_y_mint.as_ref(),
+            &pool.load()?.fee_bps.to_le_bytes(),
+        ],
+        bump = pool.load()?.bump,
+        constraint = pool.load()?.version == POOL_VERSION @ ErrorCode::UnsupportedPoolVersion,
+        has_one = admin @ ErrorCode::InvalidPoolAuthority,
+        has_one = token_x_vault @ ErrorCode::InvalidVault,
+        has_one = token_y_vault @ ErrorCode::InvalidVault,
+    )]
+    pub pool: AccountLoader<'info, MockDexPool>,
+
+    #[account(mut)]
+    pub token_x_vault: InterfaceAccount<'info, TokenAccount>,
+    #[account(mut)]
+    pub token_y_vault: InterfaceAccount<'info, TokenAccount>,
+
+    /// Token X / Y 的 mint，transfer_checked 需要
+    #[account(address = pool.load()?.token_x_mint @ ErrorCode::InvalidTokenMint)]
+    pub token_x_mint: Box<InterfaceAccount<'info, Mint>>,
+    #[account(address = pool.load()?.token_y_mint @ ErrorCode::InvalidTokenMint)]
+    pub token_y_mint: Box<InterfaceAccount<'info, Mint>>,
+
+    #[account(
+        mut,
+        token::mint = token_x_vault.mint,
+    )]
+    pub destination_x: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        token::mint = token_y_vault.mint,
+    )]
+    pub destination_y: InterfaceAccount<'info, TokenAccount>,
+
+    pub admin: Signer<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct Rebalance<'info> {
+    #[account(
+        mut,
+        seeds = [
+            b"mock_dex_pool",
+            pool.load()?.token_x_mint.as_ref(),
+            pool.load()?.token_y_mint.as_ref(),
+            &pool.load()?.fee_bps.to_le_bytes(),
+        ],
+        bump = pool.load()?.bump,
+        constraint = pool.load()?.version == POOL_VERSION @ ErrorCode::UnsupportedPoolVersion,
+        has_one = admin @ ErrorCode::InvalidPoolAuthority,
+        has_one = token_x_vault @ ErrorCode::InvalidVault,
+        has_one = token_y_vault @ ErrorCode::InvalidVault,
+    )]
+    pub pool: AccountLoader<'info, MockDexPool>,
+
+    #[account(mut)]
+    pub token_x_vault: InterfaceAccount<'info, TokenAccount>,
+    #[account(mut)]
+    pub token_y_vault: InterfaceAccount<'info, TokenAccount>,
+
+    /// Token X / Y 的 mint，transfer_checked 需要
+    #[account(address = pool.load()?.token_x_mint @ ErrorCode::InvalidTokenMint)]
+    pub token_x_mint: Box<InterfaceAccount<'info, Mint>>,
+    #[account(address = pool.load()?.token_y_mint @ ErrorCode::InvalidTokenMint)]
+    pub token_y_mint: Box<InterfaceAccount<'info, Mint>>,
+
+    #[account(
+        mut,
+        token::mint = token_x_mint,
+        token::authority = admin,
+    )]
+    pub admin_token_x: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        token::mint = token_y_mint,
+        token::authority = admin,
+    )]
+    pub admin_token_y: InterfaceAccount<'info, TokenAccount>,
+
+    pub admin: Signer<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+impl<'info> Rebalance<'info> {
+    /// 把一侧储备从 current 移动到 target，返回按 vault 实际变化计算的新储备
+    fn move_reserve(&self, is_x: bool, current: u64, target: u64, signer_seeds: &[&[&[u8]]]) -> Result<u64> {
+        let (vault, admin_token_account, mint) = if is_x {
+            (&self.token_x_vault, &self.admin_token_x, &self.token_x_mint)
+        } else {
+            (&self.token_y_vault, &self.admin_token_y, &self.token_y_mint)
+        };
+        move_pool_reserve(
+            ReserveTransferAccounts {
+                token_program: &self.token_program,
+                pool: self.pool.to_account_info(),
+                admin: self.admin.to_account_info(),
+                vault,
+                admin_token_account,
+                mint,
+            },
+            current,
+            target,
+            signer_seeds,
+        )
+    }
+}
+
+/// 测试专用的 set_reserves 账户，与 Rebalance 相同，单独声明以便 cpi feature 生成对应的账户类型
+#[cfg(feature = "test-utils")]
+#[derive(Accounts)]
+pub struct SetReserves<'info> {
+    #[account(
+        mut,
+        seeds = [
+            b"mock_dex_pool",
+            pool.load()?.token_x_mint.as_ref(),
+            pool.load()?.token_y_mint.as_ref(),
+            &pool.load()?.fee_bps.to_le_bytes(),
+        ],
+        bump = pool.load()?.bump,
+        constraint = pool.load()?.version == POOL_VERSION @ ErrorCode::UnsupportedPoolVersion,
+        has_one = admin @ ErrorCode::InvalidPoolAuthority,
+        has_one = token_x_vault @ ErrorCode::InvalidVault,
+        has_one = token_y_vault @ ErrorCode::InvalidVault,
+    )]
+    pub pool: AccountLoader<'info, MockDexPool>,
+
+    #[account(mut)]
+    pub token_x_vault: InterfaceAccount<'info, TokenAccount>,
+    #[account(mut)]
+    pub token_y_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(address = pool.load()?.token_x_mint @ ErrorCode::InvalidTokenMint)]
+    pub token_x_mint: Box<InterfaceAccount<'info, Mint>>,
+    #[account(address = pool.load()?.token_y_mint @ ErrorCode::InvalidTokenMint)]
+    pub token_y_mint: Box<InterfaceAccount<'info, Mint>>,
+
+    #[account(
+        mut,
+        token::mint = token_x_mint,
+        token::authority = admin,
+    )]
+    pub admin_token_x: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        token::mint = token_y_mint,
+        token::authority = admin,
+    )]
+    pub admin_token_y: InterfaceAccount<'info, TokenAccount>,
+
+    pub admin: Signer<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[cfg(feature = "test-utils")]
+impl<'info> SetReserves<'info> {
+    /// 把一侧储备从 current 移动到 target，返回按 vault 实际变化计算的新储备
+    fn move_reserve(&self, is_x: bool, current: u64, target: u64, signer_seeds: &[&[&[u8]]]) -> Result<u64> {
+        let (vault, admin_token_account, mint) = if is_x {
+            (&self.token_x_vault, &self.admin_token_x, &self.token_x_mint)
+        } else {
+            (&self.token_y_vault, &self.admin_token_y, &self.token_y_mint)
+        };
+        move_pool_reserve(
+            ReserveTransferAccounts {
+                token_program: &self.token_program,
+                pool: self.pool.to_account_info(),
+                admin: self.admin.to_account_info(),
+                vault,
+                admin_token_account,
+                mint,
+            },
+            current,
+            target,
+            signer_seeds,
+        )
+    }
+}
+
+/// move_pool_reserve 涉及的一侧储备账户
+struct ReserveTransferAccounts<'a, 'info> {
+    token_program: &'a Interface<'info, TokenInterface>,
+    pool: AccountInfo<'info>,
+    admin: AccountInfo<'info>,
+    vault: &'a InterfaceAccount<'info, TokenAccount>,
+    admin_token_account: &'a InterfaceAccount<'info, TokenAccount>,
+    mint: &'a InterfaceAccount<'info, Mint>,
+}
+
+/// 在 admin 与池子 vault 之间转移差额，把一侧储备从 current 移动到 target
+/// 补入时按 vault 实际到账记账，取出时由池子 PDA 签名
+fn move_pool_reserve(
+    accounts: ReserveTransferAccounts,
+    current: u64,
+    target: u64,
+    signer_seeds: &[&[&[u8]]],
+) -> Result<u64> {
+    let ReserveTransferAccounts { token_program, pool, admin, vault, admin_token_account, mint } = accounts;
+    if target > current {
+        // admin 补入差额，按 vault 实际到账记账
+        let balance_before = token_balance(&vault.to_account_info())?;
+        token_interface::transfer_checked(
+            CpiContext::new(
+                token_program.to_account_info(),
+                TransferChecked {
+                    from: admin_token_account.to_account_info(),
+                    mint: mint.to_account_info(),
+                    to: vault.to_account_info(),
+                    authority: admin,
+                },
+            ),
+            target - current,
+            mint.decimals,
+        )?;
+        let received = token_balance(&vault.to_account_info())?
+            .checked_sub(balance_before)
+            .ok_or(ErrorCode::Underflow)?;
+        Ok(current.checked_add(received).ok_or(ErrorCode::Overflow)?)
+    } else {
+        if target < current {
+            token_interface::transfer_checked(
+                CpiContext::new_with_signer(
+                    token_program.to_account_info(),
+                    TransferChecked {
+                        from: vault.to_account_info(),
+                        mint: mint.to_account_info(),
+                        to: admin_token_account.to_account_info(),
+                        authority: pool,
+                    },
+                    signer_seeds,
+                ),
+                current - target,
+                mint.decimals,
+            )?;
+        }
+        Ok(target)
+    }
+}
+
+#[derive(Accounts)]
+pub struct SetPoolPaused<'info> {
+    #[account(
+        mut,
+        seeds = [
+            b"mock_dex_pool",
+            pool.load()?.token_x_mint.as_ref(),
+            pool.load()?.token_y_mint.as_ref(),
+            &pool.load()?.fee_bps.to_le_bytes(),
+        ],
+        bump = pool.load()?.bump,
+        constraint = pool.load()?.version == POOL_VERSION @ ErrorCode::UnsupportedPoolVersion,
+        has_one = admin @ ErrorCode::InvalidPoolAuthority,
+    )]
+    pub pool: AccountLoader<'info, MockDexPool>,
+
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ProposeAdmin<'info> {
+    #[account(
+        mut,
+        seeds = [
+            b"mock_dex_pool",
+            pool.load()?.token_x_mint.as_ref(),
+            pool.load()?.token_y_mint.as_ref(),
+            &pool.load()?.fee_bps.to_le_bytes(),
+        ],
+        bump = pool.load()?.bump,
+        constraint = pool.load()?.version == POOL_VERSION @ ErrorCode::UnsupportedPoolVersion,
+        has_one = admin @ ErrorCode::InvalidPoolAuthority,
+    )]
+    pub pool: AccountLoader<'info, MockDexPool>,
+
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct AcceptAdmin<'info> {
+    #[account(
+        mut,
+        seeds = [
+            b"mock_dex_pool",
+            pool.load()?.token_x_mint.as_ref(),
+            pool.load()?.token_y_mint.as_ref(),
+            &pool.load()?.fee_bps.to_le_bytes(),
+        ],
+        bump = pool.load()?.bump,
+        constraint = pool.load()?.version == POOL_VERSION @ ErrorCode::UnsupportedPoolVersion,
+        constraint = pool.load()?.pending_admin != Pubkey::default() @ ErrorCode::NoPendingAdmin,
+        constraint = pool.load()?.pending_admin == pending_admin.key() @ ErrorCode::InvalidPoolAuthority,
+    )]
+    pub pool: AccountLoader<'info, MockDexPool>,
+
+    pub pending_admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetFeeRecipient<'info> {
+    #[account(
+        mut,
+        seeds = [
+            b"mock_dex_pool",
+            pool.load()?.token_x_mint.as_ref(),
+            pool.load()?.token_y_mint.as_ref(),
+            &pool.load()?.fee_bps.to_le_bytes(),
+        ],
+        bump = pool.load()?.bump,
+        constraint = pool.load()?.version == POOL_VERSION @ ErrorCode::UnsupportedPoolVersion,
+        has_one = admin @ ErrorCode::InvalidPoolAuthority,
+    )]
+    pub pool: AccountLoader<'info, MockDexPool>,
+
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct RenamePool<'info> {
+    #[account(
+        mut,
+        seeds = [
+            b"mock_dex_pool",
+            pool.load()?.token_x_mint.as_ref(),
+            pool.load()?.token_y_mint.as_ref(),
+            &pool.load()?.fee_bps.to_le_bytes(),
+        ],
+        bump = pool.load()?.bump,
+        constraint = pool.load()?.version == POOL_VERSION @ ErrorCode::UnsupportedPoolVersion,
+        has_one = admin @ ErrorCode::InvalidPoolAuthority,
+    )]
+    pub pool: AccountLoader<'info, MockDexPool>,
+
+    /// 注册表条目为定长，名称长度不超过 MAX_POOL_NAME_LEN 时无需扩容
+    #[account(
+        mut,
+        seeds = [
+            b"pool_registry_entry",
+            registry_entry.token_x_mint.as_ref(),
+            registry_entry.token_y_mint.as_ref(),
+            &registry_entry.fee_bps.to_le_bytes(),
+        ],
+        bump = registry_entry.bump,
+        constraint = registry_entry.pool == pool.key() @ ErrorCode::PoolNotRegistered,
+    )]
+    pub registry_entry: Box<Account<'info, PoolRegistryEntry>>,
+
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetOracleGuard<'info> {
+    #[account(
+        mut,
+        seeds = [
+            b"mock_dex_pool",
+            pool.load()?.token_x_mint.as_ref(),
+            pool.load()?.token_y_mint.as_ref(),
+            &pool.load()?.fee_bps.to_le_bytes(),
+        ],
+        bump = pool.load()?.bump,
+        constraint = pool.load()?.version == POOL_VERSION @ ErrorCode::UnsupportedPoolVersion,
+        has_one = admin @ ErrorCode::InvalidPoolAuthority,
+    )]
+    pub pool: AccountLoader<'info, MockDexPool>,
+
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetProtocolFeeShare<'info> {
+    #[account(
+        mut,
+        seeds = [
+            b"mock_dex_pool",
+            pool.load()?.token_x_mint.as_ref(),
+            pool.load()?.token_y_mint.as_ref(),
+            &pool.load()?.fee_bps.to_le_bytes(),
+        ],
+        bump = pool.load()?.bump,
+        constraint = pool.load()?.version == POOL_VERSION @ ErrorCode::UnsupportedPoolVersion,
+        has_one = admin @ ErrorCode::InvalidPoolAuthority,
+    )]
+    pub pool: AccountLoader<'info, MockDexPool>,
+
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetReferralFeeShare<'info> {
+    #[account(
+        mut,
+        seeds = [
+            b"mock_dex_pool",
+            pool.load()?.token_x_mint.as_ref(),
+            pool.load()?.token_y_mint.as_ref(),
+            &pool.load()?.fee_bps.to_le_bytes(),
+        ],
+        bump = pool.load()?.bump,
+        constraint = pool.load()?.version == POOL_VERSION @ ErrorCode::UnsupportedPoolVersion,
+        has_one = admin @ ErrorCode::InvalidPoolAuthority,
+    )]
+    pub pool: AccountLoader<'info, MockDexPool>,
+
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetMaxPriceImpact<'info> {
+    #[account(
+        mut,
+        seeds = [
+            b"mock_dex_pool",
+            pool.load()?.token_x_mint.as_ref(),
+            pool.load()?.token_y_mint.as_ref(),
+            &pool.load()?.fee_bps.to_le_bytes(),
+        ],
+        bump = pool.load()?.bump,
+        constraint = pool.load()?.version == POOL_VERSION @ ErrorCode::UnsupportedPoolVersion,
+        has_one = admin @ ErrorCode::InvalidPoolAuthority,
+    )]
+    pub pool: AccountLoader<'info, MockDexPool>,
+
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeFeeAllowlist<'info> {
+    #[account(
+        seeds = [
+            b"mock_dex_pool",
+            pool.load()?.token_x_mint.as_ref(),
+            pool.load()?.token_y_mint.as_ref(),
+            &pool.load()?.fee_bps.to_le_bytes(),
+        ],
+        bump = pool.load()?.bump,
+        constraint = pool.load()?.version == POOL_VERSION @ ErrorCode::UnsupportedPoolVersion,
+        has_one = admin @ ErrorCode::InvalidPoolAuthority,
+    )]
+    pub pool: AccountLoader<'info, MockDexPool>,
+
+    #[account(
+        init,
+        payer = admin,
+        seeds = [b"fee_allowlist", pool.key().as_ref()],
+        bump,
+        space = FeeAllowlist::SPACE,
+    )]
+    pub fee_allowlist: Account<'info, FeeAllowlist>,
+
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SetFeeExemption<'info> {
+    #[account(
+        mut,
+        seeds = [
+            b"mock_dex_pool",
+            pool.load()?.token_x_mint.as_ref(),
+            pool.load()?.token_y_mint.as_ref(),
+            &pool.load()?.fee_bps.to_le_bytes(),
+        ],
+        bump = pool.load()?.bump,
+        constraint = pool.load()?.version == POOL_VERSION @ ErrorCode::UnsupportedPoolVersion,
+        has_one = admin @ ErrorCode::InvalidPoolAuthority,
+    )]
+    pub pool: AccountLoader<'info, MockDexPool>,
+
+    #[account(
+        mut,
+        seeds = [b"fee_allowlist", pool.key().as_ref()],
+        bump = fee_allowlist.bump,
+    )]
+    pub fee_allowlist: Account<'info, FeeAllowlist>,
+
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetCircuitBreaker<'info> {
+    #[account(
+        mut,
+        seeds = [
+            b"mock_dex_pool",
+            pool.load()?.token_x_mint.as_ref(),
+            pool.load()?.token_y_mint.as_ref(),
+            &pool.load()?.fee_bps.to_le_bytes(),
+        ],
+        bump = pool.load()?.bump,
+        constraint = pool.load()?.version == POOL_VERSION @ ErrorCode::UnsupportedPoolVersion,
+        has_one = admin @ ErrorCode::InvalidPoolAuthority,
+    )]
+    pub pool: AccountLoader<'info, MockDexPool>,
+
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeTraderAllowlist<'info> {
+    #[account(
+        seeds = [
+            b"mock_dex_pool",
+            pool.load()?.token_x_mint.as_ref(),
+            pool.load()?.token_y_mint.as_ref(),
+            &pool.load()?.fee_bps.to_le_bytes(),
+        ],
+        bump = pool.load()?.bump,
+        constraint = pool.load()?.version == POOL_VERSION @ ErrorCode::UnsupportedPoolVersion,
+        has_one = admin @ ErrorCode::InvalidPoolAuthority,
+    )]
+    pub pool: AccountLoader<'info, MockDexPool>,
+
+    #[account(
+        init,
+        payer = admin,
+        seeds = [b"trader_allowlist", pool.key().as_ref()],
+        bump,
+        space = TraderAllowlist::SPACE,
+    )]
+    pub trader_allowlist: Account<'info, TraderAllowlist>,
+
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SetAllowedTrader<'info> {
+    #[account(
+        mut,
+        seeds = [
+            b"mock_dex_pool",
+            pool.load()?.token_x_mint.as_ref(),
+            pool.load()?.token_y_mint.as_ref(),
+            &pool.load()?.fee_bps.to_le_bytes(),
+        ],
+        bump = pool.load()?.bump,
+        constraint = pool.load()?.version == POOL_VERSION @ ErrorCode::UnsupportedPoolVersion,
+        has_one = admin @ ErrorCode::InvalidPoolAuthority,
+    )]
+    pub pool: AccountLoader<'info, MockDexPool>,
+
+    #[account(
+        mut,
+        seeds = [b"trader_allowlist", pool.key().as_ref()],
+        bump = trader_allowlist.bump,
+    )]
+    pub trader_allowlist: Account<'info, TraderAllowlist>,
+
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetPermissioned<'info> {
+    #[account(
+        mut,
+        seeds = [
+            b"mock_dex_pool",
+            pool.load()?.token_x_mint.as_ref(),
+            pool.load()?.token_y_mint.as_ref(),
+            &pool.load()?.fee_bps.to_le_bytes(),
+        ],
+        bump = pool.load()?.bump,
+        constraint = pool.load()?.version == POOL_VERSION @ ErrorCode::UnsupportedPoolVersion,
+        has_one = admin @ ErrorCode::InvalidPoolAuthority,
+    )]
+    pub pool: AccountLoader<'info, MockDexPool>,
+
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetFeeDiscountWindow<'info> {
+    #[account(
+        mut,
+        seeds = [
+            b"mock_dex_pool",
+            pool.load()?.token_x_mint.as_ref(),
+            pool.load()?.token_y_mint.as_ref(),
+            &pool.load()?.fee_bps.to_le_bytes(),
+        ],
+        bump = pool.load()?.bump,
+        constraint = pool.load()?.version == POOL_VERSION @ ErrorCode::UnsupportedPoolVersion,
+        has_one = admin @ ErrorCode::InvalidPoolAuthority,
+    )]
+    pub pool: AccountLoader<'info, MockDexPool>,
+
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetSwapDirections<'info> {
+    #[account(
+        mut,
+        seeds = [
+            b"mock_dex_pool",
+            pool.load()?.token_x_mint.as_ref(),
+            pool.load()?.token_y_mint.as_ref(),
+            &pool.load()?.fee_bps.to_le_bytes(),
+        ],
+        bump = pool.load()?.bump,
+        constraint = pool.load()?.version == POOL_VERSION @ ErrorCode::UnsupportedPoolVersion,
+        has_one = admin @ ErrorCode::InvalidPoolAuthority,
+    )]
+    pub pool: AccountLoader<'info, MockDexPool>,
+
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct AddLiquidity<'info> {
+    #[account(
+        mut,
+        seeds = [
+            b"mock_dex_pool",
+            pool.load()?.token_x_mint.as_ref(),
+            pool.load()?.token_y_mint.as_ref(),
+            &pool.load()?.fee_bps.to_le_bytes(),
+        ],
+        bump = pool.load()?.bump,
+        constraint = pool.load()?.version == POOL_VERSION @ ErrorCode::UnsupportedPoolVersion,
+        has_one = lp_mint,
+    )]
+    pub pool: AccountLoader<'info, MockDexPool>,
+
+    #[account(
+        mut,
+        seeds = [b"token_x_vault", pool.key().as_ref()],
+        bump,
+    )]
+    pub token_x_vault: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        seeds = [b"token_y_vault", pool.key().as_ref()],
+        bump,
+    )]
+    pub token_y_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub lp_mint: InterfaceAccount<'info, Mint>,
+
+    /// Token X / Y 的 mint，transfer_checked 需要
+    #[account(address = pool.load()?.token_x_mint @ ErrorCode::InvalidTokenMint)]
+    pub token_x_mint: Box<InterfaceAccount<'info, Mint>>,
+    #[account(address = pool.load()?.token_y_mint @ ErrorCode::InvalidTokenMint)]
+    pub token_y_mint: Box<InterfaceAccount<'info, Mint>>,
+
+    #[account(
+        mut,
+        token::mint = token_x_vault.mint,
+        token::authority = provider,
+    )]
+    pub provider_token_x: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        token::mint = token_y_vault.mint,
+        token::authority = provider,
+    )]
+    pub provider_token_y: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        token::mint = lp_mint,
+    )]
+    pub provider_lp_account: InterfaceAccount<'info, TokenAccount>,
+
+    pub provider: Signer<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+impl<'info> AddLiquidity<'info> {
+    /// 从 provider 向一侧 vault 转入 amount，按 vault 余额变化返回实际到账数量；amount 为 0 时不转账
+    fn deposit(&self, is_x: bool, amount: u64) -> Result<u64> {
+        if amount == 0 {
+            return Ok(0);
+        }
+        let (from, mint, vault) = if is_x {
+            (&self.provider_token_x, &self.token_x_mint, &self.token_x_vault)
+        } else {
+            (&self.provider_token_y, &self.token_y_mint, &self.token_y_vault)
+        };
+
+        let balance_before = token_balance(&vault.to_account_info())?;
+        token_interface::transfer_checked(
+            CpiContext::new(
+                self.token_program.to_account_info(),
+                TransferChecked {
+                    from: from.to_account_info(),
+                    mint: mint.to_account_info(),
+                    to: vault.to_account_info(),
+                    authority: self.provider.to_account_info(),
+                },
+            ),
+            amount,
+            mint.decimals,
+        )?;
+        Ok(token_balance(&vault.to_account_info())?
+            .checked_sub(balance_before)
+            .ok_or(ErrorCode::Underflow)?)
+    }
+}
+
+#[derive(Accounts)]
+pub struct RemoveLiquidity<'info> {
+    #[account(
+        mut,
+        seeds = [
+            b"mock_dex_pool",
+            pool.load()?.token_x_mint.as_ref(),
+            pool.load()?.token_y_mint.as_ref(),
+            &pool.load()?.fee_bps.to_le_bytes(),
+        ],
+        bump = pool.load()?.bump,
+        constraint = pool.load()?.version == POOL_VERSION @ ErrorCode::UnsupportedPoolVersion,
+        has_one = lp_mint,
+    )]
+    pub pool: AccountLoader<'info, MockDexPool>,
+
+    #[account(
+        mut,
+        seeds = [b"token_x_vault", pool.key().as_ref()],
+        bump,
+    )]
+    pub token_x_vault: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        seeds = [b"token_y_vault", pool.key().as_ref()],
+        bump,
+    )]
+    pub token_y_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub lp_mint: InterfaceAccount<'info, Mint>,
+
+    /// Token X / Y 的 mint，transfer_checked 需要
+    #[account(address = pool.load()?.token_x_mint @ ErrorCode::InvalidTokenMint)]
+    pub token_x_mint: Box<InterfaceAccount<'info, Mint>>,
+    #[account(address = pool.load()?.token_y_mint @ ErrorCode::InvalidTokenMint)]
+    pub token_y_mint: Box<InterfaceAccount<'info, Mint>>,
+
+    #[account(
+        mut,
+        token::mint = token_x_vault.mint,
+    )]
+    pub provider_token_x: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        token::mint = token_y_vault.mint,
+    )]
+    pub provider_token_y: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        token::mint = lp_mint,
+        token::authority = provider,
+    )]
+    pub provider_lp_account: InterfaceAccount<'info, TokenAccount>,
+
+    pub provider: Signer<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeRewarder<'info> {
+    #[account(
+        mut,
+        seeds = [
+            b"mock_dex_pool",
+            pool.load()?.token_x_mint.as_ref(),
+            pool.load()?.token_y_mint.as_ref(),
+            &pool.load()?.fee_bps.to_le_bytes(),
+        ],
+        bump = pool.load()?.bump,
+        constraint = pool.load()?.version == POOL_VERSION @ ErrorCode::UnsupportedPoolVersion,
+        has_one = admin @ ErrorCode::InvalidPoolAuthority,
+        has_one = lp_mint,
+    )]
+    pub pool: AccountLoader<'info, MockDexPool>,
+
+    #[account(
+        init,
+        payer = admin,
+        seeds = [b"rewarder", pool.key().as_ref()],
+        bump,
+        space = Rewarder::SPACE,
+    )]
+    pub rewarder: Account<'info, Rewarder>,
+
+    /// 奖励代币 vault，由 rewarder PDA 持有
+    #[account(
+        init,
+        payer = admin,
+        seeds = [b"reward_vault", rewarder.key().as_ref()],
+        bump,
+        token::mint = reward_mint,
+        token::authority = rewarder,
+        token::token_program = token_program,
+    )]
+    pub reward_vault: InterfaceAccount<'info, TokenAccount>,
+
+    /// 质押 LP 的托管 vault，由 rewarder PDA 持有
+    #[account(
+        init,
+        payer = admin,
+        seeds = [b"staked_lp_vault", rewarder.key().as_ref()],
+        bump,
+        token::mint = lp_mint,
+        token::authority = rewarder,
+        token::token_program = token_program,
+    )]
+    pub staked_lp_vault: InterfaceAccount<'info, TokenAccount>,
+
+    pub lp_mint: Box<InterfaceAccount<'info, Mint>>,
+    pub reward_mint: Box<InterfaceAccount<'info, Mint>>,
+
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct FundRewards<'info> {
+    #[account(
+        mut,
+        seeds = [
+            b"mock_dex_pool",
+            pool.load()?.token_x_mint.as_ref(),
+            pool.load()?.token_y_mint.as_ref(),
+            &pool.load()?.fee_bps.to_le_bytes(),
+        ],
+        bump = pool.load()?.bump,
+        constraint = pool.load()?.version == POOL_VERSION @ ErrorCode::UnsupportedPoolVersion,
+        has_one = admin @ ErrorCode::InvalidPoolAuthority,
+    )]
+    pub pool: AccountLoader<'info, MockDexPool>,
+
+    #[account(
+        mut,
+        seeds = [b"rewarder", pool.key().as_ref()],
+        bump = rewarder.bump,
+    )]
+    pub rewarder: Account<'info, Rewarder>,
+
+    #[account(
+        mut,
+        seeds = [b"reward_vault", rewarder.key().as_ref()],
+        bump,
+    )]
+    pub reward_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(address = rewarder.reward_mint @ ErrorCode::InvalidTokenMint)]
+    pub reward_mint: Box<InterfaceAccount<'info, Mint>>,
+
+    #[account(
+        mut,
+        token::mint = reward_mint,
+        token::authority = admin,
+    )]
+    pub admin_reward_account: InterfaceAccount<'info, TokenAccount>,
+
+    pub admin: Signer<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct SetEmissionRate<'info> {
+    #[account(
+        mut,
+        seeds = [
+            b"mock_dex_pool",
+            pool.load()?.token_x_mint.as_ref(),
+            pool.load()?.token_y_mint.as_ref(),
+            &pool.load()?.fee_bps.to_le_bytes(),
+        ],
+        bump = pool.load()?.bump,
+        constraint = pool.load()?.version == POOL_VERSION @ ErrorCode::UnsupportedPoolVersion,
+        has_one = admin @ ErrorCode::InvalidPoolAuthority,
+    )]
+    pub pool: AccountLoader<'info, MockDexPool>,
+
+    #[account(
+        mut,
+        seeds = [b"rewarder", pool.key().as_ref()],
+        bump = rewarder.bump,
+    )]
+    pub rewarder: Account<'info, Rewarder>,
+
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct OpenLpStake<'info> {
+    #[account(
+        seeds = [b"rewarder", rewarder.pool.as_ref()],
+        bump = rewarder.bump,
+    )]
+    pub rewarder: Account<'info, Rewarder>,
+
+    #[account(
+        init,
+        payer = owner,
+        seeds = [b"lp_stake", rewarder.key().as_ref(), owner.key().as_ref()],
+        bump,
+        space = LpStake::SPACE,
+    )]
+    pub lp_stake: Account<'info, LpStake>,
+
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+/// stake_lp 与 unstake_lp 共用
+#[derive(Accounts)]
+pub struct StakeLp<'info> {
+    #[account(
+        mut,
+        seeds = [
+            b"mock_dex_pool",
+            pool.load()?.token_x_mint.as_ref(),
+            pool.load()?.token_y_mint.as_ref(),
+            &pool.load()?.fee_bps.to_le_bytes(),
+        ],
+        bump = pool.load()?.bump,
+        constraint = pool.load()?.version == POOL_VERSION @ ErrorCode::UnsupportedPoolVersion,
+    )]
+    pub pool: AccountLoader<'info, MockDexPool>,
+
+    #[account(
+        mut,
+        seeds = [b"rewarder", pool.key().as_ref()],
+        bump = rewarder.bump,
+    )]
+    pub rewarder: Account<'info, Rewarder>,
+
+    #[account(
+        mut,
+        seeds = [b"lp_stake", rewarder.key().as_ref(), owner.key().as_ref()],
+        bump = lp_stake.bump,
+        has_one = owner,
+    )]
+    pub lp_stake: Account<'info, LpStake>,
+
+    #[account(
+        mut,
+        seeds = [b"staked_lp_vault", rewarder.key().as_ref()],
+        bump,
+    )]
+    pub staked_lp_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(address = rewarder.lp_mint @ ErrorCode::InvalidTokenMint)]
+    pub lp_mint: Box<InterfaceAccount<'info, Mint>>,
+
+    #[account(
+        mut,
+        token::mint = lp_mint,
+        token::authority = owner,
+    )]
+    pub owner_lp_account: InterfaceAccount<'info, TokenAccount>,
+
+    pub owner: Signer<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimRewards<'info> {
+    #[account(
+        mut,
+        seeds = [
+            b"mock_dex_pool",
+            pool.load()?.token_x_mint.as_ref(),
+            pool.load()?.token_y_mint.as_ref(),
+            &pool.load()?.fee_bps.to_le_bytes(),
+        ],
+        bump = pool.load()?.bump,
+        constraint = pool.load()?.version == POOL_VERSION @ ErrorCode::UnsupportedPoolVersion,
+    )]
+    pub pool: AccountLoader<'info, MockDexPool>,
+
+    #[account(
+        mut,
+        seeds = [b"rewarder", pool.key().as_ref()],
+        bump = rewarder.bump,
+    )]
+    pub rewarder: Account<'info, Rewarder>,
+
+    #[account(
+        mut,
+        seeds = [b"lp_stake", rewarder.key().as_ref(), owner.key().as_ref()],
+        bump = lp_stake.bump,
+        has_one = owner,
+    )]
+    pub lp_stake: Account<'info, LpStake>,
+
+    #[account(
+        mut,
+        seeds = [b"reward_vault", rewarder.key().as_ref()],
+        bump,
+    )]
+    pub reward_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(address = rewarder.reward_mint @ ErrorCode::InvalidTokenMint)]
+    pub reward_mint: Box<InterfaceAccount<'info, Mint>>,
+
+    #[account(
+        mut,
+        token::mint = reward_mint,
+    )]
+    pub owner_reward_account: InterfaceAccount<'info, TokenAccount>,
+
+    pub owner: Signer<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct GetTwap<'info> {
+    #[account(
+        seeds = [
+            b"mock_dex_pool",
+            pool.load()?.token_x_mint.as_ref(),
+            pool.load()?.token_y_mint.as_ref(),
+            &pool.load()?.fee_bps.to_le_bytes(),
+        ],
+        bump = pool.load()?.bump,
+        constraint = pool.load()?.version == POOL_VERSION @ ErrorCode::UnsupportedPoolVersion,
+    )]
+    pub pool: AccountLoader<'info, MockDexPool>,
+}
+
+#[derive(Accounts)]
+pub struct GetPoolPrice<'info> {
+    #[account(
+        seeds = [
+            b"mock_dex_pool",
+            pool.load()?.token_x_mint.as_ref(),
+            pool.load()?.token_y_mint.as_ref(),
+            &pool.load()?.fee_bps.to_le_bytes(),
+        ],
+        bump = pool.load()?.bump,
+        constraint = pool.load()?.version == POOL_VERSION @ ErrorCode::UnsupportedPoolVersion,
+    )]
+    pub pool: AccountLoader<'info, MockDexPool>,
+
+    /// 可选的价格观测缓冲区，传入时用最近一次观测计算 TWAP
+    #[account(
+        seeds = [b"price_observations", pool.key().as_ref()],
+        bump = observations.bump,
+        has_one = pool,
+    )]
+    pub observations: Option<Account<'info, PriceObservations>>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeObservations<'info> {
+    #[account(
+        seeds = [
+            b"mock_dex_pool",
+            pool.load()?.token_x_mint.as_ref(),
+            pool.load()?.token_y_mint.as_ref(),
+            &pool.load()?.fee_bps.to_le_bytes(),
+        ],
+        bump = pool.load()?.bump,
+        constraint = pool.load()?.version == POOL_VERSION @ ErrorCode::UnsupportedPoolVersion,
+    )]
+    pub pool: AccountLoader<'info, MockDexPool>,
+
+    #[account(
+        init,
+        payer = payer,
+        seeds = [b"price_observations", pool.key().as_ref()],
+        bump,
+        space = PriceObservations::SPACE,
+    )]
+    pub observations: Account<'info, PriceObservations>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CrankObservation<'info> {
+    #[account(
+        mut,
+        seeds = [
+            b"mock_dex_pool",
+            pool.load()?.token_x_mint.as_ref(),
+            pool.load()?.token_y_mint.as_ref(),
+            &pool.load()?.fee_bps.to_le_bytes(),
+        ],
+        bump = pool.load()?.bump,
+        constraint = pool.load()?.version == POOL_VERSION @ ErrorCode::UnsupportedPoolVersion,
+    )]
+    pub pool: AccountLoader<'info, MockDexPool>,
+
+    #[account(
+        mut,
+        seeds = [b"price_observations", pool.key().as_ref()],
+        bump = observations.bump,
+        has_one = pool,
+    )]
+    pub observations: Account<'info, PriceObservations>,
+}
+
+#[derive(Accounts)]
+#[instruction(day: u64)]
+pub struct SnapshotPoolStats<'info> {
+    #[account(
+        mut,
+        seeds = [
+            b"mock_dex_pool",
+            pool.load()?.token_x_mint.as_ref(),
+            pool.load()?.token_y_mint.as_ref(),
+            &pool.load()?.fee_bps.to_le_bytes(),
+        ],
+        bump = pool.load()?.bump,
+        constraint = pool.load()?.version == POOL_VERSION @ ErrorCode::UnsupportedPoolVersion,
+    )]
+    pub pool: AccountLoader<'info, MockDexPool>,
+
+    /// 当日快照，同一天重复调用会因账户已存在而失败
+    #[account(
+        init,
+        payer = payer,
+        seeds = [b"pool_stats_snapshot", pool.key().as_ref(), &day.to_le_bytes()],
+        bump,
+        space = PoolStatsSnapshot::SPACE,
+    )]
+    pub snapshot: Account<'info, PoolStatsSnapshot>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeRanges<'info> {
+    #[account(
+        mut,
+        seeds = [
+            b"mock_dex_pool",
+            pool.load()?.token_x_mint.as_ref(),
+            pool.load()?.token_y_mint.as_ref(),
+            &pool.load()?.fee_bps.to_le_bytes(),
+        ],
+        bump = pool.load()?.bump,
+        constraint = pool.load()?.version == POOL_VERSION @ ErrorCode::UnsupportedPoolVersion,
+        has_one = admin @ ErrorCode::InvalidPoolAuthority,
+        constraint = pool.load()?.curve() == CurveType::Concentrated @ ErrorCode::UnsupportedCurveOperation,
+    )]
+    pub pool: AccountLoader<'info, MockDexPool>,
+
+    #[account(
+        init,
+        payer = admin,
+        seeds = [b"liquidity_ranges", pool.key().as_ref()],
+        bump,
+        space = LiquidityRanges::SPACE,
+    )]
+    pub ranges: Account<'info, LiquidityRanges>,
+
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct AllocateRangeLiquidity<'info> {
+    #[account(
+        mut,
+        seeds = [
+            b"mock_dex_pool",
+            pool.load()?.token_x_mint.as_ref(),
+            pool.load()?.token_y_mint.as_ref(),
+            &pool.load()?.fee_bps.to_le_bytes(),
+        ],
+        bump = pool.load()?.bump,
+        constraint = pool.load()?.version == POOL_VERSION @ ErrorCode::UnsupportedPoolVersion,
+        has_one = admin @ ErrorCode::InvalidPoolAuthority,
+    )]
+    pub pool: AccountLoader<'info, MockDexPool>,
+
+    #[account(
+        mut,
+        seeds = [b"liquidity_ranges", pool.key().as_ref()],
+        bump = ranges.bump,
+        has_one = pool @ ErrorCode::InvalidLiquidityRanges,
+    )]
+    pub ranges: Account<'info, LiquidityRanges>,
+
+    pub admin: Signer<'info>,
+}
+
+#[cfg(not(feature = "mainnet"))]
+#[derive(Accounts)]
+pub struct AssertInvariants<'info> {
+    #[account(
+        seeds = [
+            b"mock_dex_pool",
+            pool.load()?.token_x_mint.as_ref(),
+            pool.load()?.token_y_mint.as_ref(),
+            &pool.load()?.fee_bps.to_le_bytes(),
+        ],
+        bump,
+    )]
+    pub pool: AccountLoader<'info, MockDexPool>,
+
+    #[account(
+        seeds = [b"token_x_vault", pool.key().as_ref()],
+        bump,
+    )]
+    pub token_x_vault: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        seeds = [b"token_y_vault", pool.key().as_ref()],
+        bump,
+    )]
+    pub token_y_vault: InterfaceAccount<'info, TokenAccount>,
+}
+
+/// 池子状态使用 zero-copy 布局，指令通过 AccountLoader 直接读写账户数据，省去 Borsh 编解码
+/// 字段按对齐要求从大到小排列（u128、u64、Pubkey、u16、u8、字节数组），repr(C) 下不产生隐式填充
+#[account(zero_copy)]
+pub struct MockDexPool {
+    pub price_cumulative: u128, // 现货价格按 slot 的累计值，用于计算 TWAP
+    pub cumulative_volume_x: u128, // 累计成交的 Token X 数量（买入与卖出合计）
+    pub cumulative_volume_y: u128, // 累计成交的 Token Y 数量（买入与卖出合计）
+    pub cumulative_fees_x: u128, // 卖出 X 时收取的累计手续费（Token X 计）
+    pub cumulative_fees_y: u128, // 卖出 Y 时收取的累计手续费（Token Y 计）
+    pub x_balance: u64,
+    pub y_balance: u64,
+    pub last_update_slot: u64, // 上次累计价格的 slot
+    pub swap_sequence: u64, // 最近一次兑换的序号，随 SwapExecuted 事件发出
+    pub swap_count: u64, // 累计成交笔数，含闪电兑换与限价单成交
+    pub breaker_slot: u64, // outflow 统计所在的 slot
+    pub slot_outflow_x: u64, // breaker_slot 内从 X 储备流出的累计数量
+    pub slot_outflow_y: u64, // breaker_slot 内从 Y 储备流出的累计数量
+    pub event_nonce: u64, // 已发出的池子事件数，每个事件带上递增后的值，供索引器检测漏收的事件
+    pub curve_amp: u64, // StableSwap 的放大系数，其余曲线为 0
+    pub lp_mint: Pubkey, // 池子的 LP 代币
+    pub admin: Pubkey, // 池子管理员
+    pub fee_x_vault: Pubkey, // Token X 协议费 vault
+    pub fee_y_vault: Pubkey, // Token Y 协议费 vault
+    pub token_x_mint: Pubkey, // Token X 的 mint
+    pub token_y_mint: Pubkey, // Token Y 的 mint
+    pub token_x_vault: Pubkey, // Token X 储备 vault
+    pub token_y_vault: Pubkey, // Token Y 储备 vault
+    pub oracle: Pubkey, // Pyth 价格账户，报价为 1 个 X 可兑换的 Y 数量
+    pub fee_recipient: Pubkey, // 协议费接收人，collect_protocol_fees 只能转入其名下的账户
+    pub pending_admin: Pubkey, // propose_admin 提名的新 admin，接受前没有任何权限；默认值表示无提名
+    pub protocol_fee_share_bps: u16, // 手续费中归协议的比例 (bps)
+    pub fee_bps: u16, // 交易手续费 (bps)
+    pub max_price_impact_bps: u16, // 单笔交易允许的最大价格影响 (bps)
+    pub referral_fee_share_bps: u16, // 带推荐人的兑换中，手续费分给推荐人的比例 (bps)
+    pub max_oracle_deviation_bps: u16, // 成交价相对预言机价格的最大偏离 (bps)，0 表示不启用
+    pub max_reserve_move_bps: u16, // 单个 slot 内每侧储备最多可流出的比例 (bps)，0 表示不启用熔断
+    pub curve_weight_x_bps: u16, // 加权池 X 侧权重，其余曲线为 0
+    pub curve_weight_y_bps: u16, // 加权池 Y 侧权重，其余曲线为 0
+    pub bump: u8,
+    pub curve_kind: u8, // 定价曲线类型，见 CurveType::kind；通过 curve() 读取完整的曲线参数
+    pub paused: u8, // 非 0 表示暂停，暂停期间拒绝兑换和流动性操作
+    pub x_to_y_enabled: u8, // 非 0 表示允许卖出 X 换 Y
+    pub y_to_x_enabled: u8, // 非 0 表示允许卖出 Y 换 X
+    pub x_decimals: u8, // Token X 精度，初始化时从 mint 读取
+    pub y_decimals: u8, // Token Y 精度，初始化时从 mint 读取
+    pub version: u8, // 账户布局版本，见 POOL_VERSION
+    pub permissioned: u8, // 非 0 表示许可模式，只有交易者名单中的账户可以兑换
+    pub name: [u8; MAX_POOL_NAME_LEN], // 池子的展示名称，UTF-8，不足部分补 0；通过 name() 读取
+    pub x_symbol: [u8; MAX_SYMBOL_LEN], // Token X 符号，不足部分补 0
+    pub y_symbol: [u8; MAX_SYMBOL_LEN], // Token Y 符号，不足部分补 0
+    pub padding: [u8; 3], // 使账户大小为 16 的倍数，u128 字段才能保持对齐
+    pub fee_discounts: [FeeDiscountWindow; MAX_FEE_DISCOUNT_WINDOWS], // 手续费折扣时间窗口，从 reserved 划出，起始偏移仍按 8 字节对齐
+    pub reserved: [u8; POOL_RESERVED_BYTES], // 预留给后续字段，当前全部为 0
+}
+
+/// 手续费折扣时间窗口：[start_ts, end_ts) 内兑换按 fee_bps 收费，end_ts 为 0 表示空槽位
+#[zero_copy]
+pub struct FeeDiscountWindow {
+    pub start_ts: i64,
+    pub end_ts: i64,
+    pub fee_bps: u16, // 窗口内的手续费 (bps)，低于池子手续费
+    pub padding: [u8; 6],
+}
+
+impl FeeDiscountWindow {
+    pub const EMPTY: FeeDiscountWindow = FeeDiscountWindow {
+        start_ts: 0,
+        end_ts: 0,
+        fee_bps: 0,
+        padding: [0; 6],
+    };
+
+    pub fn is_active(&self, now: i64) -> bool {
+        self.end_ts != 0 && self.start_ts <= now && now < self.end_ts
+    }
+}
+
+impl MockDexPool {
+    pub const SPACE: usize = 8 + // discriminator
+        std::mem::size_of::<MockDexPool>();
+
+    /// 池子的展示名称
+    pub fn name(&self) -> String {
+        padded_bytes_to_string(&self.name)
+    }
+
+    /// 写入展示名称，调用方负责校验长度不超过 MAX_POOL_NAME_LEN
+    pub fn set_name(&mut self, name: &str) {
+        self.name = [0; MAX_POOL_NAME_LEN];
+        self.name[..name.len()].copy_from_slice(name.as_bytes());
+    }
+
+    /// Token X 符号
+    pub fn x_symbol(&self) -> String {
+        padded_bytes_to_string(&self.x_symbol)
+    }
+
+    /// Token Y 符号
+    pub fn y_symbol(&self) -> String {
+        padded_bytes_to_string(&self.y_symbol)
+    }
+
+    /// 写入两侧代币符号，调用方负责校验长度不超过 MAX_SYMBOL_LEN
+    pub fn set_symbols(&mut self, x_symbol: &str, y_symbol: &str) {
+        self.x_symbol = [0; MAX_SYMBOL_LEN];
+        self.x_symbol[..x_symbol.len()].copy_from_slice(x_symbol.as_bytes());
+        self.y_symbol = [0; MAX_SYMBOL_LEN];
+        self.y_symbol[..y_symbol.len()].copy_from_slice(y_symbol.as_bytes());
+    }
+
+    /// 池子的定价曲线
+    pub fn curve(&self) -> CurveType {
+        CurveType::from_parts(self.curve_kind, self.curve_amp, self.curve_weight_x_bps, self.curve_weight_y_bps)
+    }
+
+    /// 写入定价曲线
+    pub fn set_curve(&mut self, curve: CurveType) {
+        let (kind, amp, weight_x_bps, weight_y_bps) = curve.to_parts();
+        self.curve_kind = kind;
+        self.curve_amp = amp;
+        self.curve_weight_x_bps = weight_x_bps;
+        self.curve_weight_y_bps = weight_y_bps;
+    }
+
+    /// 池子是否处于暂停状态
+    pub fn is_paused(&self) -> bool {
+        self.paused != 0
+    }
+
+    /// 按兑换方向返回 (输入储备, 输出储备)
+    pub fn reserves(&self, input_is_x: bool) -> (u64, u64) {
+        if input_is_x {
+            (self.x_balance, self.y_balance)
+        } else {
+            (self.y_balance, self.x_balance)
+        }
+    }
+
+    /// 累计一笔成交的交易量与手续费，fee_amount 以输入代币计
+    pub fn record_swap(&mut self, input_is_x: bool, amount_in: u64, amount_out: u64, fee_amount: u64) -> Result<()> {
+        let (volume_in, volume_out, fees_in) = if input_is_x {
+            (&mut self.cumulative_volume_x, &mut self.cumulative_volume_y, &mut self.cumulative_fees_x)
+        } else {
+            (&mut self.cumulative_volume_y, &mut self.cumulative_volume_x, &mut self.cumulative_fees_y)
+        };
+        *volume_in = volume_in.checked_add(amount_in as u128).ok_or(ErrorCode::Overflow)?;
+        *volume_out = volume_out.checked_add(amount_out as u128).ok_or(ErrorCode::Overflow)?;
+        *fees_in = fees_in.checked_add(fee_amount as u128).ok_or(ErrorCode::Overflow)?;
+        self.swap_count = self.swap_count.checked_add(1).ok_or(ErrorCode::Overflow)?;
+        Ok(())
+    }
+
+    /// now 时刻适用的手续费：取池子手续费与所有生效中折扣窗口费率的最小值
+    pub fn current_fee_bps(&self, now: i64) -> u16 {
+        self.fee_discounts
+            .iter()
+            .filter(|window| window.is_active(now))
+            .map(|window| window.fee_bps)
+            .fold(self.fee_bps, u16::min)
+    }
+
+    /// 许可模式下要求 trader 在交易者名单中；未开启许可模式时直接放行
+    pub fn ensure_trader_allowed(&self, trader: &Pubkey, allowlist: Option<&TraderAllowlist>) -> Result<()> {
+        if self.permissioned == 0 {
+            return Ok(());
+        }
+        require!(
+            allowlist.is_some_and(|allowlist| allowlist.is_allowed(trader)),
+            ErrorCode::TraderNotAllowed
+        );
+        Ok(())
+    }
+
+    /// 对应方向被 admin 关闭时拒绝兑换
+    pub fn ensure_direction_enabled(&self, input_is_x: bool) -> Result<()> {
+        let enabled = if input_is_x { self.x_to_y_enabled } else { self.y_to_x_enabled };
+        require!(enabled != 0, ErrorCode::SwapDirectionDisabled);
+        Ok(())
+    }
+
+    /// 熔断检查：在储备更新之后调用，累计本 slot 内输出侧的流出量
+    /// 以 当前储备 + 本 slot 累计流出 近似 slot 开始时的储备，流出超过其 max_reserve_move_bps 即拒绝
+    pub fn check_circuit_breaker(&mut self, slot: u64, input_is_x: bool, amount_out: u64) -> Result<()> {
+        if self.max_reserve_move_bps == 0 {
+            return Ok(());
+        }
+        if slot != self.breaker_slot {
+            self.breaker_slot = slot;
+            self.slot_outflow_x = 0;
+            self.slot_outflow_y = 0;
+        }
+
+        let (outflow, reserve_out) = if input_is_x {
+            (&mut self.slot_outflow_y, self.y_balance)
+        } else {
+            (&mut self.slot_outflow_x, self.x_balance)
+        };
+        *outflow = outflow.checked_add(amount_out).ok_or(ErrorCode::Overflow)?;
+
+        let reserve_at_slot_start = (reserve_out as u128) + (*outflow as u128);
+        require!(
+            (*outflow as u128) * 10_000 <= reserve_at_slot_start * self.max_reserve_move_bps as u128,
+            ErrorCode::CircuitBreakerTripped
+        );
+        Ok(())
+    }
+
+    /// 分配下一个兑换序号
+    pub fn next_swap_sequence(&mut self) -> Result<u64> {
+        self.swap_sequence = self.swap_sequence.checked_add(1).ok_or(ErrorCode::Overflow)?;
+        Ok(self.swap_sequence)
+    }
+
+    /// 递增并返回池子的事件序号，每次 emit 池子相关事件前调用一次，保证序号连续
+    pub fn next_event_nonce(&mut self) -> Result<u64> {
+        self.event_nonce = self.event_nonce.checked_add(1).ok_or(ErrorCode::Overflow)?;
+        Ok(self.event_nonce)
+    }
+
+    /// X、Y 精度差对应的换算系数 (factor_x, factor_y)，精度较低的一侧为 10^差值，另一侧为 1
+    /// 原始数量乘以各自系数后即处于同一精度，可以直接比较
+    pub fn decimal_factors(&self) -> Result<(u128, u128)> {
+        let pow = |exp: u8| 10u128.checked_pow(exp as u32).ok_or(ErrorCode::Overflow);
+        if self.x_decimals >= self.y_decimals {
+            Ok((1, pow(self.x_decimals - self.y_decimals)?))
+        } else {
+            Ok((pow(self.y_decimals - self.x_decimals)?, 1))
+        }
+    }
+
+    /// 按精度换算后的价格 (1 个 X 可兑换的 Y 数量 * PRICE_SCALE)
+    /// amount_x / amount_y 为原始数量（或按权重调整后的数量）
+    pub fn normalized_price(&self, amount_x: u128, amount_y: u128) -> Result<u128> {
+        require!(amount_x > 0, ErrorCode::InsufficientLiquidity);
+        let (factor_x, factor_y) = self.decimal_factors()?;
+        let numerator = amount_y
+            .checked_mul(factor_y)
+            .and_then(|v| v.checked_mul(PRICE_SCALE as u128))
+            .ok_or(ErrorCode::Overflow)?;
+        let denominator = amount_x.checked_mul(factor_x).ok_or(ErrorCode::Overflow)?;
+        Ok(numerator / denominator)
+    }
+
+    /// 当前现货价格 (1 个 X 可兑换的 Y 数量 * PRICE_SCALE，按精度换算)
+    pub fn spot_price(&self) -> Result<u128> {
+        require!(self.x_balance > 0, ErrorCode::InsufficientLiquidity);
+        let x = self.x_balance as u128;
+        let y = self.y_balance as u128;
+        match self.curve() {
+            // 加权池的边际价格为 (y / wy) / (x / wx)
+            CurveType::Weighted { weight_x_bps, weight_y_bps } => self.normalized_price(
+                x * weight_y_bps as u128,
+                y * weight_x_bps as u128,
+            ),
+            // 其余曲线以储备比例近似
+            _ => self.normalized_price(x, y),
+        }
+    }
+
+    /// 成交价 (1 个 X 可兑换的 Y 数量 * PRICE_SCALE，按精度换算)，含手续费
+    pub fn execution_price(&self, amount_in: u64, amount_out: u64, input_is_x: bool) -> Result<u128> {
+        let (amount_x, amount_y) = if input_is_x { (amount_in, amount_out) } else { (amount_out, amount_in) };
+        require!(amount_x > 0, ErrorCode::InvalidAmount);
+        self.normalized_price(amount_x as u128, amount_y as u128)
+    }
+
+    /// 根据成交前后的储备比例计算价格影响（基点），价格按精度换算后比较
+    pub fn price_impact_bps(&self, input_is_x: bool, amount_to_reserve: u64, amount_out: u64) -> Result<u64> {
+        let (reserve_in, reserve_out) = self.reserves(input_is_x);
+        if reserve_in == 0 || reserve_out == 0 {
+            return Ok(0);
+        }
+        let new_reserve_in = reserve_in as u128 + amount_to_reserve as u128;
+        let new_reserve_out = (reserve_out as u128).saturating_sub(amount_out as u128);
+        let (price_before, price_after) = if input_is_x {
+            (
+                self.normalized_price(reserve_in as u128, reserve_out as u128)?,
+                self.normalized_price(new_reserve_in, new_reserve_out)?,
+            )
+        } else {
+            // 储备 X 被取空时价格为无穷大，视为价格影响无上限
+            if new_reserve_out == 0 {
+                return Ok(u64::MAX);
+            }
+            (
+                self.normalized_price(reserve_out as u128, reserve_in as u128)?,
+                self.normalized_price(new_reserve_out, new_reserve_in)?,
+            )
+        };
+
+        if price_before == 0 {
+            return Ok(0);
+        }
+        let impact = price_before
+            .abs_diff(price_after)
+            .checked_mul(10_000)
+            .ok_or(ErrorCode::Overflow)?
+            / price_before;
+        Ok(impact.min(u64::MAX as u128) as u64)
+    }
+
+    /// 按整币价格 price_num / price_den（每 1 个输入代币可兑换的输出代币数量）
+    /// 换算 amount_in 对应的原始输出数量，向上取整
+    pub fn amount_at_price(
+        &self,
+        amount_in: u64,
+        price_num: u128,
+        price_den: u128,
+        input_is_x: bool,
+    ) -> Result<u64> {
+        require!(price_den > 0, ErrorCode::InvalidPriceLimit);
+        let (factor_x, factor_y) = self.decimal_factors()?;
+        let (factor_in, factor_out) = if input_is_x { (factor_x, factor_y) } else { (factor_y, factor_x) };
+        // 原始输出 = amount_in * factor_in * price / factor_out
+        let numerator = (amount_in as u128)
+            .checked_mul(price_num)
+            .and_then(|v| v.checked_mul(factor_in))
+            .ok_or(ErrorCode::Overflow)?;
+        let denominator = price_den.checked_mul(factor_out).ok_or(ErrorCode::Overflow)?;
+        u64::try_from(numerator.div_ceil(denominator)).map_err(|_| error!(ErrorCode::Overflow))
+    }
+
+    /// 计算到 slot 为止的价格累计值（不修改状态）
+    pub fn price_cumulative_at(&self, slot: u64) -> Result<u128> {
+        let elapsed = slot.saturating_sub(self.last_update_slot) as u128;
+        if elapsed == 0 {
+            return Ok(self.price_cumulative);
+        }
+        let accrued = self
+            .spot_price()?
+            .checked_mul(elapsed)
+            .ok_or(ErrorCode::Overflow)?;
+        // 累计值允许回绕，TWAP 只使用两次观测之间的差值
+        Ok(self.price_cumulative.wrapping_add(accrued))
+    }
+
+    /// 把上次更新以来的价格累计进 price_cumulative
+    pub fn accumulate_price(&mut self, slot: u64) -> Result<()> {
+        self.price_cumulative = self.price_cumulative_at(slot)?;
+        self.last_update_slot = slot;
+        Ok(())
+    }
+
+    /// 恒定乘积池中，使兑换后现货价格恰好到达 limit_price 的最大输入数量
+    /// 计算时忽略留在储备中的手续费，结果偏保守：按该数量成交后价格不会越过限价
+    pub fn max_amount_in_for_price_limit(&self, input_is_x: bool, limit_price: u64) -> Result<u64> {
+        require!(limit_price > 0, ErrorCode::InvalidPriceLimit);
+        let k = (self.x_balance as u128) * (self.y_balance as u128);
+        // 限价按精度换算，对应的原始储备比例 y / x = limit * factor_x / (PRICE_SCALE * factor_y)
+        let (factor_x, factor_y) = self.decimal_factors()?;
+        let limit = (limit_price as u128).checked_mul(factor_x).ok_or(ErrorCode::Overflow)?;
+        let scale = (PRICE_SCALE as u128).checked_mul(factor_y).ok_or(ErrorCode::Overflow)?;
+
+        let (target_reserve_in, reserve_in) = if input_is_x {
+            // 卖出 X 使价格下降：x' = sqrt(k / limit)
+            (integer_sqrt(k.checked_mul(scale).ok_or(ErrorCode::Overflow)? / limit), self.x_balance)
+        } else {
+            // 卖出 Y 使价格上升：y' = sqrt(k * limit)
+            (integer_sqrt(k.checked_mul(limit).ok_or(ErrorCode::Overflow)? / scale), self.y_balance)
+        };
+        Ok(u64::try_from(target_reserve_in.saturating_sub(reserve_in as u128)).unwrap_or(u64::MAX))
+    }
+
+    /// 定价只读取记录的储备；vault 实际余额低于记录值说明记账已与真实资产脱节，拒绝交易
+    /// 多出的捐赠余额不参与定价，可通过 skim 移走或 sync_reserves 计入
+    pub fn assert_vaults_cover_reserves(&self, vault_x_amount: u64, vault_y_amount: u64) -> Result<()> {
+        require!(
+            vault_x_amount >= self.x_balance && vault_y_amount >= self.y_balance,
+            ErrorCode::VaultBelowReserves
+        );
+        Ok(())
+    }
+}
+
+/// 全局池子注册表，只记录已登记的池子数量；各池子的登记信息在各自的 PoolRegistryEntry 中
+#[account]
+pub struct PoolRegistry {
+    pub pool_count: u64,
+    pub bump: u8,
+}
+
+impl PoolRegistry {
+    pub const SPACE: usize = 8 + // discriminator
+        8 + // pool_count
+        1; // bump
+}
+
+/// 单个池子的注册表条目，每个池子一个 PDA，种子为 [b"pool_registry_entry", token_x_mint, token_y_mint, fee_bps]
+/// 链下可按判别符筛选 getProgramAccounts 列出全部池子，也可按 mint 对和手续费档位直接推导地址
+#[account]
+#[derive(PartialEq, Eq, Debug)]
+pub struct PoolRegistryEntry {
+    pub pool: Pubkey,
+    pub name: String, // 池子的展示名称，最长 MAX_POOL_NAME_LEN 字节
+    pub token_x_mint: Pubkey,
+    pub token_y_mint: Pubkey,
+    pub token_x_vault: Pubkey,
+    pub token_y_vault: Pubkey,
+    pub fee_bps: u16,
+    pub bump: u8,
+}
+
+impl PoolRegistryEntry {
+    pub const SPACE: usize = 8 + // discriminator
+        32 + // pool
+        4 + MAX_POOL_NAME_LEN + // name
+        32 + // token_x_mint
+        32 + // token_y_mint
+        32 + // token_x_vault
+        32 + // token_y_vault
+        2 + // fee_bps
+        1; // bump
+}
+
+/// initialize_pool 的池子配置
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq, Debug)]
+pub struct InitializePoolConfig {
+    pub curve: CurveType, // 定价曲线类型
+    pub x_symbol: String, // Token X 的符号，如 "SOL"
+    pub y_symbol: String, // Token Y 的符号，如 "USDC"
+    pub initial_price_check: Option<InitialPriceCheck>, // 可选：初始价格必须接近参考价格或预言机价格
+}
+
+/// initialize_pool 的初始价格校验参数，价格均为按精度换算后 1 个 X 可兑换的 Y 数量 * PRICE_SCALE
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct InitialPriceCheck {
+    pub reference_price: u64, // 参考价格，0 表示改用传入的 oracle 账户的价格
+    pub max_deviation_bps: u16, // 初始价格相对参考价格的最大偏离
+}
+
+/// get_pool_price 通过 return data 返回的价格，均为按精度换算后 1 个 X 可兑换的 Y 数量 * PRICE_SCALE
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct PoolPrice {
+    pub spot_price: u128, // 当前储备推出的现货价格
+    pub twap: u64, // 最近一次观测到当前 slot 的 TWAP，无可用观测时为 0
+    pub twap_window_slots: u64, // TWAP 覆盖的 slot 数，0 表示 twap 不可用
+}
+
+/// swap 通过 return data 返回的成交结果
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct SwapResult {
+    pub amount_out: u64, // 用户得到的输出数量
+    pub fee_paid: u64, // 本次兑换收取的全部手续费（输入代币计，含协议费和推荐人分成）
+    pub price_impact_bps: u64,
+    pub reserve_x_after: u64, // 成交后的储备
+    pub reserve_y_after: u64,
+}
+
+/// batch_swap 中的单笔兑换
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct BatchSwapLeg {
+    pub pool: Pubkey, // 必须与 remaining_accounts 中对应位置的池子一致
+    pub amount_in: u64,
+    pub min_amount_out: u64,
+    pub direction: SwapDirection,
+}
+
+/// 兑换方向
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SwapDirection {
+    XToY, // 卖出 X 得到 Y
+    YToX, // 卖出 Y 得到 X
+}
+
+/// 池子的定价曲线
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum CurveType {
+    ConstantProduct,             // 恒定乘积 x * y = k
+    StableSwap { amp: u64 },     // Curve 风格的稳定币不变量，amp 为放大系数
+    Weighted { weight_x_bps: u16, weight_y_bps: u16 }, // Balancer 风格的加权恒定均值 x^wx * y^wy = k
+    Concentrated,                // 集中流动性，按 LiquidityRanges 中的价格区间逐段成交
+}
+
+impl CurveType {
+    /// 校验曲线参数
+    pub fn validate(&self) -> Result<()> {
+        match self {
+            CurveType::ConstantProduct | CurveType::Concentrated => Ok(()),
+            CurveType::StableSwap { amp } => {
+                require!(*amp > 0 && *amp <= MAX_AMP, ErrorCode::InvalidCurveParameters);
+                Ok(())
+            }
+            CurveType::Weighted { weight_x_bps, weight_y_bps } => {
+                require!(
+                    *weight_x_bps > 0
+                        && *weight_y_bps > 0
+                        && (*weight_x_bps as u32 + *weight_y_bps as u32) == TOTAL_WEIGHT_BPS as u32,
+                    ErrorCode::InvalidCurveParameters
+                );
+                Ok(())
+            }
+        }
+    }
+
+    /// 拆成 zero-copy 池子中存储的 (kind, amp, weight_x_bps, weight_y_bps)
+    pub fn to_parts(&self) -> (u8, u64, u16, u16) {
+        match *self {
+            CurveType::ConstantProduct => (0, 0, 0, 0),
+            CurveType::StableSwap { amp } => (1, amp, 0, 0),
+            CurveType::Weighted { weight_x_bps, weight_y_bps } => (2, 0, weight_x_bps, weight_y_bps),
+            CurveType::Concentrated => (3, 0, 0, 0),
+        }
+    }
+
+    /// 由 to_parts 的结果还原曲线；kind 只会由 MockDexPool::set_curve 写入
+    pub fn from_parts(kind: u8, amp: u64, weight_x_bps: u16, weight_y_bps: u16) -> CurveType {
+        match kind {
+            1 => CurveType::StableSwap { amp },
+            2 => CurveType::Weighted { weight_x_bps, weight_y_bps },
+            3 => CurveType::Concentrated,
+            _ => CurveType::ConstantProduct,
+        }
+    }
+
+    /// 精确输出时所需的输入数量（含手续费），目前只支持恒定乘积曲线
+    /// 两步都向上取整，保证成交后 k 不下降
+    pub fn amount_in(&self, amount_out: u64, fee_bps: u64, reserve_in: u64, reserve_out: u64) -> Result<u64> {
+        match self {
+            CurveType::ConstantProduct => {
+                require!(amount_out < reserve_out, ErrorCode::InsufficientLiquidity);
+
+                // amount_in_with_fee = reserve_in * amount_out / (reserve_out - amount_out)
+                let numerator = (reserve_in as u128)
+                    .checked_mul(amount_out as u128)
+                    .ok_or(ErrorCode::Overflow)?;
+                let denominator = (reserve_out - amount_out) as u128;
+                let amount_in_with_fee = numerator.div_ceil(denominator);
+
+                // amount_in = amount_in_with_fee * 10000 / (10000 - fee)
+                let fee_denominator = (10000 - fee_bps) as u128;
+                let amount_in = amount_in_with_fee
+                    .checked_mul(10000)
+                    .ok_or(ErrorCode::Overflow)?
+                    .div_ceil(fee_denominator);
+                u64::try_from(amount_in).map_err(|_| error!(ErrorCode::Overflow))
+            }
+            _ => err!(ErrorCode::UnsupportedCurveOperation),
+        }
+    }
+
+    /// 计算扣除手续费后的输出数量
+    pub fn amount_out(
+        &self,
+        amount_in: u64,
+        fee_bps: u64,
+        reserve_in: u64,
+        reserve_out: u64,
+        input_is_x: bool,
+    ) -> Result<u64> {
+        match self {
+            CurveType::ConstantProduct => {
+                // AMM 恒定乘积公式计算输出，中间结果使用 u128，避免大储备下 u64 溢出
+                let amount_in_with_fee = (amount_in as u128) * (10000 - fee_bps) as u128;
+
+                let numerator = amount_in_with_fee
+                    .checked_mul(reserve_out as u128)
+                    .ok_or(ErrorCode::Overflow)?;
+
+                let denominator = (reserve_in as u128) * 10000 + amount_in_with_fee;
+
+                let amount_out = numerator
+                    .checked_div(denominator)
+                    .ok_or(ErrorCode::Underflow)?;
+                u64::try_from(amount_out).map_err(|_| error!(ErrorCode::Overflow))
+            }
+            CurveType::StableSwap { amp } => {
+                let fee_amount = (amount_in as u128) * (fee_bps as u128) / 10000;
+                let net_in = amount_in as u128 - fee_amount;
+
+                let d = stable_swap_d(reserve_in as u128, reserve_out as u128, *amp as u128)?;
+                let new_reserve_in = (reserve_in as u128)
+                    .checked_add(net_in)
+                    .ok_or(ErrorCode::Overflow)?;
+                let new_reserve_out = stable_swap_y(new_reserve_in, d, *amp as u128)?;
+
+                // 向下多取 1，避免舍入让不变量下降
+                let amount_out = (reserve_out as u128)
+                    .saturating_sub(new_reserve_out)
+                    .saturating_sub(1);
+                u64::try_from(amount_out).map_err(|_| error!(ErrorCode::Overflow))
+            }
+            CurveType::Weighted { weight_x_bps, weight_y_bps } => {
+                let (weight_in, weight_out) = if input_is_x {
+                    (*weight_x_bps as u128, *weight_y_bps as u128)
+                } else {
+                    (*weight_y_bps as u128, *weight_x_bps as u128)
+                };
+
+                let fee_amount = (amount_in as u128) * (fee_bps as u128) / 10000;
+                let net_in = amount_in as u128 - fee_amount;
+
+                // out = R_out * (1 - (R_in / (R_in + in)) ^ (w_in / w_out))
+                let new_reserve_in = (reserve_in as u128)
+                    .checked_add(net_in)
+                    .ok_or(ErrorCode::Overflow)?;
+                let base = (reserve_in as u128) * WAD / new_reserve_in;
+                let exponent = weight_in * WAD / weight_out;
+                let remaining = wad_pow_below_one(base, exponent)?;
+
+                // 向下多取 1，避免舍入让不变量下降
+                let amount_out = (reserve_out as u128)
+                    .checked_mul(WAD - remaining)
+                    .ok_or(ErrorCode::Overflow)?
+                    / WAD;
+                u64::try_from(amount_out.saturating_sub(1)).map_err(|_| error!(ErrorCode::Overflow))
+            }
+            // 集中流动性的输出取决于区间账户，由 LiquidityRanges::fill 计算
+            CurveType::Concentrated => err!(ErrorCode::MissingLiquidityRanges),
+        }
+    }
+}
+
+/// 挂在池子上的限价单，卖出代币托管在订单 PDA 持有的 escrow 中
+#[account]
+pub struct LimitOrder {
+    pub pool: Pubkey, // 所属池子
+    pub owner: Pubkey, // 下单人
+    pub mint_in: Pubkey, // 卖出代币的 mint
+    pub input_is_x: bool, // 是否卖出 X
+    pub amount_in: u64, // 托管的卖出数量
+    pub limit_price: u64, // 按精度换算后 1 个 X 可兑换的 Y 数量 * PRICE_SCALE
+    pub order_id: u64, // 下单人自选的订单编号
+    pub created_at: i64,
+    pub bump: u8,
+    pub escrow_bump: u8,
+}
+
+impl LimitOrder {
+    pub const SPACE: usize = 8 + // discriminator
+        32 + // pool
+        32 + // owner
+        32 + // mint_in
+        1 + // input_is_x
+        8 + // amount_in
+        8 + // limit_price
+        8 + // order_id
+        8 + // created_at
+        1 + // bump
+        1; // escrow_bump
+}
+
+/// 池子的流动性挖矿配置：质押 LP 按份额分享每秒发放的奖励
+#[account]
+pub struct Rewarder {
+    pub pool: Pubkey, // 所属池子
+    pub lp_mint: Pubkey, // 可质押的 LP mint
+    pub reward_mint: Pubkey, // 奖励代币 mint
+    pub emission_per_second: u64, // 每秒发放的奖励数量
+    pub acc_reward_per_share: u128, // 每单位质押 LP 累计的奖励 * REWARD_PRECISION
+    pub rewards_remaining: u64, // 已注资但尚未累计给质押者的奖励
+    pub total_staked: u64, // 当前质押的 LP 总量
+    pub last_update_ts: i64, // 上次累计奖励的时间
+    pub bump: u8,
+}
+
+impl Rewarder {
+    pub const SPACE: usize = 8 + // discriminator
+        32 + // pool
+        32 + // lp_mint
+        32 + // reward_mint
+        8 + // emission_per_second
+        16 + // acc_reward_per_share
+        8 + // rewards_remaining
+        8 + // total_staked
+        8 + // last_update_ts
+        1; // bump
+
+    /// 把上次更新到 now 之间的奖励累计进 acc_reward_per_share
+    /// 无人质押时不发放；发放量不超过剩余的注资额度
+    pub fn accrue(&mut self, now: i64) -> Result<()> {
+        if now <= self.last_update_ts {
+            return Ok(());
+        }
+        if self.total_staked > 0 {
+            let elapsed = (now - self.last_update_ts) as u64;
+            let reward = elapsed
+                .saturating_mul(self.emission_per_second)
+                .min(self.rewards_remaining);
+            let per_share = (reward as u128)
+                .checked_mul(REWARD_PRECISION)
+                .ok_or(ErrorCode::Overflow)?
+                / self.total_staked as u128;
+            self.acc_reward_per_share = self
+                .acc_reward_per_share
+                .checked_add(per_share)
+                .ok_or(ErrorCode::Overflow)?;
+            self.rewards_remaining -= reward;
+        }
+        self.last_update_ts = now;
+        Ok(())
+    }
+}
+
+/// 单个 LP 在 rewarder 中的质押记录
+#[account]
+pub struct LpStake {
+    pub rewarder: Pubkey, // 所属 rewarder
+    pub owner: Pubkey, // 质押人
+    pub amount: u64, // 质押的 LP 数量
+    pub reward_debt: u128, // 上次结算时 amount * acc_reward_per_share，已计入 pending_rewards 的部分
+    pub pending_rewards: u64, // 已结算未领取的奖励
+    pub bump: u8,
+}
+
+impl LpStake {
+    pub const SPACE: usize = 8 + // discriminator
+        32 + // rewarder
+        32 + // owner
+        8 + // amount
+        16 + // reward_debt
+        8 + // pending_rewards
+        1; // bump
+
+    /// 按当前 acc_reward_per_share 把新累计的奖励计入 pending_rewards
+    pub fn settle(&mut self, acc_reward_per_share: u128) -> Result<()> {
+        let accrued = (self.amount as u128)
+            .checked_mul(acc_reward_per_share)
+            .ok_or(ErrorCode::Overflow)?;
+        let earned = (accrued - self.reward_debt) / REWARD_PRECISION;
+        self.pending_rewards = self
+            .pending_rewards
+            .checked_add(u64::try_from(earned).map_err(|_| ErrorCode::Overflow)?)
+            .ok_or(ErrorCode::Overflow)?;
+        self.reward_debt = accrued;
+        Ok(())
+    }
+
+    /// 质押数量变化后重置 reward_debt
+    pub fn reset_debt(&mut self, acc_reward_per_share: u128) -> Result<()> {
+        self.reward_debt = (self.amount as u128)
+            .checked_mul(acc_reward_per_share)
+            .ok_or(ErrorCode::Overflow)?;
+        Ok(())
+    }
+}
+
+/// 池子的手续费豁免名单，名单中的交易者按各自费率收费
+#[account]
+pub struct FeeAllowlist {
+    pub pool: Pubkey, // 所属池子
+    pub bump: u8,
+    pub entries: Vec<FeeExemption>,
+}
+
+/// 单个交易者的手续费豁免
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct FeeExemption {
+    pub trader: Pubkey, // 兑换时的 user_authority，例如套利机器人 PDA
+    pub fee_bps: u16, // 该交易者适用的手续费 (bps)，不高于池子手续费
+}
+
+impl FeeExemption {
+    pub const SPACE: usize = 32 + // trader
+        2; // fee_bps
+}
+
+impl FeeAllowlist {
+    pub const SPACE: usize = 8 + // discriminator
+        32 + // pool
+        1 + // bump
+        4 + MAX_FEE_EXEMPTIONS * FeeExemption::SPACE; // entries
+
+    /// trader 在名单中时返回其费率
+    pub fn fee_bps_for(&self, trader: &Pubkey) -> Option<u16> {
+        self.entries
+            .iter()
+            .find(|entry| entry.trader == *trader)
+            .map(|entry| entry.fee_bps)
+    }
+}
+
+/// 池子的交易者名单，池子处于许可模式时只有名单中的账户可以兑换
+#[account]
+pub struct TraderAllowlist {
+    pub pool: Pubkey, // 所属池子
+    pub bump: u8,
+    pub traders: Vec<Pubkey>, // 兑换时的 user_authority，例如通过 KYC 的做市商或套利机器人 PDA
+}
+
+impl TraderAllowlist {
+    pub const SPACE: usize = 8 + // discriminator
+        32 + // pool
+        1 + // bump
+        4 + MAX_ALLOWED_TRADERS * 32; // traders
+
+    pub fn is_allowed(&self, trader: &Pubkey) -> bool {
+        self.traders.contains(trader)
+    }
+}
+
+/// 提交后等待揭示的兑换承诺
+#[account]
+pub struct SwapCommitment {
+    pub pool: Pubkey, // 所属池子
+    pub owner: Pubkey, // 提交者
+    pub commitment: [u8; 32], // swap_commitment_hash 的结果
+    pub committed_slot: u64, // 提交时的 slot，揭示必须在其之后
+    pub bump: u8,
+}
+
+impl SwapCommitment {
+    pub const SPACE: usize = 8 + // discriminator
+        32 + // pool
+        32 + // owner
+        32 + // commitment
+        8 + // committed_slot
+        1; // bump
+}
+
+/// 三资产池：三种代币的储备放在同一个池子账户中，任意两种之间按恒定乘积兑换
+#[account]
+pub struct TriPool {
+    pub mints: [Pubkey; 3], // 按升序排列的三个 mint
+    pub vaults: [Pubkey; 3], // 与 mints 一一对应的储备 vault
+    pub balances: [u64; 3], // 与 mints 一一对应的储备
+    pub fee_bps: u16,
+    pub admin: Pubkey,
+    pub bump: u8,
+    pub event_nonce: u64, // 已发出的池子事件数，含义与 MockDexPool::event_nonce 相同
+}
+
+impl TriPool {
+    pub const SPACE: usize = 8 + // discriminator
+        32 * 3 + // mints
+        32 * 3 + // vaults
+        8 * 3 + // balances
+        2 + // fee_bps
+        32 + // admin
+        1 + // bump
+        8; // event_nonce
+
+    /// 递增并返回池子的事件序号
+    pub fn next_event_nonce(&mut self) -> Result<u64> {
+        self.event_nonce = self.event_nonce.checked_add(1).ok_or(ErrorCode::Overflow)?;
+        Ok(self.event_nonce)
+    }
+}
+
+/// 池子的价格观测环形缓冲区，由 crank_observation 写入
+#[account]
+pub struct PriceObservations {
+    pub pool: Pubkey, // 所属池子
+    pub next_index: u8, // 下一次写入的位置
+    pub bump: u8,
+    pub observations: Vec<PriceObservation>, // 未写满前按时间顺序追加，写满后循环覆盖
+}
+
+/// 单次价格观测
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct PriceObservation {
+    pub slot: u64,
+    pub timestamp: i64,
+    pub price: u128, // 按精度换算后 1 个 X 可兑换的 Y 数量 * PRICE_SCALE
+    pub price_cumulative: u128, // 观测时池子的价格累计值，两次观测之差可算出 TWAP
+    pub reserve_x: u64,
+    pub reserve_y: u64,
+}
+
+impl PriceObservation {
+    pub const SPACE: usize = 8 + // slot
+        8 + // timestamp
+        16 + // price
+        16 + // price_cumulative
+        8 + // reserve_x
+        8; // reserve_y
+}
+
+impl PriceObservations {
+    pub const SPACE: usize = 8 + // discriminator
+        32 + // pool
+        1 + // next_index
+        1 + // bump
+        4 + MAX_PRICE_OBSERVATIONS * PriceObservation::SPACE; // observations
+
+    /// 最近一次写入的观测
+    pub fn latest(&self) -> Option<&PriceObservation> {
+        if self.observations.is_empty() {
+            return None;
+        }
+        let index = (self.next_index as usize + self.observations.len() - 1) % self.observations.len();
+        self.observations.get(index)
+    }
+
+    /// 写入一次观测并返回写入位置
+    pub fn record(&mut self, observation: PriceObservation) -> u8 {
+        let index = self.next_index;
+        if self.observations.len() < MAX_PRICE_OBSERVATIONS {
+            self.observations.push(observation);
+        } else {
+            self.observations[index as usize] = observation;
+        }
+        self.next_index = ((index as usize + 1) % MAX_PRICE_OBSERVATIONS) as u8;
+        index
+    }
+}
+
+/// 池子某一天的统计快照，由 snapshot_pool_stats 写入，写入后不再修改
+#[account]
+pub struct PoolStatsSnapshot {
+    pub pool: Pubkey, // 所属池子
+    pub day: u64, // unix_timestamp / SECONDS_PER_DAY
+    pub slot: u64, // 快照时的 slot
+    pub timestamp: i64,
+    pub reserve_x: u64,
+    pub reserve_y: u64,
+    pub cumulative_volume_x: u128, // 快照时池子的累计值，与前一天的快照相减得到当天数据
+    pub cumulative_volume_y: u128,
+    pub cumulative_fees_x: u128,
+    pub cumulative_fees_y: u128,
+    pub swap_count: u64,
+    pub bump: u8,
+}
+
+impl PoolStatsSnapshot {
+    pub const SPACE: usize = 8 + // discriminator
+        32 + // pool
+        8 + // day
+        8 + // slot
+        8 + // timestamp
+        8 + // reserve_x
+        8 + // reserve_y
+        16 + // cumulative_volume_x
+        16 + // cumulative_volume_y
+        16 + // cumulative_fees_x
+        16 + // cumulative_fees_y
+        8 + // swap_count
+        1; // bump
+}
+
+/// 集中流动性池的价格区间集合，按价格升序排列
+#[account]
+pub struct LiquidityRanges {
+    pub pool: Pubkey, // 所属池子
+    pub active_index: u8, // 当前价格所在的区间
+    pub bump: u8,
+    pub ranges: Vec<LiquidityRange>,
+}
+
+/// 单个价格区间：在 price 处以固定价格成交，直到一侧储备耗尽
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct LiquidityRange {
+    pub price: u64, // 1 个 X 可兑换的 Y 数量 * PRICE_SCALE
+    pub reserve_x: u64,
+    pub reserve_y: u64,
+}
+
+impl LiquidityRange {
+    pub const SPACE: usize = 8 + // price
+        8 + // reserve_x
+        8; // reserve_y
+}
+
+impl LiquidityRanges {
+    pub const SPACE: usize = 8 + // discriminator
+        32 + // pool
+        1 + // active_index
+        1 + // bump
+        4 + MAX_LIQUIDITY_RANGES * LiquidityRange::SPACE; // ranges
+
+    /// 所有区间已分配的储备合计
+    pub fn total_reserves(&self) -> Result<(u64, u64)> {
+        let mut total_x = 0u64;
+        let mut total_y = 0u64;
+        for range in &self.ranges {
+            total_x = total_x.checked_add(range.reserve_x).ok_or(ErrorCode::Overflow)?;
+            total_y = total_y.checked_add(range.reserve_y).ok_or(ErrorCode::Overflow)?;
+        }
+        Ok((total_x, total_y))
+    }
+
+    /// 从当前区间开始逐段成交，区间耗尽后移动到下一个区间
+    /// 卖出 X 时价格向下跨区间，卖出 Y 时价格向上跨区间；所有区间耗尽仍未成交完则失败
+    pub fn fill(&mut self, amount_in: u64, input_is_x: bool) -> Result<u64> {
+        let scale = PRICE_SCALE as u128;
+        let mut remaining = amount_in as u128;
+        let mut amount_out = 0u128;
+        let mut index = self.active_index as usize;
+
+        loop {
+            let range = &mut self.ranges[index];
+            let price = range.price as u128;
+
+            // 本区间可换出的数量与对应的输入数量
+            let (available, wanted) = if input_is_x {
+                (range.reserve_y as u128, remaining * price / scale)
+            } else {
+                (range.reserve_x as u128, remaining * scale / price)
+            };
+
+            let (used_in, out) = if available > 0 && wanted <= available {
+                (remaining, wanted)
+            } else {
+                // 区间被耗尽：向上取整计算吃掉整个区间所需的输入
+                let used_in = if input_is_x {
+                    (available * scale).div_ceil(price)
+                } else {
+                    (available * price).div_ceil(scale)
+                };
+                (used_in.min(remaining), available)
+            };
+
+            let used_in = u64::try_from(used_in).map_err(|_| error!(ErrorCode::Overflow))?;
+            let out = u64::try_from(out).map_err(|_| error!(ErrorCode::Overflow))?;
+            if input_is_x {
+                range.reserve_x = range.reserve_x.checked_add(used_in).ok_or(ErrorCode::Overflow)?;
+                range.reserve_y -= out;
+            } else {
+                range.reserve_y = range.reserve_y.checked_add(used_in).ok_or(ErrorCode::Overflow)?;
+                range.reserve_x -= out;
+            }
+
+            remaining -= used_in as u128;
+            amount_out += out as u128;
+            if remaining == 0 {
+                break;
+            }
+
+            // 跨到下一个区间
+            if input_is_x {
+                require!(index > 0, ErrorCode::InsufficientLiquidity);
+                index -= 1;
+            } else {
+                require!(index + 1 < self.ranges.len(), ErrorCode::InsufficientLiquidity);
+                index += 1;
+            }
+        }
+
+        self.active_index = index as u8;
+        u64::try_from(amount_out).map_err(|_| error!(ErrorCode::Overflow))
+    }
+}
+
+/// 按路由中本跳的方向读取两侧 vault 余额，校验其覆盖池子记录的储备
+fn assert_route_vaults<'a>(
+    pool: &MockDexPool,
+    input_is_x: bool,
+    vault_in: &AccountInfo<'a>,
+    vault_out: &AccountInfo<'a>,
+) -> Result<()> {
+    let (vault_x, vault_y) = if input_is_x { (vault_in, vault_out) } else { (vault_out, vault_in) };
+    pool.assert_vaults_cover_reserves(token_balance(vault_x)?, token_balance(vault_y)?)
+}
+
+/// 从 Pyth 价格账户读取聚合价格，换算为 PRICE_SCALE 精度
+/// 要求账户处于 Trading 状态且价格不早于 MAX_ORACLE_STALENESS_SLOTS 个 slot
+fn read_pyth_price(account: &AccountInfo, current_slot: u64) -> Result<u128> {
+    let data = account.try_borrow_data()?;
+    require!(data.len() >= PYTH_AGG_PUB_SLOT_OFFSET + 8, ErrorCode::InvalidOracleAccount);
+
+    let read_u32 = |offset: usize| u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap());
+    require!(
+        read_u32(0) == PYTH_MAGIC && read_u32(8) == PYTH_PRICE_ACCOUNT_TYPE,
+        ErrorCode::InvalidOracleAccount
+    );
+    require!(
+        read_u32(PYTH_AGG_STATUS_OFFSET) == PYTH_STATUS_TRADING,
+        ErrorCode::StaleOraclePrice
+    );
+
+    let pub_slot = u64::from_le_bytes(
+        data[PYTH_AGG_PUB_SLOT_OFFSET..PYTH_AGG_PUB_SLOT_OFFSET + 8].try_into().unwrap(),
+    );
+    require!(
+        current_slot.saturating_sub(pub_slot) <= MAX_ORACLE_STALENESS_SLOTS,
+        ErrorCode::StaleOraclePrice
+    );
+
+    let expo = i32::from_le_bytes(data[PYTH_EXPO_OFFSET..PYTH_EXPO_OFFSET + 4].try_into().unwrap());
+    let price = i64::from_le_bytes(
+        data[PYTH_AGG_PRICE_OFFSET..PYTH_AGG_PRICE_OFFSET + 8].try_into().unwrap(),
+    );
+    require!(price > 0, ErrorCode::InvalidOracleAccount);
+
+    // price * 10^expo * PRICE_SCALE
+    let scaled = (price as u128)
+        .checked_mul(PRICE_SCALE as u128)
+        .ok_or(ErrorCode::Overflow)?;
+    let factor = 10u128
+        .checked_pow(expo.unsigned_abs())
+        .ok_or(ErrorCode::Overflow)?;
+    if expo < 0 {
+        Ok(scaled / factor)
+    } else {
+        Ok(scaled.checked_mul(factor).ok_or(ErrorCode::Overflow)?)
+    }
+}
+
+/// price 相对 reference 的偏离（基点）
+fn price_deviation_bps(price: u128, reference: u128) -> Result<u64> {
+    require!(reference > 0, ErrorCode::InvalidOracleAccount);
+    let deviation = price.abs_diff(reference)
+        .checked_mul(10_000)
+        .ok_or(ErrorCode::Overflow)?
+        / reference;
+    Ok(deviation.min(u64::MAX as u128) as u64)
+}
+
+/// 兑换承诺的哈希：sha256(pool || owner || mint_in || amount_in_le || min_amount_out_le || salt)
+/// 客户端按同样的顺序计算后传给 commit_swap
+pub fn swap_commitment_hash(
+    pool: &Pubkey,
+    owner: &Pubkey,
+    mint_in: &Pubkey,
+    amount_in: u64,
+    min_amount_out: u64,
+    salt: &[u8; 32],
+) -> [u8; 32] {
+    hashv(&[
+        pool.as_ref(),
+        owner.as_ref(),
+        mint_in.as_ref(),
+        &amount_in.to_le_bytes(),
+        &min_amount_out.to_le_bytes(),
+        salt,
+    ])
+    .to_bytes()
+}
+
+/// 读取 token 账户当前余额，用于按余额变化测量实际到账数量
+fn token_balance(account: &AccountInfo) -> Result<u64> {
+    let data = account.try_borrow_data()?;
+    let token_account = TokenAccount::try_deserialize(&mut &data[..])?;
+    Ok(token_account.amount)
+}
+
+/// 读取 token 账户的 mint
+fn token_mint(account: &AccountInfo) -> Result<Pubkey> {
+    let data = account.try_borrow_data()?;
+    let token_account = TokenAccount::try_deserialize(&mut &data[..])?;
+    Ok(token_account.mint)
+}
+
+/// 按 mint 精度转账 amount，数量为 0 时跳过；from 为池子 vault 时由 signer_seeds 代池子签名
+fn transfer_tokens<'info>(
+    token_program: &AccountInfo<'info>,
+    from: &AccountInfo<'info>,
+    mint: &InterfaceAccount<'info, Mint>,
+    to: &AccountInfo<'info>,
+    authority: &AccountInfo<'info>,
+    amount: u64,
+    signer_seeds: &[&[&[u8]]],
+) -> Result<()> {
+    if amount == 0 {
+        return Ok(());
+    }
+    token_interface::transfer_checked(
+        CpiContext::new_with_signer(
+            token_program.clone(),
+            TransferChecked {
+                from: from.clone(),
+                mint: mint.to_account_info(),
+                to: to.clone(),
+                authority: authority.clone(),
+            },
+            signer_seeds,
+        ),
+        amount,
+        mint.decimals,
+    )
+}
+
+/// remaining_accounts 中的可选账户：与 Anchor 的可选账户约定一致，传入本程序 ID 表示未提供
+fn optional_account<'info>(account: &'info AccountInfo<'info>) -> Option<&'info AccountInfo<'info>> {
+    (account.key() != crate::ID).then_some(account)
+}
+
+/// 定点数精度 (1e18)
+const WAD: u128 = 1_000_000_000_000_000_000;
+
+/// ln(2)，WAD 精度
+const LN_2_WAD: u128 = 693_147_180_559_945_309;
+
+/// 计算 a * b / WAD，拆分整数与小数部分以避免 u128 溢出
+fn wad_mul(a: u128, b: u128) -> Result<u128> {
+    let whole = (a / WAD).checked_mul(b).ok_or(ErrorCode::Overflow)?;
+    let frac = (a % WAD).checked_mul(b).ok_or(ErrorCode::Overflow)? / WAD;
+    Ok(whole.checked_add(frac).ok_or(ErrorCode::Overflow)?)
+}
+
+/// ln(x)，x ≥ 1，WAD 精度
+fn wad_ln(mut x: u128) -> u128 {
+    let mut result = 0u128;
+    while x >= 2 * WAD {
+        x /= 2;
+        result += LN_2_WAD;
+    }
+    // x ∈ [1, 2)：ln x = 2 * atanh(z)，z = (x - 1) / (x + 1)
+    let z = (x - WAD) * WAD / (x + WAD);
+    let z2 = z * z / WAD;
+    let mut term = z;
+    let mut sum = 0u128;
+    let mut k = 1u128;
+    while term > 0 {
+        sum += term / k;
+        term = term * z2 / WAD;
+        k += 2;
+    }
+    result + 2 * sum
+}
+
+/// exp(-t)，t ≥ 0，WAD 精度
+fn wad_exp_neg(t: u128) -> u128 {
+    let n = t / LN_2_WAD;
+    if n >= 64 {
+        return 0;
+    }
+    // exp(-t) = 2^-n * exp(-r)，r ∈ [0, ln2)
+    let r = t - n * LN_2_WAD;
+    let mut term = WAD;
+    let mut sum = WAD;
+    let mut k = 1u128;
+    while term > 0 {
+        term = term * r / WAD / k;
+        sum += term;
+        k += 1;
+    }
+    (WAD * WAD / sum) >> n
+}
+
+/// base ^ exponent，base ∈ (0, 1]，exponent ≥ 0，均为 WAD 精度
+pub fn wad_pow_below_one(base: u128, exponent: u128) -> Result<u128> {
+    require!(base > 0 && base <= WAD, ErrorCode::InvalidCurveParameters);
+    if base == WAD || exponent == 0 {
+        return Ok(WAD);
+    }
+    // base^e = exp(-e * ln(1 / base))
+    let ln_inverse = wad_ln(WAD * WAD / base);
+    Ok(wad_exp_neg(wad_mul(ln_inverse, exponent)?))
+}
+
+/// StableSwap 迭代次数上限
+const STABLE_SWAP_MAX_ITERATIONS: usize = 255;
+
+/// 计算两币种 StableSwap 的不变量 D
+/// A * n^n * S + D = A * D * n^n + D^(n+1) / (n^n * x * y)，n = 2
+pub fn stable_swap_d(x: u128, y: u128, amp: u128) -> Result<u128> {
+    let sum = x.checked_add(y).ok_or(ErrorCode::Overflow)?;
+    if sum == 0 {
+        return Ok(0);
+    }
+    let ann = amp.checked_mul(4).ok_or(ErrorCode::Overflow)?;
+
+    let mut d = sum;
+    for _ in 0..STABLE_SWAP_MAX_ITERATIONS {
+        // d_p = D^3 / (4 * x * y)
+        let d_p = d
+            .checked_mul(d).ok_or(ErrorCode::Overflow)?
+            .checked_div(x.checked_mul(2).ok_or(ErrorCode::Overflow)?).ok_or(ErrorCode::Underflow)?
+            .checked_mul(d).ok_or(ErrorCode::Overflow)?
+            .checked_div(y.checked_mul(2).ok_or(ErrorCode::Overflow)?).ok_or(ErrorCode::Underflow)?;
+
+        let d_prev = d;
+        let numerator = ann
+            .checked_mul(sum).ok_or(ErrorCode::Overflow)?
+            .checked_add(d_p.checked_mul(2).ok_or(ErrorCode::Overflow)?).ok_or(ErrorCode::Overflow)?
+            .checked_mul(d).ok_or(ErrorCode::Overflow)?;
+        let denominator = (ann - 1)
+            .checked_mul(d).ok_or(ErrorCode::Overflow)?
+            .checked_add(d_p.checked_mul(3).ok_or(ErrorCode::Overflow)?).ok_or(ErrorCode::Overflow)?;
+        d = numerator.checked_div(denominator).ok_or(ErrorCode::Underflow)?;
+
+        if d.abs_diff(d_prev) <= 1 {
+            return Ok(d);
+        }
+    }
+    err!(ErrorCode::CurveDidNotConverge)
+}
+
+/// 给定一侧新的储备和不变量 D，求另一侧储备
+pub fn stable_swap_y(x: u128, d: u128, amp: u128) -> Result<u128> {
+    let ann = amp.checked_mul(4).ok_or(ErrorCode::Overflow)?;
+    require!(x > 0 && ann > 0, ErrorCode::InsufficientLiquidity);
+
+    // c = D^3 / (4 * x * ann)，b = x + D / ann
+    let c = d
+        .checked_mul(d).ok_or(ErrorCode::Overflow)?
+        .checked_div(x.checked_mul(2).ok_or(ErrorCode::Overflow)?).ok_or(ErrorCode::Underflow)?
+        .checked_mul(d).ok_or(ErrorCode::Overflow)?
+        .checked_div(ann.checked_mul(2).ok_or(ErrorCode::Overflow)?).ok_or(ErrorCode::Underflow)?;
+    let b = x.checked_add(d / ann).ok_or(ErrorCode::Overflow)?;
+
+    let mut y = d;
+    for _ in 0..STABLE_SWAP_MAX_ITERATIONS {
+        let y_prev = y;
+        let numerator = y
+            .checked_mul(y).ok_or(ErrorCode::Overflow)?
+            .checked_add(c).ok_or(ErrorCode::Overflow)?;
+        let denominator = y
+            .checked_mul(2).ok_or(ErrorCode::Overflow)?
+            .checked_add(b).ok_or(ErrorCode::Overflow)?
+            .checked_sub(d).ok_or(ErrorCode::Underflow)?;
+        y = numerator.checked_div(denominator).ok_or(ErrorCode::Underflow)?;
+
+        if y.abs_diff(y_prev) <= 1 {
+            return Ok(y);
+        }
+    }
+    err!(ErrorCode::CurveDidNotConverge)
+}
+
+/// 把补 0 的定长字节数组还原为字符串，用于 zero-copy 账户中的名称与符号
+pub fn padded_bytes_to_string(bytes: &[u8]) -> String {
+    let len = bytes.iter().position(|b| *b == 0).unwrap_or(bytes.len());
+    String::from_utf8_lossy(&bytes[..len]).into_owned()
+}
+
+/// 整数平方根（牛顿迭代），用于计算初始 LP 数量
+pub fn integer_sqrt(value: u128) -> u128 {
+    if value < 2 {
+        return value;
+    }
+    let mut x = value;
+    let mut y = x.div_ceil(2);
+    while y < x {
+        x = y;
+        y = (x + value / x) / 2;
+    }
+    x
+}
+
+/// 计算 amount * bps / 10000，向下取整，中间结果使用 u128
+pub fn mul_bps(amount: u64, bps: u64) -> Result<u64> {
+    let result = (amount as u128) * (bps as u128) / 10000;
+    u64::try_from(result).map_err(|_| error!(ErrorCode::Overflow))
+}
+
+/// 计算 a * b / c，向下取整，中间结果使用 u128
+pub fn mul_div_floor(a: u64, b: u64, c: u64) -> Result<u64> {
+    require!(c > 0, ErrorCode::Underflow);
+    let result = (a as u128) * (b as u128) / (c as u128);
+    u64::try_from(result).map_err(|_| error!(ErrorCode::Overflow))
+}
+
+/// 计算 a * b / c，向上取整，中间结果使用 u128
+pub fn mul_div_ceil(a: u64, b: u64, c: u64) -> Result<u64> {
+    require!(c > 0, ErrorCode::Underflow);
+    let result = ((a as u128) * (b as u128)).div_ceil(c as u128);
+    u64::try_from(result).map_err(|_| error!(ErrorCode::Overflow))
+}
+
+#[event]
+pub struct SwapExecuted {
+    pub pool_name: String,
+    pub amount_in: u64,
+    pub amount_out: u64,
+    pub price_impact_bps: u64,
+    pub fee_amount: u64, // 本次兑换收取的全部手续费（输入代币计）
+    pub is_x_to_y: bool,
+    pub reserve_x_after: u64, // 成交后的储备，链下可据此重建池子状态
+    pub reserve_y_after: u64,
+    pub sequence: u64, // 池子内单调递增的兑换序号，用于检测事件遗漏
+    pub user: Pubkey,
+    pub referrer: Option<Pubkey>, // 获得手续费分成的推荐人代币账户
+    pub event_nonce: u64, // 所属池子的事件序号，逐个递增
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct SwapCommitted {
+    pub pool_name: String,
+    pub owner: Pubkey,
+    pub commitment: [u8; 32],
+    pub slot: u64,
+    pub event_nonce: u64, // 所属池子的事件序号，逐个递增
+    pub timestamp: i64,
+}
+
+/// 多跳汇总事件不属于单个池子，不带 event_nonce；每一跳各自的 SwapExecuted 带有所属池子的序号
+#[event]
+pub struct RouteSwapExecuted {
+    pub hops: u8,
+    pub amount_in: u64,
+    pub amount_out: u64,
+    pub user: Pubkey,
+    pub timestamp: i64,
+}
+
+/// 多池汇总事件不属于单个池子，不带 event_nonce；每个池子各自的 SwapExecuted 带有所属池子的序号
+#[event]
+pub struct MultiPoolSwapExecuted {
+    pub pool_count: u8,
+    pub amount_in: u64, // 各池子输入合计
+    pub amount_out: u64, // 用户实际收到的输出合计
+    pub user: Pubkey,
+    pub timestamp: i64,
+}
+
+/// 批量汇总事件不属于单个池子，不带 event_nonce；每一笔各自的 SwapExecuted 带有所属池子的序号
+#[event]
+pub struct BatchSwapExecuted {
+    pub swap_count: u8,
+    pub user: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct FlashSwapExecuted {
+    pub pool_name: String,
+    pub amount_out: u64,
+    pub amount_paid: u64,
+    pub protocol_fee: u64,
+    pub callback_program: Pubkey,
+    pub user: Pubkey,
+    pub event_nonce: u64, // 所属池子的事件序号，逐个递增
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct LimitOrderPlaced {
+    pub pool_name: String,
+    pub order: Pubkey,
+    pub owner: Pubkey,
+    pub input_is_x: bool,
+    pub amount_in: u64,
+    pub limit_price: u64,
+    pub event_nonce: u64, // 所属池子的事件序号，逐个递增
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct LimitOrderCancelled {
+    pub order: Pubkey,
+    pub owner: Pubkey,
+    pub amount_returned: u64,
+    pub event_nonce: u64, // 所属池子的事件序号，逐个递增
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct LimitOrderFilled {
+    pub pool_name: String,
+    pub order: Pubkey,
+    pub owner: Pubkey,
+    pub amount_in: u64,
+    pub amount_out: u64,
+    pub keeper: Pubkey,
+    pub event_nonce: u64, // 所属池子的事件序号，逐个递增
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct TriPoolInitialized {
+    pub pool: Pubkey,
+    pub mints: [Pubkey; 3],
+    pub balances: [u64; 3],
+    pub fee_bps: u16,
+    pub initializer: Pubkey,
+    pub event_nonce: u64, // 所属池子的事件序号，逐个递增
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct TriPoolSwapExecuted {
+    pub pool: Pubkey,
+    pub index_in: u8,
+    pub index_out: u8,
+    pub amount_in: u64,
+    pub amount_out: u64,
+    pub user: Pubkey,
+    pub event_nonce: u64, // 所属池子的事件序号，逐个递增
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct LiquidityAdded {
+    pub pool_name: String,
+    pub provider: Pubkey,
+    pub amount_x: u64,
+    pub amount_y: u64,
+    pub lp_amount: u64,
+    pub event_nonce: u64, // 所属池子的事件序号，逐个递增
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct LiquidityRemoved {
+    pub pool_name: String,
+    pub provider: Pubkey,
+    pub amount_x: u64,
+    pub amount_y: u64,
+    pub lp_amount: u64,
+    pub event_nonce: u64, // 所属池子的事件序号，逐个递增
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct RewarderInitialized {
+    pub pool: Pubkey,
+    pub rewarder: Pubkey,
+    pub reward_mint: Pubkey,
+    pub emission_per_second: u64,
+    pub event_nonce: u64, // 所属池子的事件序号，逐个递增
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct RewardsFunded {
+    pub rewarder: Pubkey,
+    pub amount: u64,
+    pub rewards_remaining: u64,
+    pub event_nonce: u64, // 所属池子的事件序号，逐个递增
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct EmissionRateUpdated {
+    pub rewarder: Pubkey,
+    pub old_emission_per_second: u64,
+    pub new_emission_per_second: u64,
+    pub admin: Pubkey,
+    pub event_nonce: u64, // 所属池子的事件序号，逐个递增
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct LpStaked {
+    pub rewarder: Pubkey,
+    pub owner: Pubkey,
+    pub amount: u64,
+    pub total_staked: u64,
+    pub event_nonce: u64, // 所属池子的事件序号，逐个递增
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct LpUnstaked {
+    pub rewarder: Pubkey,
+    pub owner: Pubkey,
+    pub amount: u64,
+    pub total_staked: u64,
+    pub event_nonce: u64, // 所属池子的事件序号，逐个递增
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct RewardsClaimed {
+    pub rewarder: Pubkey,
+    pub owner: Pubkey,
+    pub amount: u64,
+    pub event_nonce: u64, // 所属池子的事件序号，逐个递增
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct ProtocolFeesCollected {
+    pub pool_name: String,
+    pub amount_x: u64,
+    pub amount_y: u64,
+    pub admin: Pubkey,
+    pub event_nonce: u64, // 所属池子的事件序号，逐个递增
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct ReservesSynced {
+    pub pool_name: String,
+    pub old_x_balance: u64,
+    pub old_y_balance: u64,
+    pub new_x_balance: u64,
+    pub new_y_balance: u64,
+    pub caller: Pubkey,
+    pub event_nonce: u64, // 所属池子的事件序号，逐个递增
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct ReservesSkimmed {
+    pub pool_name: String,
+    pub amount_x: u64,
+    pub amount_y: u64,
+    pub admin: Pubkey,
+    pub event_nonce: u64, // 所属池子的事件序号，逐个递增
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct PoolRebalanced {
+    pub pool_name: String,
+    pub target_price_bps: u64,
+    pub old_x_balance: u64,
+    pub old_y_balance: u64,
+    pub new_x_balance: u64,
+    pub new_y_balance: u64,
+    pub admin: Pubkey,
+    pub event_nonce: u64, // 所属池子的事件序号，逐个递增
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct PoolPauseChanged {
+    pub pool_name: String,
+    pub paused: bool,
+    pub admin: Pubkey,
+    pub event_nonce: u64, // 所属池子的事件序号，逐个递增
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct AdminProposed {
+    pub pool_name: String,
+    pub admin: Pubkey,
+    pub pending_admin: Pubkey,
+    pub event_nonce: u64, // 所属池子的事件序号，逐个递增
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct AdminTransferred {
+    pub pool_name: String,
+    pub old_admin: Pubkey,
+    pub new_admin: Pubkey,
+    pub event_nonce: u64, // 所属池子的事件序号，逐个递增
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct FeeRecipientUpdated {
+    pub pool_name: String,
+    pub old_fee_recipient: Pubkey,
+    pub new_fee_recipient: Pubkey,
+    pub admin: Pubkey,
+    pub event_nonce: u64, // 所属池子的事件序号，逐个递增
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct PoolRenamed {
+    pub pool: Pubkey,
+    pub old_name: String,
+    pub new_name: String,
+    pub admin: Pubkey,
+    pub event_nonce: u64, // 所属池子的事件序号，逐个递增
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct OracleGuardUpdated {
+    pub pool_name: String,
+    pub oracle: Pubkey,
+    pub max_oracle_deviation_bps: u16,
+    pub admin: Pubkey,
+    pub event_nonce: u64, // 所属池子的事件序号，逐个递增
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct ProtocolFeeShareUpdated {
+    pub pool_name: String,
+    pub old_protocol_fee_share_bps: u16,
+    pub new_protocol_fee_share_bps: u16,
+    pub admin: Pubkey,
+    pub event_nonce: u64, // 所属池子的事件序号，逐个递增
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct ReferralFeeShareUpdated {
+    pub pool_name: String,
+    pub old_referral_fee_share_bps: u16,
+    pub new_referral_fee_share_bps: u16,
+    pub admin: Pubkey,
+    pub event_nonce: u64, // 所属池子的事件序号，逐个递增
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct MaxPriceImpactUpdated {
+    pub pool_name: String,
+    pub old_max_price_impact_bps: u16,
+    pub new_max_price_impact_bps: u16,
+    pub admin: Pubkey,
+    pub event_nonce: u64, // 所属池子的事件序号，逐个递增
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct FeeExemptionUpdated {
+    pub pool_name: String,
+    pub trader: Pubkey,
+    pub fee_bps: Option<u16>, // None 表示已移出名单
+    pub admin: Pubkey,
+    pub event_nonce: u64, // 所属池子的事件序号，逐个递增
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct AllowedTraderUpdated {
+    pub pool_name: String,
+    pub trader: Pubkey,
+    pub allowed: bool, // false 表示已移出名单
+    pub admin: Pubkey,
+    pub event_nonce: u64, // 所属池子的事件序号，逐个递增
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct PermissionedModeUpdated {
+    pub pool_name: String,
+    pub permissioned: bool,
+    pub admin: Pubkey,
+    pub event_nonce: u64, // 所属池子的事件序号，逐个递增
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct FeeDiscountWindowUpdated {
+    pub pool_name: String,
+    pub index: u8,
+    pub start_ts: i64,
+    pub end_ts: i64, // 0 表示该槽位已清空
+    pub fee_bps: u16,
+    pub admin: Pubkey,
+    pub event_nonce: u64, // 所属池子的事件序号，逐个递增
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct SwapDirectionsUpdated {
+    pub pool_name: String,
+    pub x_to_y_enabled: bool,
+    pub y_to_x_enabled: bool,
+    pub admin: Pubkey,
+    pub event_nonce: u64, // 所属池子的事件序号，逐个递增
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct CircuitBreakerUpdated {
+    pub pool_name: String,
+    pub old_max_reserve_move_bps: u16,
+    pub new_max_reserve_move_bps: u16,
+    pub admin: Pubkey,
+    pub event_nonce: u64, // 所属池子的事件序号，逐个递增
+    pub timestamp: i64,
+}
 
 #[event]
 pub struct PoolInitialized {
     pub pool_name: String,
     pub initial_x_amount: u64,
     pub initial_y_amount: u64,
+    pub fee_bps: u16,
+    pub curve: CurveType,
     pub initializer: Pubkey,
+    pub event_nonce: u64, // 所属池子的事件序号，逐个递增
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct ObservationRecorded {
+    pub pool_name: String,
+    pub index: u8,
+    pub slot: u64,
+    pub price: u128,
+    pub event_nonce: u64, // 所属池子的事件序号，逐个递增
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct PoolStatsSnapshotTaken {
+    pub pool_name: String,
+    pub day: u64,
+    pub reserve_x: u64,
+    pub reserve_y: u64,
+    pub swap_count: u64,
+    pub event_nonce: u64, // 所属池子的事件序号，逐个递增
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct LiquidityRangesInitialized {
+    pub pool_name: String,
+    pub range_count: u8,
+    pub active_index: u8,
+    pub event_nonce: u64, // 所属池子的事件序号，逐个递增
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct RangeLiquidityAllocated {
+    pub pool_name: String,
+    pub range_index: u8,
+    pub amount_x: u64,
+    pub amount_y: u64,
+    pub event_nonce: u64, // 所属池子的事件序号，逐个递增
     pub timestamp: i64,
 }
 
@@ -407,4 +7603,128 @@ pub enum ErrorCode {
     InvalidPoolAuthority,
     #[msg("Invalid amount provided.")]
     InvalidAmount,
+    #[msg("Swap fee must be one of the supported fee tiers.")]
+    InvalidFeeRate,
+    #[msg("Invalid curve parameters.")]
+    InvalidCurveParameters,
+    #[msg("Curve invariant calculation did not converge.")]
+    CurveDidNotConverge,
+    #[msg("Protocol fee share exceeds the allowed maximum.")]
+    InvalidProtocolFeeShare,
+    #[msg("Protocol fee vault does not match the pool.")]
+    InvalidFeeVault,
+    #[msg("No protocol fees to collect.")]
+    NoProtocolFees,
+    #[msg("Invariant violated: token X vault holds less than the recorded reserve.")]
+    InvariantVaultXBelowReserve,
+    #[msg("Invariant violated: token Y vault holds less than the recorded reserve.")]
+    InvariantVaultYBelowReserve,
+    #[msg("Concentrated pools require their liquidity ranges account.")]
+    MissingLiquidityRanges,
+    #[msg("Invalid liquidity ranges.")]
+    InvalidLiquidityRanges,
+    #[msg("Operation is not supported for this pool curve.")]
+    UnsupportedCurveOperation,
+    #[msg("Range allocation exceeds the pool's unallocated reserves.")]
+    RangeAllocationExceedsReserves,
+    #[msg("Invalid TWAP observation window.")]
+    InvalidTwapWindow,
+    #[msg("Required input exceeds max_amount_in.")]
+    MaxAmountInExceeded,
+    #[msg("Invalid swap route accounts.")]
+    InvalidRoute,
+    #[msg("Price impact exceeds the pool's configured maximum.")]
+    PriceImpactTooHigh,
+    #[msg("Max price impact must be between 1 and 10000 bps.")]
+    InvalidPriceImpactLimit,
+    #[msg("Vault does not match the pool.")]
+    InvalidVault,
+    #[msg("Token mint does not match the pool.")]
+    InvalidTokenMint,
+    #[msg("token_x_mint must sort before token_y_mint.")]
+    UnsortedMints,
+    #[msg("Pool is not registered.")]
+    PoolNotRegistered,
+    #[msg("Pool is paused.")]
+    PoolPaused,
+    #[msg("Pool is not paused.")]
+    PoolNotPaused,
+    #[msg("Received input is less than required after transfer fees.")]
+    InsufficientInputReceived,
+    #[msg("Flash swap callback program is invalid.")]
+    InvalidCallbackProgram,
+    #[msg("Flash swap was not repaid.")]
+    FlashSwapNotRepaid,
+    #[msg("Referral fee share exceeds the maximum.")]
+    InvalidReferralFeeShare,
+    #[msg("Referrer token account does not match the input mint.")]
+    InvalidReferrerAccount,
+    #[msg("Vaults hold no balance above the recorded reserves.")]
+    NoExcessReserves,
+    #[msg("Vault balance is below the recorded reserves.")]
+    VaultBelowReserves,
+    #[msg("Max oracle deviation exceeds 10000 bps.")]
+    InvalidOracleDeviation,
+    #[msg("Oracle account is invalid or does not match the pool.")]
+    InvalidOracleAccount,
+    #[msg("Oracle price is stale or not trading.")]
+    StaleOraclePrice,
+    #[msg("Execution price deviates too far from the oracle price.")]
+    OraclePriceDeviation,
+    #[msg("Limit price must be positive.")]
+    InvalidPriceLimit,
+    #[msg("Pool price is already beyond the limit price.")]
+    PriceLimitReached,
+    #[msg("Pool price has not crossed the order's limit price.")]
+    OrderNotCrossed,
+    #[msg("Order does not belong to this owner.")]
+    InvalidOrderOwner,
+    #[msg("Invalid batch swap legs or accounts.")]
+    InvalidBatchSwap,
+    #[msg("Token symbol must be 1-10 bytes.")]
+    InvalidSymbol,
+    #[msg("Tri-pool token indices must be distinct and below 3.")]
+    InvalidTriPoolIndex,
+    #[msg("A price observation was already recorded in this slot.")]
+    ObservationTooRecent,
+    #[msg("Withdrawn amount is below min_amount_x or min_amount_y.")]
+    WithdrawalBelowMinimum,
+    #[msg("Circuit breaker limit must not exceed 10000 bps.")]
+    InvalidCircuitBreakerLimit,
+    #[msg("Reserve outflow in this slot exceeds the circuit breaker limit.")]
+    CircuitBreakerTripped,
+    #[msg("A committed swap can only be revealed in a later slot.")]
+    RevealTooEarly,
+    #[msg("Revealed swap does not match the commitment.")]
+    CommitmentMismatch,
+    #[msg("Fee allowlist is full.")]
+    FeeAllowlistFull,
+    #[msg("Trader is not in the fee allowlist.")]
+    FeeExemptionNotFound,
+    #[msg("This swap direction is disabled for the pool.")]
+    SwapDirectionDisabled,
+    #[msg("No pending admin to accept.")]
+    NoPendingAdmin,
+    #[msg("Pool account version is not supported by this program.")]
+    UnsupportedPoolVersion,
+    #[msg("Swap amount exceeds the delegated allowance.")]
+    DelegatedAmountExceeded,
+    #[msg("Unstake amount exceeds the staked LP balance.")]
+    InsufficientStake,
+    #[msg("Trader allowlist is full.")]
+    TraderAllowlistFull,
+    #[msg("Trader is not in the trader allowlist.")]
+    AllowedTraderNotFound,
+    #[msg("Pool is permissioned and the trader is not allowed to swap.")]
+    TraderNotAllowed,
+    #[msg("Fee discount window must have start_ts < end_ts and a fee below the pool fee.")]
+    InvalidFeeDiscountWindow,
+    #[msg("Multi-pool swap must have 1-4 pools with matching accounts.")]
+    InvalidMultiPoolSwap,
+    #[msg("Initial pool price deviates too far from the reference price.")]
+    InitialPriceOutOfBounds,
+    #[msg("Stats snapshot day must be the current day.")]
+    InvalidSnapshotDay,
+    #[msg("Fee allowlist does not belong to the pool.")]
+    InvalidFeeAllowlist,
 }
\ No newline at end of file
@@ -2,14 +2,28 @@ use anchor_lang::prelude::*;
 use anchor_lang::system_program;
 use shared::{MockPoolState, PoolStatus};
 
+#[cfg(all(feature = "devnet", feature = "mainnet"))]
+compile_error!("features `devnet` and `mainnet` are mutually exclusive");
+
+// 各集群的程序 ID，通过 cargo feature 选择；未指定时默认为 localnet
+// devnet / mainnet 目前与 localnet 共用同一部署密钥，更换密钥后只需修改对应分支
+#[cfg(feature = "mainnet")]
+declare_id!("BtJ6VkrNWjgfPVH63LevLiZYSoKGKfueS1d54i6jWfzq");
+#[cfg(feature = "devnet")]
 declare_id!("BtJ6VkrNWjgfPVH63LevLiZYSoKGKfueS1d54i6jWfzq");
+#[cfg(not(any(feature = "devnet", feature = "mainnet")))]
+declare_id!("BtJ6VkrNWjgfPVH63LevLiZYSoKGKfueS1d54i6jWfzq");
+
+/// flash-loan 程序 ID，池子的 SOL 流动性存放在该程序以 [b"sol_vault", pool_state] 推导的 vault 中
+/// flash-loan 依赖本程序，这里不能反向引用其 crate，修改 flash-loan 的 declare_id 时须同步更新
+pub const FLASH_LOAN_PROGRAM_ID: Pubkey = pubkey!("HfsaDERzuB1m79Z1JHcbNz2JtwVcRowBso7xb5vWVQK");
 
 #[program]
 pub mod mock_pool {
     use super::*;
 
-    /// 初始化池，创建共享状态
-    pub fn initialize(ctx: Context<Initialize>, initial_balance: u64, fee_bps: u16) -> Result<()> {
+    /// 初始化池，创建共享状态 - pool_id 作为 PDA 种子，同一程序下可以并存多个费率、余额各不相同的池子
+    pub fn initialize(ctx: Context<Initialize>, pool_id: u64, initial_balance: u64, fee_bps: u16) -> Result<()> {
         // 验证费用率
         require!(fee_bps <= 1000, PoolError::InvalidFeeRate); // 最大 10%
         
@@ -17,7 +31,7 @@ pub mod mock_pool {
         require!(initial_balance > 0, PoolError::InvalidInitialBalance);
 
         let pool_state = &mut ctx.accounts.pool_state;
-        pool_state.pool_id = Clock::get()?.unix_timestamp as u64;
+        pool_state.pool_id = pool_id;
         pool_state.balance = initial_balance;
         pool_state.fee_bps = fee_bps;
         pool_state.authority = ctx.accounts.authority.key();
@@ -27,21 +41,30 @@ pub mod mock_pool {
         pool_state.created_at = Clock::get()?.unix_timestamp;
         pool_state.last_updated = Clock::get()?.unix_timestamp;
         pool_state.status = PoolStatus::Active;
+        pool_state.max_loan_amount = 0;
+        pool_state.max_borrowed_per_slot = 0;
+        pool_state.profit_share_bps = 0;
+        pool_state.max_utilization_bps = 0;
         pool_state.bump = ctx.bumps.pool_state;
 
-        // 将 initial_balance 的 SOL 转移到池子账户
-        if initial_balance > 0 {
-            system_program::transfer(
-                CpiContext::new(
-                    ctx.accounts.system_program.to_account_info(),
-                    system_program::Transfer {
-                        from: ctx.accounts.authority.to_account_info(),
-                        to: pool_state.to_account_info(),
-                    },
-                ),
-                initial_balance,
-            )?;
-        }
+        // 将 initial_balance 的 SOL 转入池子的 SOL vault，并补足 vault 的租金豁免下限，
+        // 使记录的 balance 全部可借
+        let rent_shortfall = Rent::get()?
+            .minimum_balance(0)
+            .saturating_sub(ctx.accounts.sol_vault.lamports());
+        let vault_deposit = initial_balance
+            .checked_add(rent_shortfall)
+            .ok_or(PoolError::Overflow)?;
+        system_program::transfer(
+            CpiContext::new(
+                ctx.accounts.system_program.to_account_info(),
+                system_program::Transfer {
+                    from: ctx.accounts.authority.to_account_info(),
+                    to: ctx.accounts.sol_vault.to_account_info(),
+                },
+            ),
+            vault_deposit,
+        )?;
 
         // 发送事件
         emit!(PoolInitialized {
@@ -69,6 +92,10 @@ pub mod mock_pool {
         msg!("  Total Repaid: {}", pool_state.total_repaid);
         msg!("  Active Loans: {}", pool_state.active_loans);
         msg!("  Status: {:?}", pool_state.status);
+        msg!("  Max Loan Amount: {}", pool_state.max_loan_amount);
+        msg!("  Max Borrowed Per Slot: {}", pool_state.max_borrowed_per_slot);
+        msg!("  Profit Share BPS: {}", pool_state.profit_share_bps);
+        msg!("  Max Utilization BPS: {}", pool_state.max_utilization_bps);
         msg!("  Utilization Rate: {} BPS", pool_state.get_utilization_rate());
         msg!("  Can Lend: {}", pool_state.can_lend());
         
@@ -100,6 +127,32 @@ pub mod mock_pool {
         Ok(())
     }
 
+    /// 暂停池子 - 暂停期间不能借款，与紧急状态不同，仅表示运维性的临时停用
+    pub fn pause_pool(ctx: Context<PausePool>) -> Result<()> {
+        let pool_state = &mut ctx.accounts.pool_state;
+        
+        // 验证权限
+        require!(
+            pool_state.authority == ctx.accounts.authority.key(),
+            PoolError::InvalidAuthority
+        );
+        
+        let old_status = pool_state.status.clone();
+        pool_state.status = PoolStatus::Paused;
+        pool_state.update_timestamp()?;
+        
+        emit!(PoolStatusChanged {
+            pool_id: pool_state.pool_id,
+            old_status,
+            new_status: PoolStatus::Paused,
+            authority: ctx.accounts.authority.key(),
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        
+        msg!("Pool {} paused", pool_state.pool_id);
+        Ok(())
+    }
+
     /// 恢复池子正常状态
     pub fn resume_pool(ctx: Context<ResumePool>) -> Result<()> {
         let pool_state = &mut ctx.accounts.pool_state;
@@ -125,18 +178,214 @@ pub mod mock_pool {
         msg!("Pool {} resumed to active status", pool_state.pool_id);
         Ok(())
     }
+
+    /// 设置借款上限 - 单笔上限、每 slot 累计上限和单笔借款占可用余额的最高比例，0 表示不限制
+    pub fn set_borrow_caps(
+        ctx: Context<SetBorrowCaps>,
+        max_loan_amount: u64,
+        max_borrowed_per_slot: u64,
+        max_utilization_bps: u16,
+    ) -> Result<()> {
+        // 验证利用率上限
+        require!(max_utilization_bps <= 10_000, PoolError::InvalidUtilizationCap);
+
+        let pool_state = &mut ctx.accounts.pool_state;
+
+        // 验证权限
+        require!(
+            pool_state.authority == ctx.accounts.authority.key(),
+            PoolError::InvalidAuthority
+        );
+
+        pool_state.max_loan_amount = max_loan_amount;
+        pool_state.max_borrowed_per_slot = max_borrowed_per_slot;
+        pool_state.max_utilization_bps = max_utilization_bps;
+        pool_state.update_timestamp()?;
+
+        emit!(BorrowCapsUpdated {
+            pool_id: pool_state.pool_id,
+            max_loan_amount,
+            max_borrowed_per_slot,
+            max_utilization_bps,
+            authority: ctx.accounts.authority.key(),
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        msg!(
+            "Pool {} borrow caps set: {} per loan, {} per slot, {} BPS utilization",
+            pool_state.pool_id,
+            max_loan_amount,
+            max_borrowed_per_slot,
+            max_utilization_bps
+        );
+        Ok(())
+    }
+
+    /// 设置协议从套利利润中抽取的份额
+    pub fn set_profit_share(ctx: Context<SetProfitShare>, profit_share_bps: u16) -> Result<()> {
+        // 验证份额
+        require!(profit_share_bps <= 5000, PoolError::InvalidProfitShare); // 最大 50%
+
+        let pool_state = &mut ctx.accounts.pool_state;
+
+        // 验证权限
+        require!(
+            pool_state.authority == ctx.accounts.authority.key(),
+            PoolError::InvalidAuthority
+        );
+
+        let old_profit_share_bps = pool_state.profit_share_bps;
+        pool_state.profit_share_bps = profit_share_bps;
+        pool_state.update_timestamp()?;
+
+        emit!(ProfitShareUpdated {
+            pool_id: pool_state.pool_id,
+            old_profit_share_bps,
+            new_profit_share_bps: profit_share_bps,
+            authority: ctx.accounts.authority.key(),
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        msg!("Pool {} profit share set to {} BPS", pool_state.pool_id, profit_share_bps);
+        Ok(())
+    }
+
+    /// 向池子的 SOL vault 存入 lamports - 存入的数量计入池子记录的 balance
+    /// 池子状态归本程序所有，balance 只能在这里更新；vault 由 flash-loan 程序推导，向其转入无需签名
+    pub fn fund_sol_vault(ctx: Context<FundSolVault>, amount: u64) -> Result<()> {
+        require!(amount > 0, PoolError::InvalidDepositAmount);
+
+        let pool_state = &mut ctx.accounts.pool_state;
+
+        // 验证权限
+        require!(
+            pool_state.authority == ctx.accounts.authority.key(),
+            PoolError::InvalidAuthority
+        );
+
+        pool_state.balance = pool_state
+            .balance
+            .checked_add(amount)
+            .ok_or(PoolError::Overflow)?;
+        pool_state.update_timestamp()?;
+
+        system_program::transfer(
+            CpiContext::new(
+                ctx.accounts.system_program.to_account_info(),
+                system_program::Transfer {
+                    from: ctx.accounts.authority.to_account_info(),
+                    to: ctx.accounts.sol_vault.to_account_info(),
+                },
+            ),
+            amount,
+        )?;
+
+        emit!(SolVaultFunded {
+            pool_id: pool_state.pool_id,
+            vault: ctx.accounts.sol_vault.key(),
+            amount,
+            authority: ctx.accounts.authority.key(),
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        msg!("Pool {} SOL vault funded with {} lamports", pool_state.pool_id, amount);
+        Ok(())
+    }
+
+    /// 弃用池子 - 仅在没有活跃借款时允许
+    pub fn deprecate_pool(ctx: Context<DeprecatePool>) -> Result<()> {
+        let pool_state = &mut ctx.accounts.pool_state;
+
+        // 验证权限
+        require!(
+            pool_state.authority == ctx.accounts.authority.key(),
+            PoolError::InvalidAuthority
+        );
+        require!(pool_state.active_loans == 0, PoolError::ActiveLoansOutstanding);
+
+        let old_status = pool_state.status.clone();
+        pool_state.status = PoolStatus::Deprecated;
+        pool_state.update_timestamp()?;
+
+        emit!(PoolStatusChanged {
+            pool_id: pool_state.pool_id,
+            old_status,
+            new_status: PoolStatus::Deprecated,
+            authority: ctx.accounts.authority.key(),
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        msg!("Pool {} deprecated", pool_state.pool_id);
+        Ok(())
+    }
+
+    /// 关闭已弃用的池子，将状态账户的租金回收到 treasury
+    /// SOL vault 由 flash-loan 程序签名转出，须先通过 flash-loan 的 drain_sol_vault 取空
+    pub fn close_pool(ctx: Context<ClosePool>) -> Result<()> {
+        let pool_state = &ctx.accounts.pool_state;
+
+        // 验证权限
+        require!(
+            pool_state.authority == ctx.accounts.authority.key(),
+            PoolError::InvalidAuthority
+        );
+        require!(
+            pool_state.status == PoolStatus::Deprecated,
+            PoolError::PoolNotDeprecated
+        );
+        require!(ctx.accounts.sol_vault.lamports() == 0, PoolError::SolVaultNotEmpty);
+
+        emit!(PoolClosed {
+            pool_id: pool_state.pool_id,
+            reclaimed_lamports: pool_state.to_account_info().lamports(),
+            treasury: ctx.accounts.treasury.key(),
+            authority: ctx.accounts.authority.key(),
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        msg!("Pool {} closed, lamports reclaimed to {}", pool_state.pool_id, ctx.accounts.treasury.key());
+        Ok(())
+    }
+
+    /// 校验池子核心不变量（仅非主网构建）
+    /// SOL vault 的 lamports 必须覆盖记录的余额加上租金豁免下限
+    #[cfg(not(feature = "mainnet"))]
+    pub fn assert_invariants(ctx: Context<AssertInvariants>) -> Result<()> {
+        let rent_floor = Rent::get()?.minimum_balance(0);
+        let required = ctx.accounts.pool_state
+            .balance
+            .checked_add(rent_floor)
+            .ok_or(PoolError::Overflow)?;
+
+        require!(
+            ctx.accounts.sol_vault.lamports() >= required,
+            PoolError::InvariantLamportsBelowBalance
+        );
+
+        Ok(())
+    }
 }
 
 #[derive(Accounts)]
+#[instruction(pool_id: u64)]
 pub struct Initialize<'info> {
     #[account(
         init,
         payer = authority,
-        seeds = [b"mock_pool_state"],
+        seeds = [b"mock_pool_state".as_ref(), &pool_id.to_le_bytes()],
         bump,
         space = MockPoolState::SPACE,
     )]
     pub pool_state: Account<'info, MockPoolState>,
+
+    /// 池子的 SOL vault，接收 initial_balance
+    #[account(
+        mut,
+        seeds = [b"sol_vault".as_ref(), pool_state.key().as_ref()],
+        bump,
+        seeds::program = FLASH_LOAN_PROGRAM_ID,
+    )]
+    pub sol_vault: SystemAccount<'info>,
     
     #[account(mut)]
     pub authority: Signer<'info>,
@@ -147,7 +396,7 @@ pub struct Initialize<'info> {
 #[derive(Accounts)]
 pub struct GetPoolInfo<'info> {
     #[account(
-        seeds = [b"mock_pool_state"],
+        seeds = [b"mock_pool_state".as_ref(), &pool_state.pool_id.to_le_bytes()],
         bump = pool_state.bump,
     )]
     pub pool_state: Account<'info, MockPoolState>,
@@ -157,7 +406,19 @@ pub struct GetPoolInfo<'info> {
 pub struct EmergencyPause<'info> {
     #[account(
         mut,
-        seeds = [b"mock_pool_state"],
+        seeds = [b"mock_pool_state".as_ref(), &pool_state.pool_id.to_le_bytes()],
+        bump = pool_state.bump,
+    )]
+    pub pool_state: Account<'info, MockPoolState>,
+    
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct PausePool<'info> {
+    #[account(
+        mut,
+        seeds = [b"mock_pool_state".as_ref(), &pool_state.pool_id.to_le_bytes()],
         bump = pool_state.bump,
     )]
     pub pool_state: Account<'info, MockPoolState>,
@@ -169,7 +430,66 @@ pub struct EmergencyPause<'info> {
 pub struct ResumePool<'info> {
     #[account(
         mut,
-        seeds = [b"mock_pool_state"],
+        seeds = [b"mock_pool_state".as_ref(), &pool_state.pool_id.to_le_bytes()],
+        bump = pool_state.bump,
+    )]
+    pub pool_state: Account<'info, MockPoolState>,
+    
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetBorrowCaps<'info> {
+    #[account(
+        mut,
+        seeds = [b"mock_pool_state".as_ref(), &pool_state.pool_id.to_le_bytes()],
+        bump = pool_state.bump,
+    )]
+    pub pool_state: Account<'info, MockPoolState>,
+    
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetProfitShare<'info> {
+    #[account(
+        mut,
+        seeds = [b"mock_pool_state".as_ref(), &pool_state.pool_id.to_le_bytes()],
+        bump = pool_state.bump,
+    )]
+    pub pool_state: Account<'info, MockPoolState>,
+    
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct FundSolVault<'info> {
+    #[account(
+        mut,
+        seeds = [b"mock_pool_state".as_ref(), &pool_state.pool_id.to_le_bytes()],
+        bump = pool_state.bump,
+    )]
+    pub pool_state: Account<'info, MockPoolState>,
+
+    #[account(
+        mut,
+        seeds = [b"sol_vault".as_ref(), pool_state.key().as_ref()],
+        bump,
+        seeds::program = FLASH_LOAN_PROGRAM_ID,
+    )]
+    pub sol_vault: SystemAccount<'info>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct DeprecatePool<'info> {
+    #[account(
+        mut,
+        seeds = [b"mock_pool_state".as_ref(), &pool_state.pool_id.to_le_bytes()],
         bump = pool_state.bump,
     )]
     pub pool_state: Account<'info, MockPoolState>,
@@ -177,6 +497,48 @@ pub struct ResumePool<'info> {
     pub authority: Signer<'info>,
 }
 
+#[derive(Accounts)]
+pub struct ClosePool<'info> {
+    #[account(
+        mut,
+        seeds = [b"mock_pool_state".as_ref(), &pool_state.pool_id.to_le_bytes()],
+        bump = pool_state.bump,
+        close = treasury,
+    )]
+    pub pool_state: Account<'info, MockPoolState>,
+
+    /// 池子的 SOL vault，关闭前必须已取空
+    #[account(
+        seeds = [b"sol_vault".as_ref(), pool_state.key().as_ref()],
+        bump,
+        seeds::program = FLASH_LOAN_PROGRAM_ID,
+    )]
+    pub sol_vault: SystemAccount<'info>,
+    
+    pub authority: Signer<'info>,
+
+    /// CHECK: 接收回收 lamports 的账户，由 authority 指定
+    #[account(mut)]
+    pub treasury: AccountInfo<'info>,
+}
+
+#[cfg(not(feature = "mainnet"))]
+#[derive(Accounts)]
+pub struct AssertInvariants<'info> {
+    #[account(
+        seeds = [b"mock_pool_state".as_ref(), &pool_state.pool_id.to_le_bytes()],
+        bump = pool_state.bump,
+    )]
+    pub pool_state: Account<'info, MockPoolState>,
+
+    #[account(
+        seeds = [b"sol_vault".as_ref(), pool_state.key().as_ref()],
+        bump,
+        seeds::program = FLASH_LOAN_PROGRAM_ID,
+    )]
+    pub sol_vault: SystemAccount<'info>,
+}
+
 #[event]
 pub struct PoolInitialized {
     pub pool_id: u64,
@@ -195,6 +557,43 @@ pub struct PoolStatusChanged {
     pub timestamp: i64,
 }
 
+#[event]
+pub struct BorrowCapsUpdated {
+    pub pool_id: u64,
+    pub max_loan_amount: u64,
+    pub max_borrowed_per_slot: u64,
+    pub max_utilization_bps: u16,
+    pub authority: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct ProfitShareUpdated {
+    pub pool_id: u64,
+    pub old_profit_share_bps: u16,
+    pub new_profit_share_bps: u16,
+    pub authority: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct SolVaultFunded {
+    pub pool_id: u64,
+    pub vault: Pubkey,
+    pub amount: u64,
+    pub authority: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct PoolClosed {
+    pub pool_id: u64,
+    pub reclaimed_lamports: u64,
+    pub treasury: Pubkey,
+    pub authority: Pubkey,
+    pub timestamp: i64,
+}
+
 #[error_code]
 pub enum PoolError {
     #[msg("Insufficient funds in pool")]
@@ -209,4 +608,18 @@ pub enum PoolError {
     Overflow,
     #[msg("Calculation underflow")]
     Underflow,
+    #[msg("Pool still has active loans")]
+    ActiveLoansOutstanding,
+    #[msg("Pool must be deprecated before it can be closed")]
+    PoolNotDeprecated,
+    #[msg("Invariant violated: SOL vault lamports do not cover recorded balance")]
+    InvariantLamportsBelowBalance,
+    #[msg("Invalid profit share")]
+    InvalidProfitShare,
+    #[msg("Invalid utilization cap")]
+    InvalidUtilizationCap,
+    #[msg("SOL vault must be drained before the pool can be closed")]
+    SolVaultNotEmpty,
+    #[msg("Invalid deposit amount")]
+    InvalidDepositAmount,
 }
\ No newline at end of file
@@ -1,6 +1,30 @@
 use anchor_lang::prelude::*;
 
-declare_id!("5kPAZ9Gox4F1rnWT3owq5S319A2sG5hdivMGPBg934tW");
+#[cfg(all(feature = "devnet", feature = "mainnet"))]
+compile_error!("features `devnet` and `mainnet` are mutually exclusive");
+
+// 各集群的程序 ID，通过 cargo feature 选择；未指定时默认为 localnet
+// devnet / mainnet 目前与 localnet 共用同一部署密钥，更换密钥后只需修改对应分支
+// 本 crate 不单独部署，这里声明的 ID 是 #[account] 类型的 owner：MockPoolState 由 mock_pool 创建，
+// 因此与 mock_pool 的程序 ID 保持一致，其他程序用 Account<MockPoolState> 加载池子时 owner 校验才能通过
+#[cfg(feature = "mainnet")]
+declare_id!("BtJ6VkrNWjgfPVH63LevLiZYSoKGKfueS1d54i6jWfzq");
+#[cfg(feature = "devnet")]
+declare_id!("BtJ6VkrNWjgfPVH63LevLiZYSoKGKfueS1d54i6jWfzq");
+#[cfg(not(any(feature = "devnet", feature = "mainnet")))]
+declare_id!("BtJ6VkrNWjgfPVH63LevLiZYSoKGKfueS1d54i6jWfzq");
+
+/// 受 `verbose-logs` feature 控制的日志宏，用法与 `msg!` 相同
+/// feature 在调用方 crate 中判断；关闭时不产生日志调用，事件仍是主要的观测手段
+#[macro_export]
+macro_rules! vlog {
+    ($($arg:tt)*) => {{
+        #[cfg(feature = "verbose-logs")]
+        ::anchor_lang::prelude::msg!($($arg)*);
+        #[cfg(not(feature = "verbose-logs"))]
+        let _ = format_args!($($arg)*);
+    }};
+}
 
 #[account]
 pub struct FlashLoanState {
@@ -55,6 +79,10 @@ pub struct MockPoolState {
     pub created_at: i64,
     pub last_updated: i64,
     pub status: PoolStatus,
+    pub max_loan_amount: u64,         // 单笔借款上限（SOL 与代币均按原始数量计），0 表示不限制
+    pub max_borrowed_per_slot: u64,   // 同一 slot 内累计借款上限，0 表示不限制；累计记录在 flash-loan 的 BorrowTracker 中
+    pub profit_share_bps: u16,        // 套利利润中归协议 treasury 的份额（基点）
+    pub max_utilization_bps: u16,     // 单笔借款占借款前可用数量（SOL 为 balance，代币为 vault 余额）的最高比例（基点），0 表示不限制
     pub bump: u8,
 }
 
@@ -62,10 +90,18 @@ pub struct MockPoolState {
 pub struct TransactionRecord {
     pub transaction_id: u64,
     pub user: Pubkey,
+    pub pool: Pubkey, // 借出资金的池子，清理记录的赏金从该池子的协议 treasury 支付
     pub loan_amount: u64,
     pub fee: u64,
     pub profit: u64,
     pub net_profit: u64,
+    pub protocol_share: u64, // 从利润中划入协议 treasury 的金额
+    pub dex_pool_a: Pubkey, // 套利路径：先在 A 兑换，再在 B 换回
+    pub dex_pool_b: Pubkey,
+    pub first_leg_amount_out: u64,
+    pub second_leg_amount_out: u64,
+    pub final_token_x_balance: u64, // 套利结束后借款人的代币余额
+    pub final_token_y_balance: u64,
     pub timestamp: i64,
     pub bump: u8,
 }
@@ -246,6 +282,10 @@ impl MockPoolState {
         8 + // created_at
         8 + // last_updated
         1 + // status
+        8 + // max_loan_amount
+        8 + // max_borrowed_per_slot
+        2 + // profit_share_bps
+        2 + // max_utilization_bps
         1; // bump
 
     /// 检查池子是否可以借贷
@@ -258,6 +298,35 @@ impl MockPoolState {
         self.balance >= amount
     }
 
+    /// 计算套利利润中归协议 treasury 的份额
+    pub fn calculate_profit_share(&self, profit: u64) -> Result<u64> {
+        let share = (profit as u128)
+            .checked_mul(self.profit_share_bps as u128)
+            .and_then(|v| v.checked_div(10_000))
+            .map(|v| v as u64);
+
+        share.ok_or_else(|| anchor_lang::error::Error::from(anchor_lang::error::ErrorCode::AccountNotEnoughKeys))
+    }
+
+    /// 检查借款是否在单笔上限之内
+    pub fn within_loan_limit(&self, amount: u64) -> bool {
+        self.max_loan_amount == 0 || amount <= self.max_loan_amount
+    }
+
+    /// 检查单笔 SOL 借款占借款前可用余额的比例是否在上限之内
+    /// 闪电贷在同一交易内归还，按借款前的 balance 计算，不受历史累计借款量影响
+    pub fn within_utilization_cap(&self, amount: u64) -> bool {
+        self.within_utilization_cap_of(amount, self.balance)
+    }
+
+    /// 检查单笔借款占借款前可用数量 liquidity 的比例是否在上限之内，代币借款以 vault 余额为 liquidity；u128 运算避免溢出
+    pub fn within_utilization_cap_of(&self, amount: u64, liquidity: u64) -> bool {
+        if self.max_utilization_bps == 0 {
+            return true;
+        }
+        (amount as u128) * 10_000 <= (liquidity as u128) * (self.max_utilization_bps as u128)
+    }
+
     /// 计算借贷费用
     pub fn calculate_fee(&self, amount: u64) -> Result<u64> {
         let fee = (amount as u128)
@@ -292,10 +361,18 @@ impl TransactionRecord {
     pub const SPACE: usize = 8 + // discriminator
         8 + // transaction_id
         32 + // user
+        32 + // pool
         8 + // loan_amount
         8 + // fee
         8 + // profit
         8 + // net_profit
+        8 + // protocol_share
+        32 + // dex_pool_a
+        32 + // dex_pool_b
+        8 + // first_leg_amount_out
+        8 + // second_leg_amount_out
+        8 + // final_token_x_balance
+        8 + // final_token_y_balance
         8 + // timestamp
         1; // bump
 
@@ -0,0 +1,367 @@
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::bpf_loader_upgradeable::{self, UpgradeableLoaderState};
+use anchor_lang::solana_program::program::invoke_signed;
+
+#[cfg(all(feature = "devnet", feature = "mainnet"))]
+compile_error!("features `devnet` and `mainnet` are mutually exclusive");
+
+// 各集群的程序 ID，通过 cargo feature 选择；未指定时默认为 localnet
+// devnet / mainnet 目前与 localnet 共用同一部署密钥，更换密钥后只需修改对应分支
+#[cfg(feature = "mainnet")]
+declare_id!("8MURxgMhpRU26qUsC7rZe8McZU5DJVXtog7ndXaX36XN");
+#[cfg(feature = "devnet")]
+declare_id!("8MURxgMhpRU26qUsC7rZe8McZU5DJVXtog7ndXaX36XN");
+#[cfg(not(any(feature = "devnet", feature = "mainnet")))]
+declare_id!("8MURxgMhpRU26qUsC7rZe8McZU5DJVXtog7ndXaX36XN");
+
+/// 升级权限 PDA 的种子，各程序的 upgrade authority 需要转交给该地址
+pub const UPGRADE_AUTHORITY_SEED: &[u8] = b"upgrade_authority";
+
+#[program]
+pub mod upgrade_timelock {
+    use super::*;
+
+    /// 初始化时间锁配置
+    /// 调用者成为 admin（负责排队升级），guardian 可以取消任何排队中的升级
+    pub fn initialize(ctx: Context<Initialize>, guardian: Pubkey, delay_secs: i64) -> Result<()> {
+        require!(delay_secs > 0, TimelockError::InvalidDelay);
+
+        let config = &mut ctx.accounts.config;
+        config.admin = ctx.accounts.admin.key();
+        config.guardian = guardian;
+        config.delay_secs = delay_secs;
+        config.bump = ctx.bumps.config;
+        config.authority_bump = ctx.bumps.upgrade_authority;
+
+        msg!(
+            "Timelock initialized: admin {}, guardian {}, delay {}s",
+            config.admin,
+            guardian,
+            delay_secs
+        );
+        Ok(())
+    }
+
+    /// 排队一次程序升级，需等待 delay_secs 之后才能执行
+    /// buffer 的 authority 必须已经是时间锁的升级权限 PDA，否则 buffer 持有者可以在等待期内替换其中的代码
+    pub fn queue_upgrade(ctx: Context<QueueUpgrade>, program: Pubkey, buffer: Pubkey) -> Result<()> {
+        assert_buffer_authority(&ctx.accounts.buffer_account, &ctx.accounts.upgrade_authority.key())?;
+
+        let config = &ctx.accounts.config;
+        let now = Clock::get()?.unix_timestamp;
+        let eta = now
+            .checked_add(config.delay_secs)
+            .ok_or(TimelockError::Overflow)?;
+
+        let queued = &mut ctx.accounts.queued_upgrade;
+        queued.program = program;
+        queued.buffer = buffer;
+        queued.queued_at = now;
+        queued.eta = eta;
+        queued.bump = ctx.bumps.queued_upgrade;
+
+        emit!(UpgradeQueued {
+            program,
+            buffer,
+            eta,
+            admin: ctx.accounts.admin.key(),
+            timestamp: now,
+        });
+
+        msg!("Upgrade of {} queued, executable after {}", program, eta);
+        Ok(())
+    }
+
+    /// 执行已到期的升级 - 任何人都可以调用，由时间锁 PDA 签名
+    pub fn execute_upgrade(ctx: Context<ExecuteUpgrade>) -> Result<()> {
+        let queued = &ctx.accounts.queued_upgrade;
+        let now = Clock::get()?.unix_timestamp;
+
+        require!(now >= queued.eta, TimelockError::TimelockNotExpired);
+        // 排队后 buffer 的 authority 可能被改回，执行前再次确认
+        assert_buffer_authority(&ctx.accounts.buffer, &ctx.accounts.upgrade_authority.key())?;
+
+        let ix = bpf_loader_upgradeable::upgrade(
+            &ctx.accounts.program.key(),
+            &ctx.accounts.buffer.key(),
+            &ctx.accounts.upgrade_authority.key(),
+            &ctx.accounts.spill.key(),
+        );
+
+        let authority_seeds = &[UPGRADE_AUTHORITY_SEED, &[ctx.accounts.config.authority_bump]];
+        invoke_signed(
+            &ix,
+            &[
+                ctx.accounts.program_data.to_account_info(),
+                ctx.accounts.program.to_account_info(),
+                ctx.accounts.buffer.to_account_info(),
+                ctx.accounts.spill.to_account_info(),
+                ctx.accounts.rent.to_account_info(),
+                ctx.accounts.clock.to_account_info(),
+                ctx.accounts.upgrade_authority.to_account_info(),
+                ctx.accounts.bpf_loader_upgradeable_program.to_account_info(),
+            ],
+            &[&authority_seeds[..]],
+        )?;
+
+        emit!(UpgradeExecuted {
+            program: queued.program,
+            buffer: queued.buffer,
+            executor: ctx.accounts.executor.key(),
+            timestamp: now,
+        });
+
+        msg!("Upgrade of {} executed", queued.program);
+        Ok(())
+    }
+
+    /// 取消排队中的升级 - guardian 或 admin 均可调用
+    pub fn cancel_upgrade(ctx: Context<CancelUpgrade>) -> Result<()> {
+        let config = &ctx.accounts.config;
+        let canceller = ctx.accounts.canceller.key();
+
+        require!(
+            canceller == config.guardian || canceller == config.admin,
+            TimelockError::Unauthorized
+        );
+
+        let queued = &ctx.accounts.queued_upgrade;
+        emit!(UpgradeCancelled {
+            program: queued.program,
+            buffer: queued.buffer,
+            canceller,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        msg!("Upgrade of {} cancelled by {}", queued.program, canceller);
+        Ok(())
+    }
+}
+
+/// 校验账户是 authority 为时间锁升级权限 PDA 的 buffer
+fn assert_buffer_authority(buffer: &UpgradeableLoaderState, upgrade_authority: &Pubkey) -> Result<()> {
+    match buffer {
+        UpgradeableLoaderState::Buffer { authority_address } if *authority_address == Some(*upgrade_authority) => Ok(()),
+        _ => err!(TimelockError::InvalidBufferAuthority),
+    }
+}
+
+#[derive(Accounts)]
+pub struct Initialize<'info> {
+    #[account(
+        init,
+        payer = admin,
+        seeds = [b"timelock_config"],
+        bump,
+        space = TimelockConfig::SPACE,
+    )]
+    pub config: Account<'info, TimelockConfig>,
+
+    /// CHECK: 仅作为签名 PDA 使用，不存储数据
+    #[account(
+        seeds = [UPGRADE_AUTHORITY_SEED],
+        bump,
+    )]
+    pub upgrade_authority: UncheckedAccount<'info>,
+
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(program: Pubkey, buffer: Pubkey)]
+pub struct QueueUpgrade<'info> {
+    #[account(
+        seeds = [b"timelock_config"],
+        bump = config.bump,
+        has_one = admin @ TimelockError::Unauthorized,
+    )]
+    pub config: Account<'info, TimelockConfig>,
+
+    #[account(
+        init,
+        payer = admin,
+        seeds = [b"queued_upgrade", program.as_ref()],
+        bump,
+        space = QueuedUpgrade::SPACE,
+    )]
+    pub queued_upgrade: Account<'info, QueuedUpgrade>,
+
+    /// 新代码所在的 buffer，authority 必须是升级权限 PDA
+    #[account(address = buffer @ TimelockError::BufferMismatch)]
+    pub buffer_account: Account<'info, UpgradeableLoaderState>,
+
+    /// CHECK: 时间锁持有的升级权限 PDA
+    #[account(
+        seeds = [UPGRADE_AUTHORITY_SEED],
+        bump = config.authority_bump,
+    )]
+    pub upgrade_authority: UncheckedAccount<'info>,
+
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ExecuteUpgrade<'info> {
+    #[account(
+        seeds = [b"timelock_config"],
+        bump = config.bump,
+    )]
+    pub config: Account<'info, TimelockConfig>,
+
+    #[account(
+        mut,
+        seeds = [b"queued_upgrade", queued_upgrade.program.as_ref()],
+        bump = queued_upgrade.bump,
+        close = admin,
+    )]
+    pub queued_upgrade: Account<'info, QueuedUpgrade>,
+
+    /// CHECK: 排队时支付租金的 admin，接收关闭后的租金
+    #[account(mut, address = config.admin @ TimelockError::Unauthorized)]
+    pub admin: UncheckedAccount<'info>,
+
+    /// CHECK: 时间锁持有的升级权限 PDA
+    #[account(
+        seeds = [UPGRADE_AUTHORITY_SEED],
+        bump = config.authority_bump,
+    )]
+    pub upgrade_authority: UncheckedAccount<'info>,
+
+    /// CHECK: 被升级的程序，必须与排队记录一致
+    #[account(mut, address = queued_upgrade.program @ TimelockError::ProgramMismatch)]
+    pub program: UncheckedAccount<'info>,
+
+    /// CHECK: 程序的 ProgramData 账户，由种子推导校验
+    #[account(
+        mut,
+        seeds = [program.key().as_ref()],
+        bump,
+        seeds::program = bpf_loader_upgradeable::ID,
+    )]
+    pub program_data: UncheckedAccount<'info>,
+
+    /// 新代码所在的 buffer，必须与排队记录一致，authority 必须仍是升级权限 PDA
+    #[account(mut, address = queued_upgrade.buffer @ TimelockError::BufferMismatch)]
+    pub buffer: Account<'info, UpgradeableLoaderState>,
+
+    /// CHECK: 接收 buffer 剩余 lamports 的账户
+    #[account(mut)]
+    pub spill: UncheckedAccount<'info>,
+
+    pub executor: Signer<'info>,
+
+    pub rent: Sysvar<'info, Rent>,
+    pub clock: Sysvar<'info, Clock>,
+
+    /// CHECK: BPF upgradeable loader
+    #[account(address = bpf_loader_upgradeable::ID)]
+    pub bpf_loader_upgradeable_program: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CancelUpgrade<'info> {
+    #[account(
+        seeds = [b"timelock_config"],
+        bump = config.bump,
+    )]
+    pub config: Account<'info, TimelockConfig>,
+
+    #[account(
+        mut,
+        seeds = [b"queued_upgrade", queued_upgrade.program.as_ref()],
+        bump = queued_upgrade.bump,
+        close = admin,
+    )]
+    pub queued_upgrade: Account<'info, QueuedUpgrade>,
+
+    /// CHECK: 排队时支付租金的 admin，接收关闭后的租金
+    #[account(mut, address = config.admin @ TimelockError::Unauthorized)]
+    pub admin: UncheckedAccount<'info>,
+
+    pub canceller: Signer<'info>,
+}
+
+#[account]
+pub struct TimelockConfig {
+    pub admin: Pubkey,
+    pub guardian: Pubkey,
+    pub delay_secs: i64,
+    pub bump: u8,
+    pub authority_bump: u8,
+}
+
+impl TimelockConfig {
+    pub const SPACE: usize = 8 + // discriminator
+        32 + // admin
+        32 + // guardian
+        8 + // delay_secs
+        1 + // bump
+        1; // authority_bump
+}
+
+#[account]
+pub struct QueuedUpgrade {
+    pub program: Pubkey,
+    pub buffer: Pubkey,
+    pub queued_at: i64,
+    pub eta: i64,
+    pub bump: u8,
+}
+
+impl QueuedUpgrade {
+    pub const SPACE: usize = 8 + // discriminator
+        32 + // program
+        32 + // buffer
+        8 + // queued_at
+        8 + // eta
+        1; // bump
+}
+
+#[event]
+pub struct UpgradeQueued {
+    pub program: Pubkey,
+    pub buffer: Pubkey,
+    pub eta: i64,
+    pub admin: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct UpgradeExecuted {
+    pub program: Pubkey,
+    pub buffer: Pubkey,
+    pub executor: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct UpgradeCancelled {
+    pub program: Pubkey,
+    pub buffer: Pubkey,
+    pub canceller: Pubkey,
+    pub timestamp: i64,
+}
+
+#[error_code]
+pub enum TimelockError {
+    #[msg("Delay must be positive")]
+    InvalidDelay,
+    #[msg("Timelock has not expired yet")]
+    TimelockNotExpired,
+    #[msg("Unauthorized")]
+    Unauthorized,
+    #[msg("Program does not match the queued upgrade")]
+    ProgramMismatch,
+    #[msg("Buffer does not match the queued upgrade")]
+    BufferMismatch,
+    #[msg("Calculation overflow")]
+    Overflow,
+    #[msg("Buffer authority must be the timelock upgrade authority")]
+    InvalidBufferAuthority,
+}
@@ -0,0 +1,234 @@
+import * as anchor from "@coral-xyz/anchor";
+import { Program } from "@coral-xyz/anchor";
+import { DexRouter } from "../target/types/dex_router";
+import { MockDex } from "../target/types/mock_dex";
+import { PublicKey, SystemProgram, SYSVAR_RENT_PUBKEY } from "@solana/web3.js";
+import {
+  TOKEN_PROGRAM_ID,
+  ASSOCIATED_TOKEN_PROGRAM_ID,
+  createMint,
+  createAccount,
+  mintTo,
+  getAssociatedTokenAddressSync,
+} from "@solana/spl-token";
+import { assert } from "chai";
+import { BN } from "@coral-xyz/anchor";
+
+describe("dex-router", () => {
+  const provider = anchor.AnchorProvider.env();
+  anchor.setProvider(provider);
+
+  const router = anchor.workspace.DexRouter as Program<DexRouter>;
+  const mockDex = anchor.workspace.MockDex as Program<MockDex>;
+
+  let tokenXMint: PublicKey;
+  let tokenYMint: PublicKey;
+  let userTokenX: PublicKey;
+  let userTokenY: PublicKey;
+  let registry: PublicKey;
+  // 同一交易对的两个手续费档位：B 的 Y 储备更多，卖出 X 时报价更高
+  let poolA: ReturnType<typeof dexPoolAccounts>;
+  let poolB: ReturnType<typeof dexPoolAccounts>;
+
+  const dexPoolAccounts = (dexFeeBps: number) => {
+    const [pool] = PublicKey.findProgramAddressSync(
+      [
+        Buffer.from("mock_dex_pool"),
+        tokenXMint.toBuffer(),
+        tokenYMint.toBuffer(),
+        new BN(dexFeeBps).toArrayLike(Buffer, "le", 2),
+      ],
+      mockDex.programId
+    );
+    const pda = (seed: string) =>
+      PublicKey.findProgramAddressSync([Buffer.from(seed), pool.toBuffer()], mockDex.programId)[0];
+    const [registryEntry] = PublicKey.findProgramAddressSync(
+      [
+        Buffer.from("pool_registry_entry"),
+        tokenXMint.toBuffer(),
+        tokenYMint.toBuffer(),
+        new BN(dexFeeBps).toArrayLike(Buffer, "le", 2),
+      ],
+      mockDex.programId
+    );
+    return {
+      feeBps: dexFeeBps,
+      pool,
+      registryEntry,
+      tokenXVault: pda("token_x_vault"),
+      tokenYVault: pda("token_y_vault"),
+      lpMint: pda("lp_mint"),
+      feeXVault: pda("fee_x_vault"),
+      feeYVault: pda("fee_y_vault"),
+    };
+  };
+
+  const initializeDexPool = async (dex: ReturnType<typeof dexPoolAccounts>, x: number, y: number) => {
+    await mockDex.methods
+      .initializePool(`router-${dex.feeBps}`, new BN(x), new BN(y), dex.feeBps, 1000, {
+        curve: { constantProduct: {} },
+        xSymbol: "TKX",
+        ySymbol: "TKY",
+        initialPriceCheck: null,
+      })
+      .accounts({
+        pool: dex.pool,
+        initializer: provider.wallet.publicKey,
+        initializerTokenXAccount: userTokenX,
+        initializerTokenYAccount: userTokenY,
+        tokenXVault: dex.tokenXVault,
+        tokenYVault: dex.tokenYVault,
+        tokenXMint,
+        tokenYMint,
+        lpMint: dex.lpMint,
+        initializerLpAccount: getAssociatedTokenAddressSync(dex.lpMint, provider.wallet.publicKey),
+        feeXVault: dex.feeXVault,
+        feeYVault: dex.feeYVault,
+        registry,
+        registryEntry: dex.registryEntry,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+        systemProgram: SystemProgram.programId,
+        rent: SYSVAR_RENT_PUBKEY,
+        oracle: null,
+      } as any)
+      .rpc();
+  };
+
+  // 卖出 X：每个候选池子传入 [pool, token_x_vault, token_y_vault, fee_x_vault]
+  const candidateAccounts = (dexes: ReturnType<typeof dexPoolAccounts>[]) =>
+    dexes.flatMap((dex) => [
+      { pubkey: dex.pool, isWritable: true, isSigner: false },
+      { pubkey: dex.tokenXVault, isWritable: true, isSigner: false },
+      { pubkey: dex.tokenYVault, isWritable: true, isSigner: false },
+      { pubkey: dex.feeXVault, isWritable: true, isSigner: false },
+    ]);
+
+  const routerAccounts = () => ({
+    tokenInAccount: userTokenX,
+    tokenOutAccount: userTokenY,
+    tokenXMint,
+    tokenYMint,
+    userAuthority: provider.wallet.publicKey,
+    tokenProgram: TOKEN_PROGRAM_ID,
+    mockDexProgram: mockDex.programId,
+  });
+
+  const quote = async (dex: ReturnType<typeof dexPoolAccounts>, amountIn: BN): Promise<BN> =>
+    mockDex.methods
+      .quoteSwap(amountIn, { xToY: {} })
+      .accounts({ pool: dex.pool } as any)
+      .view();
+
+  const tokenBalance = async (account: PublicKey) =>
+    new BN((await provider.connection.getTokenAccountBalance(account)).value.amount);
+
+  before(async () => {
+    tokenXMint = await createMint(provider.connection, provider.wallet.payer, provider.wallet.publicKey, null, 9);
+    tokenYMint = await createMint(provider.connection, provider.wallet.payer, provider.wallet.publicKey, null, 9);
+    // 池子按排序后的 mint 对寻址，X 必须是较小的那个
+    if (Buffer.compare(tokenXMint.toBuffer(), tokenYMint.toBuffer()) > 0) {
+      [tokenXMint, tokenYMint] = [tokenYMint, tokenXMint];
+    }
+    userTokenX = await createAccount(provider.connection, provider.wallet.payer, tokenXMint, provider.wallet.publicKey);
+    userTokenY = await createAccount(provider.connection, provider.wallet.payer, tokenYMint, provider.wallet.publicKey);
+    await mintTo(provider.connection, provider.wallet.payer, tokenXMint, userTokenX, provider.wallet.publicKey, 1_000_000_000);
+    await mintTo(provider.connection, provider.wallet.payer, tokenYMint, userTokenY, provider.wallet.publicKey, 1_000_000_000);
+
+    // 全局池子注册表，其他测试文件可能已经创建
+    [registry] = PublicKey.findProgramAddressSync([Buffer.from("pool_registry")], mockDex.programId);
+    if ((await provider.connection.getAccountInfo(registry)) === null) {
+      await mockDex.methods
+        .initializeRegistry()
+        .accounts({
+          registry,
+          payer: provider.wallet.publicKey,
+          systemProgram: SystemProgram.programId,
+        } as any)
+        .rpc();
+    }
+
+    poolA = dexPoolAccounts(30);
+    poolB = dexPoolAccounts(50);
+    await initializeDexPool(poolA, 100_000_000, 100_000_000);
+    await initializeDexPool(poolB, 100_000_000, 120_000_000);
+  });
+
+  it("swap_best 选择报价更高的池子整笔兑换", async () => {
+    const amountIn = new BN(1_000_000);
+    const quoteA = await quote(poolA, amountIn);
+    const quoteB = await quote(poolB, amountIn);
+    assert.isTrue(quoteB.gt(quoteA));
+
+    const poolABefore = await mockDex.account.mockDexPool.fetch(poolA.pool);
+    const poolBBefore = await mockDex.account.mockDexPool.fetch(poolB.pool);
+    const before = await tokenBalance(userTokenY);
+
+    await router.methods
+      .swapBest(amountIn, quoteB)
+      .accounts(routerAccounts() as any)
+      .remainingAccounts(candidateAccounts([poolA, poolB]))
+      .rpc();
+
+    const after = await tokenBalance(userTokenY);
+    assert.equal(after.sub(before).toString(), quoteB.toString());
+
+    // 只有报价更高的 B 成交，A 的储备不变
+    const poolAAfter = await mockDex.account.mockDexPool.fetch(poolA.pool);
+    const poolBAfter = await mockDex.account.mockDexPool.fetch(poolB.pool);
+    assert.equal(poolAAfter.xBalance.toString(), poolABefore.xBalance.toString());
+    assert.equal(poolAAfter.yBalance.toString(), poolABefore.yBalance.toString());
+    assert.equal(poolBBefore.yBalance.sub(poolBAfter.yBalance).toString(), quoteB.toString());
+  });
+
+  it("swap_split 拆单后的总输出满足 min_amount_out", async () => {
+    const amountIn = new BN(40_000_000);
+    // 大额兑换整笔放在任一池子都会有明显价格冲击，拆单输出应高于最好的单池报价
+    const quoteA = await quote(poolA, amountIn);
+    const quoteB = await quote(poolB, amountIn);
+    const minAmountOut = BN.max(quoteA, quoteB).addn(1);
+
+    const poolABefore = await mockDex.account.mockDexPool.fetch(poolA.pool);
+    const poolBBefore = await mockDex.account.mockDexPool.fetch(poolB.pool);
+    const before = await tokenBalance(userTokenY);
+
+    await router.methods
+      .swapSplit(amountIn, minAmountOut)
+      .accounts(routerAccounts() as any)
+      .remainingAccounts(candidateAccounts([poolA, poolB]))
+      .rpc();
+
+    const after = await tokenBalance(userTokenY);
+    assert.isTrue(after.sub(before).gte(minAmountOut));
+
+    // 两个池子都分到了输入
+    const poolAAfter = await mockDex.account.mockDexPool.fetch(poolA.pool);
+    const poolBAfter = await mockDex.account.mockDexPool.fetch(poolB.pool);
+    assert.isTrue(poolAAfter.xBalance.gt(poolABefore.xBalance));
+    assert.isTrue(poolBAfter.xBalance.gt(poolBBefore.xBalance));
+  });
+
+  it("swap_split 总输出不足 min_amount_out 时整体回滚", async () => {
+    const amountIn = new BN(10_000_000);
+    // 两个池子各自整笔成交的报价之和不可能由拆单达到
+    const unreachable = (await quote(poolA, amountIn)).add(await quote(poolB, amountIn));
+
+    const poolABefore = await mockDex.account.mockDexPool.fetch(poolA.pool);
+    const before = await tokenBalance(userTokenY);
+
+    try {
+      await router.methods
+        .swapSplit(amountIn, unreachable)
+        .accounts(routerAccounts() as any)
+        .remainingAccounts(candidateAccounts([poolA, poolB]))
+        .rpc();
+      assert.fail("总输出低于 min_amount_out 时不应成交");
+    } catch (err) {
+      assert.include(err.toString(), "SlippageTooHigh");
+    }
+
+    const poolAAfter = await mockDex.account.mockDexPool.fetch(poolA.pool);
+    assert.equal(poolAAfter.xBalance.toString(), poolABefore.xBalance.toString());
+    assert.equal((await tokenBalance(userTokenY)).toString(), before.toString());
+  });
+});
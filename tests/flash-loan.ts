@@ -0,0 +1,789 @@
+import * as anchor from "@coral-xyz/anchor";
+import { Program } from "@coral-xyz/anchor";
+import { FlashLoan } from "../target/types/flash_loan";
+import { MockPool } from "../target/types/mock_pool";
+import { MockDex } from "../target/types/mock_dex";
+import { ArbitrageBot } from "../target/types/arbitrage_bot";
+import {
+  AccountMeta,
+  PublicKey,
+  SystemProgram,
+  Transaction,
+  TransactionInstruction,
+  SYSVAR_INSTRUCTIONS_PUBKEY,
+  SYSVAR_RENT_PUBKEY,
+  LAMPORTS_PER_SOL,
+} from "@solana/web3.js";
+import {
+  TOKEN_PROGRAM_ID,
+  ASSOCIATED_TOKEN_PROGRAM_ID,
+  createMint,
+  createAccount,
+  mintTo,
+  approve,
+  getAssociatedTokenAddressSync,
+} from "@solana/spl-token";
+import { assert } from "chai";
+import { BN } from "@coral-xyz/anchor";
+
+describe("flash-loan", () => {
+  const provider = anchor.AnchorProvider.env();
+  anchor.setProvider(provider);
+
+  const program = anchor.workspace.FlashLoan as Program<FlashLoan>;
+  const mockPool = anchor.workspace.MockPool as Program<MockPool>;
+  const mockDex = anchor.workspace.MockDex as Program<MockDex>;
+  const arbitrageBot = anchor.workspace.ArbitrageBot as Program<ArbitrageBot>;
+
+  // 每次运行使用新的池子编号，避免与已有账户冲突
+  const poolId = new BN(Date.now());
+  const feeBps = 30;
+  const borrowAmount = new BN(LAMPORTS_PER_SOL);
+  // 与 calculate_fee 一致：amount * fee_bps / 10000，向下取整
+  const repayAmount = borrowAmount.add(borrowAmount.muln(feeBps).divn(10_000));
+
+  let poolState: PublicKey;
+  let solVault: PublicKey;
+  let borrowerAllowlist: PublicKey;
+  let borrowTracker: PublicKey;
+
+  const solVaultFor = (pool: PublicKey) =>
+    PublicKey.findProgramAddressSync([Buffer.from("sol_vault"), pool.toBuffer()], program.programId)[0];
+
+  // tokenAccounts 为每种代币的 [mint, token_vault, borrower_token_account]，与 tokenAmounts 一一对应
+  const flashBorrowIx = (tokenAmounts: BN[] = [], tokenAccounts: AccountMeta[] = []): Promise<TransactionInstruction> =>
+    program.methods
+      .flashBorrow(borrowAmount, tokenAmounts)
+      .accounts({
+        mockPoolState: poolState,
+        borrowTracker,
+        borrower: provider.wallet.publicKey,
+        borrowerAllowlist,
+        solVault,
+        instructions: SYSVAR_INSTRUCTIONS_PUBKEY,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        systemProgram: SystemProgram.programId,
+      } as any)
+      .remainingAccounts(tokenAccounts)
+      .instruction();
+
+  const flashRepayIx = (
+    borrowIndex: number,
+    {
+      solRepay = repayAmount,
+      tokenRepays = [] as BN[],
+      tokenAccounts = [] as AccountMeta[],
+      pool = poolState,
+    } = {}
+  ): Promise<TransactionInstruction> =>
+    program.methods
+      .flashRepay(solRepay, tokenRepays, borrowIndex)
+      .accounts({
+        mockPoolState: pool,
+        borrower: provider.wallet.publicKey,
+        solVault: solVaultFor(pool),
+        instructions: SYSVAR_INSTRUCTIONS_PUBKEY,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        systemProgram: SystemProgram.programId,
+      } as any)
+      .remainingAccounts(tokenAccounts)
+      .instruction();
+
+  const setStatus = (method: "pausePool" | "emergencyPause" | "resumePool") =>
+    mockPool.methods[method]()
+      .accounts({
+        poolState,
+        authority: provider.wallet.publicKey,
+      } as any);
+
+  const sendFlashLoan = async (middle: TransactionInstruction[] = []) => {
+    const tx = new Transaction().add(await flashBorrowIx(), ...middle, await flashRepayIx(0));
+    return provider.sendAndConfirm(tx);
+  };
+
+  // 创建一对按地址排序的 mint（X 较小）以及 provider 名下各持有 1_000_000_000 的代币账户
+  const createTokenPair = async () => {
+    const payer = provider.wallet.payer;
+    let tokenXMint = await createMint(provider.connection, payer, provider.wallet.publicKey, null, 9);
+    let tokenYMint = await createMint(provider.connection, payer, provider.wallet.publicKey, null, 9);
+    // 池子按排序后的 mint 对寻址，X 必须是较小的那个
+    if (Buffer.compare(tokenXMint.toBuffer(), tokenYMint.toBuffer()) > 0) {
+      [tokenXMint, tokenYMint] = [tokenYMint, tokenXMint];
+    }
+    const userTokenX = await createAccount(provider.connection, payer, tokenXMint, provider.wallet.publicKey);
+    const userTokenY = await createAccount(provider.connection, payer, tokenYMint, provider.wallet.publicKey);
+    await mintTo(provider.connection, payer, tokenXMint, userTokenX, provider.wallet.publicKey, 1_000_000_000);
+    await mintTo(provider.connection, payer, tokenYMint, userTokenY, provider.wallet.publicKey, 1_000_000_000);
+    return { tokenXMint, tokenYMint, userTokenX, userTokenY };
+  };
+
+  const dexPoolAccounts = (tokenXMint: PublicKey, tokenYMint: PublicKey, dexFeeBps: number) => {
+    const [pool] = PublicKey.findProgramAddressSync(
+      [
+        Buffer.from("mock_dex_pool"),
+        tokenXMint.toBuffer(),
+        tokenYMint.toBuffer(),
+        new BN(dexFeeBps).toArrayLike(Buffer, "le", 2),
+      ],
+      mockDex.programId
+    );
+    const pda = (seed: string) =>
+      PublicKey.findProgramAddressSync([Buffer.from(seed), pool.toBuffer()], mockDex.programId)[0];
+    const [registryEntry] = PublicKey.findProgramAddressSync(
+      [
+        Buffer.from("pool_registry_entry"),
+        tokenXMint.toBuffer(),
+        tokenYMint.toBuffer(),
+        new BN(dexFeeBps).toArrayLike(Buffer, "le", 2),
+      ],
+      mockDex.programId
+    );
+    return {
+      feeBps: dexFeeBps,
+      tokenXMint,
+      tokenYMint,
+      pool,
+      registryEntry,
+      tokenXVault: pda("token_x_vault"),
+      tokenYVault: pda("token_y_vault"),
+      lpMint: pda("lp_mint"),
+      feeXVault: pda("fee_x_vault"),
+      feeYVault: pda("fee_y_vault"),
+    };
+  };
+  type DexPoolAccounts = ReturnType<typeof dexPoolAccounts>;
+
+  const initializeDexPool = async (dex: DexPoolAccounts, userTokenX: PublicKey, userTokenY: PublicKey, x: number, y: number) => {
+    // 全局池子注册表，其他测试文件可能已经创建
+    const [registry] = PublicKey.findProgramAddressSync([Buffer.from("pool_registry")], mockDex.programId);
+    if ((await provider.connection.getAccountInfo(registry)) === null) {
+      await mockDex.methods
+        .initializeRegistry()
+        .accounts({
+          registry,
+          payer: provider.wallet.publicKey,
+          systemProgram: SystemProgram.programId,
+        } as any)
+        .rpc();
+    }
+
+    await mockDex.methods
+      .initializePool(`arb-${dex.feeBps}`, new BN(x), new BN(y), dex.feeBps, 1000, {
+        curve: { constantProduct: {} },
+        xSymbol: "TKX",
+        ySymbol: "TKY",
+        initialPriceCheck: null,
+      })
+      .accounts({
+        pool: dex.pool,
+        initializer: provider.wallet.publicKey,
+        initializerTokenXAccount: userTokenX,
+        initializerTokenYAccount: userTokenY,
+        tokenXVault: dex.tokenXVault,
+        tokenYVault: dex.tokenYVault,
+        tokenXMint: dex.tokenXMint,
+        tokenYMint: dex.tokenYMint,
+        lpMint: dex.lpMint,
+        initializerLpAccount: getAssociatedTokenAddressSync(dex.lpMint, provider.wallet.publicKey),
+        feeXVault: dex.feeXVault,
+        feeYVault: dex.feeYVault,
+        registry,
+        registryEntry: dex.registryEntry,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+        systemProgram: SystemProgram.programId,
+        rent: SYSVAR_RENT_PUBKEY,
+        oracle: null,
+      } as any)
+      .rpc();
+  };
+
+  before(async () => {
+    [poolState] = PublicKey.findProgramAddressSync(
+      [Buffer.from("mock_pool_state"), poolId.toArrayLike(Buffer, "le", 8)],
+      mockPool.programId
+    );
+    solVault = solVaultFor(poolState);
+    [borrowerAllowlist] = PublicKey.findProgramAddressSync(
+      [Buffer.from("borrower_allowlist"), poolState.toBuffer()],
+      program.programId
+    );
+    [borrowTracker] = PublicKey.findProgramAddressSync(
+      [Buffer.from("borrow_tracker"), poolState.toBuffer()],
+      program.programId
+    );
+
+    // 创建池子，initial_balance 同时转入 SOL vault
+    await mockPool.methods
+      .initialize(poolId, new BN(10 * LAMPORTS_PER_SOL), feeBps)
+      .accounts({
+        poolState,
+        solVault,
+        authority: provider.wallet.publicKey,
+        systemProgram: SystemProgram.programId,
+      } as any)
+      .rpc();
+  });
+
+  afterEach(async () => {
+    // 每个用例结束后恢复为 Active，避免状态影响后续用例
+    const pool = await mockPool.account.mockPoolState.fetch(poolState);
+    if (!("active" in pool.status)) {
+      await setStatus("resumePool").rpc();
+    }
+  });
+
+  it("池子 Active 时可以借款并在同一交易内归还", async () => {
+    const vaultBefore = await provider.connection.getBalance(solVault);
+
+    await sendFlashLoan();
+
+    // vault 收回本金并多出手续费
+    const vaultAfter = await provider.connection.getBalance(solVault);
+    assert.equal(vaultAfter - vaultBefore, repayAmount.sub(borrowAmount).toNumber());
+  });
+
+  it("池子 Paused 时拒绝借款", async () => {
+    await setStatus("pausePool").rpc();
+
+    try {
+      await sendFlashLoan();
+      assert.fail("暂停的池子不应允许借款");
+    } catch (err) {
+      assert.include(err.toString(), "PoolPaused");
+    }
+  });
+
+  it("池子 Emergency 时拒绝借款", async () => {
+    await setStatus("emergencyPause").rpc();
+
+    try {
+      await sendFlashLoan();
+      assert.fail("紧急状态的池子不应允许借款");
+    } catch (err) {
+      assert.include(err.toString(), "PoolEmergency");
+    }
+  });
+
+  it("借款后池子切换到 Emergency 时还款失败，整笔交易回滚", async () => {
+    const vaultBefore = await provider.connection.getBalance(solVault);
+    const emergencyIx = await setStatus("emergencyPause").instruction();
+
+    try {
+      await sendFlashLoan([emergencyIx]);
+      assert.fail("借款与还款之间进入紧急状态时不应允许还款");
+    } catch (err) {
+      assert.include(err.toString(), "PoolEmergency");
+    }
+
+    // 交易整体回滚：vault 余额不变，池子仍为 Active
+    assert.equal(await provider.connection.getBalance(solVault), vaultBefore);
+    const pool = await mockPool.account.mockPoolState.fetch(poolState);
+    assert.property(pool.status, "active");
+  });
+
+  describe("borrow caps", () => {
+    const setMaxBorrowedPerSlot = (maxBorrowedPerSlot: BN) =>
+      mockPool.methods
+        .setBorrowCaps(new BN(0), maxBorrowedPerSlot, 0)
+        .accounts({
+          poolState,
+          authority: provider.wallet.publicKey,
+        } as any)
+        .rpc();
+
+    before(async () => {
+      // 上限 1.5 倍借款额：同一 slot 内只能借一笔
+      await setMaxBorrowedPerSlot(borrowAmount.muln(3).divn(2));
+    });
+
+    after(async () => {
+      await setMaxBorrowedPerSlot(new BN(0));
+    });
+
+    it("借款计入本程序持有的 slot 累计账户", async () => {
+      await sendFlashLoan();
+
+      const tracker = await program.account.borrowTracker.fetch(borrowTracker);
+      assert.ok(tracker.pool.equals(poolState));
+      assert.equal(tracker.borrowedInSlot.toString(), borrowAmount.toString());
+    });
+
+    it("同一 slot 内的第二笔借款超过累计上限时拒绝", async () => {
+      // 同一交易内的指令必然处于同一 slot；每笔借款都有各自的还款，单看一笔都在上限之内
+      const tx = new Transaction().add(
+        await flashBorrowIx(),
+        await flashRepayIx(0),
+        await flashBorrowIx(),
+        await flashRepayIx(2)
+      );
+
+      try {
+        await provider.sendAndConfirm(tx);
+        assert.fail("同一 slot 内累计超过上限的借款不应被允许");
+      } catch (err) {
+        assert.include(err.toString(), "SlotBorrowLimitExceeded");
+      }
+    });
+  });
+
+  describe("flash_borrow / flash_repay 指令内省", () => {
+    const tokenAmount = new BN(1_000_000);
+    const tokenRepayAmount = tokenAmount.add(tokenAmount.muln(feeBps).divn(10_000));
+    const secondTokenAmount = new BN(2_000_000);
+    const secondTokenRepayAmount = secondTokenAmount.add(secondTokenAmount.muln(feeBps).divn(10_000));
+    // 另一个池子，用于构造还款指向其他池子或其他池子 vault 的情况
+    const otherPoolId = poolId.addn(1);
+    let otherPoolState: PublicKey;
+    let tokenMint: PublicKey;
+    let borrowerTokenAccount: PublicKey;
+    let secondTokenMint: PublicKey;
+    let secondBorrowerTokenAccount: PublicKey;
+
+    const tokenVaultFor = (pool: PublicKey, mint: PublicKey) =>
+      PublicKey.findProgramAddressSync(
+        [Buffer.from("token_vault"), pool.toBuffer(), mint.toBuffer()],
+        program.programId
+      )[0];
+
+    const tokenAssetAccounts = (mint: PublicKey, borrowerAccount: PublicKey, pool: PublicKey = poolState): AccountMeta[] => [
+      { pubkey: mint, isWritable: false, isSigner: false },
+      { pubkey: tokenVaultFor(pool, mint), isWritable: true, isSigner: false },
+      { pubkey: borrowerAccount, isWritable: true, isSigner: false },
+    ];
+
+    const initializeTokenVault = (pool: PublicKey, mint: PublicKey) =>
+      program.methods
+        .initializeTokenVault()
+        .accounts({
+          mockPoolState: pool,
+          mint,
+          tokenVault: tokenVaultFor(pool, mint),
+          authority: provider.wallet.publicKey,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          systemProgram: SystemProgram.programId,
+        } as any)
+        .rpc();
+
+    before(async () => {
+      [otherPoolState] = PublicKey.findProgramAddressSync(
+        [Buffer.from("mock_pool_state"), otherPoolId.toArrayLike(Buffer, "le", 8)],
+        mockPool.programId
+      );
+      await mockPool.methods
+        .initialize(otherPoolId, new BN(10 * LAMPORTS_PER_SOL), feeBps)
+        .accounts({
+          poolState: otherPoolState,
+          solVault: solVaultFor(otherPoolState),
+          authority: provider.wallet.publicKey,
+          systemProgram: SystemProgram.programId,
+        } as any)
+        .rpc();
+
+      const payer = provider.wallet.payer;
+      tokenMint = await createMint(provider.connection, payer, provider.wallet.publicKey, null, 9);
+      borrowerTokenAccount = await createAccount(provider.connection, payer, tokenMint, provider.wallet.publicKey);
+      await mintTo(provider.connection, payer, tokenMint, borrowerTokenAccount, provider.wallet.publicKey, 1_000_000_000);
+
+      secondTokenMint = await createMint(provider.connection, payer, provider.wallet.publicKey, null, 6);
+      secondBorrowerTokenAccount = await createAccount(provider.connection, payer, secondTokenMint, provider.wallet.publicKey);
+      await mintTo(provider.connection, payer, secondTokenMint, secondBorrowerTokenAccount, provider.wallet.publicKey, 1_000_000_000);
+
+      await initializeTokenVault(poolState, tokenMint);
+      await initializeTokenVault(otherPoolState, tokenMint);
+      await initializeTokenVault(poolState, secondTokenMint);
+      await mintTo(provider.connection, payer, tokenMint, tokenVaultFor(poolState, tokenMint), provider.wallet.publicKey, 100_000_000);
+      await mintTo(provider.connection, payer, secondTokenMint, tokenVaultFor(poolState, secondTokenMint), provider.wallet.publicKey, 100_000_000);
+    });
+
+    it("同一笔借款借出 SOL 和两种 SPL 代币并逐项归还", async () => {
+      const vaultA = tokenVaultFor(poolState, tokenMint);
+      const vaultB = tokenVaultFor(poolState, secondTokenMint);
+      const tokenBalance = async (account: PublicKey) =>
+        new BN((await provider.connection.getTokenAccountBalance(account)).value.amount);
+      const solBefore = await provider.connection.getBalance(solVault);
+      const vaultABefore = await tokenBalance(vaultA);
+      const vaultBBefore = await tokenBalance(vaultB);
+
+      const tokenAccounts = [
+        ...tokenAssetAccounts(tokenMint, borrowerTokenAccount),
+        ...tokenAssetAccounts(secondTokenMint, secondBorrowerTokenAccount),
+      ];
+      const tx = new Transaction().add(
+        await flashBorrowIx([tokenAmount, secondTokenAmount], tokenAccounts),
+        await flashRepayIx(0, {
+          tokenRepays: [tokenRepayAmount, secondTokenRepayAmount],
+          tokenAccounts,
+        })
+      );
+      await provider.sendAndConfirm(tx);
+
+      // 每个 vault 收回各自的本金并多出手续费
+      assert.equal(
+        await provider.connection.getBalance(solVault) - solBefore,
+        repayAmount.sub(borrowAmount).toNumber()
+      );
+      assert.equal(
+        (await tokenBalance(vaultA)).sub(vaultABefore).toString(),
+        tokenRepayAmount.sub(tokenAmount).toString()
+      );
+      assert.equal(
+        (await tokenBalance(vaultB)).sub(vaultBBefore).toString(),
+        secondTokenRepayAmount.sub(secondTokenAmount).toString()
+      );
+    });
+
+    it("还款的代币顺序与借款不一致时拒绝", async () => {
+      // 两种代币借款数量相同，只有 vault 顺序不同
+      const tx = new Transaction().add(
+        await flashBorrowIx(
+          [tokenAmount, tokenAmount],
+          [
+            ...tokenAssetAccounts(tokenMint, borrowerTokenAccount),
+            ...tokenAssetAccounts(secondTokenMint, secondBorrowerTokenAccount),
+          ]
+        ),
+        await flashRepayIx(0, {
+          tokenRepays: [tokenRepayAmount, tokenRepayAmount],
+          tokenAccounts: [
+            ...tokenAssetAccounts(secondTokenMint, secondBorrowerTokenAccount),
+            ...tokenAssetAccounts(tokenMint, borrowerTokenAccount),
+          ],
+        })
+      );
+
+      try {
+        await provider.sendAndConfirm(tx);
+        assert.fail("还款 vault 顺序与借款不一致时不应被接受");
+      } catch (err) {
+        assert.include(err.toString(), "InvalidFlashRepay");
+      }
+    });
+
+    it("之后没有 flash_repay 的借款被拒绝", async () => {
+      try {
+        await provider.sendAndConfirm(new Transaction().add(await flashBorrowIx()));
+        assert.fail("没有还款指令的借款不应被允许");
+      } catch (err) {
+        assert.include(err.toString(), "FlashRepayNotFound");
+      }
+    });
+
+    it("归还数量与借款不符时拒绝", async () => {
+      const tx = new Transaction().add(
+        await flashBorrowIx(),
+        await flashRepayIx(0, { solRepay: repayAmount.subn(1) })
+      );
+
+      try {
+        await provider.sendAndConfirm(tx);
+        assert.fail("少还的还款指令不应被接受");
+      } catch (err) {
+        assert.include(err.toString(), "InvalidFlashRepay");
+      }
+    });
+
+    it("归还到与借款不同的 vault 时拒绝", async () => {
+      // 借款从本池 vault 转出，还款指令却指向另一个池子同一 mint 的 vault
+      const tx = new Transaction().add(
+        await flashBorrowIx([tokenAmount], tokenAssetAccounts(tokenMint, borrowerTokenAccount)),
+        await flashRepayIx(0, {
+          tokenRepays: [tokenRepayAmount],
+          tokenAccounts: tokenAssetAccounts(tokenMint, borrowerTokenAccount, otherPoolState),
+        })
+      );
+
+      try {
+        await provider.sendAndConfirm(tx);
+        assert.fail("还款 vault 与借款不一致时不应被接受");
+      } catch (err) {
+        assert.include(err.toString(), "InvalidFlashRepay");
+      }
+    });
+
+    it("向另一个池子还款时拒绝", async () => {
+      const tx = new Transaction().add(
+        await flashBorrowIx(),
+        await flashRepayIx(0, { pool: otherPoolState })
+      );
+
+      try {
+        await provider.sendAndConfirm(tx);
+        assert.fail("还款池子与借款池子不一致时不应被接受");
+      } catch (err) {
+        assert.include(err.toString(), "InvalidFlashRepay");
+      }
+    });
+
+    it("经 CPI 调用 flash_borrow 时拒绝", async () => {
+      // mock_dex 的 flash_swap 会以调用方给定的数据 CPI 回调程序，借此从其他程序内部发起 flash_borrow
+      const { tokenXMint, tokenYMint, userTokenX, userTokenY } = await createTokenPair();
+      const dex = dexPoolAccounts(tokenXMint, tokenYMint, 30);
+      await initializeDexPool(dex, userTokenX, userTokenY, 100_000_000, 100_000_000);
+
+      const borrowIx = await flashBorrowIx();
+      try {
+        await mockDex.methods
+          .flashSwap(new BN(1_000), { xToY: {} }, borrowIx.data)
+          .accounts({
+            pool: dex.pool,
+            tokenXVault: dex.tokenXVault,
+            tokenYVault: dex.tokenYVault,
+            protocolFeeVault: dex.feeXVault,
+            receiver: userTokenY,
+            tokenXMint,
+            tokenYMint,
+            callbackProgram: program.programId,
+            userAuthority: provider.wallet.publicKey,
+            tokenProgram: TOKEN_PROGRAM_ID,
+          } as any)
+          .remainingAccounts(borrowIx.keys)
+          .rpc();
+        assert.fail("经 CPI 发起的 flash_borrow 不应被允许");
+      } catch (err) {
+        assert.include(err.toString(), "FlashLoanCpiNotAllowed");
+      }
+    });
+
+    it("两笔借款共用一条还款时拒绝", async () => {
+      const tx = new Transaction().add(
+        await flashBorrowIx(),
+        await flashBorrowIx(),
+        await flashRepayIx(1)
+      );
+
+      try {
+        await provider.sendAndConfirm(tx);
+        assert.fail("第一笔借款没有自己的还款指令时不应被允许");
+      } catch (err) {
+        assert.include(err.toString(), "MultipleFlashBorrows");
+      }
+    });
+  });
+
+  describe("protocol treasury", () => {
+    let protocolTreasury: PublicKey;
+
+    const withdrawTreasury = (amount: BN, recipient: PublicKey) =>
+      program.methods
+        .withdrawTreasury(amount)
+        .accounts({
+          mockPoolState: poolState,
+          protocolTreasury,
+          recipient,
+          authority: provider.wallet.publicKey,
+          systemProgram: SystemProgram.programId,
+        } as any);
+
+    before(async () => {
+      [protocolTreasury] = PublicKey.findProgramAddressSync(
+        [Buffer.from("protocol_treasury"), poolState.toBuffer()],
+        program.programId
+      );
+    });
+
+    it("创建 treasury 时预存租金豁免下限", async () => {
+      await program.methods
+        .initializeProtocolTreasury()
+        .accounts({
+          mockPoolState: poolState,
+          protocolTreasury,
+          authority: provider.wallet.publicKey,
+          systemProgram: SystemProgram.programId,
+        } as any)
+        .rpc();
+
+      const rentFloor = await provider.connection.getMinimumBalanceForRentExemption(0);
+      assert.equal(await provider.connection.getBalance(protocolTreasury), rentFloor);
+    });
+
+    it("authority 可以提取租金下限以上的余额", async () => {
+      // 模拟累积的利润分成
+      const share = 1_000_000;
+      await provider.sendAndConfirm(
+        new Transaction().add(
+          SystemProgram.transfer({
+            fromPubkey: provider.wallet.publicKey,
+            toPubkey: protocolTreasury,
+            lamports: share,
+          })
+        )
+      );
+
+      const recipient = anchor.web3.Keypair.generate().publicKey;
+      await withdrawTreasury(new BN(share), recipient).rpc();
+
+      assert.equal(await provider.connection.getBalance(recipient), share);
+      const rentFloor = await provider.connection.getMinimumBalanceForRentExemption(0);
+      assert.equal(await provider.connection.getBalance(protocolTreasury), rentFloor);
+    });
+
+    it("提取后低于租金下限时拒绝", async () => {
+      try {
+        await withdrawTreasury(new BN(1), provider.wallet.publicKey).rpc();
+        assert.fail("treasury 不应被提取到租金下限以下");
+      } catch (err) {
+        assert.include(err.toString(), "InsufficientTreasuryBalance");
+      }
+    });
+
+    it("非 authority 不能提取", async () => {
+      const stranger = anchor.web3.Keypair.generate();
+
+      try {
+        await program.methods
+          .withdrawTreasury(new BN(1))
+          .accounts({
+            mockPoolState: poolState,
+            protocolTreasury,
+            recipient: stranger.publicKey,
+            authority: stranger.publicKey,
+            systemProgram: SystemProgram.programId,
+          } as any)
+          .signers([stranger])
+          .rpc();
+        assert.fail("非 authority 不应能提取 treasury");
+      } catch (err) {
+        assert.include(err.toString(), "UnauthorizedAccess");
+      }
+    });
+  });
+
+  describe("deadline", () => {
+    let tokenXMint: PublicKey;
+    let tokenYMint: PublicKey;
+    let userTokenX: PublicKey;
+    let userTokenY: PublicKey;
+    let arbitrageBotState: PublicKey;
+    // 两个价格相反的 DEX 池子，X -> Y -> X 的往返有利润，用于首次创建套利机器人状态
+    let dexA: DexPoolAccounts;
+    let dexB: DexPoolAccounts;
+
+    // DEX A 输入 X 收取 X 的协议费，DEX B 输入 Y 收取 Y 的协议费
+    const arbitrageAccounts = () => ({
+      arbitrageBot: arbitrageBotState,
+      mockDexProgram: mockDex.programId,
+      dexPoolA: dexA.pool,
+      dexATokenXVault: dexA.tokenXVault,
+      dexATokenYVault: dexA.tokenYVault,
+      dexAFeeVault: dexA.feeXVault,
+      dexPoolB: dexB.pool,
+      dexBTokenXVault: dexB.tokenXVault,
+      dexBTokenYVault: dexB.tokenYVault,
+      dexBFeeVault: dexB.feeYVault,
+      tokenInAccount: userTokenX,
+      userTokenX,
+      userTokenY,
+      tokenXMint,
+      tokenYMint,
+    });
+
+    const atomicFlashLoan = async (deadline: BN | null) => {
+      const borrower = provider.wallet.publicKey;
+      const [userStats] = PublicKey.findProgramAddressSync(
+        [Buffer.from("user_stats"), borrower.toBuffer()],
+        program.programId
+      );
+      const stats = await program.account.userStats.fetchNullable(userStats);
+      const loanCount = stats ? stats.loanCount : new BN(0);
+      const [transactionRecord] = PublicKey.findProgramAddressSync(
+        [Buffer.from("transaction_record"), borrower.toBuffer(), loanCount.toArrayLike(Buffer, "le", 8)],
+        program.programId
+      );
+      const [protocolTreasury] = PublicKey.findProgramAddressSync(
+        [Buffer.from("protocol_treasury"), poolState.toBuffer()],
+        program.programId
+      );
+
+      return program.methods
+        .atomicFlashLoanWithArbitrage(new BN(1_000_000), new BN(1), null, deadline, "deadline test")
+        .accounts({
+          mockPoolState: poolState,
+          borrowTracker,
+          userStats,
+          transactionRecord,
+          borrower,
+          borrowerAllowlist,
+          solVault,
+          protocolTreasury,
+          arbitrage: {
+            arbitrageBotProgram: arbitrageBot.programId,
+            ...arbitrageAccounts(),
+          },
+          tokenProgram: TOKEN_PROGRAM_ID,
+          systemProgram: SystemProgram.programId,
+        } as any)
+        .rpc();
+    };
+
+    const chainTime = async () => {
+      const slot = await provider.connection.getSlot();
+      return (await provider.connection.getBlockTime(slot)) ?? Math.floor(Date.now() / 1000);
+    };
+
+    before(async () => {
+      const payer = provider.wallet.payer;
+      ({ tokenXMint, tokenYMint, userTokenX, userTokenY } = await createTokenPair());
+
+      dexA = dexPoolAccounts(tokenXMint, tokenYMint, 5);
+      dexB = dexPoolAccounts(tokenXMint, tokenYMint, 100);
+      await initializeDexPool(dexA, userTokenX, userTokenY, 100_000_000, 200_000_000);
+      await initializeDexPool(dexB, userTokenX, userTokenY, 200_000_000, 100_000_000);
+
+      // 套利机器人 PDA 以 delegate 身份从用户账户转出代币
+      [arbitrageBotState] = PublicKey.findProgramAddressSync([Buffer.from("arbitrage_bot")], arbitrageBot.programId);
+      await approve(provider.connection, payer, userTokenX, arbitrageBotState, provider.wallet.publicKey, 1_000_000_000);
+      await approve(provider.connection, payer, userTokenY, arbitrageBotState, provider.wallet.publicKey, 1_000_000_000);
+
+      // 套利机器人状态只在首次套利时创建，闪电贷指令要求它已存在
+      if ((await provider.connection.getAccountInfo(arbitrageBotState)) === null) {
+        await arbitrageBot.methods
+          .executeArbitrageAtomic(new BN(1_000_000), new BN(1))
+          .accounts({
+            ...arbitrageAccounts(),
+            tokenProgram: TOKEN_PROGRAM_ID,
+            payer: provider.wallet.publicKey,
+            systemProgram: SystemProgram.programId,
+          } as any)
+          .rpc();
+      }
+    });
+
+    it("deadline 已过时拒绝执行", async () => {
+      const deadline = new BN((await chainTime()) - 600);
+
+      try {
+        await atomicFlashLoan(deadline);
+        assert.fail("过期的交易不应被执行");
+      } catch (err) {
+        assert.include(err.toString(), "DeadlineExceeded");
+      }
+    });
+
+    // 未过期与不限制的情况下，先暂停池子：错误变为后续的 PoolPaused，说明 deadline 校验已通过
+    it("deadline 未到时通过 deadline 校验", async () => {
+      await setStatus("pausePool").rpc();
+      const deadline = new BN((await chainTime()) + 600);
+
+      try {
+        await atomicFlashLoan(deadline);
+        assert.fail("暂停的池子不应允许借款");
+      } catch (err) {
+        assert.notInclude(err.toString(), "DeadlineExceeded");
+        assert.include(err.toString(), "PoolPaused");
+      }
+    });
+
+    it("deadline 为 None 时不限制执行时间", async () => {
+      await setStatus("pausePool").rpc();
+
+      try {
+        await atomicFlashLoan(null);
+        assert.fail("暂停的池子不应允许借款");
+      } catch (err) {
+        assert.notInclude(err.toString(), "DeadlineExceeded");
+        assert.include(err.toString(), "PoolPaused");
+      }
+    });
+  });
+});
@@ -2,7 +2,16 @@ import * as anchor from "@coral-xyz/anchor";
 import { Program } from "@coral-xyz/anchor";
 import { MockDex } from "../target/types/mock_dex";
 import { PublicKey, SystemProgram, SYSVAR_RENT_PUBKEY } from "@solana/web3.js";
-import { TOKEN_PROGRAM_ID, createMint, createAccount, mintTo } from "@solana/spl-token";
+import {
+  TOKEN_PROGRAM_ID,
+  ASSOCIATED_TOKEN_PROGRAM_ID,
+  createMint,
+  createAccount,
+  mintTo,
+  approve,
+  createTransferInstruction,
+  getAssociatedTokenAddressSync,
+} from "@solana/spl-token";
 import { assert } from "chai";
 import { BN } from "@coral-xyz/anchor";
 
@@ -18,19 +27,33 @@ describe("mock-dex", () => {
   let userTokenXAccount: PublicKey;
   let userTokenYAccount: PublicKey;
   let poolName = "test-pool";
+  const feeBps = 30;
   let mockDexPool: PublicKey;
   let tokenXVault: PublicKey;
   let tokenYVault: PublicKey;
+  let lpMint: PublicKey;
+  let feeXVault: PublicKey;
+  let feeYVault: PublicKey;
+  let userLpAccount: PublicKey;
+  let registry: PublicKey;
+  let registryEntry: PublicKey;
   let mockDexPoolBump: number;
   let tokenXVaultBump: number;
   let tokenYVaultBump: number;
 
+  // 池子名称以定长字节数组存储，末尾用 0 填充
+  const decodeName = (bytes: number[]) => Buffer.from(bytes).toString("utf8").replace(/\0+$/, "");
+
   before(async () => {
     console.log("开始初始化测试环境...");
     
     // 创建代币
     tokenXMint = await createMint(provider.connection, provider.wallet.payer, provider.wallet.publicKey, null, 9);
     tokenYMint = await createMint(provider.connection, provider.wallet.payer, provider.wallet.publicKey, null, 9);
+    // 池子按排序后的 mint 对寻址，X 必须是较小的那个
+    if (Buffer.compare(tokenXMint.toBuffer(), tokenYMint.toBuffer()) > 0) {
+      [tokenXMint, tokenYMint] = [tokenYMint, tokenXMint];
+    }
 
     // 创建用户代币账户
     userTokenXAccount = await createAccount(provider.connection, provider.wallet.payer, tokenXMint, provider.wallet.publicKey);
@@ -59,7 +82,12 @@ describe("mock-dex", () => {
 
     // 计算 PDAs
     [mockDexPool, mockDexPoolBump] = PublicKey.findProgramAddressSync(
-      [Buffer.from("mock_dex_pool"), Buffer.from(poolName)],
+      [
+        Buffer.from("mock_dex_pool"),
+        tokenXMint.toBuffer(),
+        tokenYMint.toBuffer(),
+        new BN(feeBps).toArrayLike(Buffer, "le", 2),
+      ],
       program.programId
     );
 
@@ -72,14 +100,58 @@ describe("mock-dex", () => {
       [Buffer.from("token_y_vault"), mockDexPool.toBuffer()],
       program.programId
     );
+
+    [lpMint] = PublicKey.findProgramAddressSync(
+      [Buffer.from("lp_mint"), mockDexPool.toBuffer()],
+      program.programId
+    );
+    userLpAccount = getAssociatedTokenAddressSync(lpMint, provider.wallet.publicKey);
+
+    [feeXVault] = PublicKey.findProgramAddressSync(
+      [Buffer.from("fee_x_vault"), mockDexPool.toBuffer()],
+      program.programId
+    );
+    [feeYVault] = PublicKey.findProgramAddressSync(
+      [Buffer.from("fee_y_vault"), mockDexPool.toBuffer()],
+      program.programId
+    );
+
+    [registryEntry] = PublicKey.findProgramAddressSync(
+      [
+        Buffer.from("pool_registry_entry"),
+        tokenXMint.toBuffer(),
+        tokenYMint.toBuffer(),
+        new BN(feeBps).toArrayLike(Buffer, "le", 2),
+      ],
+      program.programId
+    );
+
+    // 全局池子注册表，其他测试文件可能已经创建
+    [registry] = PublicKey.findProgramAddressSync([Buffer.from("pool_registry")], program.programId);
+    if ((await provider.connection.getAccountInfo(registry)) === null) {
+      await program.methods
+        .initializeRegistry()
+        .accounts({
+          registry,
+          payer: provider.wallet.publicKey,
+          systemProgram: SystemProgram.programId,
+        } as any)
+        .rpc();
+    }
   });
 
   it("初始化交易池", async () => {
+    const registryBefore = await program.account.poolRegistry.fetch(registry);
     const initialXAmount = new BN(100_000_000);
     const initialYAmount = new BN(100_000_000);
 
     await program.methods
-      .initializePool(poolName, initialXAmount, initialYAmount)
+      .initializePool(poolName, initialXAmount, initialYAmount, feeBps, 1000, {
+        curve: { constantProduct: {} },
+        xSymbol: "TKX",
+        ySymbol: "TKY",
+        initialPriceCheck: null,
+      })
       .accounts({
         pool: mockDexPool,
         initializer: provider.wallet.publicKey,
@@ -89,9 +161,17 @@ describe("mock-dex", () => {
         tokenYVault,
         tokenXMint,
         tokenYMint,
+        lpMint,
+        initializerLpAccount: userLpAccount,
+        feeXVault,
+        feeYVault,
+        registry,
+        registryEntry,
         tokenProgram: TOKEN_PROGRAM_ID,
+        associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
         systemProgram: SystemProgram.programId,
         rent: SYSVAR_RENT_PUBKEY,
+        oracle: null,
       } as any)
       .signers([])
       .rpc();
@@ -99,29 +179,622 @@ describe("mock-dex", () => {
     const poolAccount = await program.account.mockDexPool.fetch(mockDexPool);
     assert.equal(poolAccount.xBalance.toString(), initialXAmount.toString());
     assert.equal(poolAccount.yBalance.toString(), initialYAmount.toString());
-    assert.equal(poolAccount.name, poolName);
+    assert.equal(decodeName(poolAccount.name), poolName);
+    assert.equal(poolAccount.feeBps, feeBps);
+    assert.equal(poolAccount.xDecimals, 9);
+    assert.equal(poolAccount.xSymbol, "TKX");
+
+    // 初始 LP = sqrt(x * y)
+    const lpBalance = await provider.connection.getTokenAccountBalance(userLpAccount);
+    assert.equal(lpBalance.value.amount, "100000000");
+
+    // 新池子已登记到注册表，条目可按 mint 对和手续费档位查到
+    const registryAccount = await program.account.poolRegistry.fetch(registry);
+    assert.equal(registryAccount.poolCount.toString(), registryBefore.poolCount.addn(1).toString());
+    const entry = await program.account.poolRegistryEntry.fetch(registryEntry);
+    assert.isTrue(entry.pool.equals(mockDexPool));
+    assert.equal(entry.feeBps, feeBps);
+
+    const registered = await program.methods
+      .getRegisteredPool(tokenXMint, tokenYMint, feeBps)
+      .accounts({ registryEntry } as any)
+      .view();
+    assert.isTrue(registered.pool.equals(mockDexPool));
+  });
+
+  it("添加并移除流动性", async () => {
+    const before = await program.account.mockDexPool.fetch(mockDexPool);
+
+    await program.methods
+      .addLiquidity(new BN(10_000_000), new BN(10_000_000), new BN(1))
+      .accounts({
+        pool: mockDexPool,
+        tokenXVault,
+        tokenYVault,
+        lpMint,
+        providerTokenX: userTokenXAccount,
+        providerTokenY: userTokenYAccount,
+        providerLpAccount: userLpAccount,
+        provider: provider.wallet.publicKey,
+        tokenXMint,
+        tokenYMint,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      } as any)
+      .rpc();
+
+    const afterAdd = await program.account.mockDexPool.fetch(mockDexPool);
+    assert.equal(afterAdd.xBalance.sub(before.xBalance).toString(), "10000000");
+    assert.equal(afterAdd.yBalance.sub(before.yBalance).toString(), "10000000");
+
+    await program.methods
+      .removeLiquidity(new BN(10_000_000), new BN(9_000_000), new BN(9_000_000))
+      .accounts({
+        pool: mockDexPool,
+        tokenXVault,
+        tokenYVault,
+        lpMint,
+        providerTokenX: userTokenXAccount,
+        providerTokenY: userTokenYAccount,
+        providerLpAccount: userLpAccount,
+        provider: provider.wallet.publicKey,
+        tokenXMint,
+        tokenYMint,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      } as any)
+      .rpc();
+
+    const afterRemove = await program.account.mockDexPool.fetch(mockDexPool);
+    assert.equal(afterRemove.xBalance.toString(), before.xBalance.toString());
+    assert.equal(afterRemove.yBalance.toString(), before.yBalance.toString());
+  });
+
+  it("单边添加流动性", async () => {
+    const before = await program.account.mockDexPool.fetch(mockDexPool);
+    const lpBefore = await provider.connection.getTokenAccountBalance(userLpAccount);
+
+    await program.methods
+      .addLiquidity(new BN(10_000_000), new BN(0), new BN(1))
+      .accounts({
+        pool: mockDexPool,
+        tokenXVault,
+        tokenYVault,
+        lpMint,
+        providerTokenX: userTokenXAccount,
+        providerTokenY: userTokenYAccount,
+        providerLpAccount: userLpAccount,
+        provider: provider.wallet.publicKey,
+        tokenXMint,
+        tokenYMint,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      } as any)
+      .rpc();
+
+    // 全部 X 进入储备，Y 侧不变；一半经过兑换并收取手续费，LP 少于双边存入 1000 万的一半
+    const after = await program.account.mockDexPool.fetch(mockDexPool);
+    assert.equal(after.xBalance.sub(before.xBalance).toString(), "10000000");
+    assert.equal(after.yBalance.toString(), before.yBalance.toString());
+    const lpAfter = await provider.connection.getTokenAccountBalance(userLpAccount);
+    const minted = new BN(lpAfter.value.amount).sub(new BN(lpBefore.value.amount));
+    assert.isTrue(minted.gt(new BN(0)) && minted.lt(new BN(5_000_000)));
+  });
+
+  it("兑换方向关闭时拒绝该方向的单边添加", async () => {
+    const setDirections = (xToY: boolean, yToX: boolean) =>
+      program.methods
+        .setSwapDirections(xToY, yToX)
+        .accounts({ pool: mockDexPool, admin: provider.wallet.publicKey } as any)
+        .rpc();
+
+    await setDirections(false, true);
+    try {
+      await program.methods
+        .addLiquidity(new BN(10_000_000), new BN(0), new BN(1))
+        .accounts({
+          pool: mockDexPool,
+          tokenXVault,
+          tokenYVault,
+          lpMint,
+          providerTokenX: userTokenXAccount,
+          providerTokenY: userTokenYAccount,
+          providerLpAccount: userLpAccount,
+          provider: provider.wallet.publicKey,
+          tokenXMint,
+          tokenYMint,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        } as any)
+        .rpc();
+      assert.fail("X -> Y 关闭时单边存入 X 不应成功");
+    } catch (err) {
+      assert.include(err.toString(), "SwapDirectionDisabled");
+    } finally {
+      await setDirections(true, true);
+    }
   });
 
   it("执行代币交换", async () => {
     const amountIn = new BN(10_000_000);
     const minAmountOut = new BN(9_000_000);
+    const poolBefore = await program.account.mockDexPool.fetch(mockDexPool);
 
     await program.methods
-      .swap(amountIn, minAmountOut, poolName)
+      .swap(amountIn, minAmountOut)
       .accounts({
         pool: mockDexPool,
         tokenInAccount: userTokenXAccount,
         tokenXVault,
         tokenYVault,
-        userTokenX: userTokenXAccount,
-        userTokenY: userTokenYAccount,
+        protocolFeeVault: feeXVault,
+        tokenOutAccount: userTokenYAccount,
         userAuthority: provider.wallet.publicKey,
+        tokenXMint,
+        tokenYMint,
         tokenProgram: TOKEN_PROGRAM_ID,
+        referrerTokenAccount: null,
+        oracle: null,
+        feeAllowlist: null,
+        traderAllowlist: null,
       } as any)
       .rpc();
 
     const poolAccount = await program.account.mockDexPool.fetch(mockDexPool);
     assert.isTrue(poolAccount.xBalance.gt(new BN(0)));
     assert.isTrue(poolAccount.yBalance.gt(new BN(0)));
+
+    // 10% 的手续费归协议: 10_000_000 * 30 / 10000 * 1000 / 10000 = 3_000
+    const feeBalance = await provider.connection.getTokenAccountBalance(feeXVault);
+    assert.equal(feeBalance.value.amount, "3000");
+
+    // 成交统计：卖出 X 的交易量与手续费计入 X 侧
+    assert.equal(poolAccount.swapCount.toString(), "1");
+    assert.equal(poolAccount.cumulativeVolumeX.toString(), amountIn.toString());
+    assert.equal(poolAccount.cumulativeFeesX.toString(), "30000");
+
+    // 每个池子事件占用一个序号
+    assert.equal(poolAccount.eventNonce.toString(), poolBefore.eventNonce.addn(1).toString());
+  });
+
+  it("报价与实际兑换结果一致", async () => {
+    const amountIn = new BN(1_000_000);
+
+    const quoted: BN = await program.methods
+      .quoteSwap(amountIn, { xToY: {} })
+      .accounts({ pool: mockDexPool } as any)
+      .view();
+
+    const before = await provider.connection.getTokenAccountBalance(userTokenYAccount);
+    await program.methods
+      .swap(amountIn, quoted)
+      .accounts({
+        pool: mockDexPool,
+        tokenInAccount: userTokenXAccount,
+        tokenXVault,
+        tokenYVault,
+        protocolFeeVault: feeXVault,
+        tokenOutAccount: userTokenYAccount,
+        userAuthority: provider.wallet.publicKey,
+        tokenXMint,
+        tokenYMint,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        referrerTokenAccount: null,
+        oracle: null,
+        feeAllowlist: null,
+        traderAllowlist: null,
+      } as any)
+      .rpc();
+    const after = await provider.connection.getTokenAccountBalance(userTokenYAccount);
+
+    assert.equal(
+      new BN(after.value.amount).sub(new BN(before.value.amount)).toString(),
+      quoted.toString()
+    );
+  });
+
+  it("精确输出兑换", async () => {
+    const amountOut = new BN(500_000);
+    const maxAmountIn = new BN(1_000_000);
+
+    const before = await provider.connection.getTokenAccountBalance(userTokenYAccount);
+    await program.methods
+      .swapExactOut(amountOut, maxAmountIn)
+      .accounts({
+        pool: mockDexPool,
+        tokenInAccount: userTokenXAccount,
+        tokenXVault,
+        tokenYVault,
+        protocolFeeVault: feeXVault,
+        tokenOutAccount: userTokenYAccount,
+        userAuthority: provider.wallet.publicKey,
+        tokenXMint,
+        tokenYMint,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        referrerTokenAccount: null,
+        oracle: null,
+        feeAllowlist: null,
+        traderAllowlist: null,
+      } as any)
+      .rpc();
+    const after = await provider.connection.getTokenAccountBalance(userTokenYAccount);
+
+    assert.equal(
+      new BN(after.value.amount).sub(new BN(before.value.amount)).toString(),
+      amountOut.toString()
+    );
+  });
+
+  it("推荐人获得手续费分成", async () => {
+    await program.methods
+      .setReferralFeeShare(2000)
+      .accounts({ pool: mockDexPool, admin: provider.wallet.publicKey } as any)
+      .rpc();
+
+    const referrer = anchor.web3.Keypair.generate();
+    const referrerTokenAccount = await createAccount(
+      provider.connection,
+      provider.wallet.payer,
+      tokenXMint,
+      referrer.publicKey
+    );
+
+    await program.methods
+      .swap(new BN(10_000_000), new BN(1))
+      .accounts({
+        pool: mockDexPool,
+        tokenInAccount: userTokenXAccount,
+        tokenXVault,
+        tokenYVault,
+        protocolFeeVault: feeXVault,
+        tokenOutAccount: userTokenYAccount,
+        userAuthority: provider.wallet.publicKey,
+        tokenXMint,
+        tokenYMint,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        referrerTokenAccount,
+        oracle: null,
+        feeAllowlist: null,
+        traderAllowlist: null,
+      } as any)
+      .rpc();
+
+    // 20% 的手续费归推荐人: 10_000_000 * 30 / 10000 * 2000 / 10000 = 6_000
+    const referrerBalance = await provider.connection.getTokenAccountBalance(referrerTokenAccount);
+    assert.equal(referrerBalance.value.amount, "6000");
+  });
+
+  // 以 Token 程序本身作为回调：回调指令就是一笔从用户账户转入 X vault 的 transfer
+  const flashSwapWithRepay = async (amountOut: BN, repayAmount: BN) => {
+    const repayIx = createTransferInstruction(
+      userTokenXAccount,
+      tokenXVault,
+      provider.wallet.publicKey,
+      BigInt(repayAmount.toString())
+    );
+    await program.methods
+      .flashSwap(amountOut, { xToY: {} }, Buffer.from(repayIx.data))
+      .accounts({
+        pool: mockDexPool,
+        tokenXVault,
+        tokenYVault,
+        protocolFeeVault: feeXVault,
+        receiver: userTokenYAccount,
+        tokenXMint,
+        tokenYMint,
+        callbackProgram: TOKEN_PROGRAM_ID,
+        userAuthority: provider.wallet.publicKey,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      } as any)
+      .remainingAccounts(repayIx.keys)
+      .rpc();
+  };
+
+  it("闪电兑换回调还款不足时失败", async () => {
+    const repayAmount = new BN(1_000_000);
+    const amountOut: BN = await program.methods
+      .quoteSwap(repayAmount, { xToY: {} })
+      .accounts({ pool: mockDexPool } as any)
+      .view();
+
+    try {
+      await flashSwapWithRepay(amountOut, repayAmount.divn(2));
+      assert.fail("还款不足以按曲线换出借走数量时不应成功");
+    } catch (err) {
+      assert.include(err.toString(), "FlashSwapNotRepaid");
+    }
+  });
+
+  it("闪电兑换回调足额还款时成功", async () => {
+    const repayAmount = new BN(1_000_000);
+    // 报价按同一条曲线和手续费计算，恰好还入 repayAmount 即可覆盖借出的 amountOut
+    const amountOut: BN = await program.methods
+      .quoteSwap(repayAmount, { xToY: {} })
+      .accounts({ pool: mockDexPool } as any)
+      .view();
+
+    const poolBefore = await program.account.mockDexPool.fetch(mockDexPool);
+    const receiverBefore = await provider.connection.getTokenAccountBalance(userTokenYAccount);
+    await flashSwapWithRepay(amountOut, repayAmount);
+    const receiverAfter = await provider.connection.getTokenAccountBalance(userTokenYAccount);
+    const poolAfter = await program.account.mockDexPool.fetch(mockDexPool);
+
+    assert.equal(
+      new BN(receiverAfter.value.amount).sub(new BN(receiverBefore.value.amount)).toString(),
+      amountOut.toString()
+    );
+    assert.equal(poolBefore.yBalance.sub(poolAfter.yBalance).toString(), amountOut.toString());
+    assert.equal(poolAfter.swapCount.toString(), poolBefore.swapCount.addn(1).toString());
+  });
+
+  it("挂限价单并撤单", async () => {
+    const orderId = new BN(1);
+    const [order] = PublicKey.findProgramAddressSync(
+      [
+        Buffer.from("limit_order"),
+        mockDexPool.toBuffer(),
+        provider.wallet.publicKey.toBuffer(),
+        orderId.toArrayLike(Buffer, "le", 8),
+      ],
+      program.programId
+    );
+    const [escrow] = PublicKey.findProgramAddressSync(
+      [Buffer.from("order_escrow"), order.toBuffer()],
+      program.programId
+    );
+
+    const before = await provider.connection.getTokenAccountBalance(userTokenXAccount);
+    // 限价远高于当前价格，订单不会被成交
+    await program.methods
+      .placeLimitOrder(orderId, new BN(1_000_000), new BN(10_000_000_000))
+      .accounts({
+        pool: mockDexPool,
+        order,
+        escrow,
+        mintIn: tokenXMint,
+        ownerTokenIn: userTokenXAccount,
+        owner: provider.wallet.publicKey,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        systemProgram: SystemProgram.programId,
+      } as any)
+      .rpc();
+
+    const orderAccount = await program.account.limitOrder.fetch(order);
+    assert.equal(orderAccount.amountIn.toString(), "1000000");
+    assert.isTrue(orderAccount.inputIsX);
+
+    await program.methods
+      .cancelOrder()
+      .accounts({
+        pool: mockDexPool,
+        order,
+        escrow,
+        mintIn: tokenXMint,
+        ownerTokenIn: userTokenXAccount,
+        owner: provider.wallet.publicKey,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      } as any)
+      .rpc();
+
+    const after = await provider.connection.getTokenAccountBalance(userTokenXAccount);
+    assert.equal(after.value.amount, before.value.amount);
+    assert.isNull(await provider.connection.getAccountInfo(order));
+  });
+
+  it("delegate 通过 approve 授权代替 owner 兑换", async () => {
+    const delegate = anchor.web3.Keypair.generate();
+    const amountIn = new BN(1_000_000);
+    await approve(
+      provider.connection,
+      provider.wallet.payer,
+      userTokenXAccount,
+      delegate.publicKey,
+      provider.wallet.publicKey,
+      amountIn.toNumber()
+    );
+
+    const before = await provider.connection.getTokenAccountBalance(userTokenYAccount);
+    await program.methods
+      .swap(amountIn, new BN(0))
+      .accounts({
+        pool: mockDexPool,
+        tokenInAccount: userTokenXAccount,
+        tokenXVault,
+        tokenYVault,
+        protocolFeeVault: feeXVault,
+        tokenOutAccount: userTokenYAccount,
+        userAuthority: delegate.publicKey,
+        tokenXMint,
+        tokenYMint,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        referrerTokenAccount: null,
+        oracle: null,
+        feeAllowlist: null,
+        traderAllowlist: null,
+      } as any)
+      .signers([delegate])
+      .rpc();
+
+    // 输出回到输入账户 owner 名下的 Y 账户
+    const after = await provider.connection.getTokenAccountBalance(userTokenYAccount);
+    assert.isTrue(new BN(after.value.amount).gt(new BN(before.value.amount)));
+  });
+
+  it("许可模式下只有名单中的交易者可以兑换", async () => {
+    const [traderAllowlist] = PublicKey.findProgramAddressSync(
+      [Buffer.from("trader_allowlist"), mockDexPool.toBuffer()],
+      program.programId
+    );
+    await program.methods
+      .initializeTraderAllowlist()
+      .accounts({ pool: mockDexPool, traderAllowlist, admin: provider.wallet.publicKey } as any)
+      .rpc();
+    await program.methods
+      .setPermissioned(true)
+      .accounts({ pool: mockDexPool, admin: provider.wallet.publicKey } as any)
+      .rpc();
+
+    const swapAccounts = {
+      pool: mockDexPool,
+      tokenInAccount: userTokenXAccount,
+      tokenXVault,
+      tokenYVault,
+      protocolFeeVault: feeXVault,
+      tokenOutAccount: userTokenYAccount,
+      userAuthority: provider.wallet.publicKey,
+      tokenXMint,
+      tokenYMint,
+      tokenProgram: TOKEN_PROGRAM_ID,
+      referrerTokenAccount: null,
+      oracle: null,
+      feeAllowlist: null,
+      traderAllowlist,
+    };
+
+    // 未登记的交易者被拒绝
+    try {
+      await program.methods.swap(new BN(1_000_000), new BN(0)).accounts(swapAccounts as any).rpc();
+      assert.fail("未登记的交易者不应能兑换");
+    } catch (err) {
+      assert.include(err.toString(), "TraderNotAllowed");
+    }
+
+    await program.methods
+      .addAllowedTrader(provider.wallet.publicKey)
+      .accounts({ pool: mockDexPool, traderAllowlist, admin: provider.wallet.publicKey } as any)
+      .rpc();
+    await program.methods.swap(new BN(1_000_000), new BN(0)).accounts(swapAccounts as any).rpc();
+
+    // 恢复为开放池子，避免影响后续测试
+    await program.methods
+      .setPermissioned(false)
+      .accounts({ pool: mockDexPool, admin: provider.wallet.publicKey } as any)
+      .rpc();
+  });
+
+  it("质押 LP 参与流动性挖矿", async () => {
+    const [rewarder] = PublicKey.findProgramAddressSync(
+      [Buffer.from("rewarder"), mockDexPool.toBuffer()],
+      program.programId
+    );
+    const [rewardVault] = PublicKey.findProgramAddressSync(
+      [Buffer.from("reward_vault"), rewarder.toBuffer()],
+      program.programId
+    );
+    const [stakedLpVault] = PublicKey.findProgramAddressSync(
+      [Buffer.from("staked_lp_vault"), rewarder.toBuffer()],
+      program.programId
+    );
+    const [lpStake] = PublicKey.findProgramAddressSync(
+      [Buffer.from("lp_stake"), rewarder.toBuffer(), provider.wallet.publicKey.toBuffer()],
+      program.programId
+    );
+
+    // 以 Token Y 作为奖励代币
+    await program.methods
+      .initializeRewarder(new BN(1_000))
+      .accounts({
+        pool: mockDexPool,
+        rewarder,
+        rewardVault,
+        stakedLpVault,
+        lpMint,
+        rewardMint: tokenYMint,
+        admin: provider.wallet.publicKey,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        systemProgram: SystemProgram.programId,
+      } as any)
+      .rpc();
+
+    await program.methods
+      .fundRewards(new BN(1_000_000))
+      .accounts({
+        pool: mockDexPool,
+        rewarder,
+        rewardVault,
+        rewardMint: tokenYMint,
+        adminRewardAccount: userTokenYAccount,
+        admin: provider.wallet.publicKey,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      } as any)
+      .rpc();
+
+    await program.methods
+      .openLpStake()
+      .accounts({
+        rewarder,
+        lpStake,
+        owner: provider.wallet.publicKey,
+        systemProgram: SystemProgram.programId,
+      } as any)
+      .rpc();
+
+    const stakeAmount = new BN(1_000_000);
+    await program.methods
+      .stakeLp(stakeAmount)
+      .accounts({
+        pool: mockDexPool,
+        rewarder,
+        lpStake,
+        stakedLpVault,
+        lpMint,
+        ownerLpAccount: userLpAccount,
+        owner: provider.wallet.publicKey,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      } as any)
+      .rpc();
+
+    const rewarderAccount = await program.account.rewarder.fetch(rewarder);
+    assert.equal(rewarderAccount.totalStaked.toString(), stakeAmount.toString());
+    assert.equal(rewarderAccount.rewardsRemaining.toString(), "1000000");
+    const stakeAccount = await program.account.lpStake.fetch(lpStake);
+    assert.equal(stakeAccount.amount.toString(), stakeAmount.toString());
+  });
+
+  it("keeper 记录价格观测", async () => {
+    const [observations] = PublicKey.findProgramAddressSync(
+      [Buffer.from("price_observations"), mockDexPool.toBuffer()],
+      program.programId
+    );
+    await program.methods
+      .initializeObservations()
+      .accounts({
+        pool: mockDexPool,
+        observations,
+        payer: provider.wallet.publicKey,
+        systemProgram: SystemProgram.programId,
+      } as any)
+      .rpc();
+
+    await program.methods
+      .crankObservation()
+      .accounts({ pool: mockDexPool, observations } as any)
+      .rpc();
+
+    const account = await program.account.priceObservations.fetch(observations);
+    const poolAccount = await program.account.mockDexPool.fetch(mockDexPool);
+    assert.equal(account.observations.length, 1);
+    assert.equal(account.nextIndex, 1);
+    assert.equal(account.observations[0].reserveX.toString(), poolAccount.xBalance.toString());
+
+    // 价格查询的现货价格与观测时一致（两者之间没有兑换）
+    const price = await program.methods
+      .getPoolPrice()
+      .accounts({ pool: mockDexPool, observations } as any)
+      .view();
+    assert.equal(price.spotPrice.toString(), account.observations[0].price.toString());
+  });
+
+  it("修改池子名称并同步注册表", async () => {
+    const newName = "A".repeat(32);
+    await program.methods
+      .renamePool(newName)
+      .accounts({
+        pool: mockDexPool,
+        registryEntry,
+        admin: provider.wallet.publicKey,
+      } as any)
+      .rpc();
+
+    const poolAccount = await program.account.mockDexPool.fetch(mockDexPool);
+    assert.equal(decodeName(poolAccount.name), newName);
+    const entry = await program.account.poolRegistryEntry.fetch(registryEntry);
+    assert.equal(entry.name, newName);
   });
 }); 
\ No newline at end of file